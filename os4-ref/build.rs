@@ -6,10 +6,13 @@ use std::io::{Result, Write};
 fn main() {
     println!("cargo:rerun-if-changed=../user/src/");
     println!("cargo:rerun-if-changed={}", TARGET_PATH);
+    println!("cargo:rerun-if-changed={}", BLOB_PATH);
     insert_app_data().unwrap();
 }
 
 static TARGET_PATH: &str = "../user/build/elf/";
+/// read-only data blobs for `sys_mmap_data`, numbered in file name order
+static BLOB_PATH: &str = "../user/data/";
 
 /// get app data and build linker
 fn insert_app_data() -> Result<()> {
@@ -43,6 +46,16 @@ _num_app:
         writeln!(f, r#"    .quad app_{}_end"#, apps.len() - 1)?;
     }
 
+    writeln!(
+        f,
+        r#"
+    .global _app_names
+_app_names:"#
+    )?;
+    for app in apps.iter() {
+        writeln!(f, r#"    .string "{}""#, app)?;
+    }
+
     for (idx, app) in apps.iter().enumerate() {
         println!("app_{}: {}", idx, app);
         writeln!(
@@ -58,5 +71,49 @@ app_{0}_end:"#,
             idx, app, TARGET_PATH
         )?;
     }
+    insert_blobs(&mut f)
+}
+
+/// add the blob table, empty without a blob directory
+fn insert_blobs(f: &mut File) -> Result<()> {
+    let mut blobs: Vec<_> = match read_dir(BLOB_PATH) {
+        Ok(dir) => dir
+            .map(|dir_entry| dir_entry.unwrap().file_name().into_string().unwrap())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    blobs.sort();
+
+    writeln!(
+        f,
+        r#"
+    .section .rodata
+    .align 3
+    .global _num_blob
+_num_blob:
+    .quad {}"#,
+        blobs.len()
+    )?;
+    for i in 0..blobs.len() {
+        writeln!(f, r#"    .quad blob_{}_start"#, i)?;
+    }
+    if !blobs.is_empty() {
+        writeln!(f, r#"    .quad blob_{}_end"#, blobs.len() - 1)?;
+    }
+
+    for (idx, blob) in blobs.iter().enumerate() {
+        println!("blob_{}: {}", idx, blob);
+        writeln!(
+            f,
+            r#"
+    .section .rodata
+    .global blob_{0}_start
+    .global blob_{0}_end
+blob_{0}_start:
+    .incbin "{2}{1}"
+blob_{0}_end:"#,
+            idx, blob, BLOB_PATH
+        )?;
+    }
     Ok(())
 }
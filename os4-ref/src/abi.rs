@@ -0,0 +1,199 @@
+//! Layouts shared with user space
+//!
+//! Everything here is `#[repr(C)]` and mirrored field by field in the user
+//! library.
+
+use crate::config::MAX_SYSCALL_NUM;
+use crate::task::TaskStatus;
+
+/// Section an app embeds its [`KernelRequirements`] in.
+pub const KREQ_SECTION: &str = ".note.kreq";
+/// `KernelRequirements::magic`, "KREQ" in little endian.
+pub const KREQ_MAGIC: u32 = u32::from_le_bytes(*b"KREQ");
+
+/// Kernel features an app may require, see [`KernelRequirements`].
+///
+/// Features this kernel cannot have yet are listed too, so that an app
+/// needing them is turned away instead of loaded.
+#[allow(dead_code)]
+pub mod kernel_feature {
+    pub const TRACE: u64 = 1 << 0;
+    pub const PROFILE: u64 = 1 << 1;
+    pub const KSTAT: u64 = 1 << 2;
+    pub const SLEEP: u64 = 1 << 3;
+    pub const SHM: u64 = 1 << 4;
+    pub const SIGNALS: u64 = 1 << 5;
+    pub const DEBUG_SYSCALLS: u64 = 1 << 6;
+    pub const MQ: u64 = 1 << 7;
+    pub const WAITPID: u64 = 1 << 8;
+}
+
+/// The [`kernel_feature`]s this kernel was built with.
+pub const KERNEL_FEATURES: u64 = {
+    let mut features = 0;
+    if cfg!(feature = "trace") {
+        features |= kernel_feature::TRACE;
+    }
+    if cfg!(feature = "profile") {
+        features |= kernel_feature::PROFILE;
+    }
+    if cfg!(feature = "kstat") {
+        features |= kernel_feature::KSTAT;
+    }
+    if cfg!(feature = "debug-syscalls") {
+        features |= kernel_feature::DEBUG_SYSCALLS;
+    }
+    features |=
+        kernel_feature::SLEEP | kernel_feature::SHM | kernel_feature::MQ | kernel_feature::WAITPID;
+    features
+};
+
+/// Version of the layouts in this file, bumped whenever one of them changes.
+pub const ABI_VERSION: u32 = 4;
+
+/// Bits of [`KernelConfig::flags`].
+pub mod config_flag {
+    /// `sys_mmap` refuses areas that are both writable and executable
+    pub const DENY_WX: u64 = 1 << 0;
+    /// tasks are killed when found executing outside their ELF text
+    pub const EXEC_TEXT_ONLY: u64 = 1 << 1;
+}
+
+/// Bits of the `flags` of `sys_waitpid`.
+pub mod wait_flag {
+    /// return -2 instead of blocking while no child waited for has exited
+    pub const WNOHANG: usize = 1 << 0;
+}
+
+/// Values of [`KernelConfig::sched_policy`].
+pub mod sched_policy {
+    /// the first `Ready` task after the current one runs next
+    pub const ROUND_ROBIN: u32 = 0;
+    /// the `Ready` task with the smallest pass runs next, see `sys_set_priority`
+    pub const STRIDE: u32 = 1;
+}
+
+/// Values of [`KernelConfig::null_policy`], see `NullMmapPolicy`.
+pub mod null_policy {
+    pub const NULL_INVALID: u32 = 0;
+    pub const NULL_MEANS_AUTO: u32 = 1;
+}
+
+/// How the kernel was configured at build time, see `sys_kernel_config`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct KernelConfig {
+    /// [`ABI_VERSION`]
+    pub abi_version: u32,
+    /// one of [`sched_policy`]
+    pub sched_policy: u32,
+    /// timer ticks per second of scheduler profile 0
+    pub ticks_per_sec: u32,
+    /// timer ticks in a time slice, see `TICKS_PER_SLICE`
+    pub ticks_per_slice: u32,
+    /// one of [`null_policy`]
+    pub null_policy: u32,
+    /// ASID bits the user spaces are tagged with, 0 if they are not
+    pub asid_bits: u32,
+    /// name of the feature profile, NUL padded
+    pub profile: [u8; 16],
+    /// bits of [`kernel_feature`] this kernel was built with
+    pub features: u64,
+    /// bits of [`config_flag`]
+    pub flags: u64,
+    /// most pages a task may hold in its user stack and mmap areas
+    pub mmap_max_pages: u64,
+    /// mmap areas of at least this many pages are populated lazily
+    pub mmap_lazy_pages: u64,
+}
+
+/// Kernel features an app needs to run, read when the app is loaded; an
+/// app without one loads anywhere.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct KernelRequirements {
+    /// [`KREQ_MAGIC`]
+    pub magic: u32,
+    pub reserved: u32,
+    /// bits of [`kernel_feature`]
+    pub features: u64,
+}
+
+/// Snapshot of the calling task, see `sys_task_info`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct TaskInfo {
+    pub status: TaskStatus,
+    /// how often each syscall was made, this `sys_task_info` included
+    pub syscall_times: [u32; MAX_SYSCALL_NUM],
+    /// milliseconds since the task was first dispatched
+    pub time: usize,
+    /// milliseconds the task ran in user mode
+    pub user_time: usize,
+    /// milliseconds the kernel ran on the task's behalf, this call included
+    pub kernel_time: usize,
+    /// times the task yielded the processor
+    pub voluntary_switches: usize,
+    /// times the task was suspended at the end of its time slice
+    pub preempted_switches: usize,
+    /// app name, or the task id if it has none, truncated and NUL terminated
+    pub name: [u8; TASK_NAME_LEN],
+    /// page faults taken, the resolved ones like first touches of lazy
+    /// pages included
+    pub page_faults: usize,
+}
+
+/// Size of [`TaskInfo::name`], its NUL included.
+pub const TASK_NAME_LEN: usize = 32;
+
+/// One syscall recorded in a task's trace ring, see `sys_trace_read`.
+#[repr(C)]
+#[derive(Copy, Clone, Default, Debug)]
+pub struct TraceEntry {
+    /// 1 for the first syscall recorded since tracing was enabled, counting
+    /// up; an entry whose number is not the expected one was torn
+    pub seq: u32,
+    /// microseconds since the previous entry, saturating
+    pub delta_us: u32,
+    pub syscall_id: u32,
+    /// low 32 bits of the first argument
+    pub arg0: u32,
+    pub ret: i64,
+}
+
+/// Frames released by `sys_munmap2`.
+#[repr(C)]
+#[derive(Copy, Clone, Default, Debug)]
+pub struct MunmapStats {
+    /// frames that held data of the unmapped pages
+    pub data_frames: u64,
+    /// intermediate page-table frames the unmap left empty
+    pub table_frames: u64,
+}
+
+/// Address-space summary of a task, see `sys_mem_info`.
+#[repr(C)]
+#[derive(Copy, Clone, Default, Debug)]
+pub struct MemInfo {
+    /// pages held by the user stack and mmap areas
+    pub user_pages: u64,
+    /// most `user_pages` held so far
+    pub peak_pages: u64,
+    /// start of the largest free range of the mmap window, 0 if it is full
+    pub gap_start: u64,
+    /// length of that range in bytes
+    pub gap_len: u64,
+    /// frames of the whole system: handed out, free and all of them
+    pub frames_used: u64,
+    pub frames_free: u64,
+    pub frames_total: u64,
+}
+
+/// Samples the profiler took in one address bucket, see `sys_profile_read`.
+#[repr(C)]
+#[derive(Copy, Clone, Default, Debug)]
+pub struct ProfileBucket {
+    /// lowest user address of the bucket
+    pub base: u64,
+    pub samples: u64,
+}
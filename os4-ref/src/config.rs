@@ -1,15 +1,69 @@
 //! Constants used in rCore
 
+use crate::abi::{
+    config_flag, null_policy, sched_policy, KernelConfig, ABI_VERSION, KERNEL_FEATURES,
+};
+use crate::mm::{asid_bits, MapPolicy, RecyclePolicy};
+use crate::task::CapSet;
+
 pub const USER_STACK_SIZE: usize = 4096 * 2;
 pub const KERNEL_STACK_SIZE: usize = 4096 * 20;
+/// Stack left below which a probed kernel path panics in debug builds.
+pub const KERNEL_STACK_RED_LINE: usize = 4096;
+/// Share of a task's kernel stack, in percent, past which the
+/// `stack-watermark` feature warns about the task.
+pub const KERNEL_STACK_WARN_PERCENT: usize = 87;
 pub const KERNEL_HEAP_SIZE: usize = 0x30_0000;
 pub const MEMORY_END: usize = 0x80800000;
+/// The sifive test device of the qemu virt board, through which the
+/// `board_test` self-tests stop qemu with an exit status.
+#[cfg(feature = "board_test")]
+pub const VIRT_TEST: usize = 0x10_0000;
+/// Exit status of qemu once every `board_test` self-test passed.
+#[cfg(feature = "board_test")]
+pub const KERNEL_TEST_PASS_CODE: u32 = 33;
+/// Order in which freed frames are reused; switch to `Fifo` when chasing
+/// a missing flush, since `Lifo` hands the just-freed frame right back.
+pub const FRAME_RECYCLE_POLICY: RecyclePolicy = RecyclePolicy::DEFAULT;
 pub const PAGE_SIZE: usize = 0x1000;
 pub const PAGE_SIZE_BITS: usize = 0xc;
 pub const MAX_SYSCALL_NUM: usize = 500;
+/// Log2 of the size of an address bucket of the sampling profiler.
+pub const PROFILE_BUCKET_SHIFT: usize = 12;
+/// Address buckets each task's profile has room for.
+pub const PROFILE_BUCKETS: usize = 64;
+/// Trace rings lent to tasks at once, see `sys_trace_ctl`.
+pub const TRACE_POOL_RINGS: usize = 8;
+/// Whether every task starts out with its syscalls logged, as if it had
+/// called `sys_strace` first.
+pub const TRACE_SYSCALLS: bool = false;
+/// Profiles lent to tasks at once; a task sampled while none is left
+/// runs unprofiled.
+pub const PROFILE_POOL_SIZE: usize = 32;
+/// Free frames below which memory pressure is `Low`, see `sys_mem_pressure`.
+pub const PRESSURE_LOW_FRAMES: usize = 256;
+/// Free frames below which memory pressure is `Critical`.
+pub const PRESSURE_CRITICAL_FRAMES: usize = 64;
+/// Frames above a threshold needed to drop back below its level, so the
+/// level does not flap with every frame.
+pub const PRESSURE_HYSTERESIS_FRAMES: usize = 32;
+/// Tasks mirrored in `KERNEL_DEBUG_MIRROR`.
+pub const DEBUG_MIRROR_TASKS: usize = 64;
+/// Tasks `sys_spawn` may add on top of the apps loaded at boot.
+pub const MAX_SPAWNED_TASKS: usize = 16;
+/// Low bits of a task id, which name its slot; the bits above count the
+/// restarts of the slot, so that an id held across a restart names no task
+/// rather than the new run, see `sys_task_restart`.
+pub const TASK_SLOT_BITS: usize = 16;
+/// Pages a syscall copies between two checks whether it should yield.
+pub const COPY_PAGES_PER_YIELD_CHECK: usize = 16;
 
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 pub const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE;
+/// End of the lower half of the SV39 address space, which holds every user
+/// area but the trap context. Addresses from here up to the upper half are
+/// not canonical, and a page table walk would alias them into the upper half.
+pub const USER_SPACE_END: usize = 1 << 38;
 /// Return (bottom, top) of a kernel stack in kernel space.
 pub fn kernel_stack_position(app_id: usize) -> (usize, usize) {
     let top = TRAMPOLINE - app_id * (KERNEL_STACK_SIZE + PAGE_SIZE);
@@ -18,3 +72,135 @@ pub fn kernel_stack_position(app_id: usize) -> (usize, usize) {
 }
 
 pub const CLOCK_FREQ: usize = 12500000;
+/// Timer interrupts per second for each scheduler profile, indexed by the
+/// `sys_reboot` flags; profile 0 is used at boot.
+pub const TIME_SLICE_PROFILES: [usize; 2] = [100, 1000];
+/// Timer ticks a task runs for before it is preempted.
+pub const TICKS_PER_SLICE: usize = 4;
+/// Pick the `Ready` task with the smallest pass instead of plain round robin.
+pub const SCHED_STRIDE: bool = true;
+/// Whether the scheduling policy may keep a `Ready` task waiting on purpose;
+/// neither round robin nor stride does, so a long wait is a scheduler bug.
+pub const SCHED_STARVATION_EXPECTED: bool = false;
+/// Time slices a task may wait `Ready` before a starvation warning.
+pub const STARVATION_SLICES: usize = 256;
+/// Pass a task advances by per dispatch at priority 1.
+pub const BIG_STRIDE: usize = 1 << 20;
+/// Priority every task starts with.
+pub const DEFAULT_PRIORITY: usize = 16;
+/// Clock ticks per second that `sys_times` reports times in.
+pub const CLOCK_TICKS_PER_SEC: usize = 100;
+
+/// Refuse `sys_mmap` areas that are both writable and executable.
+pub const MMAP_DENY_WX: bool = false;
+/// Upper bound on pages a task may hold in its user stack and mmap areas.
+pub const MMAP_MAX_PAGES: usize = 0x10000;
+/// Pages from which on an mmap area gets its frames at the first access
+/// instead of right away.
+pub const MMAP_LAZY_PAGES: usize = 256;
+/// Lowest address considered when the kernel picks an mmap address itself.
+pub const MMAP_AUTO_BASE: usize = 0x1000_0000;
+/// Move each task's user stack, and where the kernel starts looking when it
+/// picks an mmap address, up by a random number of pages; off for runs that
+/// must lay out every address space the same way, such as grading.
+pub const ASLR: bool = true;
+/// Most pages [`ASLR`] moves an area by.
+pub const ASLR_WINDOW_PAGES: usize = 256;
+/// Shared memory segments that can exist at once, see `sys_shm_create`.
+pub const SHM_MAX_SEGMENTS: usize = 16;
+/// Pages a shared memory segment may have.
+pub const SHM_MAX_PAGES: usize = 64;
+/// Message queues that can exist at once, see `sys_mq_create`.
+pub const MQ_MAX_QUEUES: usize = 16;
+/// Kernel heap the message queues a task created may hold, in bytes.
+pub const MQ_MAX_BYTES: usize = 64 * 1024;
+
+/// Default for killing tasks found executing outside their ELF text.
+pub const EXEC_TEXT_ONLY: bool = false;
+
+/// Capabilities every app starts with.
+pub const CAP_DEFAULT: CapSet = CapSet::empty();
+/// Extra capabilities granted to apps by name.
+pub const CAP_GRANTS: &[(&str, CapSet)] = &[
+    ("ch4b_cap_drop", CapSet::REBOOT),
+    ("ch4b_kill", CapSet::CROSS_TASK_CONTROL),
+    ("ch4b_restart", CapSet::CROSS_TASK_CONTROL),
+    ("ch4b_selfcheck", CapSet::DEBUG_INSPECT),
+    ("ch4b_waitpid", CapSet::CROSS_TASK_CONTROL),
+];
+/// App that may shut the kernel down besides task 0, see `sys_shutdown`.
+pub const SHUTDOWN_APP: &str = "init";
+
+/// How `sys_mmap` treats a `start` of 0.
+#[allow(dead_code)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum NullMmapPolicy {
+    /// let the kernel pick a free address and return it
+    NullMeansAuto,
+    /// reject the request like any other reserved address
+    NullInvalid,
+}
+
+pub const MMAP_NULL_POLICY: NullMmapPolicy = NullMmapPolicy::NullInvalid;
+
+/// Feature profile the kernel was built with.
+pub const FEATURE_PROFILE: &str = if cfg!(feature = "minimal") {
+    "minimal"
+} else if cfg!(feature = "full") {
+    "full"
+} else {
+    "custom"
+};
+/// Every diagnostic feature and whether it is enabled.
+pub const FEATURES: [(&str, bool); 6] = [
+    ("kstat", cfg!(feature = "kstat")),
+    ("dispatch-check", cfg!(feature = "dispatch-check")),
+    ("trace", cfg!(feature = "trace")),
+    ("profile", cfg!(feature = "profile")),
+    ("stack-watermark", cfg!(feature = "stack-watermark")),
+    ("board_test", cfg!(feature = "board_test")),
+];
+
+/// The configuration `sys_kernel_config` reports and the boot banner shows.
+///
+/// The default task policy is taken apart field by field, so a new policy
+/// field does not compile until it is either reported here or left out on
+/// purpose.
+pub fn kernel_config() -> KernelConfig {
+    let MapPolicy {
+        deny_wx,
+        max_pages,
+        exec_text_only,
+        lazy_pages,
+    } = MapPolicy::default();
+    let mut flags = 0;
+    if deny_wx {
+        flags |= config_flag::DENY_WX;
+    }
+    if exec_text_only {
+        flags |= config_flag::EXEC_TEXT_ONLY;
+    }
+    let null = match MMAP_NULL_POLICY {
+        NullMmapPolicy::NullInvalid => null_policy::NULL_INVALID,
+        NullMmapPolicy::NullMeansAuto => null_policy::NULL_MEANS_AUTO,
+    };
+    let mut profile = [0; 16];
+    profile[..FEATURE_PROFILE.len()].copy_from_slice(FEATURE_PROFILE.as_bytes());
+    KernelConfig {
+        abi_version: ABI_VERSION,
+        sched_policy: if SCHED_STRIDE {
+            sched_policy::STRIDE
+        } else {
+            sched_policy::ROUND_ROBIN
+        },
+        ticks_per_sec: TIME_SLICE_PROFILES[0] as u32,
+        ticks_per_slice: TICKS_PER_SLICE as u32,
+        null_policy: null,
+        asid_bits: asid_bits(),
+        profile,
+        features: KERNEL_FEATURES,
+        flags,
+        mmap_max_pages: max_pages as u64,
+        mmap_lazy_pages: lazy_pages as u64,
+    }
+}
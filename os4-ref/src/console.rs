@@ -1,5 +1,6 @@
 //! SBI console driver, for text output
 
+use crate::kstat::{self, KSTAT};
 use crate::sbi::console_putchar;
 use core::fmt::{self, Write};
 
@@ -18,6 +19,17 @@ pub fn print(args: fmt::Arguments) {
     Stdout.write_fmt(args).unwrap();
 }
 
+/// Print `bytes` on behalf of an app, counting them.
+///
+/// The bytes go out as they are, not as `str`: neither may a buffer a page
+/// boundary splits hold whole characters, nor is an app bound to UTF-8.
+pub fn print_app(bytes: &[u8]) {
+    for &byte in bytes {
+        console_putchar(byte as usize);
+    }
+    kstat::add(&KSTAT.app_output_bytes, bytes.len());
+}
+
 #[macro_export]
 /// print string macro
 macro_rules! print {
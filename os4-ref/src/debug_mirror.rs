@@ -0,0 +1,161 @@
+//! Debugger-friendly mirror of the scheduler state
+//!
+//! [`KERNEL_DEBUG_MIRROR`] is a plain, unmangled static updated with
+//! volatile stores, so GDB attached to QEMU (`p KERNEL_DEBUG_MIRROR`) or a
+//! raw memory dump shows where boot got to and what every task was doing,
+//! even when the console or the task manager lock are wedged.
+//!
+//! Layout, fixed by the size checks below:
+//!
+//! | offset | field          |
+//! |--------|----------------|
+//! | 0      | `magic`        |
+//! | 4      | `boot_stage`   |
+//! | 8      | `current_task` |
+//! | 16     | `epoch`        |
+//! | 24     | `num_tasks`    |
+//! | 32     | `tasks[0]`, 16 bytes each: `pid` at 0, `status` at 4, `from_status` at 5, `sepc` at 8 |
+
+use crate::config::DEBUG_MIRROR_TASKS;
+use crate::task::TaskStatus;
+use core::mem::size_of;
+use core::ptr::{addr_of, addr_of_mut, read_volatile, write_volatile};
+
+/// "KDBG" in little endian, written once boot starts
+pub const DEBUG_MIRROR_MAGIC: u32 = u32::from_le_bytes(*b"KDBG");
+
+/// How far boot got, in order.
+#[repr(u32)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BootStage {
+    BssCleared = 1,
+    MemoryReady = 2,
+    TrapReady = 3,
+    TasksRunning = 4,
+    /// every task exited, the summary is being printed
+    Shutdown = 5,
+    /// the kernel panicked before reaching `Shutdown`
+    Panicked = 6,
+}
+
+/// One task of the mirror; status bytes are `TaskStatus as u8`.
+#[repr(C)]
+pub struct DebugTaskRecord {
+    pub pid: u32,
+    pub status: u8,
+    /// status the task left at its last transition
+    pub from_status: u8,
+    _pad: [u8; 2],
+    /// user pc saved in the trap context at the last transition
+    pub sepc: u64,
+}
+
+/// The whole mirror, see the module documentation for offsets.
+#[repr(C)]
+pub struct DebugMirror {
+    pub magic: u32,
+    pub boot_stage: u32,
+    pub current_task: u64,
+    pub epoch: u64,
+    /// tasks loaded, only the first [`DEBUG_MIRROR_TASKS`] are mirrored
+    pub num_tasks: u64,
+    pub tasks: [DebugTaskRecord; DEBUG_MIRROR_TASKS],
+}
+
+const _: () = assert!(size_of::<DebugTaskRecord>() == 16);
+const _: () = assert!(size_of::<DebugMirror>() == 32 + 16 * DEBUG_MIRROR_TASKS);
+
+const EMPTY_RECORD: DebugTaskRecord = DebugTaskRecord {
+    pid: 0,
+    status: 0,
+    from_status: 0,
+    _pad: [0; 2],
+    sepc: 0,
+};
+
+/// the mirror itself, only ever written through the functions below
+#[no_mangle]
+pub static mut KERNEL_DEBUG_MIRROR: DebugMirror = DebugMirror {
+    magic: 0,
+    boot_stage: 0,
+    current_task: 0,
+    epoch: 0,
+    num_tasks: 0,
+    tasks: [EMPTY_RECORD; DEBUG_MIRROR_TASKS],
+};
+
+/// Record that boot reached `stage`; a panic after `Shutdown` is the
+/// normal end and does not count as `Panicked`.
+pub fn set_boot_stage(stage: BootStage) {
+    unsafe {
+        let mirror = addr_of_mut!(KERNEL_DEBUG_MIRROR);
+        if stage == BootStage::Panicked && (*mirror).boot_stage == BootStage::Shutdown as u32 {
+            return;
+        }
+        write_volatile(addr_of_mut!((*mirror).magic), DEBUG_MIRROR_MAGIC);
+        write_volatile(addr_of_mut!((*mirror).boot_stage), stage as u32);
+    }
+}
+
+/// The stage boot reached, as `BootStage as u32`, 0 before it started.
+pub fn boot_stage() -> u32 {
+    unsafe { read_volatile(addr_of!(KERNEL_DEBUG_MIRROR.boot_stage)) }
+}
+
+/// Record that `count` tasks were loaded, all of them `UnInit` or `Ready`.
+pub fn set_num_tasks(count: usize) {
+    unsafe {
+        let mirror = addr_of_mut!(KERNEL_DEBUG_MIRROR);
+        write_volatile(addr_of_mut!((*mirror).num_tasks), count as u64);
+        for id in 0..count.min(DEBUG_MIRROR_TASKS) {
+            write_volatile(
+                addr_of_mut!((*mirror).tasks[id]),
+                DebugTaskRecord {
+                    pid: id as u32,
+                    ..EMPTY_RECORD
+                },
+            );
+        }
+    }
+}
+
+/// Record that task `id` was added after the others.
+pub fn add_task(id: usize) {
+    unsafe {
+        let mirror = addr_of_mut!(KERNEL_DEBUG_MIRROR);
+        write_volatile(addr_of_mut!((*mirror).num_tasks), id as u64 + 1);
+        if id < DEBUG_MIRROR_TASKS {
+            write_volatile(
+                addr_of_mut!((*mirror).tasks[id]),
+                DebugTaskRecord {
+                    pid: id as u32,
+                    ..EMPTY_RECORD
+                },
+            );
+        }
+    }
+}
+
+/// Record a status transition of task `id`, see `TaskManagerInner::set_status`;
+/// a task becoming `Running` is the current task from now on.
+pub fn record_transition(id: usize, from: TaskStatus, to: TaskStatus, sepc: usize, epoch: usize) {
+    unsafe {
+        let mirror = addr_of_mut!(KERNEL_DEBUG_MIRROR);
+        if to == TaskStatus::Running {
+            write_volatile(addr_of_mut!((*mirror).current_task), id as u64);
+        }
+        write_volatile(addr_of_mut!((*mirror).epoch), epoch as u64);
+        if id < DEBUG_MIRROR_TASKS {
+            write_volatile(
+                addr_of_mut!((*mirror).tasks[id]),
+                DebugTaskRecord {
+                    pid: id as u32,
+                    status: to as u8,
+                    from_status: from as u8,
+                    _pad: [0; 2],
+                    sepc: sepc as u64,
+                },
+            );
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Kernel self-tests, run at boot with the `board_test` feature
+//!
+//! A test is a function returning a [`TestResult`], listed once in
+//! [`TESTS`]; its checks return early with a message naming their file and
+//! line, see `ktest_assert!` and `ktest_assert_eq!`. The tests run once
+//! memory management is up, before any task ran: the syscall replays and
+//! the task manager tests build the task manager and make tasks current,
+//! which is why they come last.
+//! The first test to fail stops the boot with a panic. Once all of them passed, the
+//! kernel leaves qemu through its test device with exit status
+//! [`KERNEL_TEST_PASS_CODE`], which neither a plain shutdown nor a panic
+//! gives; `make ktest` checks for it.
+
+use crate::config::{KERNEL_TEST_PASS_CODE, VIRT_TEST};
+use crate::mm::kernel_tests::*;
+use crate::syscall::kernel_tests::*;
+use crate::task::kernel_tests::*;
+use alloc::string::String;
+
+/// what a test returns, the message of its first failed check as error
+pub type TestResult = Result<(), String>;
+
+/// Fail the running test unless `cond` holds, with the condition or the
+/// given message.
+macro_rules! ktest_assert {
+    ($cond:expr) => {
+        ktest_assert!($cond, "{}", stringify!($cond))
+    };
+    ($cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            return Err(alloc::format!(
+                "{}:{}: {}",
+                file!(),
+                line!(),
+                format_args!($($arg)+)
+            ));
+        }
+    };
+}
+
+/// Fail the running test unless both sides are equal, printing both, and
+/// the given message if any.
+macro_rules! ktest_assert_eq {
+    ($left:expr, $right:expr) => {
+        ktest_assert_eq!($left, $right, "")
+    };
+    ($left:expr, $right:expr, $($arg:tt)+) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if left != right {
+                    return Err(alloc::format!(
+                        "{}:{}: {} == {} failed, {:?} != {:?} {}",
+                        file!(),
+                        line!(),
+                        stringify!($left),
+                        stringify!($right),
+                        left,
+                        right,
+                        format_args!($($arg)+)
+                    ));
+                }
+            }
+        }
+    };
+}
+
+/// A test and the name it is reported under.
+pub struct KernelTest {
+    pub name: &'static str,
+    pub run: fn() -> TestResult,
+}
+
+/// Name each of the test functions after itself.
+macro_rules! tests {
+    ($($test:ident),* $(,)?) => {
+        &[$(KernelTest {
+            name: stringify!($test),
+            run: $test,
+        }),*]
+    };
+}
+
+/// every test, in the order they run
+static TESTS: &[KernelTest] = tests![
+    vpn_range_iteration,
+    address_arithmetic,
+    page_table_round_trip,
+    frame_recycle_order,
+    area_splitting,
+    mmap_port_matrix,
+    checked_insert_refusals,
+    selfcheck_injected,
+    replay_get_time,
+    replay_task_info,
+    replay_mmap,
+    replay_errno,
+    dispatch_check_faults,
+    app_catalog_bounds,
+    running_unique,
+    selfcheck_sched_injected,
+    mq_fifo_receivers,
+    mq_fifo_senders,
+    mq_kill_blocked,
+    syscall_ids_past_table,
+    sleepers_equal_deadlines,
+    sleepers_drain_in_order,
+    exit_races_wakes,
+    restart_stale_ids,
+    spawned_past_boot,
+];
+
+/// Run `tests` in order, printing PASS or FAIL for each; the first to fail
+/// panics with its message.
+pub fn test_runner(tests: &[KernelTest]) {
+    println!("[kernel test] running {} tests", tests.len());
+    for test in tests {
+        match (test.run)() {
+            Ok(()) => {
+                println!("[kernel test] {} ... PASS", test.name);
+            }
+            Err(message) => {
+                println!("[kernel test] {} ... FAIL", test.name);
+                panic!("kernel test {} failed: {}", test.name, message);
+            }
+        }
+    }
+    println!("[kernel test] all {} tests passed", tests.len());
+}
+
+/// Run every test, then leave qemu with [`KERNEL_TEST_PASS_CODE`]. This
+/// never returns, but is not declared `-> !` so that the boot code after
+/// the call is not flagged as unreachable.
+pub fn run() {
+    test_runner(TESTS);
+    exit_qemu(KERNEL_TEST_PASS_CODE)
+}
+
+/// Stop qemu with exit status `code` through the sifive test device at
+/// [`VIRT_TEST`], which the kernel space maps with this feature.
+fn exit_qemu(code: u32) -> ! {
+    unsafe {
+        (VIRT_TEST as *mut u32).write_volatile(code << 16 | 0x3333);
+    }
+    panic!("the qemu test device did not stop qemu");
+}
@@ -0,0 +1,203 @@
+//! Kernel-wide statistics
+//!
+//! Counters here are plain atomics so that they can be updated from trap
+//! context without borrowing the task manager.
+//!
+//! A soft reboot closes the current boot pass with [`end_pass()`]: its
+//! counters are kept in kernel memory, which a soft reboot does not touch, and
+//! printed next to the later passes at shutdown.
+
+use crate::sync::UPSafeCell;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use lazy_static::*;
+
+/// global kernel counters
+pub struct KernelStats {
+    /// timer interrupts taken
+    pub ticks: AtomicUsize,
+    /// longest interval from trap entry to return to user mode, in cycles
+    pub max_trap_cycles: AtomicUsize,
+    /// longest run of the timer bottom half, in cycles
+    pub max_bottom_half_cycles: AtomicUsize,
+    /// moving average of `Ready` tasks sampled once per tick, scaled by 256
+    pub ready_ewma: AtomicUsize,
+    /// user page translations served from a memory set's translate cache
+    pub translate_hits: AtomicUsize,
+    /// user page translations that walked the page table
+    pub translate_misses: AtomicUsize,
+    /// task switches after which not exactly the current task was `Running`
+    pub running_violations: AtomicUsize,
+    /// trace rings and profiles currently lent to tasks
+    pub buffers_lent: AtomicUsize,
+    /// trace rings or profiles asked for while their pool was empty
+    pub buffer_pool_misses: AtomicUsize,
+    /// empty page-table frames freed by `munmap`
+    pub tables_pruned: AtomicUsize,
+    /// cycles spent at `MemPressure::Low`, counted when the level changes
+    pub pressure_low_cycles: AtomicUsize,
+    /// cycles spent at `MemPressure::Critical`, counted when the level changes
+    pub pressure_critical_cycles: AtomicUsize,
+    /// bytes apps wrote to the console, see [`crate::console::print_app`]
+    pub app_output_bytes: AtomicUsize,
+    /// warnings about tasks kept `Ready` for too long
+    pub starvation_warnings: AtomicUsize,
+    /// task ids refused for naming an earlier run of their slot
+    pub stale_task_ids: AtomicUsize,
+}
+
+/// the global [`KernelStats`] instance
+pub static KSTAT: KernelStats = KernelStats {
+    ticks: AtomicUsize::new(0),
+    max_trap_cycles: AtomicUsize::new(0),
+    max_bottom_half_cycles: AtomicUsize::new(0),
+    ready_ewma: AtomicUsize::new(0),
+    translate_hits: AtomicUsize::new(0),
+    translate_misses: AtomicUsize::new(0),
+    running_violations: AtomicUsize::new(0),
+    buffers_lent: AtomicUsize::new(0),
+    buffer_pool_misses: AtomicUsize::new(0),
+    tables_pruned: AtomicUsize::new(0),
+    pressure_low_cycles: AtomicUsize::new(0),
+    pressure_critical_cycles: AtomicUsize::new(0),
+    app_output_bytes: AtomicUsize::new(0),
+    starvation_warnings: AtomicUsize::new(0),
+    stale_task_ids: AtomicUsize::new(0),
+};
+
+/// whether counters are kept at all, see the `kstat` feature
+pub const ENABLED: bool = cfg!(feature = "kstat");
+
+/// add one to `counter`
+pub fn inc(counter: &AtomicUsize) {
+    if ENABLED {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// add `value` to `counter`
+pub fn add(counter: &AtomicUsize, value: usize) {
+    if ENABLED {
+        counter.fetch_add(value, Ordering::Relaxed);
+    }
+}
+
+/// set the gauge `counter` to `value`
+pub fn set(counter: &AtomicUsize, value: usize) {
+    if ENABLED {
+        counter.store(value, Ordering::Relaxed);
+    }
+}
+
+/// raise `counter` to `value` if `value` is larger
+pub fn record_max(counter: &AtomicUsize, value: usize) {
+    if ENABLED {
+        counter.fetch_max(value, Ordering::Relaxed);
+    }
+}
+
+/// number of counters in [`KernelStats`]
+const NUM_COUNTERS: usize = 15;
+
+/// names of the counters, in the order of [`KernelStats::counters()`]
+const COUNTER_NAMES: [&str; NUM_COUNTERS] = [
+    "ticks",
+    "max trap cycles",
+    "max bottom half cycles",
+    "ready tasks x256",
+    "translate hits",
+    "translate misses",
+    "running violations",
+    "buffers lent",
+    "buffer pool misses",
+    "tables pruned",
+    "low pressure cycles",
+    "critical pressure cycles",
+    "app output bytes",
+    "starvation warnings",
+    "stale task ids",
+];
+
+impl KernelStats {
+    /// all counters, in the order of [`COUNTER_NAMES`]
+    fn counters(&self) -> [&AtomicUsize; NUM_COUNTERS] {
+        [
+            &self.ticks,
+            &self.max_trap_cycles,
+            &self.max_bottom_half_cycles,
+            &self.ready_ewma,
+            &self.translate_hits,
+            &self.translate_misses,
+            &self.running_violations,
+            &self.buffers_lent,
+            &self.buffer_pool_misses,
+            &self.tables_pruned,
+            &self.pressure_low_cycles,
+            &self.pressure_critical_cycles,
+            &self.app_output_bytes,
+            &self.starvation_warnings,
+            &self.stale_task_ids,
+        ]
+    }
+}
+
+/// plain copy of the counters at one point in time
+pub type Snapshot = [usize; NUM_COUNTERS];
+
+lazy_static! {
+    /// counters of the boot passes finished by a soft reboot
+    static ref PASSES: UPSafeCell<Vec<Snapshot>> = unsafe { UPSafeCell::new(Vec::new()) };
+}
+
+/// copy the current counters
+pub fn snapshot() -> Snapshot {
+    KSTAT.counters().map(|c| c.load(Ordering::Relaxed))
+}
+
+/// keep the counters of the current boot pass and restart them from zero
+pub fn end_pass() {
+    PASSES.exclusive_access().push(snapshot());
+    for counter in KSTAT.counters() {
+        counter.store(0, Ordering::Relaxed);
+    }
+}
+
+/// number of boot passes finished so far
+pub fn finished_passes() -> usize {
+    PASSES.exclusive_access().len()
+}
+
+/// print one line of `name: value` pairs
+fn print_line<T: core::fmt::Display>(prefix: &str, values: impl Iterator<Item = T>) {
+    let mut line = String::new();
+    for (i, (name, value)) in COUNTER_NAMES.iter().zip(values).enumerate() {
+        let sep = if i == 0 { "" } else { ", " };
+        write!(line, "{}{}: {}", sep, name, value).unwrap();
+    }
+    println!("[kernel] {}{}", prefix, line);
+}
+
+/// print all counters, one line per boot pass followed by the change of the
+/// last pass against the first one
+pub fn print() {
+    if !ENABLED {
+        return;
+    }
+    let passes = PASSES.exclusive_access();
+    let last = snapshot();
+    if passes.is_empty() {
+        print_line("", last.iter());
+        return;
+    }
+    for (i, pass) in passes.iter().chain(core::iter::once(&last)).enumerate() {
+        print_line(&alloc::format!("pass {}: ", i), pass.iter());
+    }
+    let first = &passes[0];
+    let delta = last
+        .iter()
+        .zip(first.iter())
+        .map(|(a, b)| alloc::format!("{:+}", *a as isize - *b as isize));
+    print_line(&alloc::format!("pass {} - pass 0: ", passes.len()), delta);
+}
@@ -1,11 +1,28 @@
 //! The panic handler
+//!
+//! Besides the message, a panic prints the current task with its trap
+//! context, if tasks are running, and a backtrace of the kernel stack from
+//! the frame pointers, which the kernel is built with.
 
+use crate::debug_mirror::{self, BootStage};
 use crate::sbi::shutdown;
+use crate::stack_probe::stack_bounds;
+use crate::task::try_current_task;
+use crate::trap::dump_trap_cx;
 use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Frames printed at most, in case the frame records loop.
+const BACKTRACE_DEPTH: usize = 32;
+
+/// set by the first panic; a panic while reporting only prints its message
+static PANICKING: AtomicBool = AtomicBool::new(false);
 
 #[panic_handler]
 /// panic handler
 fn panic(info: &PanicInfo) -> ! {
+    let stage = debug_mirror::boot_stage();
+    debug_mirror::set_boot_stage(BootStage::Panicked);
     if let Some(location) = info.location() {
         println!(
             "Panicked at {}:{} {}",
@@ -16,5 +33,56 @@ fn panic(info: &PanicInfo) -> ! {
     } else {
         println!("[kernel] Panicked: {}", info.message().unwrap());
     }
-    shutdown()
+    if !PANICKING.swap(true, Ordering::Relaxed) {
+        // the task manager only exists once tasks run
+        if stage == BootStage::TasksRunning as u32 || stage == BootStage::Shutdown as u32 {
+            print_current_task();
+        }
+        print_backtrace();
+    }
+    shutdown(true)
+}
+
+fn print_current_task() {
+    match try_current_task() {
+        Some((task, status, cx)) => {
+            println!(
+                "[kernel] current task {}, {:?}, user pc {:#x}, sp {:#x}",
+                task, status, cx.sepc, cx.x[2]
+            );
+            dump_trap_cx(cx);
+        }
+        None => {
+            println!("[kernel] task manager in use, current task unknown");
+        }
+    }
+}
+
+/// Walk the frame records from the current `fp`, printing each return
+/// address, up to the top of the kernel stack `fp` lies on.
+fn print_backtrace() {
+    let mut fp: usize;
+    unsafe { core::arch::asm!("mv {}, fp", out(reg) fp) };
+    let (bottom, top) = match stack_bounds(fp) {
+        Some(bounds) => bounds,
+        None => {
+            println!("[kernel] no backtrace, fp {:#x} is on no kernel stack", fp);
+            return;
+        }
+    };
+    println!("[kernel] backtrace:");
+    for _ in 0..BACKTRACE_DEPTH {
+        // the record right below fp holds the return address and the
+        // caller's fp; the outermost frame's caller fp is not a kernel one
+        if fp % 8 != 0 || fp < bottom + 16 || fp > top {
+            break;
+        }
+        let (ra, caller_fp) =
+            unsafe { (*((fp - 8) as *const usize), *((fp - 16) as *const usize)) };
+        println!("  {:#x}", ra);
+        if caller_fp <= fp {
+            break;
+        }
+        fp = caller_fp;
+    }
 }
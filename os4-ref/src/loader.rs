@@ -1,22 +1,58 @@
 //! Loading user applications into memory
 
-/// Get the total number of applications.
-pub fn get_num_app() -> usize {
+use crate::abi::{KERNEL_FEATURES, KREQ_MAGIC, KREQ_SECTION};
+use crate::config::{PAGE_SIZE, USER_SPACE_END};
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use lazy_static::*;
+
+/// Get the total number of applications linked into the kernel image.
+fn get_num_app() -> usize {
     extern "C" {
         fn _num_app();
     }
     unsafe { (_num_app as usize as *const usize).read_volatile() }
 }
 
+/// Get the name of an application, as its file name without extension;
+/// empty if there is no such app or its name is not printable ASCII.
+pub fn get_app_name(app_id: usize) -> &'static str {
+    extern "C" {
+        fn _app_names();
+    }
+    if app_id >= get_num_app() {
+        return "";
+    }
+    // names are stored back to back, each terminated by a NUL byte
+    let mut start = _app_names as usize as *const u8;
+    unsafe {
+        for _ in 0..app_id {
+            while start.read_volatile() != b'\0' {
+                start = start.add(1);
+            }
+            start = start.add(1);
+        }
+        let mut end = start;
+        while end.read_volatile() != b'\0' {
+            end = end.add(1);
+        }
+        let name = core::slice::from_raw_parts(start, end as usize - start as usize);
+        if name.iter().all(|byte| byte.is_ascii_graphic()) {
+            core::str::from_utf8_unchecked(name)
+        } else {
+            ""
+        }
+    }
+}
+
 /// get applications data
-pub fn get_app_data(app_id: usize) -> &'static [u8] {
+fn get_app_data(app_id: usize) -> &'static [u8] {
     extern "C" {
         fn _num_app();
     }
     let num_app_ptr = _num_app as usize as *const usize;
     let num_app = get_num_app();
     let app_start = unsafe { core::slice::from_raw_parts(num_app_ptr.add(1), num_app + 1) };
-    assert!(app_id < num_app);
     unsafe {
         core::slice::from_raw_parts(
             app_start[app_id] as *const u8,
@@ -24,3 +60,262 @@ pub fn get_app_data(app_id: usize) -> &'static [u8] {
         )
     }
 }
+
+/// Get read-only data blob `id`, numbered by file name like the apps, for
+/// `sys_mmap_data`; `None` if there is no such blob.
+pub fn get_blob(id: usize) -> Option<&'static [u8]> {
+    extern "C" {
+        fn _num_blob();
+    }
+    let num_blob_ptr = _num_blob as usize as *const usize;
+    let num_blob = unsafe { num_blob_ptr.read_volatile() };
+    if id >= num_blob {
+        return None;
+    }
+    let blob_start = unsafe { core::slice::from_raw_parts(num_blob_ptr.add(1), num_blob + 1) };
+    Some(unsafe {
+        core::slice::from_raw_parts(
+            blob_start[id] as *const u8,
+            blob_start[id + 1] - blob_start[id],
+        )
+    })
+}
+
+/// Why an app linked into the kernel was not put in the catalog.
+#[derive(Clone, Copy, Debug)]
+pub enum SkipReason {
+    InvalidElf,
+    /// its requirements section is there but malformed
+    InvalidRequirements,
+    /// it requires these kernel features, which this kernel lacks
+    MissingFeatures(u64),
+    /// its program header with this index describes a segment that cannot
+    /// be loaded
+    InvalidSegment(usize, SegmentError),
+}
+
+/// What is wrong with a loadable segment.
+#[derive(Clone, Copy, Debug)]
+pub enum SegmentError {
+    /// it has more bytes in the file than in memory
+    FileLargerThanMemory,
+    /// its bytes run past the end of the image
+    OutsideImage,
+    /// it does not end below `USER_SPACE_END`
+    OutsideUserSpace,
+    /// its address and file offset differ modulo its alignment, or the
+    /// alignment is not a power of two
+    Misaligned,
+    /// it shares a page with an earlier segment
+    Overlapping,
+}
+
+/// What the loader checked about an app's ELF header.
+#[derive(Clone, Copy, Debug)]
+pub struct ElfInfo {
+    pub entry: usize,
+    pub ph_count: usize,
+    /// pages the loadable segments span
+    pub load_pages: usize,
+    /// kernel features the app requires, see `abi::KernelRequirements`
+    pub required_features: u64,
+    /// whether `PT_GNU_STACK` asks for an executable user stack; without
+    /// that header the stack is not executable either
+    pub exec_stack: bool,
+}
+
+/// `PT_GNU_STACK`, whose flags are the ones the user stack wants
+const PT_GNU_STACK: u32 = 0x6474_e551;
+
+impl ElfInfo {
+    /// Parse the header and the requirements section, if any.
+    fn parse(data: &[u8]) -> Result<Self, SkipReason> {
+        let elf = xmas_elf::ElfFile::new(data).map_err(|_| SkipReason::InvalidElf)?;
+        if elf.header.pt1.magic != [0x7f, 0x45, 0x4c, 0x46] {
+            return Err(SkipReason::InvalidElf);
+        }
+        let required_features = match elf.find_section_by_name(KREQ_SECTION) {
+            Some(section) => {
+                parse_requirements(section.raw_data(&elf)).ok_or(SkipReason::InvalidRequirements)?
+            }
+            None => 0,
+        };
+        let mut load_pages = 0;
+        let mut exec_stack = false;
+        // page ranges of the segments checked so far
+        let mut segments: Vec<(usize, usize)> = Vec::new();
+        for i in 0..elf.header.pt2.ph_count() {
+            let ph = elf.program_header(i).map_err(|_| SkipReason::InvalidElf)?;
+            match ph.get_type() {
+                Ok(xmas_elf::program::Type::Load) => {}
+                Ok(xmas_elf::program::Type::OsSpecific(PT_GNU_STACK)) => {
+                    exec_stack = ph.flags().is_execute();
+                    continue;
+                }
+                _ => continue,
+            }
+            let pages = check_segment(data.len(), &ph)
+                .map_err(|error| SkipReason::InvalidSegment(i as usize, error))?;
+            if pages.0 == pages.1 {
+                continue;
+            }
+            if segments
+                .iter()
+                .any(|&(start, end)| pages.0 < end && start < pages.1)
+            {
+                return Err(SkipReason::InvalidSegment(
+                    i as usize,
+                    SegmentError::Overlapping,
+                ));
+            }
+            segments.push(pages);
+            load_pages += pages.1 - pages.0;
+        }
+        Ok(Self {
+            entry: elf.header.pt2.entry_point() as usize,
+            ph_count: elf.header.pt2.ph_count() as usize,
+            load_pages,
+            required_features,
+            exec_stack,
+        })
+    }
+}
+
+/// Check a loadable segment on its own, returning the pages it spans as
+/// `(start, end)` page numbers, empty for a segment with no bytes in memory.
+/// An alignment is only held to up to a page, the granularity it is mapped
+/// at.
+fn check_segment(
+    image_len: usize,
+    ph: &xmas_elf::program::ProgramHeader,
+) -> Result<(usize, usize), SegmentError> {
+    let vaddr = ph.virtual_addr() as usize;
+    let mem_size = ph.mem_size() as usize;
+    let file_size = ph.file_size() as usize;
+    let offset = ph.offset() as usize;
+    if file_size > mem_size {
+        return Err(SegmentError::FileLargerThanMemory);
+    }
+    match offset.checked_add(file_size) {
+        Some(end) if end <= image_len => {}
+        _ => return Err(SegmentError::OutsideImage),
+    }
+    let end = match vaddr.checked_add(mem_size) {
+        Some(end) if end <= USER_SPACE_END => end,
+        _ => return Err(SegmentError::OutsideUserSpace),
+    };
+    let align = ph.align() as usize;
+    if align > 1 {
+        let align = if align.is_power_of_two() {
+            align.min(PAGE_SIZE)
+        } else {
+            return Err(SegmentError::Misaligned);
+        };
+        if vaddr % align != offset % align {
+            return Err(SegmentError::Misaligned);
+        }
+    }
+    if mem_size == 0 {
+        return Ok((0, 0));
+    }
+    Ok((vaddr / PAGE_SIZE, (end + PAGE_SIZE - 1) / PAGE_SIZE))
+}
+
+/// Read the feature mask of a `KernelRequirements`, which may be unaligned.
+fn parse_requirements(raw: &[u8]) -> Option<u64> {
+    if raw.len() != 16 || u32::from_le_bytes(raw[0..4].try_into().ok()?) != KREQ_MAGIC {
+        return None;
+    }
+    Some(u64::from_le_bytes(raw[8..16].try_into().ok()?))
+}
+
+/// One validated application.
+pub struct AppEntry {
+    pub name: &'static str,
+    pub data: &'static [u8],
+    pub checksum: u32,
+    pub elf: ElfInfo,
+}
+
+/// FNV-1a over the raw image, so a later copy can be compared against it.
+fn checksum(data: &[u8]) -> u32 {
+    data.iter().fold(0x811c_9dc5, |hash: u32, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// The apps linked into the kernel, validated once at boot.
+///
+/// Everything that needs an app goes through here instead of doing
+/// symbol arithmetic on `_num_app`, so a bad index is a `None`.
+pub struct AppCatalog {
+    apps: Vec<AppEntry>,
+    /// apps left out, by name
+    skipped: Vec<(&'static str, SkipReason)>,
+}
+
+impl AppCatalog {
+    fn from_link_app() -> Self {
+        Self::from_images((0..get_num_app()).map(|i| (get_app_name(i), get_app_data(i))))
+    }
+    /// Validate `images`, each app's name and data in link order; an app
+    /// left out takes no index, the ones after it move up.
+    pub fn from_images(images: impl Iterator<Item = (&'static str, &'static [u8])>) -> Self {
+        let mut apps = Vec::new();
+        let mut skipped = Vec::new();
+        for (i, (name, data)) in images.enumerate() {
+            let checked = ElfInfo::parse(data).and_then(|elf| {
+                match elf.required_features & !KERNEL_FEATURES {
+                    0 => Ok(elf),
+                    missing => Err(SkipReason::MissingFeatures(missing)),
+                }
+            });
+            match checked {
+                Ok(elf) => apps.push(AppEntry {
+                    name,
+                    data,
+                    checksum: checksum(data),
+                    elf,
+                }),
+                Err(reason) => {
+                    error!("[kernel] app {} ({:?}) skipped: {:?}", i, name, reason);
+                    skipped.push((name, reason));
+                }
+            }
+        }
+        Self { apps, skipped }
+    }
+    /// Print the apps that were left out and why.
+    pub fn print_skipped(&self) {
+        for (name, reason) in &self.skipped {
+            println!("[kernel] app {:?} skipped: {:?}", name, reason);
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.apps.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.apps.is_empty()
+    }
+    /// The app at `index`, for the self-tests; the kernel keeps
+    /// [`AppEntry`] references rather than indices.
+    #[cfg(feature = "board_test")]
+    pub fn get(&self, index: usize) -> Option<&AppEntry> {
+        self.apps.get(index)
+    }
+    /// Look an app up by name, together with its index.
+    pub fn get_by_name(&self, name: &str) -> Option<(usize, &AppEntry)> {
+        self.apps
+            .iter()
+            .enumerate()
+            .find(|(_, app)| app.name == name)
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &AppEntry> {
+        self.apps.iter()
+    }
+}
+
+lazy_static! {
+    /// The app catalog, built on first use.
+    pub static ref APP_CATALOG: AppCatalog = AppCatalog::from_link_app();
+}
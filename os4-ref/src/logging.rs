@@ -1,4 +1,8 @@
 //! Global logger
+//!
+//! `LOG` sets the level at build time; `LOG_MUTE` lists log targets, comma
+//! separated, whose records are dropped whatever their level, e.g.
+//! `LOG_MUTE=strace` for the syscall log.
 
 use log::{self, Level, LevelFilter, Log, Metadata, Record};
 
@@ -6,8 +10,10 @@ use log::{self, Level, LevelFilter, Log, Metadata, Record};
 struct SimpleLogger;
 
 impl Log for SimpleLogger {
-    fn enabled(&self, _metadata: &Metadata) -> bool {
-        true
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        option_env!("LOG_MUTE").map_or(true, |muted| {
+            !muted.split(',').any(|target| target == metadata.target())
+        })
     }
     fn log(&self, record: &Record) {
         if !self.enabled(record.metadata()) {
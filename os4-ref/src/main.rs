@@ -27,9 +27,20 @@ extern crate log;
 
 extern crate alloc;
 
+use alloc::string::String;
+use debug_mirror::BootStage;
+
 #[macro_use]
 mod console;
+#[macro_use]
+mod stack_probe;
+#[cfg(feature = "board_test")]
+#[macro_use]
+mod kernel_tests;
+mod abi;
 mod config;
+mod debug_mirror;
+mod kstat;
 mod lang_items;
 mod loader;
 mod logging;
@@ -56,19 +67,54 @@ fn clear_bss() {
     }
 }
 
+/// print the feature profile and the enabled features, so logs say what was measured
+fn print_build_profile() {
+    let mut features = String::new();
+    for (name, _) in config::FEATURES.iter().filter(|(_, enabled)| *enabled) {
+        features.push(' ');
+        features.push_str(name);
+    }
+    println!(
+        "[kernel] profile: {}, features:{}",
+        config::FEATURE_PROFILE,
+        features
+    );
+    let kernel_config = config::kernel_config();
+    println!(
+        "[kernel] abi {}, scheduler {}, {} ticks/s, {} ticks/slice, null mmap {:?}, flags {:#x}, mmap pages {} (lazy from {})",
+        kernel_config.abi_version,
+        kernel_config.sched_policy,
+        kernel_config.ticks_per_sec,
+        kernel_config.ticks_per_slice,
+        config::MMAP_NULL_POLICY,
+        kernel_config.flags,
+        kernel_config.mmap_max_pages,
+        kernel_config.mmap_lazy_pages
+    );
+}
+
 #[no_mangle]
 /// the rust entry-point of os
 pub fn rust_main() -> ! {
     clear_bss();
+    debug_mirror::set_boot_stage(BootStage::BssCleared);
     logging::init();
     println!("[kernel] Hello, world!");
+    print_build_profile();
     mm::init();
     println!("[kernel] back to world!");
     mm::remap_test();
+    mm::user_range_test();
+    mm::map_permission_test();
+    mm::range_state_test();
+    debug_mirror::set_boot_stage(BootStage::MemoryReady);
+    #[cfg(feature = "board_test")]
+    kernel_tests::run();
     trap::init();
     //trap::enable_interrupt();
     trap::enable_timer_interrupt();
     timer::set_next_trigger();
+    debug_mirror::set_boot_stage(BootStage::TrapReady);
     task::run_first_task();
     panic!("Unreachable in rust_main!");
 }
@@ -92,8 +92,10 @@ impl VirtAddr {
     pub fn floor(&self) -> VirtPageNum {
         VirtPageNum(self.0 / PAGE_SIZE)
     }
+    /// The page number rounded up, without wrapping at either end of the
+    /// address space.
     pub fn ceil(&self) -> VirtPageNum {
-        VirtPageNum((self.0 - 1 + PAGE_SIZE) / PAGE_SIZE)
+        VirtPageNum(self.0 / PAGE_SIZE + !self.aligned() as usize)
     }
     pub fn page_offset(&self) -> usize {
         self.0 & (PAGE_SIZE - 1)
@@ -101,6 +103,10 @@ impl VirtAddr {
     pub fn aligned(&self) -> bool {
         self.page_offset() == 0
     }
+    /// `len` bytes further on, `None` past the end of the address space.
+    pub fn checked_add(self, len: usize) -> Option<VirtAddr> {
+        self.0.checked_add(len).map(VirtAddr)
+    }
 }
 impl From<VirtAddr> for VirtPageNum {
     fn from(v: VirtAddr) -> Self {
@@ -118,7 +124,7 @@ impl PhysAddr {
         PhysPageNum(self.0 / PAGE_SIZE)
     }
     pub fn ceil(&self) -> PhysPageNum {
-        PhysPageNum((self.0 - 1 + PAGE_SIZE) / PAGE_SIZE)
+        PhysPageNum(self.0 / PAGE_SIZE + !self.aligned() as usize)
     }
     pub fn page_offset(&self) -> usize {
         self.0 & (PAGE_SIZE - 1)
@@ -160,6 +166,10 @@ impl PhysPageNum {
         let pa: PhysAddr = (*self).into();
         unsafe { core::slice::from_raw_parts_mut(pa.0 as *mut u8, 4096) }
     }
+    pub fn get_words_array(&self) -> &'static mut [u64] {
+        let pa: PhysAddr = (*self).into();
+        unsafe { core::slice::from_raw_parts_mut(pa.0 as *mut u64, 512) }
+    }
     pub fn get_mut<T>(&self) -> &'static mut T {
         let pa: PhysAddr = (*self).into();
         unsafe { (pa.0 as *mut T).as_mut().unwrap() }
@@ -168,11 +178,23 @@ impl PhysPageNum {
 
 pub trait StepByOne {
     fn step(&mut self);
+    /// how many steps lead from `self` to `end`, 0 if `end` is not ahead
+    fn steps_to(&self, end: &Self) -> usize;
 }
 impl StepByOne for VirtPageNum {
     fn step(&mut self) {
         self.0 += 1;
     }
+    fn steps_to(&self, end: &Self) -> usize {
+        end.0.saturating_sub(self.0)
+    }
+}
+
+/// why [`SimpleRange::try_new`] refused a range
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum RangeError {
+    /// the end lies before the start, as when computing it wrapped around
+    EndBeforeStart,
 }
 
 #[derive(Copy, Clone)]
@@ -188,10 +210,25 @@ impl<T> SimpleRange<T>
 where
     T: StepByOne + Copy + PartialEq + PartialOrd + Debug,
 {
+    /// For endpoints known to be in order; anything computed from user
+    /// input goes through [`SimpleRange::try_new`] instead.
     pub fn new(start: T, end: T) -> Self {
         assert!(start <= end, "start {:?} > end {:?}!", start, end);
         Self { l: start, r: end }
     }
+    pub fn try_new(start: T, end: T) -> Result<Self, RangeError> {
+        if end < start {
+            return Err(RangeError::EndBeforeStart);
+        }
+        Ok(Self { l: start, r: end })
+    }
+    /// Number of steps in the range, pages for a [`VPNRange`].
+    pub fn len(&self) -> usize {
+        self.l.steps_to(&self.r)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
     pub fn get_start(&self) -> T {
         self.l
     }
@@ -231,7 +268,8 @@ where
 {
     type Item = T;
     fn next(&mut self) -> Option<Self::Item> {
-        if self.current == self.end {
+        // an end before the start is an empty range too
+        if self.current >= self.end {
             None
         } else {
             let t = self.current;
@@ -239,6 +277,14 @@ where
             Some(t)
         }
     }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.current.steps_to(&self.end);
+        (len, Some(len))
+    }
+}
+impl<T> ExactSizeIterator for SimpleRangeIterator<T> where
+    T: StepByOne + Copy + PartialEq + PartialOrd + Debug
+{
 }
 
 /// a simple range structure for virtual page number
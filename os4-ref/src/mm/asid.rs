@@ -0,0 +1,149 @@
+//! Address space identifiers of the user spaces
+//!
+//! SV39 tags TLB entries with the ASID in `satp`, so a user space with an
+//! ASID of its own keeps its entries over a switch to the kernel and back;
+//! they only need a flush once its page table changes. ASID 0 belongs to
+//! the kernel space. Once the hardware ones run out, the user spaces left
+//! over share the highest ASID, flushed whenever a different page table is
+//! loaded under it. Without any hardware ASID every space runs under 0 and
+//! the trampoline flushes the whole TLB on each switch, as before.
+
+use crate::sync::UPSafeCell;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use lazy_static::*;
+use riscv::register::satp;
+
+/// lowest bit of the ASID field in `satp`
+pub const ASID_SHIFT: usize = 44;
+const ASID_MASK: usize = 0xffff;
+
+/// ASID of a user space, the shared or an unused one back when dropped
+pub struct AsidTracker {
+    asid: usize,
+}
+
+impl AsidTracker {
+    /// ASID 0, for the kernel space and the spaces that never run
+    pub const fn kernel() -> Self {
+        Self { asid: 0 }
+    }
+    pub fn asid(&self) -> usize {
+        self.asid
+    }
+}
+
+impl Drop for AsidTracker {
+    fn drop(&mut self) {
+        if self.asid != 0 {
+            asid_dealloc(self.asid);
+        }
+    }
+}
+
+/// ASIDs handed out to user spaces, recycled in LIFO order
+struct AsidAllocator {
+    /// next ASID never handed out
+    current: usize,
+    /// ASID the spaces share once `current` reaches it, 0 without any
+    shared: usize,
+    recycled: Vec<usize>,
+}
+
+impl AsidAllocator {
+    const fn new() -> Self {
+        Self {
+            current: 1,
+            shared: 0,
+            recycled: Vec::new(),
+        }
+    }
+    fn alloc(&mut self) -> usize {
+        if let Some(asid) = self.recycled.pop() {
+            asid
+        } else if self.current < self.shared {
+            self.current += 1;
+            self.current - 1
+        } else {
+            self.shared
+        }
+    }
+    fn dealloc(&mut self, asid: usize) {
+        if asid == self.shared {
+            return;
+        }
+        debug_assert!(
+            asid < self.current && !self.recycled.contains(&asid),
+            "ASID {} has not been allocated",
+            asid
+        );
+        self.recycled.push(asid);
+    }
+}
+
+lazy_static! {
+    static ref ASID_ALLOCATOR: UPSafeCell<AsidAllocator> =
+        unsafe { UPSafeCell::new(AsidAllocator::new()) };
+}
+
+/// root page number of the page table last run under the shared ASID
+static SHARED_ROOT: AtomicUsize = AtomicUsize::new(0);
+
+/// Find out how many ASID bits the hardware implements, with the kernel
+/// space active; the ones it does not implement read back as 0.
+pub fn init_asid_allocator() {
+    let kernel_satp = satp::read().bits();
+    let bits = unsafe {
+        satp::write(kernel_satp | ASID_MASK << ASID_SHIFT);
+        let bits = (satp::read().bits() >> ASID_SHIFT & ASID_MASK).count_ones();
+        satp::write(kernel_satp);
+        core::arch::asm!("sfence.vma");
+        bits
+    };
+    ASID_ALLOCATOR.exclusive_access().shared = (1 << bits) - 1;
+    println!("[kernel] {} ASID bits", bits);
+}
+
+/// number of ASID bits in use, 0 before [`init_asid_allocator`]
+pub fn asid_bits() -> u32 {
+    ASID_ALLOCATOR.exclusive_access().shared.count_ones()
+}
+
+/// An ASID for a new user space, with nothing of an earlier space left in
+/// the TLB under it unless it is the shared one.
+pub fn asid_alloc() -> AsidTracker {
+    let asid = ASID_ALLOCATOR.exclusive_access().alloc();
+    if asid != 0 {
+        flush_asid(asid);
+    }
+    AsidTracker { asid }
+}
+
+/// Drop the TLB entries loaded under `asid` that may be stale: all of them
+/// if `modified`, or if the shared ASID last ran another page table than
+/// the one at `root_ppn`. The space must still hold `asid`.
+pub fn sync_asid(asid: usize, root_ppn: usize, modified: bool) {
+    if asid == 0 {
+        // the trampoline flushes everything on the way in
+        return;
+    }
+    let shared = asid == ASID_ALLOCATOR.exclusive_access().shared;
+    if (shared && SHARED_ROOT.swap(root_ppn, Ordering::Relaxed) != root_ppn) || modified {
+        flush_asid(asid);
+    }
+}
+
+fn asid_dealloc(asid: usize) {
+    let mut allocator = ASID_ALLOCATOR.exclusive_access();
+    if asid == allocator.shared {
+        // a page table at the same frames later must not hit its entries
+        SHARED_ROOT.store(0, Ordering::Relaxed);
+    }
+    allocator.dealloc(asid);
+}
+
+fn flush_asid(asid: usize) {
+    unsafe {
+        core::arch::asm!("sfence.vma zero, {}", in(reg) asid);
+    }
+}
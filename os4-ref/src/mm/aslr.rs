@@ -0,0 +1,55 @@
+//! Randomized placement of the user stack and of kernel-picked mmap areas
+//!
+//! Each new user space moves its stack, and the address where the kernel
+//! starts looking for room when it picks an mmap address itself, up by a
+//! random number of pages, at most [`ASLR_WINDOW_PAGES`]. The generator is
+//! a xorshift seeded once at boot from the time; the offsets of each task
+//! are kept in its control block and shown in its address-space dump. With
+//! [`ASLR`] off every offset is 0 and the layout is the same on every run.
+
+use crate::config::{ASLR, ASLR_WINDOW_PAGES};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// xorshift state, 0 until seeded
+static STATE: AtomicUsize = AtomicUsize::new(0);
+
+/// Pages a user space is moved up by, see [`super::MemorySet::from_elf`].
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub struct AslrOffsets {
+    /// between the end of the ELF segments and the stack guard page
+    pub stack_pages: usize,
+    /// above `MMAP_AUTO_BASE`, where the search for a free mmap range starts
+    pub mmap_pages: usize,
+}
+
+impl AslrOffsets {
+    /// Offsets for a new user space, all 0 with [`ASLR`] off.
+    pub fn pick() -> Self {
+        if !ASLR {
+            return Self::default();
+        }
+        Self {
+            stack_pages: next() % (ASLR_WINDOW_PAGES + 1),
+            mmap_pages: next() % (ASLR_WINDOW_PAGES + 1),
+        }
+    }
+}
+
+/// Seed the generator, before the first user space is built.
+pub fn aslr_seed(seed: usize) {
+    if ASLR {
+        println!("[kernel] ASLR seed {:#x}", seed);
+    }
+    // a xorshift never leaves 0
+    STATE.store(seed | 1, Ordering::Relaxed);
+}
+
+fn next() -> usize {
+    let mut x = STATE.load(Ordering::Relaxed);
+    debug_assert!(x != 0, "ASLR offsets picked before the seed");
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    STATE.store(x, Ordering::Relaxed);
+    x
+}
@@ -1,9 +1,15 @@
-//! Implementation of [`FrameAllocator`] which 
+//! Implementation of [`FrameAllocator`] which
 //! controls all the frames in the operating system.
 
 use super::{PhysAddr, PhysPageNum};
-use crate::config::MEMORY_END;
+use crate::config::{
+    FRAME_RECYCLE_POLICY, MEMORY_END, PRESSURE_CRITICAL_FRAMES, PRESSURE_HYSTERESIS_FRAMES,
+    PRESSURE_LOW_FRAMES,
+};
+use crate::kstat::{self, KSTAT};
 use crate::sync::UPSafeCell;
+use crate::timer::get_time;
+use alloc::collections::VecDeque;
 use alloc::vec::Vec;
 use core::fmt::{self, Debug, Formatter};
 use lazy_static::*;
@@ -14,12 +20,11 @@ pub struct FrameTracker {
 }
 
 impl FrameTracker {
+    /// Take frame `ppn`, zeroed a word at a time. Every frame a user page
+    /// or a page table gets comes through here, so nothing an exited task
+    /// left in a recycled frame shows through a fresh mapping.
     pub fn new(ppn: PhysPageNum) -> Self {
-        // page cleaning
-        let bytes_array = ppn.get_bytes_array();
-        for i in bytes_array {
-            *i = 0;
-        }
+        ppn.get_words_array().fill(0);
         Self { ppn }
     }
 }
@@ -36,42 +41,209 @@ impl Drop for FrameTracker {
     }
 }
 
-trait FrameAllocator {
+pub(super) trait FrameAllocator {
     fn new() -> Self;
     fn alloc(&mut self) -> Option<PhysPageNum>;
     fn dealloc(&mut self, ppn: PhysPageNum);
 }
 
+/// which recycled frame the allocator hands out next
+#[allow(dead_code)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum RecyclePolicy {
+    /// the most recently freed one, still warm in the cache
+    Lifo,
+    /// the longest freed one, so stale mappings of a frame show up
+    /// long after it was freed rather than being hidden by its reuse
+    Fifo,
+    /// any of them, picked by a fixed-seed xorshift
+    Randomized,
+}
+
+impl RecyclePolicy {
+    /// the policy [`FRAME_RECYCLE_POLICY`] is expected to keep
+    pub const DEFAULT: Self = Self::Lifo;
+}
+
+/// coarse level of free frames, see [`mem_pressure`]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum MemPressure {
+    Normal = 0,
+    Low = 1,
+    Critical = 2,
+}
+
+impl MemPressure {
+    /// The strict level for `available` free frames, or with the thresholds
+    /// raised by `margin`.
+    fn for_available(available: usize, margin: usize) -> Self {
+        if available < PRESSURE_CRITICAL_FRAMES + margin {
+            Self::Critical
+        } else if available < PRESSURE_LOW_FRAMES + margin {
+            Self::Low
+        } else {
+            Self::Normal
+        }
+    }
+    /// The level after `self` once `available` frames are free: it rises as
+    /// soon as a threshold is crossed but only falls with some margin.
+    fn next(self, available: usize) -> Self {
+        let strict = Self::for_available(available, 0);
+        if strict >= self {
+            strict
+        } else {
+            Self::for_available(available, PRESSURE_HYSTERESIS_FRAMES).min(self)
+        }
+    }
+}
+
+/// snapshot of the frame allocator, see [`frame_stats`]
+#[derive(Copy, Clone, Debug)]
+pub struct FrameStats {
+    pub policy: RecyclePolicy,
+    pub in_use: usize,
+    /// frames freed and waiting to be handed out again
+    pub recycled: usize,
+    /// frames never handed out
+    pub untouched: usize,
+    /// all frames the allocator manages, the three above together
+    pub total: usize,
+}
+
 /// an implementation for frame allocator
 pub struct StackFrameAllocator {
+    start: usize,
     current: usize,
     end: usize,
-    recycled: Vec<usize>,
+    recycled: VecDeque<usize>,
+    policy: RecyclePolicy,
+    seed: u64,
+    pressure: MemPressure,
+    /// when `pressure` was entered, in cycles
+    pressure_since: usize,
 }
 
 impl StackFrameAllocator {
+    /// An empty allocator recycling by `policy` rather than
+    /// [`FRAME_RECYCLE_POLICY`], for the kernel self-tests.
+    #[cfg(feature = "board_test")]
+    pub fn with_policy(policy: RecyclePolicy) -> Self {
+        Self {
+            policy,
+            ..Self::new()
+        }
+    }
+    /// Put `ppn` on the recycled list without the checks `dealloc` makes,
+    /// to corrupt a scratch allocator for the self-check tests.
+    #[cfg(feature = "board_test")]
+    pub fn recycle_unchecked(&mut self, ppn: PhysPageNum) {
+        self.recycled.push_back(ppn.0);
+    }
     pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum) {
+        self.start = l.0;
         self.current = l.0;
         self.end = r.0;
     }
+    /// number of frames handed out and not yet returned
+    pub fn in_use(&self) -> usize {
+        self.current - self.start - self.recycled.len()
+    }
+    pub fn stats(&self) -> FrameStats {
+        FrameStats {
+            policy: self.policy,
+            in_use: self.in_use(),
+            recycled: self.recycled.len(),
+            untouched: self.end - self.current,
+            total: self.end - self.start,
+        }
+    }
+    /// Check that the recycled list only holds handed-out frames, each once.
+    pub fn self_check(&self) -> bool {
+        let mut recycled: Vec<usize> = self.recycled.iter().copied().collect();
+        recycled.sort_unstable();
+        let mut ok = true;
+        if let Some(&ppn) = recycled
+            .iter()
+            .find(|&&ppn| ppn < self.start || ppn >= self.current)
+        {
+            error!(
+                "[kernel] selfcheck: recycled frame {:#x} was never handed out",
+                ppn
+            );
+            ok = false;
+        }
+        if let Some(pair) = recycled.windows(2).find(|pair| pair[0] == pair[1]) {
+            error!("[kernel] selfcheck: frame {:#x} recycled twice", pair[0]);
+            ok = false;
+        }
+        ok
+    }
+    /// number of frames that can still be handed out
+    pub fn available(&self) -> usize {
+        self.end - self.current + self.recycled.len()
+    }
+    /// Whether `ppn` is waiting in the recycled list.
+    pub fn is_free(&self, ppn: usize) -> bool {
+        ppn >= self.current || self.recycled.contains(&ppn)
+    }
+    /// Move to the pressure level of the current free count, charging the
+    /// time spent at the old level to kstat.
+    fn update_pressure(&mut self) {
+        let next = self.pressure.next(self.available());
+        if next == self.pressure {
+            return;
+        }
+        let now = get_time();
+        let spent = now - self.pressure_since;
+        match self.pressure {
+            MemPressure::Normal => {}
+            MemPressure::Low => kstat::add(&KSTAT.pressure_low_cycles, spent),
+            MemPressure::Critical => kstat::add(&KSTAT.pressure_critical_cycles, spent),
+        }
+        self.pressure = next;
+        self.pressure_since = now;
+    }
+    fn take_recycled(&mut self) -> Option<usize> {
+        match self.policy {
+            RecyclePolicy::Lifo => self.recycled.pop_back(),
+            RecyclePolicy::Fifo => self.recycled.pop_front(),
+            RecyclePolicy::Randomized => {
+                if self.recycled.is_empty() {
+                    return None;
+                }
+                self.seed ^= self.seed << 13;
+                self.seed ^= self.seed >> 7;
+                self.seed ^= self.seed << 17;
+                let index = self.seed as usize % self.recycled.len();
+                self.recycled.swap_remove_back(index)
+            }
+        }
+    }
 }
 impl FrameAllocator for StackFrameAllocator {
     fn new() -> Self {
         Self {
+            start: 0,
             current: 0,
             end: 0,
-            recycled: Vec::new(),
+            recycled: VecDeque::new(),
+            policy: FRAME_RECYCLE_POLICY,
+            seed: 0x2545_f491_4f6c_dd1d,
+            pressure: MemPressure::Normal,
+            pressure_since: 0,
         }
     }
     fn alloc(&mut self) -> Option<PhysPageNum> {
-        if let Some(ppn) = self.recycled.pop() {
+        let ppn = if let Some(ppn) = self.take_recycled() {
             Some(ppn.into())
         } else if self.current == self.end {
             None
         } else {
             self.current += 1;
             Some((self.current - 1).into())
-        }
+        };
+        self.update_pressure();
+        ppn
     }
     fn dealloc(&mut self, ppn: PhysPageNum) {
         let ppn = ppn.0;
@@ -80,7 +252,8 @@ impl FrameAllocator for StackFrameAllocator {
             panic!("Frame ppn={:#x} has not been allocated!", ppn);
         }
         // recycle
-        self.recycled.push(ppn);
+        self.recycled.push_back(ppn);
+        self.update_pressure();
     }
 }
 
@@ -111,6 +284,36 @@ pub fn frame_alloc() -> Option<FrameTracker> {
         .map(FrameTracker::new)
 }
 
+/// number of frames currently allocated
+pub fn frames_in_use() -> usize {
+    FRAME_ALLOCATOR.exclusive_access().in_use()
+}
+
+/// recycling policy and list lengths of the frame allocator
+pub fn frame_stats() -> FrameStats {
+    FRAME_ALLOCATOR.exclusive_access().stats()
+}
+
+/// check the frame allocator's own bookkeeping, see `sys_selfcheck`
+pub fn frame_allocator_check() -> bool {
+    FRAME_ALLOCATOR.exclusive_access().self_check()
+}
+
+/// number of frames that can still be allocated
+pub fn frames_available() -> usize {
+    FRAME_ALLOCATOR.exclusive_access().available()
+}
+
+/// current memory pressure level, see `sys_mem_pressure`
+pub fn mem_pressure() -> MemPressure {
+    FRAME_ALLOCATOR.exclusive_access().pressure
+}
+
+/// whether frame `ppn` is not handed out
+pub fn frame_is_free(ppn: PhysPageNum) -> bool {
+    FRAME_ALLOCATOR.exclusive_access().is_free(ppn.0)
+}
+
 /// deallocate a frame
 fn frame_dealloc(ppn: PhysPageNum) {
     FRAME_ALLOCATOR.exclusive_access().dealloc(ppn);
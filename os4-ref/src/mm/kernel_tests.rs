@@ -0,0 +1,503 @@
+//! Memory management tests of the `board_test` self-tests, see
+//! [`crate::kernel_tests`]
+//!
+//! They sit inside `mm` to reach the page table and the frame allocator
+//! directly, and work on scratch page tables, allocators and memory sets,
+//! so they leave the kernel space as they found it.
+
+use super::address::{RangeError, SimpleRangeIterator};
+use super::frame_allocator::{FrameAllocator, StackFrameAllocator};
+use super::*;
+use crate::config::{
+    FRAME_RECYCLE_POLICY, MMAP_AUTO_BASE, MMAP_DENY_WX, PAGE_SIZE, USER_SPACE_END,
+};
+use crate::kernel_tests::TestResult;
+use alloc::format;
+use alloc::vec::Vec;
+
+/// `VPNRange` iteration at its edges: an empty range, a single page, the
+/// last pages of the user space, and an iterator whose end lies before its
+/// start, which yields nothing rather than running away.
+pub fn vpn_range_iteration() -> TestResult {
+    let empty = VPNRange::new(VirtPageNum(7), VirtPageNum(7));
+    ktest_assert_eq!(empty.into_iter().count(), 0);
+    ktest_assert_eq!(empty.get_start(), empty.get_end());
+    let one = VPNRange::new(VirtPageNum(7), VirtPageNum(8));
+    ktest_assert!(one.into_iter().eq([VirtPageNum(7)]));
+    let end = VirtAddr::from(USER_SPACE_END).floor();
+    let top = VPNRange::new(VirtPageNum(end.0 - 2), end);
+    ktest_assert_eq!(top.into_iter().count(), 2);
+    ktest_assert_eq!(top.into_iter().last(), Some(VirtPageNum(end.0 - 1)));
+    let backwards = SimpleRangeIterator::new(VirtPageNum(8), VirtPageNum(7));
+    ktest_assert_eq!(backwards.len(), 0);
+    ktest_assert_eq!(backwards.count(), 0);
+    Ok(())
+}
+
+/// The checked address arithmetic at the ends of the address space: an
+/// addition that would wrap, rounding up the first and the last addresses,
+/// a range built backwards, and the page count of a range and of an
+/// iterator partly walked.
+pub fn address_arithmetic() -> TestResult {
+    let top = VirtAddr(usize::MAX - 1);
+    ktest_assert_eq!(top.checked_add(1), Some(VirtAddr(usize::MAX)));
+    ktest_assert_eq!(top.checked_add(2), None);
+    ktest_assert_eq!(VirtAddr(0).ceil(), VirtPageNum(0));
+    ktest_assert_eq!(VirtAddr(1).ceil(), VirtPageNum(1));
+    ktest_assert_eq!(top.ceil(), VirtPageNum(usize::MAX / PAGE_SIZE + 1));
+    ktest_assert_eq!(
+        VPNRange::try_new(VirtPageNum(8), VirtPageNum(7)).err(),
+        Some(RangeError::EndBeforeStart)
+    );
+    let range =
+        VPNRange::try_new(VirtPageNum(7), VirtPageNum(10)).map_err(|err| format!("{:?}", err))?;
+    ktest_assert_eq!(range.len(), 3);
+    let mut pages = range.into_iter();
+    pages.next();
+    ktest_assert_eq!(pages.len(), 2);
+    ktest_assert_eq!(pages.count(), 2);
+    Ok(())
+}
+
+/// `PageTable::map`, `unmap` and `translate` on a scratch page table: a
+/// mapping reads back as written, a second one of the same page is refused
+/// and leaves the first alone, and an unmapped page, or one whose tables
+/// were never created, cannot be unmapped again.
+pub fn page_table_round_trip() -> TestResult {
+    let mut page_table = PageTable::new();
+    let frame = frame_alloc().ok_or("no frame for the test")?;
+    let vpn = VirtPageNum(0x12345);
+    let flags = PTEFlags::R | PTEFlags::W | PTEFlags::U;
+    let unmapped = |page_table: &PageTable| {
+        page_table
+            .translate(vpn)
+            .map_or(true, |pte| !pte.is_valid())
+    };
+    ktest_assert!(unmapped(&page_table));
+    ktest_assert_eq!(page_table.map(vpn, frame.ppn, flags), Ok(()));
+    let pte = page_table.translate(vpn).ok_or("no PTE after map")?;
+    ktest_assert_eq!(pte.ppn(), frame.ppn);
+    ktest_assert_eq!(pte.flags(), flags | PTEFlags::V);
+    ktest_assert_eq!(
+        page_table.map(vpn, PhysPageNum(frame.ppn.0 + 1), PTEFlags::R),
+        Err(PageTableError::AlreadyMapped)
+    );
+    ktest_assert_eq!(
+        page_table
+            .translate(vpn)
+            .map(|pte| (pte.ppn(), pte.flags())),
+        Some((frame.ppn, flags | PTEFlags::V))
+    );
+    // the next page shares the last-level table
+    let tables = page_table.table_frames();
+    ktest_assert_eq!(
+        page_table.map(VirtPageNum(vpn.0 + 1), frame.ppn, PTEFlags::R),
+        Ok(())
+    );
+    ktest_assert_eq!(page_table.table_frames(), tables);
+    ktest_assert_eq!(page_table.unmap(vpn), Ok(()));
+    ktest_assert!(unmapped(&page_table));
+    ktest_assert_eq!(page_table.unmap(vpn), Err(PageTableError::NotMapped));
+    ktest_assert_eq!(
+        page_table.set_flags(vpn, flags),
+        Err(PageTableError::NotMapped)
+    );
+    ktest_assert_eq!(
+        page_table.unmap(VirtPageNum(vpn.0 + (1 << 18))),
+        Err(PageTableError::NotMapped)
+    );
+    // the page can be mapped again once it is unmapped
+    ktest_assert_eq!(page_table.map(vpn, frame.ppn, flags), Ok(()));
+    ktest_assert_eq!(
+        page_table.translate(vpn).map(|pte| pte.ppn()),
+        Some(frame.ppn)
+    );
+    Ok(())
+}
+
+/// The frame allocator's reuse order under each recycling policy, on a
+/// scratch allocator over four made-up frames, and on the real one under
+/// [`FRAME_RECYCLE_POLICY`].
+pub fn frame_recycle_order() -> TestResult {
+    let policies = [
+        RecyclePolicy::Lifo,
+        RecyclePolicy::Fifo,
+        RecyclePolicy::Randomized,
+    ];
+    for policy in policies {
+        let mut allocator = StackFrameAllocator::with_policy(policy);
+        allocator.init(PhysPageNum(0x100), PhysPageNum(0x104));
+        let frames: Vec<PhysPageNum> = (0..4).filter_map(|_| allocator.alloc()).collect();
+        // untouched frames go out in address order
+        ktest_assert!(
+            frames.iter().map(|ppn| ppn.0).eq(0x100..0x104),
+            "{:?}: handed out {:?}",
+            policy,
+            frames
+        );
+        ktest_assert_eq!(allocator.alloc(), None, "{:?}", policy);
+        allocator.dealloc(frames[1]);
+        allocator.dealloc(frames[2]);
+        ktest_assert!(allocator.self_check(), "{:?}", policy);
+        ktest_assert_eq!(allocator.available(), 2, "{:?}", policy);
+        let mut reused = [allocator.alloc(), allocator.alloc()];
+        match policy {
+            RecyclePolicy::Lifo => {
+                ktest_assert_eq!(reused, [Some(frames[2]), Some(frames[1])])
+            }
+            RecyclePolicy::Fifo => {
+                ktest_assert_eq!(reused, [Some(frames[1]), Some(frames[2])])
+            }
+            RecyclePolicy::Randomized => {
+                reused.sort();
+                ktest_assert_eq!(reused, [Some(frames[1]), Some(frames[2])])
+            }
+        }
+        ktest_assert_eq!(allocator.alloc(), None, "{:?}", policy);
+        ktest_assert_eq!(allocator.in_use(), 4, "{:?}", policy);
+    }
+    let in_use = frames_in_use();
+    if FRAME_RECYCLE_POLICY == RecyclePolicy::Lifo {
+        let ppn = frame_alloc().ok_or("no frame for the test")?.ppn;
+        ktest_assert_eq!(frame_alloc().map(|frame| frame.ppn), Some(ppn));
+    }
+    ktest_assert_eq!(frames_in_use(), in_use);
+    Ok(())
+}
+
+/// Partial munmap and mprotect split an mmap area: unmapping its middle
+/// keeps both ends mapped, a range reaching over the hole is refused as a
+/// whole, and mprotect of one page changes only that page.
+pub fn area_splitting() -> TestResult {
+    let mut memory_set = MemorySet::new_bare();
+    let policy = MapPolicy::default();
+    let page = |i: usize| VirtAddr::from(MMAP_AUTO_BASE + i * PAGE_SIZE);
+    let pages = |from: usize, to: usize| VPNRange::new(page(from).floor(), page(to).floor());
+    ktest_assert_eq!(
+        memory_set.checked_insert(
+            page(0),
+            8 * PAGE_SIZE,
+            MapPermission::R | MapPermission::W,
+            AreaKind::Mmap,
+            &policy,
+            None,
+        ),
+        Ok(())
+    );
+    ktest_assert_eq!(memory_set.user_pages(), 8);
+    ktest_assert_eq!(
+        memory_set.checked_remove(page(2), 2 * PAGE_SIZE, AreaKind::Mmap),
+        Ok((2, 0))
+    );
+    ktest_assert_eq!(memory_set.user_pages(), 6);
+    ktest_assert_eq!(memory_set.range_state(pages(0, 2)), RangeState::AllMapped);
+    ktest_assert_eq!(memory_set.range_state(pages(2, 4)), RangeState::AllUnmapped);
+    ktest_assert_eq!(memory_set.range_state(pages(4, 8)), RangeState::AllMapped);
+    ktest_assert_eq!(memory_set.area_kind(page(1)), Some(AreaKind::Mmap));
+    ktest_assert_eq!(memory_set.area_kind(page(2)), None);
+    ktest_assert_eq!(memory_set.area_kind(page(4)), Some(AreaKind::Mmap));
+    ktest_assert_eq!(
+        memory_set.checked_remove(page(1), 4 * PAGE_SIZE, AreaKind::Mmap),
+        Err(MapError::NotMapped)
+    );
+    ktest_assert_eq!(memory_set.user_pages(), 6);
+    ktest_assert_eq!(
+        memory_set.mprotect(page(5), PAGE_SIZE, MapPermission::R, &policy),
+        Ok(0)
+    );
+    let writable = |memory_set: &MemorySet, i: usize| {
+        memory_set
+            .translate(page(i).floor())
+            .map_or(false, |pte| pte.writable())
+    };
+    ktest_assert!(
+        writable(&memory_set, 4) && !writable(&memory_set, 5) && writable(&memory_set, 6)
+    );
+    ktest_assert!(memory_set.self_check());
+    // the pieces go in one call, as long as they leave no hole
+    ktest_assert_eq!(
+        memory_set
+            .checked_remove(page(4), 4 * PAGE_SIZE, AreaKind::Mmap)
+            .map(|(freed, _)| freed),
+        Ok(4)
+    );
+    ktest_assert_eq!(
+        memory_set
+            .checked_remove(page(0), 2 * PAGE_SIZE, AreaKind::Mmap)
+            .map(|(freed, _)| freed),
+        Ok(2)
+    );
+    ktest_assert_eq!(memory_set.user_pages(), 0);
+    Ok(())
+}
+
+/// Every `sys_mmap` port from 0 to 15 down the path `sys_mmap` takes, a
+/// page each on a scratch memory set: what is returned and, for those that
+/// map the page, its PTE flags; then an unaligned start, a taken range and
+/// what [`MemorySet::intersects`] finds around the hole port 2 leaves.
+pub fn mmap_port_matrix() -> TestResult {
+    use MapPermission as P;
+    let mut memory_set = MemorySet::new_bare();
+    let policy = MapPolicy::default();
+    let page = |i: usize| VirtAddr::from(MMAP_AUTO_BASE + i * PAGE_SIZE);
+    for port in 0..16 {
+        let expected = match port {
+            // a reservation, see `MemorySet::reserve`
+            0 => Ok(P::empty()),
+            1 => Ok(P::R),
+            3 => Ok(P::R | P::W),
+            4 => Ok(P::X),
+            5 => Ok(P::R | P::X),
+            7 if MMAP_DENY_WX => Err(MapError::WriteExecute),
+            7 => Ok(P::R | P::W | P::X),
+            _ => Err(MapError::InvalidPermission),
+        };
+        let result = P::from_port(port).and_then(|perm| {
+            if perm.is_empty() {
+                memory_set.reserve(page(port), PAGE_SIZE)
+            } else {
+                memory_set.checked_insert(
+                    page(port),
+                    PAGE_SIZE,
+                    perm,
+                    AreaKind::Mmap,
+                    &policy,
+                    None,
+                )
+            }
+            .map(|()| perm)
+        });
+        ktest_assert_eq!(result, expected, "port {:#x}", port);
+        let pte = memory_set
+            .translate(page(port).floor())
+            .filter(|pte| pte.is_valid());
+        match expected {
+            Ok(perm) if !perm.is_empty() => {
+                let pte = pte.ok_or_else(|| format!("port {:#x}: page not mapped", port))?;
+                let flags = pte.flags() & !(PTEFlags::V | PTEFlags::A | PTEFlags::D);
+                ktest_assert_eq!(
+                    Some(flags),
+                    PTEFlags::from_bits((perm | P::U).bits()),
+                    "port {:#x}",
+                    port
+                );
+            }
+            _ => ktest_assert!(pte.is_none(), "port {:#x}: page mapped", port),
+        }
+    }
+    let unaligned = VirtAddr::from(page(16).0 + 1);
+    ktest_assert_eq!(
+        memory_set.checked_insert(unaligned, PAGE_SIZE, P::R, AreaKind::Mmap, &policy, None),
+        Err(MapError::Unaligned)
+    );
+    ktest_assert_eq!(
+        memory_set.reserve(unaligned, PAGE_SIZE),
+        Err(MapError::Unaligned)
+    );
+    ktest_assert_eq!(
+        memory_set.checked_insert(page(1), PAGE_SIZE, P::R, AreaKind::Mmap, &policy, None),
+        Err(MapError::Overlap)
+    );
+    ktest_assert_eq!(
+        memory_set.reserve(page(0), PAGE_SIZE),
+        Err(MapError::Overlap)
+    );
+    let pages = |start: usize, end: usize| VPNRange::new(page(start).floor(), page(end).floor());
+    ktest_assert_eq!(memory_set.intersects(&pages(2, 3)), None);
+    ktest_assert_eq!(memory_set.intersects(&pages(2, 4)), Some(AreaKind::Mmap));
+    ktest_assert_eq!(MapError::Overlap.errno(), -2);
+    Ok(())
+}
+
+/// Every reason [`MemorySet::checked_insert`] has to refuse an area, each
+/// with the errno `sys_mmap2` hands back for it: an overlap with an area of
+/// each [`AreaKind`], whole or in part, a start that is not page aligned,
+/// ranges leaving the user window, refused permissions, a full page budget
+/// and more pages than there are free frames. No refusal maps anything.
+pub fn checked_insert_refusals() -> TestResult {
+    use MapPermission as P;
+    const KINDS: [AreaKind; 9] = [
+        AreaKind::Kernel,
+        AreaKind::ElfText,
+        AreaKind::ElfData,
+        AreaKind::UserStack,
+        AreaKind::TrapContext,
+        AreaKind::Mmap,
+        AreaKind::Heap,
+        AreaKind::Reserved,
+        AreaKind::StackGuard,
+    ];
+    let mut memory_set = MemorySet::new_bare();
+    let policy = MapPolicy {
+        deny_wx: true,
+        max_pages: usize::MAX,
+        exec_text_only: false,
+        lazy_pages: usize::MAX,
+    };
+    let page = |i: usize| VirtAddr::from(MMAP_AUTO_BASE + i * PAGE_SIZE);
+    let insert = |memory_set: &mut MemorySet,
+                  start: VirtAddr,
+                  len: usize,
+                  perm: MapPermission,
+                  policy: &MapPolicy| {
+        memory_set
+            .checked_insert(start, len, perm, AreaKind::Mmap, policy, None)
+            .map_err(|err| (err, err.errno()))
+    };
+    // one page of each kind with a free page after it
+    for (i, &kind) in KINDS.iter().enumerate() {
+        memory_set
+            .checked_insert(
+                page(2 * i + 1),
+                PAGE_SIZE,
+                P::R,
+                kind,
+                &MapPolicy::kernel(),
+                None,
+            )
+            .map_err(|err| format!("{:?} area: {:?}", kind, err))?;
+    }
+    let user_pages = memory_set.user_pages();
+    for (i, &kind) in KINDS.iter().enumerate() {
+        let taken = page(2 * i + 1);
+        ktest_assert_eq!(
+            insert(&mut memory_set, taken, PAGE_SIZE, P::R, &policy),
+            Err((MapError::Overlap, -2)),
+            "{:?} area",
+            kind
+        );
+        ktest_assert_eq!(
+            insert(&mut memory_set, page(2 * i), 2 * PAGE_SIZE, P::R, &policy),
+            Err((MapError::Overlap, -2)),
+            "{:?} area from below",
+            kind
+        );
+        let pages = VPNRange::new(page(2 * i).floor(), page(2 * i + 2).floor());
+        ktest_assert_eq!(memory_set.intersects(&pages), Some(kind));
+    }
+    let free = page(2 * KINDS.len() + 1);
+    ktest_assert_eq!(
+        insert(
+            &mut memory_set,
+            VirtAddr::from(free.0 + 1),
+            PAGE_SIZE,
+            P::R,
+            &policy
+        ),
+        Err((MapError::Unaligned, -1))
+    );
+    let last = VirtAddr::from(USER_SPACE_END - PAGE_SIZE);
+    ktest_assert_eq!(
+        insert(&mut memory_set, last, 2 * PAGE_SIZE, P::R, &policy),
+        Err((MapError::Reserved, -1))
+    );
+    ktest_assert_eq!(
+        insert(
+            &mut memory_set,
+            VirtAddr::from(USER_SPACE_END),
+            PAGE_SIZE,
+            P::R,
+            &policy
+        ),
+        Err((MapError::Reserved, -1))
+    );
+    ktest_assert_eq!(
+        insert(&mut memory_set, free, usize::MAX - PAGE_SIZE, P::R, &policy),
+        Err((MapError::Reserved, -1))
+    );
+    for perm in [P::empty(), P::U, P::R | P::U] {
+        ktest_assert_eq!(
+            insert(&mut memory_set, free, PAGE_SIZE, perm, &policy),
+            Err((MapError::InvalidPermission, -1)),
+            "{:?}",
+            perm
+        );
+    }
+    ktest_assert_eq!(
+        insert(&mut memory_set, free, PAGE_SIZE, P::W | P::X, &policy),
+        Err((MapError::WriteExecute, -1))
+    );
+    ktest_assert_eq!(
+        insert(
+            &mut memory_set,
+            free,
+            PAGE_SIZE,
+            P::R | P::W | P::X,
+            &policy
+        ),
+        Err((MapError::WriteExecute, -1))
+    );
+    let budget = MapPolicy {
+        max_pages: user_pages + 1,
+        ..policy
+    };
+    ktest_assert_eq!(
+        insert(&mut memory_set, free, 2 * PAGE_SIZE, P::R, &budget),
+        Err((MapError::BudgetExceeded, -3))
+    );
+    let frames = frames_available();
+    ktest_assert_eq!(
+        insert(
+            &mut memory_set,
+            free,
+            (frames + 1) * PAGE_SIZE,
+            P::R,
+            &policy
+        ),
+        Err((MapError::OutOfMemory, -3))
+    );
+    ktest_assert_eq!(frames_available(), frames);
+    ktest_assert_eq!(memory_set.user_pages(), user_pages);
+    let pages = VPNRange::new(free.floor(), VirtAddr::from(USER_SPACE_END).floor());
+    ktest_assert_eq!(memory_set.intersects(&pages), None);
+    // the budget still has room for the page it was asked for
+    ktest_assert_eq!(
+        insert(&mut memory_set, free, PAGE_SIZE, P::R, &budget),
+        Ok(())
+    );
+    Ok(())
+}
+
+/// The frame and address space checks behind `sys_selfcheck` each catch a
+/// violation injected behind the bookkeeping's back: a frame recycled
+/// twice or that was never handed out, on scratch allocators, and a
+/// populated page that lost its PTE or an area overlapping another, on
+/// scratch memory sets. The scheduler's check is among the task manager
+/// tests.
+pub fn selfcheck_injected() -> TestResult {
+    let allocator_over = |frames| {
+        let mut allocator = StackFrameAllocator::with_policy(RecyclePolicy::Lifo);
+        allocator.init(PhysPageNum(0x100), PhysPageNum(0x104));
+        let handed: Vec<PhysPageNum> = (0..frames).filter_map(|_| allocator.alloc()).collect();
+        (allocator, handed)
+    };
+    let (mut allocator, handed) = allocator_over(2);
+    allocator.dealloc(handed[0]);
+    ktest_assert!(allocator.self_check());
+    allocator.recycle_unchecked(handed[0]);
+    ktest_assert!(!allocator.self_check(), "frame recycled twice");
+    let (mut allocator, _) = allocator_over(2);
+    ktest_assert!(allocator.self_check());
+    allocator.recycle_unchecked(PhysPageNum(0x103));
+    ktest_assert!(!allocator.self_check(), "recycled frame never handed out");
+    ktest_assert!(frame_allocator_check());
+
+    let policy = MapPolicy::default();
+    let page = |i: usize| VirtAddr::from(MMAP_AUTO_BASE + i * PAGE_SIZE);
+    let mapped = || {
+        let mut memory_set = MemorySet::new_bare();
+        let perm = MapPermission::R | MapPermission::W;
+        memory_set
+            .checked_insert(page(0), 2 * PAGE_SIZE, perm, AreaKind::Mmap, &policy, None)
+            .map(|_| memory_set)
+            .map_err(|err| format!("mapping the scratch pages: {:?}", err))
+    };
+    let mut memory_set = mapped()?;
+    ktest_assert!(memory_set.self_check());
+    ktest_assert_eq!(memory_set.unmap_pte_unchecked(page(1).floor()), Ok(()));
+    ktest_assert!(!memory_set.self_check(), "populated page without a PTE");
+    let mut memory_set = mapped()?;
+    memory_set.push_area_unchecked(page(1), page(3));
+    ktest_assert!(!memory_set.self_check(), "overlapping areas");
+    Ok(())
+}
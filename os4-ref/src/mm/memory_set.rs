@@ -1,13 +1,26 @@
 //! Implementation of [`MapArea`] and [`MemorySet`].
 
+use super::asid::{asid_alloc, sync_asid, AsidTracker, ASID_SHIFT};
+use super::frame_allocator::{frame_is_free, frames_available};
+use super::shm::ShmMapping;
+use super::AslrOffsets;
+use super::{check_user_range, UserPtrError};
 use super::{frame_alloc, FrameTracker};
-use super::{PTEFlags, PageTable, PageTableEntry};
+use super::{PTEFlags, PageTable, PageTableEntry, PageTableError, RangeState, HUGE_PAGE_PAGES};
 use super::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
-use super::{StepByOne, VPNRange};
-use crate::config::{MEMORY_END, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_STACK_SIZE};
+use super::{RangeError, StepByOne, VPNRange};
+use crate::config::{
+    EXEC_TEXT_ONLY, MEMORY_END, MMAP_AUTO_BASE, MMAP_DENY_WX, MMAP_LAZY_PAGES, MMAP_MAX_PAGES,
+    PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_SPACE_END, USER_STACK_SIZE,
+};
+use crate::kstat::{self, KSTAT};
+use crate::sbi::shutdown;
+use crate::stack_probe::StackPath;
+use crate::timer::get_time;
 use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
 use lazy_static::*;
 use riscv::register::satp;
 use spin::Mutex;
@@ -23,18 +36,87 @@ extern "C" {
     fn ebss();
     fn ekernel();
     fn strampoline();
+    fn boot_stack();
+    fn boot_stack_top();
 }
 
 lazy_static! {
     /// a memory set instance through lazy_static! managing kernel space
     pub static ref KERNEL_SPACE: Arc<Mutex<MemorySet>> =
-        Arc::new(Mutex::new(MemorySet::new_kernel()));
+        Arc::new(Mutex::new(MemorySet::new_kernel().finalize()));
+}
+
+/// number of entries in a [`TranslateCache`]
+const TRANSLATE_CACHE_SIZE: usize = 4;
+
+/// A few recent user page translations, so that syscalls touching the same
+/// buffer pages over and over skip the page table walk.
+///
+/// It must be flushed whenever a page of the owning memory set is unmapped or
+/// its flags change; address space switches need nothing as every memory set
+/// has its own cache.
+struct TranslateCache {
+    entries: [Option<(VirtPageNum, PageTableEntry)>; TRANSLATE_CACHE_SIZE],
+    /// slot replaced by the next miss
+    next: usize,
+}
+
+impl TranslateCache {
+    fn new() -> Self {
+        Self {
+            entries: [None; TRANSLATE_CACHE_SIZE],
+            next: 0,
+        }
+    }
+    fn lookup(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
+        self.entries
+            .iter()
+            .flatten()
+            .find(|(cached, _)| *cached == vpn)
+            .map(|(_, pte)| *pte)
+    }
+    fn insert(&mut self, vpn: VirtPageNum, pte: PageTableEntry) {
+        self.entries[self.next] = Some((vpn, pte));
+        self.next = (self.next + 1) % TRANSLATE_CACHE_SIZE;
+    }
+    fn flush(&mut self) {
+        self.entries = [None; TRANSLATE_CACHE_SIZE];
+    }
+}
+
+/// 64-bit FNV-1a, a fixed hash for [`MemorySet::fingerprint`]
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+    fn write(&mut self, value: usize) {
+        for byte in (value as u64).to_le_bytes() {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    fn finish(&self) -> u64 {
+        self.0
+    }
 }
 
 /// memory set structure, controls virtual-memory space
 pub struct MemorySet {
     page_table: PageTable,
     areas: Vec<MapArea>,
+    translate_cache: TranslateCache,
+    /// most `user_pages` seen after a `checked_insert`
+    peak_pages: usize,
+    /// named regions mapped so far
+    regions: Regions,
+    /// set by `finalize`, checked by `activate`
+    finalized: bool,
+    /// what tags the TLB entries of this space, see [`TlbSync::sync`]
+    asid: AsidTracker,
+    /// where [`MemorySet::find_free_area`] starts looking
+    mmap_base: VirtPageNum,
 }
 
 impl MemorySet {
@@ -42,37 +124,114 @@ impl MemorySet {
         Self {
             page_table: PageTable::new(),
             areas: Vec::new(),
+            translate_cache: TranslateCache::new(),
+            peak_pages: 0,
+            regions: Regions::empty(),
+            finalized: false,
+            asid: AsidTracker::kernel(),
+            mmap_base: VirtAddr::from(MMAP_AUTO_BASE).floor(),
         }
     }
+    /// Mark the kernel space as fully built; `activate` refuses it before.
+    pub fn finalize(mut self) -> Self {
+        self.finalized = true;
+        self
+    }
+    /// Check a user space has what the first trap return needs.
+    fn finalize_user(mut self) -> Self {
+        let missing = Regions::USER - self.regions;
+        assert!(
+            missing.is_empty(),
+            "user memory set is missing {:?}",
+            missing
+        );
+        self.finalized = true;
+        self.asid = asid_alloc();
+        self
+    }
     pub fn token(&self) -> usize {
-        self.page_table.token()
+        self.page_table.token() | self.asid.asid() << ASID_SHIFT
     }
-    /// Assume that no conflicts.
+    /// What it takes to sync the TLB with this space once it is out of
+    /// reach, see [`TlbSync::sync`]; valid as long as the space is.
+    pub fn tlb_sync(&self) -> TlbSync {
+        TlbSync {
+            asid: self.asid.asid(),
+            root_ppn: self.page_table.root_ppn().0,
+            modified: self.page_table.modified_flag(),
+        }
+    }
+    /// Assume that no conflicts. Fails without mapping anything when the
+    /// frames run out, or when `end_va` lies before `start_va`.
     pub fn insert_framed_area(
         &mut self,
         start_va: VirtAddr,
         end_va: VirtAddr,
         permission: MapPermission,
-    ) {
-        self.push(
-            MapArea::new(start_va, end_va, MapType::Framed, permission),
+    ) -> Result<(), MapError> {
+        VPNRange::try_new(start_va.floor(), end_va.ceil())?;
+        self.try_push(
+            MapArea::new(
+                start_va,
+                end_va,
+                MapType::Framed,
+                permission,
+                AreaKind::Kernel,
+            ),
             None,
-        );
+        )?;
+        // this is the active address space, and no trap flushes its ASID
+        unsafe {
+            core::arch::asm!("sfence.vma");
+        }
+        Ok(())
     }
-    fn push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) {
-        map_area.map(&mut self.page_table);
+    /// Unmap the kernel area starting at `start_va` and free its frames,
+    /// returning whether there was one. The page tables stay, the next
+    /// area mapped there reuses them.
+    pub fn remove_kernel_area(&mut self, start_va: VirtAddr) -> bool {
+        let start = start_va.floor();
+        let index =
+            match self.areas.iter().position(|area| {
+                area.kind == AreaKind::Kernel && area.vpn_range.get_start() == start
+            }) {
+                Some(index) => index,
+                None => return false,
+            };
+        self.translate_cache.flush();
+        let mut area = self.areas.swap_remove(index);
+        area.unmap(&mut self.page_table)
+            .expect("kernel area lost a page while mapped");
+        // this is the active address space, drop the stale translations
+        unsafe {
+            core::arch::asm!("sfence.vma");
+        }
+        true
+    }
+    /// Map and record an area the kernel itself asked for; failing here
+    /// means the kernel's own layout does not fit.
+    fn push(&mut self, map_area: MapArea, data: Option<&[u8]>) {
+        self.try_push(map_area, data)
+            .expect("kernel-built area could not be mapped");
+    }
+    fn try_push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) -> Result<(), MapError> {
+        map_area.map(&mut self.page_table)?;
         if let Some(data) = data {
-            map_area.copy_data(&mut self.page_table, data);
+            map_area.copy_data(&mut self.page_table, data, 0);
         }
         self.areas.push(map_area);
+        Ok(())
     }
     /// Mention that trampoline is not collected by areas.
     fn map_trampoline(&mut self) {
-        self.page_table.map(
-            VirtAddr::from(TRAMPOLINE).into(),
-            PhysAddr::from(strampoline as usize).into(),
-            PTEFlags::R | PTEFlags::X,
-        );
+        self.page_table
+            .map(
+                VirtAddr::from(TRAMPOLINE).into(),
+                PhysAddr::from(strampoline as usize).into(),
+                PTEFlags::R | PTEFlags::X,
+            )
+            .expect("trampoline could not be mapped");
+        self.regions |= Regions::TRAMPOLINE;
     }
     /// Without kernel stacks.
     pub fn new_kernel() -> Self {
@@ -94,9 +253,11 @@ impl MemorySet {
                 (etext as usize).into(),
                 MapType::Identical,
                 MapPermission::R | MapPermission::X,
+                AreaKind::Kernel,
             ),
             None,
         );
+        memory_set.regions |= Regions::TEXT;
         info!("mapping .rodata section");
         memory_set.push(
             MapArea::new(
@@ -104,9 +265,11 @@ impl MemorySet {
                 (erodata as usize).into(),
                 MapType::Identical,
                 MapPermission::R,
+                AreaKind::Kernel,
             ),
             None,
         );
+        memory_set.regions |= Regions::RODATA;
         info!("mapping .data section");
         memory_set.push(
             MapArea::new(
@@ -114,9 +277,11 @@ impl MemorySet {
                 (edata as usize).into(),
                 MapType::Identical,
                 MapPermission::R | MapPermission::W,
+                AreaKind::Kernel,
             ),
             None,
         );
+        memory_set.regions |= Regions::DATA;
         info!("mapping .bss section");
         memory_set.push(
             MapArea::new(
@@ -124,9 +289,16 @@ impl MemorySet {
                 (ebss as usize).into(),
                 MapType::Identical,
                 MapPermission::R | MapPermission::W,
+                AreaKind::Kernel,
             ),
             None,
         );
+        memory_set.regions |= Regions::BSS;
+        if (sbss_with_stack as usize..ebss as usize).contains(&(boot_stack as usize))
+            && boot_stack_top as usize <= ebss as usize
+        {
+            memory_set.regions |= Regions::BOOT_STACK;
+        }
         info!("mapping physical memory");
         memory_set.push(
             MapArea::new(
@@ -134,15 +306,46 @@ impl MemorySet {
                 MEMORY_END.into(),
                 MapType::Identical,
                 MapPermission::R | MapPermission::W,
+                AreaKind::Kernel,
             ),
             None,
         );
+        memory_set.regions |= Regions::FRAME_POOL;
+        #[cfg(feature = "board_test")]
+        {
+            info!("mapping the qemu test device");
+            memory_set.push(
+                MapArea::new(
+                    crate::config::VIRT_TEST.into(),
+                    (crate::config::VIRT_TEST + PAGE_SIZE).into(),
+                    MapType::Identical,
+                    MapPermission::R | MapPermission::W,
+                    AreaKind::Kernel,
+                ),
+                None,
+            );
+        }
+        // each 2 MiB page saved a last-level table
+        let huge_pages: usize = memory_set.areas.iter().map(MapArea::huge_pages).sum();
+        info!(
+            "kernel page tables: {} frames, {} fewer thanks to 2 MiB pages",
+            memory_set.page_table.table_frames(),
+            huge_pages
+        );
         memory_set
     }
     /// Include sections in elf and trampoline and TrapContext and user stack,
-    /// also returns user_sp and entry point.
-    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
+    /// also returns user_sp and entry point. The stack and the start of the
+    /// mmap search are moved up by `aslr`, and the stack is executable only
+    /// with `exec_stack`.
+    ///
+    /// Each loadable segment becomes an area of its own with exactly its
+    /// flags, so a gap between segments stays unmapped. The segments must
+    /// have passed `loader::ElfInfo::parse`, which skips an app whose
+    /// segments overlap or cannot be mapped.
+    pub fn from_elf(elf_data: &[u8], exec_stack: bool, aslr: AslrOffsets) -> (Self, usize, usize) {
         let mut memory_set = Self::new_bare();
+        memory_set.mmap_base = VirtPageNum(memory_set.mmap_base.0 + aslr.mmap_pages);
         // map trampoline
         memory_set.map_trampoline();
         // map program headers of elf, with U flag
@@ -154,7 +357,7 @@ impl MemorySet {
         let mut max_end_vpn = VirtPageNum(0);
         for i in 0..ph_count {
             let ph = elf.program_header(i).unwrap();
-            if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
+            if ph.get_type().unwrap() == xmas_elf::program::Type::Load && ph.mem_size() > 0 {
                 let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
                 let end_va: VirtAddr = ((ph.virtual_addr() + ph.mem_size()) as usize).into();
                 let mut map_perm = MapPermission::U;
@@ -168,26 +371,81 @@ impl MemorySet {
                 if ph_flags.is_execute() {
                     map_perm |= MapPermission::X;
                 }
-                let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
-                max_end_vpn = map_area.vpn_range.get_end();
-                memory_set.push(
-                    map_area,
-                    Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize]),
+                let kind = if map_perm.contains(MapPermission::X) {
+                    AreaKind::ElfText
+                } else {
+                    AreaKind::ElfData
+                };
+                let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm, kind);
+                // the segments need not come in address order
+                max_end_vpn = max_end_vpn.max(map_area.vpn_range.get_end());
+                memory_set.push(map_area, None);
+                let data =
+                    &elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize];
+                memory_set.areas.last_mut().unwrap().copy_data(
+                    &mut memory_set.page_table,
+                    data,
+                    start_va.page_offset(),
                 );
             }
         }
+        // keep the null page unmappable unless the ELF itself starts there
+        let null_page = VPNRange::new(VirtPageNum(0), VirtPageNum(1));
+        if !memory_set
+            .areas
+            .iter()
+            .any(|area| area.overlaps(&null_page))
+        {
+            memory_set.push(
+                MapArea::new(
+                    VirtAddr(0),
+                    VirtAddr(PAGE_SIZE),
+                    MapType::Reserved,
+                    MapPermission::empty(),
+                    AreaKind::Reserved,
+                ),
+                None,
+            );
+        }
         // map user stack with U flags
-        let max_end_va: VirtAddr = max_end_vpn.into();
+        let max_end_va: VirtAddr = VirtPageNum(max_end_vpn.0 + aslr.stack_pages).into();
         let mut user_stack_bottom: usize = max_end_va.into();
-        // guard page
+        // guard page, recorded so that mmap cannot fill it and an overflow
+        // into it can be told apart from other faults
         user_stack_bottom += PAGE_SIZE;
-        let user_stack_top = user_stack_bottom + USER_STACK_SIZE;
         memory_set.push(
             MapArea::new(
+                max_end_va,
+                user_stack_bottom.into(),
+                MapType::Reserved,
+                MapPermission::empty(),
+                AreaKind::StackGuard,
+            ),
+            None,
+        );
+        let user_stack_top = user_stack_bottom + USER_STACK_SIZE;
+        let mut stack_perm = MapPermission::R | MapPermission::W;
+        if exec_stack {
+            stack_perm |= MapPermission::X;
+        }
+        memory_set
+            .checked_insert(
                 user_stack_bottom.into(),
+                USER_STACK_SIZE,
+                stack_perm,
+                AreaKind::UserStack,
+                &MapPolicy::kernel(),
+                None,
+            )
+            .expect("failed to map user stack");
+        // the heap starts empty right above the stack, see `change_program_brk`
+        memory_set.push(
+            MapArea::new(
+                user_stack_top.into(),
                 user_stack_top.into(),
                 MapType::Framed,
                 MapPermission::R | MapPermission::W | MapPermission::U,
+                AreaKind::Heap,
             ),
             None,
         );
@@ -198,16 +456,29 @@ impl MemorySet {
                 TRAMPOLINE.into(),
                 MapType::Framed,
                 MapPermission::R | MapPermission::W,
+                AreaKind::TrapContext,
             ),
             None,
         );
+        memory_set.regions |= Regions::TRAP_CONTEXT;
         (
-            memory_set,
+            memory_set.finalize_user(),
             user_stack_top,
             elf.header.pt2.entry_point() as usize,
         )
     }
+    /// Switch to this memory set; it must be a finalized kernel space with
+    /// every mandatory region, or the kernel shuts down naming what is
+    /// missing instead of faulting on an unmapped page later.
     pub fn activate(&self) {
+        let missing = Regions::KERNEL - self.regions;
+        if !self.finalized || !missing.is_empty() {
+            error!(
+                "[kernel] refusing to activate memory set: finalized {}, missing {:?}",
+                self.finalized, missing
+            );
+            shutdown(true);
+        }
         let satp = self.page_table.token();
         unsafe {
             satp::write(satp);
@@ -217,6 +488,732 @@ impl MemorySet {
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.page_table.translate(vpn)
     }
+    /// Try to resolve a user page fault at `va`, returning whether the access
+    /// can be retried.
+    ///
+    /// Only a page of a lazy area that has no frame yet is resolved, by
+    /// giving it a zeroed one; a fault on any other page, or a lack of
+    /// frames, is left to kill the task.
+    pub fn handle_page_fault(&mut self, va: VirtAddr) -> bool {
+        let vpn = va.floor();
+        let page_table = &mut self.page_table;
+        match self.areas.iter_mut().find(|area| {
+            area.map_type == MapType::Lazy
+                && area.vpn_range.get_start() <= vpn
+                && vpn < area.vpn_range.get_end()
+        }) {
+            Some(area) if !area.data_frames.contains_key(&vpn) => {
+                area.populate(page_table, vpn).is_ok()
+            }
+            _ => false,
+        }
+    }
+    /// Print every area in address order, one line per run of pages with
+    /// the same PTE flags. Allocates nothing, so it also works when the
+    /// heap or the frames have run out.
+    pub fn debug_print(&self) {
+        let mut last: Option<(usize, usize)> = None;
+        // (start, index) orders areas without sorting a copy of the list
+        while let Some((key, area)) = self
+            .areas
+            .iter()
+            .enumerate()
+            .map(|(i, area)| ((area.vpn_range.get_start().0, i), area))
+            .filter(|(key, _)| last.map_or(true, |last| *key > last))
+            .min_by_key(|(key, _)| *key)
+        {
+            last = Some(key);
+            area.debug_print(&self.page_table);
+        }
+    }
+    /// Hash of the user-visible layout: range, kind, permission and which
+    /// pages are populated, for every non-kernel area in address order.
+    ///
+    /// Physical frames do not enter the hash, so equal layouts give equal
+    /// fingerprints across runs.
+    pub fn fingerprint(&self) -> u64 {
+        let mut areas: Vec<&MapArea> = self
+            .areas
+            .iter()
+            .filter(|area| area.kind != AreaKind::Kernel)
+            .collect();
+        areas.sort_by_key(|area| area.vpn_range.get_start().0);
+        let mut hash = Fnv1a::new();
+        for area in areas {
+            let start = area.vpn_range.get_start().0;
+            hash.write(start);
+            hash.write(area.vpn_range.get_end().0);
+            hash.write(area.kind as usize);
+            hash.write(area.map_perm.bits() as usize);
+            hash.write(area.data_frames.len());
+            for vpn in area.data_frames.keys() {
+                hash.write(vpn.0 - start);
+            }
+        }
+        hash.finish()
+    }
+    /// Kind of the area containing `va`, if any.
+    pub fn area_kind(&self, va: VirtAddr) -> Option<AreaKind> {
+        self.area_at(va).map(|area| area.kind)
+    }
+    /// Kind of the lowest area sharing a page with `range`, if any; tells an
+    /// mmap over the program from one over an earlier mmap.
+    pub fn intersects(&self, range: &VPNRange) -> Option<AreaKind> {
+        self.areas
+            .iter()
+            .filter(|area| area.overlaps(range))
+            .min_by_key(|area| area.vpn_range.get_start())
+            .map(|area| area.kind)
+    }
+    /// Whether `va` lies in a reservation of [`MemorySet::reserve`] that is
+    /// not committed yet.
+    pub fn reserved_at(&self, va: VirtAddr) -> bool {
+        self.area_at(va).map_or(false, |area| {
+            area.kind == AreaKind::Mmap && area.map_type == MapType::Reserved
+        })
+    }
+    fn area_at(&self, va: VirtAddr) -> Option<&MapArea> {
+        let vpn = va.floor();
+        self.areas
+            .iter()
+            .find(|area| area.vpn_range.get_start() <= vpn && vpn < area.vpn_range.get_end())
+    }
+    /// Like [`MemorySet::translate`], but served from the translate cache
+    /// when possible. Only valid entries are cached.
+    pub fn translate_cached(&mut self, vpn: VirtPageNum) -> Option<PageTableEntry> {
+        if let Some(pte) = self.translate_cache.lookup(vpn) {
+            kstat::inc(&KSTAT.translate_hits);
+            return Some(pte);
+        }
+        kstat::inc(&KSTAT.translate_misses);
+        let pte = self
+            .page_table
+            .translate(vpn)
+            .filter(|pte| pte.is_valid())?;
+        self.translate_cache.insert(vpn, pte);
+        Some(pte)
+    }
+    /// Split the user buffer `[ptr, ptr + len)`, which the task must be able
+    /// to read, into per-page byte slices, like [`super::UserSlice::readable`]
+    /// but through the translate cache. Lazy pages the task has not touched
+    /// yet are not mapped: see [`MemorySet::populate_lazy`], whose count of
+    /// pages the caller has to charge.
+    pub fn user_byte_buffer(
+        &mut self,
+        ptr: *const u8,
+        len: usize,
+    ) -> Result<Vec<&'static mut [u8]>, UserPtrError> {
+        let mut start = ptr as usize;
+        check_user_range(start, len, 1)?;
+        let end = start + len;
+        let mut v = Vec::new();
+        while start < end {
+            let start_va = VirtAddr::from(start);
+            let mut vpn = start_va.floor();
+            let ppn = self
+                .translate_cached(vpn)
+                .filter(|pte| pte.flags().contains(PTEFlags::U | PTEFlags::R))
+                .ok_or(UserPtrError::NotMapped)?
+                .ppn();
+            vpn.step();
+            let end_va = VirtAddr::from(vpn).min(VirtAddr::from(end));
+            let bytes = ppn.get_bytes_array();
+            if end_va.page_offset() == 0 {
+                v.push(&mut bytes[start_va.page_offset()..]);
+            } else {
+                v.push(&mut bytes[start_va.page_offset()..end_va.page_offset()]);
+            }
+            start = end_va.into();
+        }
+        Ok(v)
+    }
+    /// Give every untouched lazy page of `[ptr, ptr + len)` its frame, so
+    /// that a copy walking the page table alone finds the whole buffer;
+    /// returns how many pages got one.
+    pub fn populate_lazy(&mut self, ptr: usize, len: usize) -> usize {
+        let range = match user_vpn_range(VirtAddr::from(ptr), len) {
+            Ok(range) => range,
+            Err(_) => return 0,
+        };
+        let mut populated = 0;
+        for vpn in range {
+            if self.translate_cached(vpn).is_none() && self.handle_page_fault(vpn.into()) {
+                populated += 1;
+            }
+        }
+        populated
+    }
+    /// One bit per page of `[start, start + len)`, least significant bit
+    /// first, set if a frame backs the page; with `take_accessed` only if it
+    /// was accessed since the last such call as well, clearing the bits.
+    ///
+    /// Untouched lazy pages and reserved pages read 0. The range must be
+    /// page aligned at `start`, within [`USER_SPACE_END`] and at most
+    /// [`MMAP_MAX_PAGES`] long. Returns the bitmap and the number of bits set.
+    pub fn page_bitmap(
+        &mut self,
+        start: usize,
+        len: usize,
+        take_accessed: bool,
+    ) -> Result<(Vec<u8>, usize), MapError> {
+        if start % PAGE_SIZE != 0 {
+            return Err(MapError::Unaligned);
+        }
+        let range = user_vpn_range(VirtAddr::from(start), len)?;
+        let pages = range.len();
+        if pages > MMAP_MAX_PAGES {
+            return Err(MapError::BudgetExceeded);
+        }
+        let mut bitmap = Vec::new();
+        bitmap.resize((pages + 7) / 8, 0u8);
+        let mut set = 0;
+        for (i, vpn) in range.into_iter().enumerate() {
+            let hit = if take_accessed {
+                self.page_table.take_accessed(vpn)
+            } else {
+                self.page_table
+                    .translate(vpn)
+                    .map_or(false, |pte| pte.is_valid())
+            };
+            if hit {
+                bitmap[i / 8] |= 1 << (i % 8);
+                set += 1;
+            }
+        }
+        // a cached translation would go on accessing without setting A, the
+        // ASID is flushed on the way back to user space
+        Ok((bitmap, set))
+    }
+    /// The single entry point for creating user-visible areas.
+    ///
+    /// `perm` describes the requested access without the `U` bit, which is
+    /// always added here. The range `[start_va, start_va + len)` is rounded up
+    /// to whole pages and checked against alignment, the end of the user
+    /// address space, the existing area records, the limits in `policy` and
+    /// the free frames before anything is mapped. An aligned empty range
+    /// maps nothing and succeeds wherever it starts.
+    ///
+    /// An area with initial `data`, which must fit in `len`, is populated
+    /// right away whatever its size, with the data at its start and zeros
+    /// after it.
+    pub fn checked_insert(
+        &mut self,
+        start_va: VirtAddr,
+        len: usize,
+        perm: MapPermission,
+        kind: AreaKind,
+        policy: &MapPolicy,
+        data: Option<&[u8]>,
+    ) -> Result<(), MapError> {
+        assert!(
+            data.map_or(true, |data| data.len() <= len),
+            "initial data longer than the area"
+        );
+        stack_probe!(StackPath::CheckedInsert);
+        let vpn_range = match self.check_new_area(start_va, len, perm, policy)? {
+            Some(vpn_range) => vpn_range,
+            None => return Ok(()),
+        };
+        let end_va: VirtAddr = vpn_range.get_end().into();
+        let pages = vpn_range.len();
+        let map_type = if pages >= policy.lazy_pages && data.is_none() {
+            MapType::Lazy
+        } else {
+            MapType::Framed
+        };
+        // framed areas are populated right away, so the frames must be there
+        // now, including the page tables the range may need at worst
+        if map_type == MapType::Framed && frames_available() < pages + (pages + 511) / 512 + 2 {
+            return Err(MapError::OutOfMemory);
+        }
+        self.try_push(
+            MapArea::new(start_va, end_va, map_type, perm | MapPermission::U, kind),
+            data,
+        )?;
+        self.peak_pages = self.peak_pages.max(self.user_pages());
+        if cfg!(debug_assertions) {
+            let check = match map_type {
+                MapType::Lazy => self.assert_unmapped(vpn_range),
+                _ => self.assert_mapped_with(vpn_range, perm | MapPermission::U),
+            };
+            if let Err(vpn) = check {
+                panic!("page {:#x} mapped wrongly after checked_insert", vpn.0);
+            }
+        }
+        Ok(())
+    }
+    /// Reserve `[start_va, start_va + len)` as an mmap area without any
+    /// access, frames or valid PTEs, checked like
+    /// [`MemorySet::checked_insert`] but for the page budget: the pages only
+    /// count once [`MemorySet::mprotect`] commits them.
+    pub fn reserve(&mut self, start_va: VirtAddr, len: usize) -> Result<(), MapError> {
+        if !start_va.aligned() {
+            return Err(MapError::Unaligned);
+        }
+        let vpn_range = match self.check_new_range(start_va, len)? {
+            Some(vpn_range) => vpn_range,
+            None => return Ok(()),
+        };
+        self.try_push(
+            MapArea::new(
+                start_va,
+                vpn_range.get_end().into(),
+                MapType::Reserved,
+                MapPermission::empty(),
+                AreaKind::Mmap,
+            ),
+            None,
+        )?;
+        Ok(())
+    }
+    /// Map the shared memory of `mapping` at `start_va` as an mmap area,
+    /// checked like [`MemorySet::checked_insert`]; the frames are the
+    /// segment's, so only page tables may have to be allocated.
+    pub fn insert_shared(
+        &mut self,
+        start_va: VirtAddr,
+        mapping: ShmMapping,
+        perm: MapPermission,
+        policy: &MapPolicy,
+    ) -> Result<(), MapError> {
+        let pages = mapping.pages();
+        let vpn_range = self
+            .check_new_area(start_va, pages * PAGE_SIZE, perm, policy)?
+            .expect("shared memory segment without pages");
+        if frames_available() < (pages + 511) / 512 + 2 {
+            return Err(MapError::OutOfMemory);
+        }
+        self.try_push(
+            MapArea::new_shared(start_va, mapping, perm | MapPermission::U),
+            None,
+        )?;
+        self.peak_pages = self.peak_pages.max(self.user_pages());
+        if cfg!(debug_assertions) {
+            if let Err(vpn) = self.assert_mapped_with(vpn_range, perm | MapPermission::U) {
+                panic!("page {:#x} mapped wrongly after insert_shared", vpn.0);
+            }
+        }
+        Ok(())
+    }
+    /// The checks of [`MemorySet::checked_insert`] but for the free frames,
+    /// returning the pages of a new area, or `None` if it would be empty.
+    fn check_new_area(
+        &self,
+        start_va: VirtAddr,
+        len: usize,
+        perm: MapPermission,
+        policy: &MapPolicy,
+    ) -> Result<Option<VPNRange>, MapError> {
+        if !start_va.aligned() {
+            return Err(MapError::Unaligned);
+        }
+        check_perm(perm, policy)?;
+        let vpn_range = match self.check_new_range(start_va, len)? {
+            Some(vpn_range) => vpn_range,
+            None => return Ok(None),
+        };
+        let pages = vpn_range.len();
+        if self.user_pages() + pages > policy.max_pages {
+            return Err(MapError::BudgetExceeded);
+        }
+        Ok(Some(vpn_range))
+    }
+    /// The pages of a new area at an aligned `start_va`, `None` if it would
+    /// be empty; refused, with a warning naming the area in the way, if they
+    /// overlap an area.
+    fn check_new_range(
+        &self,
+        start_va: VirtAddr,
+        len: usize,
+    ) -> Result<Option<VPNRange>, MapError> {
+        if len == 0 {
+            return Ok(None);
+        }
+        let vpn_range = user_vpn_range(start_va, len)?;
+        if let Some(kind) = self.intersects(&vpn_range) {
+            warn!(
+                "[kernel] [{:#x}, {:#x}) overlaps the {:?} area",
+                start_va.0,
+                VirtAddr::from(vpn_range.get_end()).0,
+                kind
+            );
+            return Err(MapError::Overlap);
+        }
+        Ok(Some(vpn_range))
+    }
+    /// Find the lowest free range of `len` bytes at or above `MMAP_AUTO_BASE`,
+    /// moved up by the space's ASLR offset.
+    pub fn find_free_area(&self, len: usize) -> Option<VirtAddr> {
+        let pages = len.checked_add(PAGE_SIZE - 1)? / PAGE_SIZE;
+        let limit = VirtAddr::from(USER_SPACE_END).floor();
+        let mut start = self.mmap_base;
+        while start.0 + pages <= limit.0 {
+            let candidate = VPNRange::new(start, VirtPageNum(start.0 + pages));
+            match self.areas.iter().find(|area| area.overlaps(&candidate)) {
+                Some(area) => start = area.vpn_range.get_end(),
+                None => return Some(start.into()),
+            }
+        }
+        None
+    }
+    /// Free page ranges of the mmap window `[MMAP_AUTO_BASE, USER_SPACE_END)`,
+    /// lowest first, as `(start, end)` pairs.
+    fn mmap_window_gaps(&self) -> Vec<(VirtPageNum, VirtPageNum)> {
+        let window_start = VirtAddr::from(MMAP_AUTO_BASE).floor();
+        let window_end = VirtAddr::from(USER_SPACE_END).floor();
+        let window = VPNRange::new(window_start, window_end);
+        let mut taken: Vec<(VirtPageNum, VirtPageNum)> = self
+            .areas
+            .iter()
+            .filter(|area| area.overlaps(&window))
+            .map(|area| (area.vpn_range.get_start(), area.vpn_range.get_end()))
+            .collect();
+        taken.sort_unstable_by_key(|&(start, _)| start);
+        let mut gaps = Vec::new();
+        let mut cursor = window_start;
+        for (start, end) in taken {
+            if start > cursor {
+                gaps.push((cursor, start));
+            }
+            cursor = cursor.max(end);
+        }
+        if cursor < window_end {
+            gaps.push((cursor, window_end));
+        }
+        gaps
+    }
+    /// Largest free range of the mmap window, as start and length in bytes.
+    pub fn largest_free_gap(&self) -> Option<(VirtAddr, usize)> {
+        self.mmap_window_gaps()
+            .into_iter()
+            .max_by_key(|&(start, end)| (end.0 - start.0, core::cmp::Reverse(start)))
+            .map(|(start, end)| (start.into(), (end.0 - start.0) * PAGE_SIZE))
+    }
+    /// Lowest start in the mmap window, aligned to `align`, where
+    /// `checked_insert` of `len` bytes under `policy` would not overlap or
+    /// exceed the page budget. Nothing is mapped, the answer is advisory.
+    pub fn find_free_aligned(
+        &self,
+        len: usize,
+        align: usize,
+        policy: &MapPolicy,
+    ) -> Result<VirtAddr, MapError> {
+        if !align.is_power_of_two() || align < PAGE_SIZE {
+            return Err(MapError::Unaligned);
+        }
+        let pages = len.checked_add(PAGE_SIZE - 1).ok_or(MapError::Reserved)? / PAGE_SIZE;
+        if self.user_pages() + pages > policy.max_pages {
+            return Err(MapError::BudgetExceeded);
+        }
+        let align_pages = align / PAGE_SIZE;
+        self.mmap_window_gaps()
+            .into_iter()
+            .find_map(|(start, end)| {
+                let aligned = (start.0 + align_pages - 1) & !(align_pages - 1);
+                (aligned + pages <= end.0).then(|| VirtPageNum(aligned).into())
+            })
+            .ok_or(MapError::OutOfMemory)
+    }
+    /// Move the end of the heap area to `new_brk`, mapping or unmapping
+    /// whole pages; the heap never ends below `heap_bottom`.
+    ///
+    /// Growing fails without touching the page table if the new pages would
+    /// run into another area or the trap context, or exceed the page
+    /// budget or the free frames.
+    pub fn change_program_brk(
+        &mut self,
+        heap_bottom: VirtAddr,
+        new_brk: VirtAddr,
+        policy: &MapPolicy,
+    ) -> Result<(), MapError> {
+        if new_brk < heap_bottom {
+            return Err(MapError::Reserved);
+        }
+        let new_end = new_brk.ceil();
+        let heap = self
+            .areas
+            .iter()
+            .position(|area| area.kind == AreaKind::Heap)
+            .ok_or(MapError::NotMapped)?;
+        let old_end = self.areas[heap].vpn_range.get_end();
+        if new_end < old_end {
+            self.translate_cache.flush();
+            return Ok(self.areas[heap].shrink_to(&mut self.page_table, new_end)?);
+        }
+        let grown = VPNRange::new(old_end, new_end);
+        let pages = grown.len();
+        if pages == 0 {
+            return Ok(());
+        }
+        if new_end > VirtAddr::from(USER_SPACE_END).floor() {
+            return Err(MapError::Reserved);
+        }
+        if self.intersects(&grown).is_some() {
+            return Err(MapError::Overlap);
+        }
+        if self.user_pages() + pages > policy.max_pages {
+            return Err(MapError::BudgetExceeded);
+        }
+        if frames_available() < pages + (pages + 511) / 512 + 2 {
+            return Err(MapError::OutOfMemory);
+        }
+        self.areas[heap].append_to(&mut self.page_table, new_end)?;
+        self.peak_pages = self.peak_pages.max(self.user_pages());
+        Ok(())
+    }
+    /// Remove `[start_va, start_va + len)`, which areas of `kind` must cover
+    /// without a hole, returning how many data frames and page-table frames
+    /// were freed. An area reaching past either end of the range is split
+    /// there, and only the part inside is removed.
+    ///
+    /// Shared pages count as freed data frames as well, though their frames
+    /// stay as long as another area maps the segment.
+    ///
+    /// Intermediate tables the removal left empty are freed right away, see
+    /// [`PageTable::prune_empty_tables`].
+    ///
+    /// No TLB shootdown is issued here: this only runs inside a trap, and
+    /// the trap return flushes the space's ASID once any PTE changed, see
+    /// [`TlbSync::sync`], so one flush per syscall already covers any
+    /// number of unmapped pages.
+    pub fn checked_remove(
+        &mut self,
+        start_va: VirtAddr,
+        len: usize,
+        kind: AreaKind,
+    ) -> Result<(usize, usize), MapError> {
+        stack_probe!(StackPath::CheckedRemove);
+        let (vpn_range, hit) = self.take_covered(start_va, len, kind)?;
+        let mut freed = 0;
+        let mut result = Ok(());
+        for mut area in hit {
+            freed += area.resident_pages();
+            result = result.and(area.unmap(&mut self.page_table));
+        }
+        result?;
+        if cfg!(debug_assertions) {
+            if let Err(vpn) = self.assert_unmapped(vpn_range) {
+                panic!("page {:#x} still mapped after checked_remove", vpn.0);
+            }
+        }
+        let tables = self.page_table.prune_empty_tables();
+        kstat::add(&KSTAT.tables_pruned, tables);
+        Ok((freed, tables))
+    }
+    /// Change the permission of `[start_va, start_va + len)`, which mmap
+    /// areas must cover without a hole, to `perm` without touching the
+    /// data; checked like [`MemorySet::checked_insert`]. An area reaching
+    /// past either end of the range is split there, as in
+    /// [`MemorySet::checked_remove`], and the TLB is flushed the same way.
+    ///
+    /// Reserved pages in the range are committed, populated at once or
+    /// lazily as `checked_insert` would; returns how many frames that took.
+    pub fn mprotect(
+        &mut self,
+        start_va: VirtAddr,
+        len: usize,
+        perm: MapPermission,
+        policy: &MapPolicy,
+    ) -> Result<usize, MapError> {
+        check_perm(perm, policy)?;
+        let vpn_range = user_vpn_range(start_va, len)?;
+        let reserved: usize = self
+            .areas
+            .iter()
+            .filter(|area| area.kind == AreaKind::Mmap && area.map_type == MapType::Reserved)
+            .map(|area| area.overlap_pages(&vpn_range))
+            .sum();
+        if self.user_pages() + reserved > policy.max_pages {
+            return Err(MapError::BudgetExceeded);
+        }
+        let (_, hit) = self.take_covered(start_va, len, AreaKind::Mmap)?;
+        let mut result = Ok(());
+        let mut committed = 0;
+        for mut area in hit {
+            let map_perm = perm | MapPermission::U;
+            if area.map_type == MapType::Reserved {
+                let pages = area.vpn_range.len();
+                let lazy = pages >= policy.lazy_pages;
+                match area.commit(&mut self.page_table, map_perm, lazy) {
+                    Ok(frames) => committed += frames,
+                    Err(err) => result = result.and(Err(err)),
+                }
+            } else {
+                result = result.and(area.set_perm(&mut self.page_table, map_perm));
+            }
+            self.areas.push(area);
+        }
+        self.peak_pages = self.peak_pages.max(self.user_pages());
+        result?;
+        Ok(committed)
+    }
+    /// Take the areas covering `[start_va, start_va + len)` out of the set,
+    /// split at either end of the range so that only the parts inside are
+    /// returned, along with the range as pages. They must all be of `kind`
+    /// and leave no hole, or nothing is taken.
+    fn take_covered(
+        &mut self,
+        start_va: VirtAddr,
+        len: usize,
+        kind: AreaKind,
+    ) -> Result<(VPNRange, Vec<MapArea>), MapError> {
+        if !start_va.aligned() {
+            return Err(MapError::Unaligned);
+        }
+        let vpn_range = user_vpn_range(start_va, len)?;
+        let mut covered = 0;
+        for area in self.areas.iter().filter(|area| area.overlaps(&vpn_range)) {
+            if area.kind != kind {
+                return Err(MapError::NotMapped);
+            }
+            covered += area.overlap_pages(&vpn_range);
+        }
+        if covered != vpn_range.len() {
+            return Err(MapError::NotMapped);
+        }
+        self.translate_cache.flush();
+        let (mut hit, kept): (Vec<MapArea>, Vec<MapArea>) = core::mem::take(&mut self.areas)
+            .into_iter()
+            .partition(|area| area.overlaps(&vpn_range));
+        self.areas = kept;
+        for area in hit.iter_mut() {
+            if area.vpn_range.get_start() < vpn_range.get_start() {
+                let inside = area.split_off(vpn_range.get_start());
+                self.areas.push(core::mem::replace(area, inside));
+            }
+            if area.vpn_range.get_end() > vpn_range.get_end() {
+                self.areas.push(area.split_off(vpn_range.get_end()));
+            }
+        }
+        Ok((vpn_range, hit))
+    }
+    /// Whether every page of `range`, none or only some are mapped, see
+    /// [`PageTable::range_state`].
+    pub fn range_state(&self, range: VPNRange) -> RangeState {
+        self.page_table.range_state(range)
+    }
+    /// First page of `range` that still has a valid PTE, if any; only
+    /// searched for page by page once a single walk found one.
+    pub fn assert_unmapped(&self, range: VPNRange) -> Result<(), VirtPageNum> {
+        if self.range_state(range) == RangeState::AllUnmapped {
+            return Ok(());
+        }
+        match range.into_iter().find(|&vpn| {
+            self.page_table
+                .translate(vpn)
+                .map_or(false, |pte| pte.is_valid())
+        }) {
+            Some(vpn) => Err(vpn),
+            None => Ok(()),
+        }
+    }
+    /// First page of `range` that is not mapped with exactly `perm`, if any.
+    pub fn assert_mapped_with(
+        &self,
+        range: VPNRange,
+        perm: MapPermission,
+    ) -> Result<(), VirtPageNum> {
+        let expected = PTEFlags::from_bits(perm.bits).unwrap();
+        let mask = PTEFlags::R | PTEFlags::W | PTEFlags::X | PTEFlags::U;
+        match range.into_iter().find(|&vpn| {
+            self.page_table
+                .translate(vpn)
+                .filter(|pte| pte.is_valid())
+                .map_or(true, |pte| pte.flags() & mask != expected)
+        }) {
+            Some(vpn) => Err(vpn),
+            None => Ok(()),
+        }
+    }
+    /// Check the area records against the page table, logging every mismatch.
+    ///
+    /// No two areas may overlap, and every page of a framed or lazy area
+    /// must have a valid PTE pointing at its own, handed-out frame if it is
+    /// populated, and no valid PTE otherwise.
+    pub fn self_check(&self) -> bool {
+        let mut ok = true;
+        for (i, area) in self.areas.iter().enumerate() {
+            if self.areas[i + 1..]
+                .iter()
+                .any(|other| other.overlaps(&area.vpn_range))
+            {
+                error!(
+                    "[kernel] selfcheck: {:?} area at {:#x} overlaps another",
+                    area.kind,
+                    area.vpn_range.get_start().0
+                );
+                ok = false;
+            }
+            if !matches!(area.map_type, MapType::Framed | MapType::Lazy) {
+                continue;
+            }
+            for vpn in area.vpn_range {
+                let pte = self.page_table.translate(vpn).filter(|pte| pte.is_valid());
+                let consistent = match (area.data_frames.get(&vpn), pte) {
+                    (Some(frame), Some(pte)) => pte.ppn() == frame.ppn && !frame_is_free(frame.ppn),
+                    (None, None) => true,
+                    _ => false,
+                };
+                if !consistent {
+                    error!(
+                        "[kernel] selfcheck: page {:#x} of {:?} area: frame {:?}, valid PTE {}",
+                        vpn.0,
+                        area.kind,
+                        area.data_frames.get(&vpn).map(|frame| frame.ppn.0),
+                        pte.is_some()
+                    );
+                    ok = false;
+                }
+            }
+        }
+        ok
+    }
+    /// Clear the PTE of `vpn` behind its area's back, to corrupt a scratch
+    /// memory set for the self-check tests.
+    #[cfg(feature = "board_test")]
+    pub fn unmap_pte_unchecked(&mut self, vpn: VirtPageNum) -> Result<(), PageTableError> {
+        self.page_table.unmap(vpn)
+    }
+    /// Add a reserved area over `[start, end)` without looking for the
+    /// areas it overlaps, to corrupt a scratch memory set for the
+    /// self-check tests.
+    #[cfg(feature = "board_test")]
+    pub fn push_area_unchecked(&mut self, start: VirtAddr, end: VirtAddr) {
+        self.areas.push(MapArea::new(
+            start,
+            end,
+            MapType::Reserved,
+            MapPermission::empty(),
+            AreaKind::Reserved,
+        ));
+    }
+    /// Most pages user-visible areas have held at once.
+    pub fn peak_user_pages(&self) -> usize {
+        self.peak_pages.max(self.user_pages())
+    }
+    /// Number of frames backing user pages, the trap context included;
+    /// untouched lazy pages have none.
+    pub fn resident_pages(&self) -> usize {
+        self.areas
+            .iter()
+            .filter(|area| area.kind != AreaKind::Kernel)
+            .map(|area| area.resident_pages())
+            .sum()
+    }
+    /// Number of pages held by user-visible areas, excluding the trap context
+    /// and reserved mmap pages.
+    pub fn user_pages(&self) -> usize {
+        self.areas
+            .iter()
+            .filter(|area| {
+                matches!(
+                    area.kind,
+                    AreaKind::UserStack | AreaKind::Mmap | AreaKind::Heap
+                ) && area.map_type != MapType::Reserved
+            })
+            .map(|area| area.vpn_range.len())
+            .sum()
+    }
 }
 
 /// map area structure, controls a contiguous piece of virtual memory
@@ -225,6 +1222,9 @@ pub struct MapArea {
     data_frames: BTreeMap<VirtPageNum, FrameTracker>,
     map_type: MapType,
     map_perm: MapPermission,
+    kind: AreaKind,
+    /// the segment a `Shared` area maps
+    shared: Option<ShmMapping>,
 }
 
 impl MapArea {
@@ -233,6 +1233,7 @@ impl MapArea {
         end_va: VirtAddr,
         map_type: MapType,
         map_perm: MapPermission,
+        kind: AreaKind,
     ) -> Self {
         let start_vpn: VirtPageNum = start_va.floor();
         let end_vpn: VirtPageNum = end_va.ceil();
@@ -241,74 +1242,482 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type,
             map_perm,
+            kind,
+            shared: None,
+        }
+    }
+    /// An mmap area at `start_va` mapping all of `mapping`.
+    pub fn new_shared(start_va: VirtAddr, mapping: ShmMapping, map_perm: MapPermission) -> Self {
+        let end_va = VirtAddr::from(start_va.0 + mapping.pages() * PAGE_SIZE);
+        Self {
+            shared: Some(mapping),
+            ..Self::new(start_va, end_va, MapType::Shared, map_perm, AreaKind::Mmap)
+        }
+    }
+    /// Number of pages with a frame; every page of a shared area has one.
+    fn resident_pages(&self) -> usize {
+        match self.map_type {
+            MapType::Shared => self.vpn_range.len(),
+            _ => self.data_frames.len(),
+        }
+    }
+    /// Print the area as runs of pages with the same PTE flags, see
+    /// [`MemorySet::debug_print`].
+    fn debug_print(&self, page_table: &PageTable) {
+        let flags_of = |vpn| {
+            page_table
+                .translate(vpn)
+                .filter(|pte| pte.is_valid())
+                // A and D differ between pages that are otherwise alike
+                .map(|pte| pte.flags() & (PTEFlags::R | PTEFlags::W | PTEFlags::X | PTEFlags::U))
+        };
+        let (start, end) = (self.vpn_range.get_start(), self.vpn_range.get_end());
+        let mut run_start = start;
+        for vpn in start.0 + 1..=end.0 {
+            let flags = flags_of(run_start);
+            if vpn < end.0 && flags_of(VirtPageNum(vpn)) == flags {
+                continue;
+            }
+            let bit = |flag: PTEFlags, c: char| match flags {
+                Some(flags) if flags.contains(flag) => c,
+                _ => '-',
+            };
+            println!(
+                "[kernel]   [{:#x}, {:#x}) perms={}{}{}{} frames={} type={:?} kind={:?}",
+                VirtAddr::from(run_start).0,
+                VirtAddr::from(VirtPageNum(vpn)).0,
+                bit(PTEFlags::R, 'R'),
+                bit(PTEFlags::W, 'W'),
+                bit(PTEFlags::X, 'X'),
+                bit(PTEFlags::U, 'U'),
+                if self.map_type == MapType::Shared {
+                    vpn - run_start.0
+                } else {
+                    self.data_frames.range(run_start..VirtPageNum(vpn)).count()
+                },
+                self.map_type,
+                self.kind
+            );
+            run_start = VirtPageNum(vpn);
         }
     }
-    pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
-        let ppn: PhysPageNum;
+    /// Whether this area shares at least one page with `range`.
+    pub fn overlaps(&self, range: &VPNRange) -> bool {
+        self.vpn_range.get_start() < range.get_end() && range.get_start() < self.vpn_range.get_end()
+    }
+    /// Number of pages this area shares with `range`.
+    fn overlap_pages(&self, range: &VPNRange) -> usize {
+        let start = self.vpn_range.get_start().max(range.get_start());
+        let end = self.vpn_range.get_end().min(range.get_end());
+        start.steps_to(&end)
+    }
+    pub fn map_one(
+        &mut self,
+        page_table: &mut PageTable,
+        vpn: VirtPageNum,
+    ) -> Result<(), PageTableError> {
         match self.map_type {
+            // kernel memory mapped as it is, nothing to zero
             MapType::Identical => {
-                ppn = PhysPageNum(vpn.0);
+                let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+                page_table.map(vpn, PhysPageNum(vpn.0), pte_flags)
             }
-            MapType::Framed => {
-                let frame = frame_alloc().unwrap();
-                ppn = frame.ppn;
-                self.data_frames.insert(vpn, frame);
+            MapType::Framed => self.populate(page_table, vpn),
+            MapType::Shared => {
+                let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+                let page = vpn.0 - self.vpn_range.get_start().0;
+                let ppn = self.shared.as_ref().unwrap().ppn(page);
+                page_table.map(vpn, ppn, pte_flags)
             }
+            MapType::Lazy | MapType::Reserved => Ok(()),
         }
+    }
+    /// Give page `vpn` a fresh frame and map it; the frame is zeroed before
+    /// the PTE becomes valid, see [`FrameTracker::new`].
+    fn populate(
+        &mut self,
+        page_table: &mut PageTable,
+        vpn: VirtPageNum,
+    ) -> Result<(), PageTableError> {
         let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
-        page_table.map(vpn, ppn, pte_flags);
+        let frame = frame_alloc().ok_or(PageTableError::OutOfMemory)?;
+        page_table.map(vpn, frame.ppn, pte_flags)?;
+        self.data_frames.insert(vpn, frame);
+        Ok(())
     }
-    #[allow(unused)]
-    pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
-        #[allow(clippy::single_match)]
+    pub fn unmap_one(
+        &mut self,
+        page_table: &mut PageTable,
+        vpn: VirtPageNum,
+    ) -> Result<(), PageTableError> {
         match self.map_type {
+            // the frames stay with the segment
+            MapType::Identical | MapType::Shared => {}
             MapType::Framed => {
                 self.data_frames.remove(&vpn);
             }
-            _ => {}
+            MapType::Lazy => {
+                // a page never touched has nothing to unmap
+                if self.data_frames.remove(&vpn).is_none() {
+                    return Ok(());
+                }
+            }
+            MapType::Reserved => return Ok(()),
         }
-        page_table.unmap(vpn);
+        page_table.unmap(vpn)
     }
-    pub fn map(&mut self, page_table: &mut PageTable) {
-        for vpn in self.vpn_range {
-            self.map_one(page_table, vpn);
+    /// Turn a reserved area into a lazy or a framed one with `map_perm`,
+    /// returning the frames it took; it stays reserved if they run out.
+    fn commit(
+        &mut self,
+        page_table: &mut PageTable,
+        map_perm: MapPermission,
+        lazy: bool,
+    ) -> Result<usize, PageTableError> {
+        debug_assert_eq!(self.map_type, MapType::Reserved, "area already committed");
+        self.map_type = if lazy { MapType::Lazy } else { MapType::Framed };
+        self.map_perm = map_perm;
+        if let Err(err) = self.map(page_table) {
+            self.map_type = MapType::Reserved;
+            self.map_perm = MapPermission::empty();
+            return Err(err);
         }
+        Ok(self.resident_pages())
     }
-    #[allow(unused)]
-    pub fn unmap(&mut self, page_table: &mut PageTable) {
+    /// Record `map_perm` as the area's permission and give it to every page
+    /// mapped so far; pages mapped later get it from the record.
+    fn set_perm(
+        &mut self,
+        page_table: &mut PageTable,
+        map_perm: MapPermission,
+    ) -> Result<(), PageTableError> {
+        self.map_perm = map_perm;
+        let pte_flags = PTEFlags::from_bits(map_perm.bits).unwrap();
         for vpn in self.vpn_range {
-            self.unmap_one(page_table, vpn);
+            if page_table
+                .translate(vpn)
+                .map_or(false, |pte| pte.is_valid())
+            {
+                page_table.set_flags(vpn, pte_flags)?;
+            }
+        }
+        Ok(())
+    }
+    /// Whether the 2 MiB page at `vpn` lies within this identical area, so
+    /// that one level-1 leaf maps it. Identical areas are never split or
+    /// resized, so this stays the same while the area is mapped.
+    fn huge_at(&self, vpn: VirtPageNum) -> bool {
+        self.map_type == MapType::Identical
+            && vpn.0 % HUGE_PAGE_PAGES == 0
+            && vpn.0 + HUGE_PAGE_PAGES <= self.vpn_range.get_end().0
+    }
+    /// Map the page at `vpn`, or the 2 MiB page there, returning how many
+    /// pages were mapped.
+    fn map_at(
+        &mut self,
+        page_table: &mut PageTable,
+        vpn: VirtPageNum,
+    ) -> Result<usize, PageTableError> {
+        if self.huge_at(vpn) {
+            let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+            page_table.map_huge(vpn, PhysPageNum(vpn.0), pte_flags)?;
+            Ok(HUGE_PAGE_PAGES)
+        } else {
+            self.map_one(page_table, vpn)?;
+            Ok(1)
+        }
+    }
+    /// Undo [`MapArea::map_at`] of `vpn`, returning how many pages it unmapped.
+    fn unmap_at(
+        &mut self,
+        page_table: &mut PageTable,
+        vpn: VirtPageNum,
+    ) -> Result<usize, PageTableError> {
+        if self.huge_at(vpn) {
+            page_table.unmap_huge(vpn)?;
+            Ok(HUGE_PAGE_PAGES)
+        } else {
+            self.unmap_one(page_table, vpn)?;
+            Ok(1)
+        }
+    }
+    /// Map every page, or none of them if one fails.
+    pub fn map(&mut self, page_table: &mut PageTable) -> Result<(), PageTableError> {
+        let (start, end) = (self.vpn_range.get_start(), self.vpn_range.get_end());
+        let mut vpn = start;
+        while vpn < end {
+            match self.map_at(page_table, vpn) {
+                Ok(pages) => vpn = VirtPageNum(vpn.0 + pages),
+                Err(err) => {
+                    let mut mapped = start;
+                    while mapped < vpn {
+                        let pages = self
+                            .unmap_at(page_table, mapped)
+                            .expect("page mapped a moment ago is gone");
+                        mapped = VirtPageNum(mapped.0 + pages);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Unmap every page; the 4 KiB pages of a user area go in a single
+    /// walk of the page table, see [`PageTable::unmap_range`].
+    pub fn unmap(&mut self, page_table: &mut PageTable) -> Result<(), PageTableError> {
+        let expected = match self.map_type {
+            MapType::Framed | MapType::Lazy => self.data_frames.len(),
+            MapType::Shared => self.vpn_range.len(),
+            MapType::Reserved => return Ok(()),
+            MapType::Identical => {
+                let mut vpn = self.vpn_range.get_start();
+                while vpn < self.vpn_range.get_end() {
+                    vpn = VirtPageNum(vpn.0 + self.unmap_at(page_table, vpn)?);
+                }
+                return Ok(());
+            }
+        };
+        let unmapped = page_table.unmap_range(self.vpn_range);
+        self.data_frames.clear();
+        if unmapped == expected {
+            Ok(())
+        } else {
+            Err(PageTableError::NotMapped)
+        }
+    }
+    /// Number of 2 MiB pages the area is mapped with.
+    fn huge_pages(&self) -> usize {
+        self.vpn_range
+            .into_iter()
+            .filter(|&vpn| self.huge_at(vpn))
+            .count()
+    }
+    /// Grow the area up to `new_end`, mapping every new page or none.
+    pub fn append_to(
+        &mut self,
+        page_table: &mut PageTable,
+        new_end: VirtPageNum,
+    ) -> Result<(), PageTableError> {
+        let old_end = self.vpn_range.get_end();
+        for vpn in VPNRange::new(old_end, new_end) {
+            if let Err(err) = self.map_one(page_table, vpn) {
+                for mapped in VPNRange::new(old_end, vpn) {
+                    self.unmap_one(page_table, mapped)
+                        .expect("page mapped a moment ago is gone");
+                }
+                return Err(err);
+            }
+        }
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
+        Ok(())
+    }
+    /// Split the area at `at`, keeping `[start, at)` and returning `[at, end)`
+    /// with its frames; nothing is mapped or unmapped.
+    pub fn split_off(&mut self, at: VirtPageNum) -> Self {
+        let (start, end) = (self.vpn_range.get_start(), self.vpn_range.get_end());
+        assert!(start < at && at < end, "split outside the area");
+        self.vpn_range = VPNRange::new(start, at);
+        Self {
+            vpn_range: VPNRange::new(at, end),
+            data_frames: self.data_frames.split_off(&at),
+            map_type: self.map_type,
+            map_perm: self.map_perm,
+            kind: self.kind,
+            shared: self
+                .shared
+                .as_ref()
+                .map(|mapping| mapping.split_at(at.0 - start.0)),
         }
     }
-    /// data: start-aligned but maybe with shorter length
+    /// Shrink the area down to `new_end`, unmapping the pages past it.
+    pub fn shrink_to(
+        &mut self,
+        page_table: &mut PageTable,
+        new_end: VirtPageNum,
+    ) -> Result<(), PageTableError> {
+        for vpn in VPNRange::new(new_end, self.vpn_range.get_end()) {
+            self.unmap_one(page_table, vpn)?;
+        }
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
+        Ok(())
+    }
+    /// data: starting `offset` bytes into the first page, maybe shorter
+    /// than the area
     /// assume that all frames were cleared before
-    pub fn copy_data(&mut self, page_table: &mut PageTable, data: &[u8]) {
+    pub fn copy_data(&mut self, page_table: &mut PageTable, data: &[u8], offset: usize) {
         assert_eq!(self.map_type, MapType::Framed);
         let mut start: usize = 0;
+        let mut page_offset = offset;
         let mut current_vpn = self.vpn_range.get_start();
         let len = data.len();
-        loop {
-            let src = &data[start..len.min(start + PAGE_SIZE)];
+        while start < len {
+            let src = &data[start..len.min(start + PAGE_SIZE - page_offset)];
             let dst = &mut page_table
                 .translate(current_vpn)
                 .unwrap()
                 .ppn()
-                .get_bytes_array()[..src.len()];
+                .get_bytes_array()[page_offset..page_offset + src.len()];
             dst.copy_from_slice(src);
-            start += PAGE_SIZE;
-            if start >= len {
-                break;
-            }
+            start += src.len();
+            page_offset = 0;
             current_vpn.step();
         }
     }
 }
 
 #[derive(Copy, Clone, PartialEq, Debug)]
-/// map type for memory set: identical or framed
+/// map type for memory set: identical, framed, framed on first access,
+/// reserved without any PTE, or the frames of a shared memory segment
 pub enum MapType {
     Identical,
     Framed,
+    Lazy,
+    Reserved,
+    Shared,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+/// what an area is used for, recorded so that checks can tell areas apart
+pub enum AreaKind {
+    /// kernel sections and kernel stacks
+    Kernel,
+    /// an executable LOAD segment of the application ELF
+    ElfText,
+    /// any other LOAD segment of the application ELF
+    ElfData,
+    UserStack,
+    TrapContext,
+    /// an area created by `sys_mmap`
+    Mmap,
+    /// the area `sys_sbrk` grows and shrinks
+    Heap,
+    /// address space that must never be mapped, such as the null page
+    Reserved,
+    /// the page right below the user stack, never mapped
+    StackGuard,
+}
+
+/// The ASID, root and modified flag of a memory set, see
+/// [`MemorySet::tlb_sync`].
+pub struct TlbSync {
+    asid: usize,
+    root_ppn: usize,
+    modified: Arc<AtomicBool>,
+}
+
+impl TlbSync {
+    /// Flush whatever the TLB may still hold of the space's old PTEs, or of
+    /// another space sharing its ASID; the trap return calls it before
+    /// loading [`MemorySet::token`].
+    pub fn sync(&self) {
+        let modified = self.modified.swap(false, Ordering::Relaxed);
+        sync_asid(self.asid, self.root_ppn, modified);
+    }
+}
+
+/// Limits applied by [`MemorySet::checked_insert`] on behalf of a task.
+#[derive(Copy, Clone, Debug)]
+pub struct MapPolicy {
+    /// refuse areas that are both writable and executable
+    pub deny_wx: bool,
+    /// upper bound on pages held by user stack and mmap areas
+    pub max_pages: usize,
+    /// kill the task when it is found executing outside its ELF text
+    pub exec_text_only: bool,
+    /// areas of at least this many pages are populated lazily
+    pub lazy_pages: usize,
+}
+
+impl MapPolicy {
+    /// Policy for mappings the kernel sets up itself, e.g. the user stack.
+    pub fn kernel() -> Self {
+        Self {
+            deny_wx: false,
+            max_pages: usize::MAX,
+            exec_text_only: false,
+            lazy_pages: usize::MAX,
+        }
+    }
+}
+
+impl Default for MapPolicy {
+    fn default() -> Self {
+        Self {
+            deny_wx: MMAP_DENY_WX,
+            max_pages: MMAP_MAX_PAGES,
+            exec_text_only: EXEC_TEXT_ONLY,
+            lazy_pages: MMAP_LAZY_PAGES,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+/// reasons for [`MemorySet::checked_insert`] and [`MemorySet::checked_remove`] to refuse
+pub enum MapError {
+    Unaligned,
+    InvalidPermission,
+    WriteExecute,
+    Reserved,
+    Overlap,
+    BudgetExceeded,
+    /// not enough free frames to populate the area
+    OutOfMemory,
+    NotMapped,
+}
+
+impl From<PageTableError> for MapError {
+    fn from(err: PageTableError) -> Self {
+        match err {
+            PageTableError::OutOfMemory => Self::OutOfMemory,
+            PageTableError::AlreadyMapped => Self::Overlap,
+            PageTableError::NotMapped => Self::NotMapped,
+        }
+    }
+}
+
+/// A range that cannot exist lies in no space that may be mapped.
+impl From<RangeError> for MapError {
+    fn from(_: RangeError) -> Self {
+        Self::Reserved
+    }
+}
+
+impl MapError {
+    /// The value returned to user space by the memory syscalls: -2 for a
+    /// range overlapping an area, -3 for one the task's page budget or the
+    /// free frames cannot back, -1 for any other refusal. The lab's
+    /// `sys_mmap` and `sys_munmap` return -1 for all of them instead.
+    pub fn errno(self) -> isize {
+        match self {
+            Self::Overlap => -2,
+            Self::BudgetExceeded | Self::OutOfMemory => -3,
+            _ => -1,
+        }
+    }
+}
+
+bitflags! {
+    /// Named regions of a memory set, recorded as they are mapped.
+    pub struct Regions: u16 {
+        const TEXT = 1 << 0;
+        const RODATA = 1 << 1;
+        const DATA = 1 << 2;
+        const BSS = 1 << 3;
+        const BOOT_STACK = 1 << 4;
+        /// physical memory after the kernel, which the frame allocator hands out
+        const FRAME_POOL = 1 << 5;
+        const TRAMPOLINE = 1 << 6;
+        const TRAP_CONTEXT = 1 << 7;
+        /// what `activate` requires of the kernel space
+        const KERNEL = Self::TEXT.bits
+            | Self::RODATA.bits
+            | Self::DATA.bits
+            | Self::BSS.bits
+            | Self::BOOT_STACK.bits
+            | Self::FRAME_POOL.bits;
+        /// what a user space needs before its first dispatch
+        const USER = Self::TRAMPOLINE.bits | Self::TRAP_CONTEXT.bits;
+    }
 }
 
 bitflags! {
@@ -321,6 +1730,61 @@ bitflags! {
     }
 }
 
+impl MapPermission {
+    /// Convert the `port` argument of `sys_mmap2` (bit 0: R, bit 1: W, bit 2: X).
+    ///
+    /// W without R is refused, SV39 reserves that combination in a PTE.
+    pub fn from_port(port: usize) -> Result<Self, MapError> {
+        let perm = Self::from_lab_port(port)?;
+        if perm.contains(Self::W) && !perm.contains(Self::R) {
+            return Err(MapError::InvalidPermission);
+        }
+        Ok(perm)
+    }
+
+    /// Convert `port` as the lab's `sys_mmap` does, which takes W without R
+    /// as it is: the page is mapped, and any access to it faults.
+    pub fn from_lab_port(port: usize) -> Result<Self, MapError> {
+        if port & !0x7 != 0 {
+            return Err(MapError::InvalidPermission);
+        }
+        let mut perm = Self::empty();
+        if port & 0x1 != 0 {
+            perm |= Self::R;
+        }
+        if port & 0x2 != 0 {
+            perm |= Self::W;
+        }
+        if port & 0x4 != 0 {
+            perm |= Self::X;
+        }
+        Ok(perm)
+    }
+}
+
+/// The permission checks of [`MemorySet::checked_insert`] and
+/// [`MemorySet::mprotect`].
+fn check_perm(perm: MapPermission, policy: &MapPolicy) -> Result<(), MapError> {
+    if perm.is_empty() || perm.contains(MapPermission::U) {
+        return Err(MapError::InvalidPermission);
+    }
+    if policy.deny_wx && perm.contains(MapPermission::W | MapPermission::X) {
+        return Err(MapError::WriteExecute);
+    }
+    Ok(())
+}
+
+/// `[start_va, start_va + len)` as whole pages, refused as reserved if it
+/// overflows or does not end within [`USER_SPACE_END`].
+fn user_vpn_range(start_va: VirtAddr, len: usize) -> Result<VPNRange, MapError> {
+    match start_va.checked_add(len) {
+        Some(end) if end.0 <= USER_SPACE_END => {
+            Ok(VPNRange::try_new(start_va.floor(), end.ceil())?)
+        }
+        _ => Err(MapError::Reserved),
+    }
+}
+
 #[allow(unused)]
 pub fn remap_test() {
     let mut kernel_space = KERNEL_SPACE.lock();
@@ -342,5 +1806,139 @@ pub fn remap_test() {
         .translate(mid_data.floor())
         .unwrap()
         .executable());
+    // the last frame lies in a 2 MiB page unless memory ends unaligned, and
+    // is identity mapped either way
+    let last_frame: VirtAddr = (MEMORY_END - PAGE_SIZE).into();
+    let pte = kernel_space
+        .page_table
+        .translate(last_frame.floor())
+        .unwrap();
+    assert!(pte.is_valid() && pte.writable() && pte.ppn().0 == last_frame.floor().0);
     info!("remap_test passed!");
 }
+
+/// Check on a scratch memory set that `checked_insert` and `checked_remove`
+/// refuse ranges that overflow or leave the user address space.
+pub fn user_range_test() {
+    let mut memory_set = MemorySet::new_bare();
+    let policy = MapPolicy::default();
+    let mut insert = |start: usize, len: usize| {
+        memory_set.checked_insert(
+            start.into(),
+            len,
+            MapPermission::R | MapPermission::W,
+            AreaKind::Mmap,
+            &policy,
+            None,
+        )
+    };
+    let last = USER_SPACE_END - PAGE_SIZE;
+    // `start + len` overflows
+    assert_eq!(insert(TRAMPOLINE, PAGE_SIZE), Err(MapError::Reserved));
+    assert_eq!(insert(last, usize::MAX), Err(MapError::Reserved));
+    // the trap context, the trampoline and the non-canonical hole
+    assert_eq!(insert(TRAP_CONTEXT, PAGE_SIZE), Err(MapError::Reserved));
+    assert_eq!(insert(TRAMPOLINE, 1), Err(MapError::Reserved));
+    assert_eq!(insert(USER_SPACE_END, PAGE_SIZE), Err(MapError::Reserved));
+    assert_eq!(insert(last, PAGE_SIZE + 1), Err(MapError::Reserved));
+    // an empty range maps nothing, wherever it is
+    assert_eq!(insert(TRAP_CONTEXT, 0), Ok(()));
+    assert_eq!(insert(last, PAGE_SIZE), Ok(()));
+    assert_eq!(memory_set.user_pages(), 1);
+    let mut remove =
+        |start: usize, len: usize| memory_set.checked_remove(start.into(), len, AreaKind::Mmap);
+    assert_eq!(remove(last, usize::MAX), Err(MapError::Reserved));
+    assert_eq!(remove(TRAMPOLINE, PAGE_SIZE), Err(MapError::Reserved));
+    assert_eq!(remove(last, 2 * PAGE_SIZE), Err(MapError::Reserved));
+    assert!(remove(last, PAGE_SIZE).is_ok());
+    assert_eq!(memory_set.user_pages(), 0);
+    info!("user_range_test passed!");
+}
+
+/// Check `MemorySet::range_state` on a 64 MiB lazy area of a scratch memory
+/// set, against the page by page walk it replaces, which is timed too.
+pub fn range_state_test() {
+    let mut memory_set = MemorySet::new_bare();
+    let start = VirtAddr::from(MMAP_AUTO_BASE);
+    let pages = (64 << 20) / PAGE_SIZE;
+    memory_set
+        .checked_insert(
+            start,
+            pages * PAGE_SIZE,
+            MapPermission::R | MapPermission::W,
+            AreaKind::Mmap,
+            &MapPolicy::default(),
+            None,
+        )
+        .unwrap();
+    let vpn = |page: usize| VirtPageNum(start.floor().0 + page);
+    let range = |from: usize, to: usize| VPNRange::new(vpn(from), vpn(to));
+    let walk_start = get_time();
+    assert_eq!(
+        memory_set.range_state(range(0, pages)),
+        RangeState::AllUnmapped
+    );
+    let walk_cycles = get_time() - walk_start;
+    let by_page_start = get_time();
+    assert!(range(0, pages).into_iter().all(|vpn| memory_set
+        .translate(vpn)
+        .map_or(true, |pte| !pte.is_valid())));
+    let by_page_cycles = get_time() - by_page_start;
+    // 511 and 512 sit in different last-level tables
+    for page in [0, 511, 512, pages - 1] {
+        assert!(memory_set.handle_page_fault(vpn(page).into()));
+    }
+    assert_eq!(memory_set.range_state(range(0, pages)), RangeState::Mixed);
+    assert_eq!(memory_set.range_state(range(510, 513)), RangeState::Mixed);
+    assert_eq!(
+        memory_set.range_state(range(511, 513)),
+        RangeState::AllMapped
+    );
+    assert_eq!(
+        memory_set.range_state(range(1, 511)),
+        RangeState::AllUnmapped
+    );
+    assert_eq!(
+        memory_set.range_state(range(513, 513)),
+        RangeState::AllUnmapped
+    );
+    // the three last-level tables go, and the level-1 one above them
+    assert_eq!(
+        memory_set.checked_remove(start, pages * PAGE_SIZE, AreaKind::Mmap),
+        Ok((4, 4))
+    );
+    assert_eq!(
+        memory_set.range_state(range(0, pages)),
+        RangeState::AllUnmapped
+    );
+    info!(
+        "range_state_test passed! 64 MiB in {} cycles, {} page by page",
+        walk_cycles, by_page_cycles
+    );
+}
+
+/// Check `MapPermission::from_port` on every combination of the R, W and X
+/// bits, and that other bits are refused.
+pub fn map_permission_test() {
+    use MapPermission as P;
+    let expected = [
+        Ok(P::empty()),
+        Ok(P::R),
+        Err(MapError::InvalidPermission),
+        Ok(P::R | P::W),
+        Ok(P::X),
+        Ok(P::R | P::X),
+        Err(MapError::InvalidPermission),
+        Ok(P::R | P::W | P::X),
+    ];
+    for (port, expected) in expected.iter().enumerate() {
+        assert_eq!(P::from_port(port), *expected, "port {:#x}", port);
+    }
+    for port in [0x8, 0x9, 0x10, usize::MAX] {
+        assert_eq!(P::from_port(port), Err(MapError::InvalidPermission));
+        assert_eq!(P::from_lab_port(port), Err(MapError::InvalidPermission));
+    }
+    assert_eq!(P::from_lab_port(0x2), Ok(P::W));
+    assert_eq!(P::from_lab_port(0x6), Ok(P::W | P::X));
+    info!("map_permission_test passed!");
+}
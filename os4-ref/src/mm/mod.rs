@@ -1,29 +1,44 @@
 //! Memory management implementation
-//! 
+//!
 //! SV39 page-based virtual-memory architecture for RV64 systems, and
 //! everything about memory management, like frame allocator, page table,
 //! map area and memory set, is implemented here.
-//! 
+//!
 //! Every task or process has a memory_set to control its virtual memory.
 
-
 mod address;
+mod asid;
+mod aslr;
 mod frame_allocator;
 mod heap_allocator;
+#[cfg(feature = "board_test")]
+pub mod kernel_tests;
 mod memory_set;
 mod page_table;
+mod shm;
+mod user_ptr;
 
 pub use address::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
-use address::{StepByOne, VPNRange};
-pub use frame_allocator::{frame_alloc, FrameTracker};
-pub use memory_set::remap_test;
-pub use memory_set::{MapPermission, MemorySet, KERNEL_SPACE};
-pub use page_table::{translated_byte_buffer, PageTableEntry};
-use page_table::{PTEFlags, PageTable};
+use address::{RangeError, StepByOne, VPNRange};
+pub use asid::asid_bits;
+pub use aslr::{aslr_seed, AslrOffsets};
+pub use frame_allocator::{
+    frame_alloc, frame_allocator_check, frame_stats, frames_available, frames_in_use, mem_pressure,
+    FrameTracker, MemPressure, RecyclePolicy,
+};
+pub use memory_set::{map_permission_test, range_state_test, remap_test, user_range_test};
+pub use memory_set::{
+    AreaKind, MapError, MapPermission, MapPolicy, MemorySet, TlbSync, KERNEL_SPACE,
+};
+use page_table::{checked_user_buffer, PTEFlags, PageTable, RangeState, HUGE_PAGE_PAGES};
+pub use page_table::{copy_str_from_user, translated_byte_buffer, PageTableEntry, PageTableError};
+pub use shm::{shm_clear, shm_create, shm_get};
+pub use user_ptr::{check_user_range, UserPtr, UserPtrError, UserSlice};
 
 /// initiate heap allocator, frame allocator and kernel space
 pub fn init() {
     heap_allocator::init_heap();
     frame_allocator::init_frame_allocator();
     KERNEL_SPACE.lock().activate();
+    asid::init_asid_allocator();
 }
@@ -1,9 +1,12 @@
 //! Implementation of [`PageTableEntry`] and [`PageTable`].
 
-use super::{frame_alloc, FrameTracker, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
+use super::{frame_alloc, FrameTracker, PhysPageNum, StepByOne, VPNRange, VirtAddr, VirtPageNum};
+use alloc::sync::Arc;
 use alloc::vec;
 use alloc::vec::Vec;
 use bitflags::*;
+use core::sync::atomic::{AtomicBool, Ordering};
+use lazy_static::*;
 
 bitflags! {
     /// page table entry flags
@@ -53,86 +56,349 @@ impl PageTableEntry {
     pub fn executable(&self) -> bool {
         (self.flags() & PTEFlags::X) != PTEFlags::empty()
     }
+    /// Whether this valid PTE maps memory rather than pointing to a table.
+    pub fn is_leaf(&self) -> bool {
+        self.is_valid() && (self.readable() || self.writable() || self.executable())
+    }
+}
+
+/// Pages a 2 MiB page spans, as a level-1 leaf.
+pub const HUGE_PAGE_PAGES: usize = 512;
+
+/// Why a page table operation on behalf of a user request failed.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PageTableError {
+    /// no frame left for an intermediate table
+    OutOfMemory,
+    AlreadyMapped,
+    NotMapped,
+}
+
+/// What a range of pages looks like in a page table, see
+/// [`PageTable::range_state`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum RangeState {
+    /// no page has a valid PTE, which an empty range counts as
+    AllUnmapped,
+    AllMapped,
+    Mixed,
+}
+
+/// Complain about a leaf where only a table can be: the only superpages
+/// ever created are 2 MiB pages at level 1, so one at level 0 is a kernel
+/// bug.
+fn check_table_pte(vpn: VirtPageNum, level: usize, pte: &PageTableEntry) {
+    if level == 0 && pte.is_leaf() {
+        panic!(
+            "leaf PTE {:#x} at level {} walking to vpn {:#x}",
+            pte.bits, level, vpn.0
+        );
+    }
+}
+
+lazy_static! {
+    /// the modified flag of every [`PageTable::from_token`] view
+    static ref VIEW_MODIFIED: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
 }
 
 /// page table structure
 pub struct PageTable {
     root_ppn: PhysPageNum,
     frames: Vec<FrameTracker>,
+    /// a PTE changed since the TLB was last synced; shared, so that the
+    /// trap return can take it without the page table, see
+    /// [`PageTable::modified_flag`]
+    modified: Arc<AtomicBool>,
 }
 
-/// Assume that it won't oom when creating/mapping.
 impl PageTable {
     pub fn new() -> Self {
         let frame = frame_alloc().unwrap();
         PageTable {
             root_ppn: frame.ppn,
             frames: vec![frame],
+            modified: Arc::new(AtomicBool::new(false)),
         }
     }
+    #[allow(unused)]
     /// Temporarily used to get arguments from user space.
     pub fn from_token(satp: usize) -> Self {
         Self {
             root_ppn: PhysPageNum::from(satp & ((1usize << 44) - 1)),
             frames: Vec::new(),
+            // only read through, such views need no flag of their own
+            modified: VIEW_MODIFIED.clone(),
         }
     }
-    fn find_pte_create(&mut self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
-        let mut idxs = vpn.indexes();
+    fn find_pte_create(&mut self, vpn: VirtPageNum) -> Result<&mut PageTableEntry, PageTableError> {
+        let idxs = vpn.indexes();
         let mut ppn = self.root_ppn;
-        let mut result: Option<&mut PageTableEntry> = None;
-        for (i, idx) in idxs.iter_mut().enumerate() {
+        for (i, idx) in idxs.iter().enumerate() {
             let pte = &mut ppn.get_pte_array()[*idx];
             if i == 2 {
-                result = Some(pte);
-                break;
+                return Ok(pte);
+            }
+            check_table_pte(vpn, i, pte);
+            if pte.is_leaf() {
+                // inside a 2 MiB page
+                return Err(PageTableError::AlreadyMapped);
             }
             if !pte.is_valid() {
-                let frame = frame_alloc().unwrap();
+                let frame = frame_alloc().ok_or(PageTableError::OutOfMemory)?;
                 *pte = PageTableEntry::new(frame.ppn, PTEFlags::V);
                 self.frames.push(frame);
             }
             ppn = pte.ppn();
         }
-        result
+        unreachable!()
     }
-    fn find_pte(&self, vpn: VirtPageNum) -> Option<&PageTableEntry> {
+    /// The last-level table holding the PTE of `vpn`, if it exists; a page
+    /// inside a 2 MiB page has none.
+    fn find_leaf_table(&self, vpn: VirtPageNum) -> Option<PhysPageNum> {
         let idxs = vpn.indexes();
         let mut ppn = self.root_ppn;
-        let mut result: Option<&PageTableEntry> = None;
-        for (i, idx) in idxs.iter().enumerate() {
+        for (i, idx) in idxs[..2].iter().enumerate() {
             let pte = &ppn.get_pte_array()[*idx];
-            if i == 2 {
-                result = Some(pte);
-                break;
-            }
-            if !pte.is_valid() {
+            check_table_pte(vpn, i, pte);
+            if !pte.is_valid() || pte.is_leaf() {
                 return None;
             }
             ppn = pte.ppn();
         }
-        result
+        Some(ppn)
     }
-    #[allow(unused)]
-    pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
-        let pte = self.find_pte_create(vpn).unwrap();
-        assert!(!pte.is_valid(), "vpn {:?} is mapped before mapping", vpn);
+    /// The level-1 PTE of `vpn`, creating the level-1 table if needed.
+    fn find_huge_pte_create(
+        &mut self,
+        vpn: VirtPageNum,
+    ) -> Result<&mut PageTableEntry, PageTableError> {
+        let idxs = vpn.indexes();
+        let root = &mut self.root_ppn.get_pte_array()[idxs[0]];
+        check_table_pte(vpn, 0, root);
+        if !root.is_valid() {
+            let frame = frame_alloc().ok_or(PageTableError::OutOfMemory)?;
+            *root = PageTableEntry::new(frame.ppn, PTEFlags::V);
+            self.frames.push(frame);
+        }
+        Ok(&mut root.ppn().get_pte_array()[idxs[1]])
+    }
+    /// Map the 2 MiB page at `vpn` to the one at `ppn`, both aligned to
+    /// [`HUGE_PAGE_PAGES`], with a single level-1 leaf.
+    pub fn map_huge(
+        &mut self,
+        vpn: VirtPageNum,
+        ppn: PhysPageNum,
+        flags: PTEFlags,
+    ) -> Result<(), PageTableError> {
+        assert!(
+            vpn.0 % HUGE_PAGE_PAGES == 0 && ppn.0 % HUGE_PAGE_PAGES == 0,
+            "unaligned 2 MiB page"
+        );
+        let pte = self.find_huge_pte_create(vpn)?;
+        if pte.is_valid() {
+            return Err(PageTableError::AlreadyMapped);
+        }
         *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+        self.modified.store(true, Ordering::Relaxed);
+        Ok(())
     }
-    #[allow(unused)]
-    pub fn unmap(&mut self, vpn: VirtPageNum) {
-        let pte = self.find_pte_create(vpn).unwrap();
-        assert!(pte.is_valid(), "vpn {:?} is invalid before unmapping", vpn);
+    /// Unmap the 2 MiB page at `vpn` mapped by [`PageTable::map_huge`].
+    pub fn unmap_huge(&mut self, vpn: VirtPageNum) -> Result<(), PageTableError> {
+        let pte = self.find_huge_pte_create(vpn)?;
+        if !pte.is_leaf() {
+            return Err(PageTableError::NotMapped);
+        }
         *pte = PageTableEntry::empty();
+        self.modified.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+    pub fn map(
+        &mut self,
+        vpn: VirtPageNum,
+        ppn: PhysPageNum,
+        flags: PTEFlags,
+    ) -> Result<(), PageTableError> {
+        let pte = self.find_pte_create(vpn)?;
+        if pte.is_valid() {
+            return Err(PageTableError::AlreadyMapped);
+        }
+        *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+        self.modified.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+    pub fn unmap(&mut self, vpn: VirtPageNum) -> Result<(), PageTableError> {
+        let table = self.find_leaf_table(vpn).ok_or(PageTableError::NotMapped)?;
+        let pte = &mut table.get_pte_array()[vpn.indexes()[2]];
+        if !pte.is_valid() {
+            return Err(PageTableError::NotMapped);
+        }
+        *pte = PageTableEntry::empty();
+        self.modified.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+    /// Replace the permission bits of the valid PTE of `vpn` with `flags`,
+    /// keeping its frame and its accessed and dirty bits.
+    pub fn set_flags(&mut self, vpn: VirtPageNum, flags: PTEFlags) -> Result<(), PageTableError> {
+        let table = self.find_leaf_table(vpn).ok_or(PageTableError::NotMapped)?;
+        let pte = &mut table.get_pte_array()[vpn.indexes()[2]];
+        if !pte.is_valid() {
+            return Err(PageTableError::NotMapped);
+        }
+        let kept = pte.flags() & (PTEFlags::A | PTEFlags::D);
+        *pte = PageTableEntry::new(pte.ppn(), flags | kept | PTEFlags::V);
+        self.modified.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+    /// Free every intermediate table whose entries are all invalid, bottom
+    /// up, returning how many table frames were freed; the root stays.
+    pub fn prune_empty_tables(&mut self) -> usize {
+        let mut freed = Vec::new();
+        Self::prune_level(self.root_ppn, 0, &mut freed);
+        self.frames.retain(|frame| !freed.contains(&frame.ppn));
+        if !freed.is_empty() {
+            self.modified.store(true, Ordering::Relaxed);
+        }
+        freed.len()
+    }
+    /// Prune below the table at `ppn` on `level`, collecting the freed
+    /// tables in `freed`; returns whether the table is empty afterwards.
+    fn prune_level(ppn: PhysPageNum, level: usize, freed: &mut Vec<PhysPageNum>) -> bool {
+        let mut empty = true;
+        for pte in ppn.get_pte_array().iter_mut() {
+            if !pte.is_valid() {
+                continue;
+            }
+            if level < 2 && !pte.is_leaf() && Self::prune_level(pte.ppn(), level + 1, freed) {
+                freed.push(pte.ppn());
+                *pte = PageTableEntry::empty();
+            } else {
+                empty = false;
+            }
+        }
+        empty
+    }
+    /// Call `visit` on every valid leaf PTE mapping a page of
+    /// `[start, end)`, below the table at `ppn` on `level` whose first entry
+    /// maps page `base`, with the leaf's level and how many pages of the
+    /// range it maps. An invalid entry skips its whole subtree, and each
+    /// table is looked at once however many pages it holds.
+    fn visit_leaves(
+        ppn: PhysPageNum,
+        level: usize,
+        base: usize,
+        (start, end): (usize, usize),
+        visit: &mut dyn FnMut(&mut PageTableEntry, usize, usize),
+    ) {
+        let span = 1 << (9 * (2 - level));
+        let first = start.saturating_sub(base) / span;
+        let last = ((end - base + span - 1) / span).min(512);
+        for (i, pte) in ppn.get_pte_array()[first..last].iter_mut().enumerate() {
+            if !pte.is_valid() {
+                continue;
+            }
+            let entry_base = base + (first + i) * span;
+            if level == 2 || pte.is_leaf() {
+                let pages = (entry_base + span).min(end) - entry_base.max(start);
+                visit(pte, level, pages);
+            } else {
+                Self::visit_leaves(pte.ppn(), level + 1, entry_base, (start, end), visit);
+            }
+        }
+    }
+    /// Whether every page of `range`, none or only some have a valid PTE,
+    /// in a single walk of the tables covering it.
+    pub fn range_state(&self, range: VPNRange) -> RangeState {
+        let (start, end) = (range.get_start().0, range.get_end().0);
+        if start >= end {
+            return RangeState::AllUnmapped;
+        }
+        let mut mapped = 0;
+        Self::visit_leaves(self.root_ppn, 0, 0, (start, end), &mut |_, _, pages| {
+            mapped += pages
+        });
+        match mapped {
+            0 => RangeState::AllUnmapped,
+            mapped if mapped == end - start => RangeState::AllMapped,
+            _ => RangeState::Mixed,
+        }
     }
+    /// Unmap every page of `range` that has a valid PTE in a single walk,
+    /// returning how many there were; 2 MiB pages are left alone.
+    pub fn unmap_range(&mut self, range: VPNRange) -> usize {
+        let (start, end) = (range.get_start().0, range.get_end().0);
+        if start >= end {
+            return 0;
+        }
+        let mut unmapped = 0;
+        Self::visit_leaves(self.root_ppn, 0, 0, (start, end), &mut |pte, level, _| {
+            if level == 2 {
+                *pte = PageTableEntry::empty();
+                unmapped += 1;
+            }
+        });
+        if unmapped > 0 {
+            self.modified.store(true, Ordering::Relaxed);
+        }
+        unmapped
+    }
+    /// The PTE mapping `vpn`; inside a 2 MiB page, one for just its 4 KiB
+    /// page, with the frame offset into the huge one.
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
-        self.find_pte(vpn).copied()
+        let idxs = vpn.indexes();
+        let mut ppn = self.root_ppn;
+        for (i, idx) in idxs.iter().enumerate() {
+            let pte = ppn.get_pte_array()[*idx];
+            if i == 2 {
+                return Some(pte);
+            }
+            check_table_pte(vpn, i, &pte);
+            if !pte.is_valid() {
+                return None;
+            }
+            if pte.is_leaf() {
+                let offset = idxs[2];
+                return Some(PageTableEntry::new(
+                    PhysPageNum(pte.ppn().0 + offset),
+                    pte.flags(),
+                ));
+            }
+            ppn = pte.ppn();
+        }
+        unreachable!()
+    }
+    /// Number of frames holding the tables, the root included.
+    pub fn table_frames(&self) -> usize {
+        self.frames.len()
+    }
+    /// Clear the accessed bit of the valid PTE of `vpn`, returning whether it
+    /// was set. The TLB has to be synced before relying on the bit again.
+    pub fn take_accessed(&mut self, vpn: VirtPageNum) -> bool {
+        let table = match self.find_leaf_table(vpn) {
+            Some(table) => table,
+            None => return false,
+        };
+        let pte = &mut table.get_pte_array()[vpn.indexes()[2]];
+        if !pte.is_valid() || !pte.flags().contains(PTEFlags::A) {
+            return false;
+        }
+        pte.bits &= !(PTEFlags::A.bits() as usize);
+        self.modified.store(true, Ordering::Relaxed);
+        true
+    }
+    /// The flag set whenever a PTE changes, for [`super::TlbSync`] to take:
+    /// the TLB may still hold the old one.
+    pub fn modified_flag(&self) -> Arc<AtomicBool> {
+        self.modified.clone()
+    }
+    pub fn root_ppn(&self) -> PhysPageNum {
+        self.root_ppn
     }
     pub fn token(&self) -> usize {
         8usize << 60 | self.root_ppn.0
     }
 }
 
+#[allow(unused)]
 /// translate a pointer to a mutable u8 Vec through page table
 pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&'static mut [u8]> {
     let page_table = PageTable::from_token(token);
@@ -155,3 +421,61 @@ pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&
     }
     v
 }
+
+/// Per-page slices of the user buffer `[ptr, ptr + len)` in the address
+/// space of `token`, if every page of it is mapped with all of `flags`.
+pub fn checked_user_buffer(
+    token: usize,
+    ptr: usize,
+    len: usize,
+    flags: PTEFlags,
+) -> Result<Vec<&'static mut [u8]>, PageTableError> {
+    let page_table = PageTable::from_token(token);
+    let end = ptr.checked_add(len).ok_or(PageTableError::NotMapped)?;
+    let mut start = ptr;
+    let mut v = Vec::new();
+    while start < end {
+        let start_va = VirtAddr::from(start);
+        let mut vpn = start_va.floor();
+        let ppn = page_table
+            .translate(vpn)
+            .filter(|pte| pte.is_valid() && pte.flags().contains(flags))
+            .ok_or(PageTableError::NotMapped)?
+            .ppn();
+        vpn.step();
+        let end_va = VirtAddr::from(vpn).min(VirtAddr::from(end));
+        let bytes = ppn.get_bytes_array();
+        if end_va.page_offset() == 0 {
+            v.push(&mut bytes[start_va.page_offset()..]);
+        } else {
+            v.push(&mut bytes[start_va.page_offset()..end_va.page_offset()]);
+        }
+        start = end_va.into();
+    }
+    Ok(v)
+}
+
+/// Copy the NUL-terminated string at `src` in the address space of `token`
+/// to `dst`, returning its length without the NUL; `None` if a byte before
+/// the NUL is not in a user page with `R`, or if there is no NUL within
+/// `dst.len()` bytes.
+pub fn copy_str_from_user(token: usize, dst: &mut [u8], src: *const u8) -> Option<usize> {
+    let page_table = PageTable::from_token(token);
+    let mut len = 0;
+    while len < dst.len() {
+        let va = VirtAddr::from((src as usize).checked_add(len)?);
+        let bytes = page_table
+            .translate(va.floor())
+            .filter(|pte| pte.is_valid() && pte.flags().contains(PTEFlags::U | PTEFlags::R))?
+            .ppn()
+            .get_bytes_array();
+        for &byte in bytes[va.page_offset()..].iter().take(dst.len() - len) {
+            if byte == 0 {
+                return Some(len);
+            }
+            dst[len] = byte;
+            len += 1;
+        }
+    }
+    None
+}
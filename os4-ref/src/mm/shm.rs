@@ -0,0 +1,124 @@
+//! Shared memory segments, see `sys_shm_create` and `sys_shm_map`
+//!
+//! A segment is a run of frames registered under a small id. Each area
+//! mapping it holds a [`ShmMapping`]; once the segment has been mapped, the
+//! last one to go takes it out of the registry, which frees its frames. A
+//! segment nobody has mapped yet stays registered, until the registry is
+//! cleared at a soft reboot.
+
+use super::{frame_alloc, FrameTracker, PhysPageNum};
+use crate::config::{SHM_MAX_PAGES, SHM_MAX_SEGMENTS};
+use crate::sync::UPSafeCell;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use lazy_static::*;
+
+/// frames of one segment, zeroed when it is created
+struct ShmSegment {
+    id: usize,
+    frames: Vec<FrameTracker>,
+    /// set by the first successful mapping, see [`ShmMapping::set_mapped`]
+    mapped: AtomicBool,
+}
+
+/// One area's reference to a segment, whose first page is the segment's
+/// page `first_page`.
+pub struct ShmMapping {
+    segment: Arc<ShmSegment>,
+    first_page: usize,
+}
+
+impl ShmMapping {
+    /// Pages of the segment from `first_page` on.
+    pub fn pages(&self) -> usize {
+        self.segment.frames.len() - self.first_page
+    }
+    /// Frame of the area's page `page`.
+    pub fn ppn(&self, page: usize) -> PhysPageNum {
+        self.segment.frames[self.first_page + page].ppn
+    }
+    /// Record that an area maps the segment now; from then on it goes once
+    /// no area maps it any more.
+    pub fn set_mapped(&self) {
+        self.segment.mapped.store(true, Ordering::Relaxed);
+    }
+    /// Another reference, for the part of the area from its page `page` on.
+    pub fn split_at(&self, page: usize) -> Self {
+        Self {
+            segment: self.segment.clone(),
+            first_page: self.first_page + page,
+        }
+    }
+}
+
+impl Clone for ShmMapping {
+    fn clone(&self) -> Self {
+        self.split_at(0)
+    }
+}
+
+impl Drop for ShmMapping {
+    fn drop(&mut self) {
+        // the registry holds the other reference
+        if Arc::strong_count(&self.segment) == 2 && self.segment.mapped.load(Ordering::Relaxed) {
+            SHM_REGISTRY.exclusive_access()[self.segment.id] = None;
+        }
+    }
+}
+
+lazy_static! {
+    /// segments by id, `None` where an id is free
+    static ref SHM_REGISTRY: UPSafeCell<Vec<Option<Arc<ShmSegment>>>> =
+        unsafe { UPSafeCell::new(Vec::new()) };
+}
+
+/// Create a segment of `pages` zeroed pages, returning its id, the lowest
+/// free one; `None` if `pages` is 0 or too many, if every id is taken or if
+/// the frames run out.
+pub fn shm_create(pages: usize) -> Option<usize> {
+    if pages == 0 || pages > SHM_MAX_PAGES {
+        return None;
+    }
+    let mut registry = SHM_REGISTRY.exclusive_access();
+    let id = match registry.iter().position(|slot| slot.is_none()) {
+        Some(id) => id,
+        None if registry.len() < SHM_MAX_SEGMENTS => {
+            registry.push(None);
+            registry.len() - 1
+        }
+        None => return None,
+    };
+    // frames allocated so far go back if one is missing
+    let frames = (0..pages)
+        .map(|_| frame_alloc())
+        .collect::<Option<Vec<_>>>()?;
+    registry[id] = Some(Arc::new(ShmSegment {
+        id,
+        frames,
+        mapped: AtomicBool::new(false),
+    }));
+    Some(id)
+}
+
+/// A new reference to segment `id`, if there is one.
+pub fn shm_get(id: usize) -> Option<ShmMapping> {
+    let registry = SHM_REGISTRY.exclusive_access();
+    registry.get(id)?.as_ref().map(|segment| ShmMapping {
+        segment: segment.clone(),
+        first_page: 0,
+    })
+}
+
+/// Drop every segment; nothing may map one any more.
+pub fn shm_clear() {
+    let mut registry = SHM_REGISTRY.exclusive_access();
+    debug_assert!(
+        registry
+            .iter()
+            .flatten()
+            .all(|segment| Arc::strong_count(segment) == 1),
+        "shared memory still mapped"
+    );
+    registry.clear();
+}
@@ -0,0 +1,116 @@
+//! Checked views of user memory passed to syscalls
+//!
+//! A syscall gets its pointers as plain numbers. [`UserSlice`] and
+//! [`UserPtr`] check once, when they are made, that the address is aligned
+//! for the type, that the whole range lies in the user half of the address
+//! space and that every page of it is a user page with the access asked
+//! for. The kernel then copies through the frames directly, whichever
+//! pages the range spans.
+
+use super::checked_user_buffer;
+use super::PTEFlags;
+use crate::config::USER_SPACE_END;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::mem::{align_of, size_of};
+
+/// Why a user pointer was refused.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum UserPtrError {
+    /// not a multiple of the alignment its type needs
+    Misaligned,
+    /// the range wraps around or reaches into the kernel half
+    KernelAddress,
+    /// a page of the range is not a user page with the access asked for
+    NotMapped,
+}
+
+/// Check that `[start, start + len)` is aligned to `align` and within the
+/// user half of the address space, without looking at what is mapped.
+pub fn check_user_range(start: usize, len: usize, align: usize) -> Result<(), UserPtrError> {
+    if start % align != 0 {
+        return Err(UserPtrError::Misaligned);
+    }
+    match start.checked_add(len) {
+        Some(end) if end <= USER_SPACE_END => Ok(()),
+        _ => Err(UserPtrError::KernelAddress),
+    }
+}
+
+/// `len` values of type `T` in a user address space, checked to be there.
+pub struct UserSlice<T> {
+    /// the bytes of the values, one slice per page
+    pages: Vec<&'static mut [u8]>,
+    len: usize,
+    _marker: PhantomData<*mut T>,
+}
+
+impl<T> UserSlice<T> {
+    /// The `len` values at `ptr` in the address space of `token`, which the
+    /// task may read.
+    pub fn readable(token: usize, ptr: *const T, len: usize) -> Result<Self, UserPtrError> {
+        Self::new(token, ptr as usize, len, PTEFlags::U | PTEFlags::R)
+    }
+    /// The `len` values at `ptr` in the address space of `token`, which the
+    /// task may write.
+    pub fn writable(token: usize, ptr: *mut T, len: usize) -> Result<Self, UserPtrError> {
+        Self::new(token, ptr as usize, len, PTEFlags::U | PTEFlags::W)
+    }
+    fn new(token: usize, ptr: usize, len: usize, flags: PTEFlags) -> Result<Self, UserPtrError> {
+        let bytes = len
+            .checked_mul(size_of::<T>())
+            .ok_or(UserPtrError::KernelAddress)?;
+        check_user_range(ptr, bytes, align_of::<T>())?;
+        let pages =
+            checked_user_buffer(token, ptr, bytes, flags).map_err(|_| UserPtrError::NotMapped)?;
+        Ok(Self {
+            pages,
+            len,
+            _marker: PhantomData,
+        })
+    }
+    /// Copy `values`, exactly as many as the slice holds, to user memory.
+    pub fn write(&mut self, values: &[T]) {
+        assert_eq!(values.len(), self.len, "user slice written partly");
+        let src = unsafe {
+            core::slice::from_raw_parts(values.as_ptr() as *const u8, self.len * size_of::<T>())
+        };
+        let mut copied = 0;
+        for page in self.pages.iter_mut() {
+            page.copy_from_slice(&src[copied..copied + page.len()]);
+            copied += page.len();
+        }
+    }
+    /// Copy the values to `values`, exactly as many as the slice holds; any
+    /// bit pattern must be a valid `T`.
+    pub fn read(&self, values: &mut [T]) {
+        assert_eq!(values.len(), self.len, "user slice read partly");
+        let dst = unsafe {
+            core::slice::from_raw_parts_mut(
+                values.as_mut_ptr() as *mut u8,
+                self.len * size_of::<T>(),
+            )
+        };
+        let mut copied = 0;
+        for page in self.pages.iter() {
+            dst[copied..copied + page.len()].copy_from_slice(page);
+            copied += page.len();
+        }
+    }
+}
+
+/// A single value of type `T` in a user address space, checked to be
+/// there and writable.
+pub struct UserPtr<T>(UserSlice<T>);
+
+impl<T> UserPtr<T> {
+    /// The value at `ptr` in the address space of `token`, which the task
+    /// may write.
+    pub fn writable(token: usize, ptr: *mut T) -> Result<Self, UserPtrError> {
+        UserSlice::writable(token, ptr, 1).map(Self)
+    }
+    /// Copy `value` to user memory.
+    pub fn write(&mut self, value: &T) {
+        self.0.write(core::slice::from_ref(value));
+    }
+}
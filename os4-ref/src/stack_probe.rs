@@ -0,0 +1,128 @@
+//! Kernel stack depth probes
+//!
+//! [`stack_probe!`] at the entry of the deepest kernel paths measures how
+//! much of the running kernel stack is left and keeps the minimum per path,
+//! so stacks can be sized from data. In debug builds it panics with the path
+//! name once less than [`KERNEL_STACK_RED_LINE`] bytes remain, instead of
+//! overflowing into whatever lies below the stack later on.
+//!
+//! Task kernel stacks are also filled with [`STACK_POISON`] when they are
+//! built, so that [`kernel_stack_usage`] can tell how deep one ever went,
+//! whichever path it was.
+
+use crate::config::{
+    kernel_stack_position, KERNEL_STACK_RED_LINE, KERNEL_STACK_SIZE, PAGE_SIZE, TRAMPOLINE,
+};
+use core::mem::size_of;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// word a fresh task kernel stack is filled with
+const STACK_POISON: usize = 0x5a5a_5a5a_5a5a_5a5a;
+const WORD: usize = size_of::<usize>();
+
+/// kernel paths carrying a stack probe
+#[derive(Copy, Clone, Debug)]
+pub enum StackPath {
+    TrapHandler,
+    CheckedInsert,
+    CheckedRemove,
+}
+
+const STACK_PATHS: [StackPath; 3] = [
+    StackPath::TrapHandler,
+    StackPath::CheckedInsert,
+    StackPath::CheckedRemove,
+];
+
+/// least stack ever left per path, indexed by `StackPath as usize`
+static MIN_REMAINING: [AtomicUsize; 3] = [
+    AtomicUsize::new(usize::MAX),
+    AtomicUsize::new(usize::MAX),
+    AtomicUsize::new(usize::MAX),
+];
+
+/// Bottom and top of the stack that `sp` lies on: the boot stack or a
+/// task's kernel stack. Below a kernel stack sits its guard page, which
+/// counts as part of the same stack so that an overflow shows up as nothing
+/// left.
+pub fn stack_bounds(sp: usize) -> Option<(usize, usize)> {
+    extern "C" {
+        fn boot_stack();
+        fn boot_stack_top();
+    }
+    if (boot_stack as usize..=boot_stack_top as usize).contains(&sp) {
+        return Some((boot_stack as usize, boot_stack_top as usize));
+    }
+    // task kernel stacks are stacked downwards right below the trampoline
+    if !(TRAMPOLINE / 2..=TRAMPOLINE).contains(&sp) {
+        return None;
+    }
+    let app_id = (TRAMPOLINE - sp) / (KERNEL_STACK_SIZE + PAGE_SIZE);
+    Some(kernel_stack_position(app_id))
+}
+
+/// Record the stack left at `sp` for `path`, see [`stack_probe!`].
+pub fn probe(path: StackPath, sp: usize) {
+    let bottom = match stack_bounds(sp) {
+        Some((bottom, _)) => bottom,
+        None => return,
+    };
+    let remaining = sp.saturating_sub(bottom);
+    MIN_REMAINING[path as usize].fetch_min(remaining, Ordering::Relaxed);
+    if cfg!(debug_assertions) && remaining < KERNEL_STACK_RED_LINE {
+        panic!(
+            "kernel stack nearly exhausted in {:?}: {} bytes left",
+            path, remaining
+        );
+    }
+}
+
+/// Fill the kernel stack of task `id`, which must be mapped, with
+/// [`STACK_POISON`], unless it is the stack running this.
+pub fn poison_kernel_stack(id: usize) {
+    let (bottom, top) = kernel_stack_position(id);
+    let sp: usize;
+    unsafe { core::arch::asm!("mv {}, sp", out(reg) sp) };
+    if (bottom..=top).contains(&sp) {
+        return;
+    }
+    for word in (bottom..top).step_by(WORD) {
+        unsafe { (word as *mut usize).write_volatile(STACK_POISON) };
+    }
+}
+
+/// Bytes of the kernel stack of task `id` used at the deepest so far, up
+/// from the lowest word without [`STACK_POISON`]. The stack must be mapped;
+/// it is read through the kernel space, word by word from its bottom.
+pub fn kernel_stack_usage(id: usize) -> usize {
+    let (bottom, top) = kernel_stack_position(id);
+    let untouched = (bottom..top)
+        .step_by(WORD)
+        .take_while(|&word| unsafe { (word as *const usize).read_volatile() } == STACK_POISON)
+        .count();
+    top - bottom - untouched * WORD
+}
+
+/// Print the least stack left per probed path.
+pub fn print() {
+    for path in STACK_PATHS {
+        let remaining = MIN_REMAINING[path as usize].load(Ordering::Relaxed);
+        if remaining != usize::MAX {
+            println!(
+                "[kernel] min kernel stack left in {:?}: {} bytes",
+                path, remaining
+            );
+        }
+    }
+}
+
+/// Probe the remaining kernel stack at the current `sp` for a [`StackPath`].
+macro_rules! stack_probe {
+    ($path: expr) => {
+        $crate::stack_probe::probe($path, {
+            let sp: usize;
+            unsafe { core::arch::asm!("mv {}, sp", out(reg) sp) };
+            sp
+        })
+    };
+}
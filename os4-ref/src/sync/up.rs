@@ -1,6 +1,12 @@
 //! Uniprocessor interior mutability primitives
 
-use core::cell::{RefCell, RefMut};
+use core::cell::{Cell, Ref, RefCell, RefMut};
+use core::ops::{Deref, DerefMut};
+use core::panic::Location;
+
+/// Whether a cell remembers where it was borrowed, to name both call sites
+/// when a second borrow collides with the first.
+const TRACK_BORROWS: bool = cfg!(debug_assertions);
 
 /// Wrap a static data structure inside it so that we are
 /// able to access it without any `unsafe`.
@@ -12,6 +18,9 @@ use core::cell::{RefCell, RefMut};
 pub struct UPSafeCell<T> {
     /// inner data
     inner: RefCell<T>,
+    /// where the outstanding exclusive borrow, or the first of the shared
+    /// ones, was taken; only kept in debug builds
+    borrowed_at: Cell<Option<&'static Location<'static>>>,
 }
 
 unsafe impl<T> Sync for UPSafeCell<T> {}
@@ -19,13 +28,111 @@ unsafe impl<T> Sync for UPSafeCell<T> {}
 impl<T> UPSafeCell<T> {
     /// User is responsible to guarantee that inner struct is only used in
     /// uniprocessor.
-    pub unsafe fn new(value: T) -> Self {
+    pub const unsafe fn new(value: T) -> Self {
         Self {
             inner: RefCell::new(value),
+            borrowed_at: Cell::new(None),
+        }
+    }
+    /// Panic if the data has been borrowed, naming where in debug builds.
+    #[track_caller]
+    pub fn exclusive_access(&self) -> UPRefMut<'_, T> {
+        match self.try_exclusive_access() {
+            Some(value) => value,
+            None => self.conflict("exclusively"),
+        }
+    }
+    /// `None` if the data has been borrowed, for paths that must not panic.
+    #[track_caller]
+    pub fn try_exclusive_access(&self) -> Option<UPRefMut<'_, T>> {
+        let value = self.inner.try_borrow_mut().ok()?;
+        Some(UPRefMut {
+            value,
+            _mark: self.mark(),
+        })
+    }
+    /// Panic if the data has been borrowed exclusively; other shared
+    /// accesses may overlap this one.
+    #[track_caller]
+    pub fn shared_access(&self) -> UPRef<'_, T> {
+        match self.inner.try_borrow() {
+            Ok(value) => UPRef {
+                value,
+                _mark: self.mark(),
+            },
+            Err(_) => self.conflict("shared"),
+        }
+    }
+    /// Access through a unique reference, which needs no runtime check.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+    /// Record the caller as the borrower, unless an earlier borrow still is.
+    #[track_caller]
+    fn mark(&self) -> BorrowMark<'_> {
+        let owner = TRACK_BORROWS && self.borrowed_at.get().is_none();
+        if owner {
+            self.borrowed_at.set(Some(Location::caller()));
+        }
+        BorrowMark {
+            borrowed_at: &self.borrowed_at,
+            owner,
+        }
+    }
+    /// Panic for a borrow that collides with an outstanding one; the panic
+    /// location is the second borrow's.
+    #[track_caller]
+    fn conflict(&self, access: &str) -> ! {
+        match self.borrowed_at.get() {
+            Some(at) => panic!("UPSafeCell borrowed {} while borrowed at {}", access, at),
+            None => panic!("UPSafeCell borrowed {} while already borrowed", access),
+        }
+    }
+}
+
+/// Forgets the borrower recorded by [`UPSafeCell::mark`] when the borrow
+/// that recorded it ends.
+struct BorrowMark<'a> {
+    borrowed_at: &'a Cell<Option<&'static Location<'static>>>,
+    owner: bool,
+}
+
+impl Drop for BorrowMark<'_> {
+    fn drop(&mut self) {
+        if self.owner {
+            self.borrowed_at.set(None);
         }
     }
-    /// Panic if the data has been borrowed.
-    pub fn exclusive_access(&self) -> RefMut<'_, T> {
-        self.inner.borrow_mut()
+}
+
+/// Exclusive borrow of a [`UPSafeCell`]'s data.
+pub struct UPRefMut<'a, T> {
+    value: RefMut<'a, T>,
+    _mark: BorrowMark<'a>,
+}
+
+impl<T> Deref for UPRefMut<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for UPRefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+/// Shared borrow of a [`UPSafeCell`]'s data.
+pub struct UPRef<'a, T> {
+    value: Ref<'a, T>,
+    _mark: BorrowMark<'a>,
+}
+
+impl<T> Deref for UPRef<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.value
     }
 }
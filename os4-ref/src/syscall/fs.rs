@@ -1,16 +1,31 @@
 //! File and filesystem-related syscalls
 
-use crate::mm::translated_byte_buffer;
-use crate::task::current_user_token;
+use crate::config::COPY_PAGES_PER_YIELD_CHECK;
+use crate::console::print_app;
+use crate::task::{count_current_output, current_user_buffer, yield_if_slice_over};
+
+use super::process::refuse_arg;
 
 const FD_STDOUT: usize = 1;
 
+/// write the `len` bytes at `buf` to file `fd`, returns `len`; -1 if the
+/// caller may not read all of them, in which case nothing is written
 pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
     match fd {
         FD_STDOUT => {
-            let buffers = translated_byte_buffer(current_user_token(), buf, len);
-            for buffer in buffers {
-                print!("{}", core::str::from_utf8(buffer).unwrap());
+            let buffers = match current_user_buffer(buf, len) {
+                Ok(buffers) => buffers,
+                Err(err) => {
+                    refuse_arg("sys_write(buf)", buf as usize, err);
+                    return -1;
+                }
+            };
+            for (i, buffer) in buffers.into_iter().enumerate() {
+                if i > 0 && i % COPY_PAGES_PER_YIELD_CHECK == 0 {
+                    yield_if_slice_over();
+                }
+                print_app(buffer);
+                count_current_output(buffer.len());
             }
             len as isize
         }
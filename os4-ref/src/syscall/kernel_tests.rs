@@ -0,0 +1,271 @@
+//! Syscall replay tests of the `board_test` self-tests, see
+//! [`crate::kernel_tests`]
+//!
+//! Each test replays a recorded sequence of syscalls through [`syscall()`]
+//! on behalf of a real task, the first app's, made current without ever
+//! running it. Every step is checked against the golden result recorded
+//! from the kernel when the sequence was written: the value returned and
+//! the user memory it should leave behind, read back through the task's
+//! page table. Preconditions, areas mapped and pages filled before the
+//! first step, are set up by the kernel directly, not by syscalls.
+//!
+//! Only what does not depend on time or on the random layout is recorded,
+//! so a step failing means something a user program sees has changed.
+
+use super::strace::signature;
+use super::*;
+use crate::config::{MMAP_MAX_PAGES, PAGE_SIZE, USER_SPACE_END};
+use crate::kernel_tests::TestResult;
+use crate::mm::{MapPermission, VirtAddr};
+use crate::task::{current_user_buffer, enter_task_for_test, task_mmap, translate_current};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// scratch page the sequences pass as a buffer, mapped read-write and
+/// filled with [`FILL`] before each of them; the page above is free
+const BUF: usize = 0x1000_0000;
+/// what [`BUF`] is filled with, so that bytes left untouched show
+const FILL: u8 = 0xaa;
+/// where the mmap and munmap sequence maps its pages
+const MAP: usize = 0x1001_0000;
+/// where the errno sequence maps its pages
+const ERR: usize = 0x1002_0000;
+const PAGE: usize = PAGE_SIZE;
+/// the `TaskInfo` field offsets the recorded effects look at
+const TASK_INFO_STATUS: usize = 0;
+const TASK_INFO_CALLS: usize = 4 + 4 * SYSCALL_TASK_INFO;
+/// `TaskStatus::Running` as `sys_task_info` writes it
+const RUNNING: u8 = 2;
+/// the count of `sys_task_info` calls after two of them
+const TWO_CALLS: [u8; 4] = 2u32.to_le_bytes();
+
+/// What a step leaves in the user address space.
+enum Effect {
+    /// these bytes at this address
+    Bytes(usize, &'static [u8]),
+    /// no valid PTE for the page at this address
+    Unmapped(usize),
+}
+
+use Effect::*;
+
+/// One recorded syscall with its golden result.
+struct Step {
+    id: usize,
+    args: [usize; 4],
+    ret: isize,
+    effects: &'static [Effect],
+}
+
+/// Step `id(args)` returning `ret`, followed by `effects` if given.
+macro_rules! step {
+    ($id:expr, [$($arg:expr),*] => $ret:expr $(, $effect:expr)* $(,)?) => {
+        Step {
+            id: $id,
+            args: args([$($arg),*]),
+            ret: $ret,
+            effects: &[$($effect),*],
+        }
+    };
+}
+
+/// Up to four syscall arguments, zeros after them.
+const fn args<const N: usize>(given: [usize; N]) -> [usize; 4] {
+    let mut args = [0; 4];
+    let mut i = 0;
+    while i < N {
+        args[i] = given[i];
+        i += 1;
+    }
+    args
+}
+
+/// valid, null, misaligned, straddling and out-of-range `TimeVal` pointers
+static GET_TIME: &[Step] = &[
+    step!(SYSCALL_GET_TIME, [BUF, 0] => 0),
+    step!(SYSCALL_GET_TIME, [0, 0] => -1),
+    step!(SYSCALL_GET_TIME, [BUF + 0x101, 0] => -1, Bytes(BUF + 0x100, &[FILL; 24])),
+    step!(SYSCALL_GET_TIME, [BUF + PAGE - 8, 0] => -1, Bytes(BUF + PAGE - 8, &[FILL; 8])),
+    step!(SYSCALL_GET_TIME, [USER_SPACE_END - 8, 0] => -1),
+    step!(SYSCALL_GET_TIME, [usize::MAX - 7, 0] => -1),
+];
+
+/// a `TaskInfo` straddling into an unmapped page, then a valid one, which
+/// counts both calls
+static TASK_INFO: &[Step] = &[
+    step!(SYSCALL_TASK_INFO, [BUF + PAGE - 8] => -1, Bytes(BUF + PAGE - 8, &[FILL; 8])),
+    step!(
+        SYSCALL_TASK_INFO,
+        [BUF] => 0,
+        Bytes(BUF + TASK_INFO_STATUS, &[RUNNING]),
+        Bytes(BUF + TASK_INFO_CALLS, &TWO_CALLS),
+    ),
+    step!(SYSCALL_TASK_INFO, [0] => -1),
+];
+
+/// the lab's `sys_mmap` and `sys_munmap`: refused ports and ranges, empty
+/// lengths, overlaps, lengths rounded up to pages, W only and X only, and
+/// unmapping across areas, in parts and over holes
+static MMAP: &[Step] = &[
+    step!(SYSCALL_MMAP, [MAP, PAGE, 0] => -1),
+    step!(SYSCALL_MMAP, [MAP, PAGE, 8] => -1),
+    step!(SYSCALL_MMAP, [MAP, PAGE, 0x13] => -1),
+    step!(SYSCALL_MMAP, [MAP + 1, PAGE, 3] => -1),
+    step!(SYSCALL_MMAP, [0, PAGE, 3] => -1),
+    step!(SYSCALL_MMAP, [USER_SPACE_END - PAGE, 2 * PAGE, 3] => -1),
+    step!(SYSCALL_MMAP, [usize::MAX - PAGE + 1, PAGE, 3] => -1),
+    step!(SYSCALL_MMAP, [MAP, 0, 3] => 0, Unmapped(MAP)),
+    step!(SYSCALL_MMAP, [MAP, PAGE, 1] => 0, Bytes(MAP, &[0; 8])),
+    step!(SYSCALL_MMAP, [MAP, PAGE, 3] => -1),
+    step!(SYSCALL_MMAP, [MAP + PAGE, 2 * PAGE + 1, 3] => 0, Bytes(MAP + 3 * PAGE, &[0; 8])),
+    step!(SYSCALL_MMAP, [MAP + 2 * PAGE, PAGE, 3] => -1),
+    step!(SYSCALL_MMAP, [MAP + 4 * PAGE, PAGE, 2] => 0),
+    step!(SYSCALL_MMAP, [MAP + 5 * PAGE, PAGE, 4] => 0),
+    step!(SYSCALL_MUNMAP, [MAP + 6 * PAGE, PAGE] => -1),
+    step!(SYSCALL_MUNMAP, [MAP + 1, PAGE] => -1, Bytes(MAP, &[0; 8])),
+    step!(SYSCALL_MUNMAP, [MAP, 0] => 0, Bytes(MAP, &[0; 8])),
+    step!(SYSCALL_MUNMAP, [MAP, 2 * PAGE] => 0, Unmapped(MAP), Unmapped(MAP + PAGE)),
+    step!(SYSCALL_MMAP, [MAP, PAGE, 3] => 0, Bytes(MAP, &[0; 8])),
+    step!(
+        SYSCALL_MUNMAP,
+        [MAP + 3 * PAGE, 3 * PAGE] => 0,
+        Bytes(MAP + 2 * PAGE, &[0; 8]),
+        Unmapped(MAP + 3 * PAGE),
+        Unmapped(MAP + 5 * PAGE),
+    ),
+    step!(SYSCALL_MUNMAP, [MAP, 3 * PAGE] => -1, Bytes(MAP, &[0; 8])),
+    step!(SYSCALL_MUNMAP, [MAP, PAGE] => 0, Unmapped(MAP)),
+    step!(SYSCALL_MUNMAP, [MAP + 2 * PAGE, PAGE] => 0, Unmapped(MAP + 2 * PAGE)),
+];
+
+/// what `sys_mmap2` and `sys_mprotect` return for each kind of refusal, and
+/// the lab's `sys_mmap` for the same ones; the page at `ERR + 3 * PAGE` is
+/// mapped and filled with [`FILL`] beforehand
+static ERRNO: &[Step] = &[
+    step!(SYSCALL_MMAP2, [ERR, PAGE, 2] => -1),
+    step!(SYSCALL_MMAP2, [ERR, PAGE, 8] => -1),
+    step!(SYSCALL_MMAP2, [ERR + 1, PAGE, 3] => -1),
+    step!(SYSCALL_MMAP2, [ERR, PAGE, 3] => 0),
+    step!(SYSCALL_MMAP2, [ERR, PAGE, 1] => -2),
+    step!(SYSCALL_MMAP, [ERR, PAGE, 1] => -1),
+    step!(SYSCALL_MMAP2, [ERR + 4 * PAGE, (MMAP_MAX_PAGES + 1) * PAGE, 3] => -3),
+    step!(SYSCALL_MMAP, [ERR + 4 * PAGE, (MMAP_MAX_PAGES + 1) * PAGE, 3] => -1),
+    step!(SYSCALL_MMAP2, [ERR + PAGE, PAGE, 0] => 0, Unmapped(ERR + PAGE)),
+    step!(SYSCALL_MPROTECT, [ERR + PAGE, PAGE, 3] => 0, Bytes(ERR + PAGE, &[0; 8])),
+    step!(SYSCALL_MPROTECT, [ERR + 2 * PAGE, PAGE, 3] => -1),
+    step!(SYSCALL_MPROTECT, [ERR, PAGE, 2] => -1),
+    step!(SYSCALL_MPROTECT, [ERR + 3 * PAGE, PAGE, 1] => 0, Bytes(ERR + 3 * PAGE, &[FILL; 8])),
+    step!(SYSCALL_MUNMAP, [ERR, 2 * PAGE] => 0, Unmapped(ERR), Unmapped(ERR + PAGE)),
+    step!(SYSCALL_MUNMAP, [ERR + 3 * PAGE, PAGE] => 0, Unmapped(ERR + 3 * PAGE)),
+    step!(999, [] => -1),
+];
+
+/// `get_time` pointer checks, see [`GET_TIME`].
+pub fn replay_get_time() -> TestResult {
+    replay(GET_TIME, &[])
+}
+
+/// `task_info` pointer checks and counts, see [`TASK_INFO`].
+pub fn replay_task_info() -> TestResult {
+    replay(TASK_INFO, &[])
+}
+
+/// The lab's mmap and munmap edge cases, see [`MMAP`].
+pub fn replay_mmap() -> TestResult {
+    replay(MMAP, &[])
+}
+
+/// The errno of every refusal that can be told apart, see [`ERRNO`].
+pub fn replay_errno() -> TestResult {
+    replay(ERRNO, &[(ERR + 3 * PAGE, PAGE)])
+}
+
+/// Make the replay task current, map `areas` read-write for it on top of
+/// [`BUF`], fill them all with [`FILL`], then run `steps` in order; the
+/// first step diverging from its golden result fails the test, naming it.
+fn replay(steps: &[Step], areas: &[(usize, usize)]) -> TestResult {
+    enter_task_for_test(0);
+    for &(start, len) in [(BUF, PAGE)].iter().chain(areas) {
+        // the scratch page stays mapped from one sequence to the next
+        if start != BUF || translate(BUF).is_none() {
+            premap(start, len)?;
+        }
+        for page in (start..start + len).step_by(PAGE) {
+            prefill(page, &[FILL; PAGE])?;
+        }
+    }
+    for (i, step) in steps.iter().enumerate() {
+        let ret = syscall(step.id, step.args);
+        if ret != step.ret {
+            return Err(format!(
+                "step {}: {} returned {}, expected {}",
+                i,
+                describe(step),
+                ret,
+                step.ret
+            ));
+        }
+        for effect in step.effects {
+            check_effect(effect)
+                .map_err(|found| format!("step {}: after {}, {}", i, describe(step), found))?;
+        }
+    }
+    Ok(())
+}
+
+/// Map `[start, start + len)` read-write for the replay task.
+fn premap(start: usize, len: usize) -> TestResult {
+    task_mmap(start, len, MapPermission::R | MapPermission::W)
+        .map(|_| ())
+        .map_err(|err| format!("premapping {:#x}: {:?}", start, err))
+}
+
+/// Write `bytes` to the replay task's memory at `start`.
+fn prefill(start: usize, bytes: &[u8]) -> TestResult {
+    let pages = current_user_buffer(start as *const u8, bytes.len())
+        .map_err(|err| format!("prefilling {:#x}: {:?}", start, err))?;
+    let mut filled = 0;
+    for page in pages {
+        page.copy_from_slice(&bytes[filled..filled + page.len()]);
+        filled += page.len();
+    }
+    Ok(())
+}
+
+/// The bytes of the valid page at `va` from `va` on, through the page table.
+fn translate(va: usize) -> Option<&'static [u8]> {
+    let va = VirtAddr::from(va);
+    let pte = translate_current(va.floor()).filter(|pte| pte.is_valid())?;
+    Some(&pte.ppn().get_bytes_array()[va.page_offset()..])
+}
+
+/// Compare `effect` with the replay task's memory, describing a mismatch.
+fn check_effect(effect: &Effect) -> Result<(), String> {
+    match *effect {
+        Bytes(va, expected) => match translate(va) {
+            Some(bytes) if &bytes[..expected.len()] == expected => Ok(()),
+            Some(bytes) => Err(format!(
+                "{:#x} holds {:02x?}, expected {:02x?}",
+                va,
+                &bytes[..expected.len()],
+                expected
+            )),
+            None => Err(format!("{:#x} is unmapped, expected {:02x?}", va, expected)),
+        },
+        Unmapped(va) => match translate(va) {
+            Some(_) => Err(format!("{:#x} is mapped, expected it unmapped", va)),
+            None => Ok(()),
+        },
+    }
+}
+
+/// `name(args)` of the syscall of `step`, as the strace log prints it.
+fn describe(step: &Step) -> String {
+    let (name, kinds) = signature(step.id).unwrap_or(("unknown", &[]));
+    let args: Vec<String> = step.args[..kinds.len()]
+        .iter()
+        .map(|arg| format!("{:#x}", arg))
+        .collect();
+    format!("sys_{}({})", name, args.join(", "))
+}
@@ -12,31 +12,161 @@
 
 const SYSCALL_WRITE: usize = 64;
 const SYSCALL_EXIT: usize = 93;
+const SYSCALL_SLEEP: usize = 101;
+const SYSCALL_WAITPID: usize = 260;
 const SYSCALL_YIELD: usize = 124;
 const SYSCALL_GET_TIME: usize = 169;
+const SYSCALL_SBRK: usize = 214;
 const SYSCALL_MUNMAP: usize = 215;
 const SYSCALL_MMAP: usize = 222;
+const SYSCALL_MPROTECT: usize = 226;
+const SYSCALL_TIMES: usize = 153;
+const SYSCALL_SPAWN: usize = 400;
 const SYSCALL_SET_PRIORITY: usize = 140;
+const SYSCALL_REBOOT: usize = 142;
 const SYSCALL_TASK_INFO: usize = 410;
+const SYSCALL_EXEC_TEXT_ONLY: usize = 411;
+const SYSCALL_CAP_DROP: usize = 412;
+const SYSCALL_MM_FINGERPRINT: usize = 413;
+const SYSCALL_TRACE_CTL: usize = 414;
+const SYSCALL_TRACE_READ: usize = 415;
+const SYSCALL_SCHED_EPOCH: usize = 416;
+const SYSCALL_EPOCH_OF: usize = 417;
+const SYSCALL_PROFILE_READ: usize = 418;
+const SYSCALL_SELFCHECK: usize = 419;
+const SYSCALL_MUNMAP2: usize = 420;
+const SYSCALL_MEM_INFO: usize = 421;
+const SYSCALL_VM_FIND_FREE: usize = 422;
+const SYSCALL_SYSCALL_FILTER: usize = 423;
+const SYSCALL_MEM_PRESSURE: usize = 424;
+const SYSCALL_KERNEL_CONFIG: usize = 425;
+const SYSCALL_VM_POPULATED: usize = 426;
+const SYSCALL_VM_ACCESSED: usize = 427;
+#[cfg(feature = "debug-syscalls")]
+const SYSCALL_TRANSLATE: usize = 428;
+#[cfg(feature = "debug-syscalls")]
+const SYSCALL_DUMP_VM: usize = 429;
+const SYSCALL_SHM_CREATE: usize = 430;
+const SYSCALL_SHM_MAP: usize = 431;
+const SYSCALL_STRACE: usize = 432;
+const SYSCALL_WAIT_TASK: usize = 433;
+const SYSCALL_MMAP_DATA: usize = 434;
+#[cfg(feature = "debug-syscalls")]
+const SYSCALL_SYSCALL_STATS: usize = 435;
+const SYSCALL_SHUTDOWN: usize = 436;
+const SYSCALL_TASK_RESTART: usize = 437;
+const SYSCALL_MMAP2: usize = 438;
+const SYSCALL_MQ_CREATE: usize = 439;
+const SYSCALL_MQ_SEND: usize = 440;
+const SYSCALL_MQ_RECV: usize = 441;
+const SYSCALL_MQ_DESTROY: usize = 442;
+const SYSCALL_KILL: usize = 443;
 
 mod fs;
+#[cfg(feature = "board_test")]
+pub mod kernel_tests;
 mod process;
+mod stats;
+mod strace;
 
+use crate::abi::{MemInfo, MunmapStats, ProfileBucket, TaskInfo, TraceEntry};
+use crate::task::{
+    count_current_syscall, current_strace, current_syscall_allowed, exit_current_and_run_next,
+    reject_current_unknown_syscall, trace_current_syscall, ExitReason,
+};
+use crate::timer::get_time_ns;
 use fs::*;
 use process::*;
+pub use stats::print_syscall_stats;
 
 /// handle syscall exception with `syscall_id` and other arguments
-pub fn syscall(syscall_id: usize, args: [usize; 3]) -> isize {
+pub fn syscall(syscall_id: usize, args: [usize; 4]) -> isize {
     // LAB1: You may need to update syscall info here.
-    match syscall_id {
+    // counted up front, `sys_exit` does not return
+    count_current_syscall(syscall_id);
+    // the only cost of the log while it is off
+    let strace = current_strace();
+    // before anything looks at the arguments of a forbidden call
+    if !current_syscall_allowed(syscall_id) {
+        warn!(
+            "[kernel] syscall {} forbidden by filter, killed",
+            syscall_id
+        );
+        exit_current_and_run_next(ExitReason::FilteredSyscall(syscall_id));
+        panic!("Unreachable in syscall!");
+    }
+    if strace && syscall_id == SYSCALL_EXIT {
+        strace::log_call(syscall_id, args, None);
+    }
+    if syscall_id == SYSCALL_EXIT {
+        stats::record(syscall_id, 0);
+    }
+    let start_ns = get_time_ns();
+    let ret = match syscall_id {
         SYSCALL_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
         SYSCALL_EXIT => sys_exit(args[0] as i32),
+        SYSCALL_SLEEP => sys_sleep(args[0]),
         SYSCALL_YIELD => sys_yield(),
         SYSCALL_GET_TIME => sys_get_time(args[0] as *mut TimeVal, args[1]),
         SYSCALL_MMAP => sys_mmap(args[0], args[1], args[2]),
         SYSCALL_MUNMAP => sys_munmap(args[0], args[1]),
+        SYSCALL_MPROTECT => sys_mprotect(args[0], args[1], args[2]),
+        SYSCALL_TIMES => sys_times(args[0] as *mut Tms),
+        SYSCALL_REBOOT => sys_reboot(args[0]),
+        SYSCALL_SPAWN => sys_spawn(args[0] as *const u8),
+        SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1] as *mut i32, args[2]),
         SYSCALL_SET_PRIORITY => sys_set_priority(args[0] as isize),
         SYSCALL_TASK_INFO => sys_task_info(args[0] as *mut TaskInfo),
-        _ => panic!("Unsupported syscall_id: {}", syscall_id),
+        SYSCALL_EXEC_TEXT_ONLY => sys_exec_text_only(args[0]),
+        SYSCALL_CAP_DROP => sys_cap_drop(args[0]),
+        SYSCALL_MM_FINGERPRINT => sys_mm_fingerprint(),
+        SYSCALL_TRACE_CTL => sys_trace_ctl(args[0]),
+        SYSCALL_TRACE_READ => sys_trace_read(args[0] as isize, args[1] as *mut TraceEntry, args[2]),
+        SYSCALL_SCHED_EPOCH => sys_sched_epoch(),
+        SYSCALL_EPOCH_OF => sys_epoch_of(args[0] as isize),
+        SYSCALL_PROFILE_READ => {
+            sys_profile_read(args[0] as isize, args[1] as *mut ProfileBucket, args[2])
+        }
+        SYSCALL_SELFCHECK => sys_selfcheck(args[0]),
+        SYSCALL_MUNMAP2 => sys_munmap2(args[0], args[1], args[2] as *mut MunmapStats),
+        SYSCALL_MEM_INFO => sys_mem_info(args[0] as *mut MemInfo),
+        SYSCALL_VM_FIND_FREE => sys_vm_find_free(args[0], args[1]),
+        SYSCALL_SYSCALL_FILTER => sys_syscall_filter(args[0] as *const usize, args[1]),
+        SYSCALL_MEM_PRESSURE => sys_mem_pressure(),
+        SYSCALL_KERNEL_CONFIG => sys_kernel_config(args[0] as *mut u8, args[1]),
+        SYSCALL_VM_POPULATED => sys_vm_populated(args[0], args[1], args[2] as *mut u8),
+        SYSCALL_VM_ACCESSED => sys_vm_accessed(args[0], args[1], args[2] as *mut u8),
+        SYSCALL_SHM_CREATE => sys_shm_create(args[0]),
+        SYSCALL_SHM_MAP => sys_shm_map(args[0], args[1], args[2]),
+        SYSCALL_STRACE => sys_strace(args[0]),
+        SYSCALL_WAIT_TASK => sys_wait_task(args[0]),
+        SYSCALL_MMAP_DATA => sys_mmap_data(args[0], args[1], args[2], args[3]),
+        SYSCALL_SHUTDOWN => sys_shutdown(args[0]),
+        SYSCALL_TASK_RESTART => sys_task_restart(args[0]),
+        SYSCALL_MMAP2 => sys_mmap2(args[0], args[1], args[2]),
+        SYSCALL_MQ_CREATE => sys_mq_create(args[0], args[1]),
+        SYSCALL_MQ_SEND => sys_mq_send(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_MQ_RECV => sys_mq_recv(args[0], args[1] as *mut u8, args[2]),
+        SYSCALL_MQ_DESTROY => sys_mq_destroy(args[0]),
+        SYSCALL_KILL => sys_kill(args[0]),
+        #[cfg(feature = "debug-syscalls")]
+        SYSCALL_TRANSLATE => sys_translate(args[0]),
+        #[cfg(feature = "debug-syscalls")]
+        SYSCALL_DUMP_VM => sys_dump_vm(),
+        #[cfg(feature = "debug-syscalls")]
+        SYSCALL_SYSCALL_STATS => sys_syscall_stats(),
+        SYSCALL_SBRK => sys_sbrk(args[0] as i32),
+        _ => {
+            reject_current_unknown_syscall(syscall_id);
+            -1
+        }
+    };
+    stats::record(syscall_id, get_time_ns() - start_ns);
+    if cfg!(feature = "trace") {
+        trace_current_syscall(syscall_id, args[0], ret);
+    }
+    if strace {
+        strace::log_call(syscall_id, args, Some(ret));
     }
+    ret
 }
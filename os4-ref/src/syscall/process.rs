@@ -1,8 +1,33 @@
 //! Process management syscalls
 
-use crate::config::MAX_SYSCALL_NUM;
-use crate::task::{exit_current_and_run_next, suspend_current_and_run_next, TaskStatus};
-use crate::timer::get_time_us;
+use crate::abi::wait_flag::WNOHANG;
+use crate::abi::{KernelConfig, MemInfo, MunmapStats, ProfileBucket, TaskInfo, TraceEntry};
+use crate::config::{
+    kernel_config, MAX_SYSCALL_NUM, PAGE_SIZE, PROFILE_BUCKETS, SHUTDOWN_APP, TIME_SLICE_PROFILES,
+};
+use crate::kstat;
+use crate::loader::get_blob;
+use crate::mm::{
+    check_user_range, copy_str_from_user, frame_allocator_check, mem_pressure, shm_create,
+    MapError, MapPermission, UserPtr, UserPtrError, UserSlice,
+};
+use crate::task::{
+    block_current_and_run_next, block_current_on_and_run_next, block_current_on_child_and_run_next,
+    change_current_program_brk, check_current_memory_set, check_scheduler,
+    current_children_cpu_time, current_fingerprint, current_has_caps, current_has_child,
+    current_mem_info, current_page_bitmap, current_pending_exit, current_task_cpu_time,
+    current_task_id, current_task_info, current_task_label, current_user_token, drain_task_trace,
+    drop_current_caps, exit_current_and_run_next, get_task_exit_code, kill_task, mq_create,
+    mq_destroy, mq_recv, mq_send, narrow_current_syscall_filter, populate_current_lazy,
+    read_task_profile, reap_current_child, reboot, restart_task, sched_epoch,
+    set_current_exec_text_only, set_current_priority, set_current_strace, set_current_trace,
+    shutdown_all, spawn, suspend_current_and_run_next, task_epoch, task_find_free, task_mmap,
+    task_mmap_data, task_mprotect, task_munmap, task_shm_map, task_slot, tasks_spawned, CapSet,
+    ExitReason, MqError, SyscallFilter, TRACE_ENTRIES,
+};
+use crate::timer::{cycles_to_clock_ticks, get_time, get_time_us};
+use alloc::vec;
+use core::mem::size_of;
 
 #[repr(C)]
 #[derive(Debug)]
@@ -11,16 +36,19 @@ pub struct TimeVal {
     pub usec: usize,
 }
 
-#[derive(Clone, Copy)]
-pub struct TaskInfo {
-    pub status: TaskStatus,
-    pub syscall_times: [u32; MAX_SYSCALL_NUM],
-    pub time: usize,
+/// process times in clock ticks, see [`crate::config::CLOCK_TICKS_PER_SEC`]
+#[repr(C)]
+#[derive(Debug)]
+pub struct Tms {
+    pub utime: usize,
+    pub stime: usize,
+    pub cutime: usize,
+    pub cstime: usize,
 }
 
 pub fn sys_exit(exit_code: i32) -> ! {
     info!("[kernel] Application exited with code {}", exit_code);
-    exit_current_and_run_next();
+    exit_current_and_run_next(ExitReason::Normal(exit_code));
     panic!("Unreachable in sys_exit!");
 }
 
@@ -30,33 +58,707 @@ pub fn sys_yield() -> isize {
     0
 }
 
-// YOUR JOB: 引入虚地址后重写 sys_get_time
-pub fn sys_get_time(_ts: *mut TimeVal, _tz: usize) -> isize {
-    let _us = get_time_us();
-    // unsafe {
-    //     *ts = TimeVal {
-    //         sec: us / 1_000_000,
-    //         usec: us % 1_000_000,
-    //     };
-    // }
+/// current task sleeps for `ms` milliseconds without taking the processor
+pub fn sys_sleep(ms: usize) -> isize {
+    let wakeup = get_time_us().saturating_add(ms.saturating_mul(1000));
+    block_current_and_run_next(wakeup);
+    0
+}
+
+/// write the time since boot to `ts`, -1 if it is not writable
+pub fn sys_get_time(ts: *mut TimeVal, _tz: usize) -> isize {
+    let us = get_time_us();
+    let time = TimeVal {
+        sec: us / 1_000_000,
+        usec: us % 1_000_000,
+    };
+    copy_value_to_user("sys_get_time(ts)", ts, &time).map_or(-1, |_| 0)
+}
+
+/// report the current task's times into `tms`, returns the uptime in clock ticks
+///
+/// `cutime` and `cstime` add up the children reaped by [`sys_waitpid`] so
+/// far, with the children they reaped in turn
+pub fn sys_times(tms: *mut Tms) -> isize {
+    let (utime, stime) = current_task_cpu_time();
+    let (cutime, cstime) = current_children_cpu_time();
+    let times = Tms {
+        utime: cycles_to_clock_ticks(utime),
+        stime: cycles_to_clock_ticks(stime),
+        cutime: cycles_to_clock_ticks(cutime),
+        cstime: cycles_to_clock_ticks(cstime),
+    };
+    if copy_value_to_user("sys_times(tms)", tms, &times).is_err() {
+        return -1;
+    }
+    cycles_to_clock_ticks(get_time()) as isize
+}
+
+/// Log why the current syscall refused its argument `arg`, the user
+/// address `addr`, and pass the reason on.
+pub(super) fn refuse_arg(arg: &str, addr: usize, err: UserPtrError) -> UserPtrError {
+    debug!(
+        "[kernel] task {}: {} {:#x} refused: {:?}",
+        current_task_label(),
+        arg,
+        addr,
+        err
+    );
+    err
+}
+
+/// copy `value` to `dst`, the syscall argument `arg`, in the current task's
+/// address space, which may span pages; see [`UserPtr`]
+fn copy_value_to_user<T>(arg: &str, dst: *mut T, value: &T) -> Result<(), UserPtrError> {
+    populate_current_lazy(dst as usize, size_of::<T>());
+    let mut ptr = UserPtr::writable(current_user_token(), dst)
+        .map_err(|err| refuse_arg(arg, dst as usize, err))?;
+    ptr.write(value);
+    Ok(())
+}
+
+/// copy `values` to the array at `dst`, the syscall argument `arg`, in the
+/// current task's address space; see [`UserSlice`]
+fn copy_slice_to_user<T>(arg: &str, dst: *mut T, values: &[T]) -> Result<(), UserPtrError> {
+    populate_current_lazy(dst as usize, size_of::<T>().saturating_mul(values.len()));
+    let mut slice = UserSlice::writable(current_user_token(), dst, values.len())
+        .map_err(|err| refuse_arg(arg, dst as usize, err))?;
+    slice.write(values);
+    Ok(())
+}
+
+/// copy the array at `src`, the syscall argument `arg`, in the current
+/// task's address space to `values`; see [`UserSlice`]
+fn copy_slice_from_user<T>(arg: &str, src: *const T, values: &mut [T]) -> Result<(), UserPtrError> {
+    populate_current_lazy(src as usize, size_of::<T>().saturating_mul(values.len()));
+    let slice = UserSlice::readable(current_user_token(), src, values.len())
+        .map_err(|err| refuse_arg(arg, src as usize, err))?;
+    slice.read(values);
+    Ok(())
+}
+
+/// soft reboot: rerun all apps with scheduler profile `flags`, see
+/// [`crate::config::TIME_SLICE_PROFILES`]
+///
+/// Needs [`CapSet::REBOOT`]. Only a single reboot is allowed, which is
+/// enough for one A/B comparison and keeps a rebooting app from looping,
+/// and none once a task was spawned. Returns -1 if refused.
+pub fn sys_reboot(flags: usize) -> isize {
+    if !current_has_caps(CapSet::REBOOT) {
+        return -1;
+    }
+    if flags >= TIME_SLICE_PROFILES.len() || kstat::finished_passes() > 0 || tasks_spawned() {
+        return -1;
+    }
+    reboot(flags)
+}
+
+/// end every task and power off, qemu exiting with a failure status if
+/// `failure` is nonzero
+///
+/// Only task 0 and the tasks of [`SHUTDOWN_APP`], a test harness that
+/// decides whether the run passed, may shut down; -1 for anyone else.
+/// Tasks not exited yet get exit code -14, see [`ExitReason::Shutdown`],
+/// and the shutdown summary is printed as when every app finished.
+pub fn sys_shutdown(failure: usize) -> isize {
+    let label = current_task_label();
+    if label.id != 0 && label.name != SHUTDOWN_APP {
+        return -1;
+    }
+    shutdown_all(failure != 0)
+}
+
+/// longest app name `sys_spawn` takes, the NUL included
+const SPAWN_NAME_MAX: usize = 64;
+
+/// start a new task running the app named by the NUL-terminated string at
+/// `name`, returns its id; -1 for an unknown app or if no task can be added
+pub fn sys_spawn(name: *const u8) -> isize {
+    let mut buf = [0; SPAWN_NAME_MAX];
+    copy_str_from_user(current_user_token(), &mut buf, name)
+        .and_then(|len| core::str::from_utf8(&buf[..len]).ok())
+        .and_then(spawn)
+        .map_or(-1, |id| id as isize)
+}
+
+/// run exited task `task_id` again, from the start of the same app and in
+/// the same slot, returns the id of the new run; -1 for an id no task has, a
+/// task not exited yet, or too few free frames
+///
+/// The new id has the slot in its low [`crate::config::TASK_SLOT_BITS`]
+/// bits and the count of restarts above, so the old id names no task any
+/// more: `sys_kill`, `sys_waitpid` and `sys_task_restart` refuse it, and
+/// `sys_wait_task` on it still returns the old run's exit code until the
+/// slot is restarted again. Needs [`CapSet::CROSS_TASK_CONTROL`], returns
+/// -1 if refused.
+pub fn sys_task_restart(task_id: usize) -> isize {
+    if !current_has_caps(CapSet::CROSS_TASK_CONTROL) {
+        return -1;
+    }
+    restart_task(task_id).map_or(-1, |id| id as isize)
+}
+
+/// wait until task `task_id` has exited, returns its exit code; -1 for an id
+/// no task has or for the caller's own, and if the caller is killed while it
+/// waits
+///
+/// An id of the run before the last restart of its slot is told its exit
+/// code, see `sys_task_restart`, and earlier ones get -1. A caller whose
+/// wait would close a cycle of tasks waiting for each other is killed
+/// instead, see [`ExitReason::Deadlock`].
+pub fn sys_wait_task(task_id: usize) -> isize {
+    loop {
+        if let Some(exit_code) = get_task_exit_code(task_id) {
+            return exit_code as isize;
+        }
+        // woken by the exit, the task may have been restarted before the
+        // waiter ran again, which the exit code above was kept for; a second
+        // restart turned the id stale
+        let slot = match task_slot(task_id) {
+            Some(slot) if slot != current_task_id() => slot,
+            _ => return -1,
+        };
+        if !block_current_on_and_run_next(slot) {
+            exit_current_and_run_next(ExitReason::Deadlock(slot));
+            panic!("Unreachable in sys_wait_task!");
+        }
+        if current_pending_exit().is_some() {
+            return -1;
+        }
+    }
+}
+
+/// wait for child `pid` of the caller, or any child for a `pid` of -1, to
+/// exit, and reap it: returns its id and writes its exit code to
+/// `exit_code` unless that is null. Children that exited first are reaped
+/// first, and a reaped one is no child any more.
+///
+/// Blocks until one exits, unless `flags` has [`WNOHANG`], which returns
+/// -2 while none has. -1 if the caller has no such child, for a bad `pid`,
+/// `flags` or `exit_code`, and if the caller is killed while it waits; a
+/// wait that would close a cycle of tasks waiting for each other kills the
+/// caller, see [`ExitReason::Deadlock`].
+pub fn sys_waitpid(pid: isize, exit_code: *mut i32, flags: usize) -> isize {
+    // a child restarted since `pid` was had is reaped under its new id only
+    let pid = match pid {
+        -1 => None,
+        pid if pid >= 0 => match task_slot(pid as usize) {
+            Some(slot) => Some(slot),
+            None => return -1,
+        },
+        _ => return -1,
+    };
+    if flags & !WNOHANG != 0 {
+        return -1;
+    }
+    let writable = || {
+        exit_code.is_null()
+            || check_user_bytes(
+                "sys_waitpid(exit_code)",
+                exit_code as usize,
+                size_of::<i32>(),
+                true,
+            )
+            .is_ok()
+    };
+    loop {
+        // before a child is reaped, whose exit code would be lost otherwise;
+        // again after each wait, which may have let its page be reclaimed
+        if !writable() {
+            return -1;
+        }
+        if let Some((child, code)) = reap_current_child(pid) {
+            if !exit_code.is_null() {
+                copy_value_to_user("sys_waitpid(exit_code)", exit_code, &code)
+                    .expect("exit code checked writable");
+            }
+            return child as isize;
+        }
+        if !current_has_child(pid) {
+            return -1;
+        }
+        if flags & WNOHANG != 0 {
+            return -2;
+        }
+        let blocked = match pid {
+            Some(pid) => block_current_on_and_run_next(pid),
+            None => block_current_on_child_and_run_next(),
+        };
+        if !blocked {
+            let target = pid.unwrap_or_else(current_task_id);
+            exit_current_and_run_next(ExitReason::Deadlock(target));
+            panic!("Unreachable in sys_waitpid!");
+        }
+        if current_pending_exit().is_some() {
+            return -1;
+        }
+    }
+}
+
+/// kill task `task_id`, which exits with code -9 before it runs any more of
+/// its own code, returns 0; a syscall it is blocked in returns -1 first
+///
+/// -1 for an id no task has, an earlier run of a restarted slot included,
+/// the caller's own or a task that exited, and without
+/// [`CapSet::CROSS_TASK_CONTROL`].
+pub fn sys_kill(task_id: usize) -> isize {
+    if !current_has_caps(CapSet::CROSS_TASK_CONTROL) {
+        return -1;
+    }
+    if kill_task(task_id) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// create a message queue of `capacity` messages of up to `msg_size` bytes,
+/// returns its id; -1 if either is 0, -3 if every id is taken or the
+/// caller's queues would hold more than [`crate::config::MQ_MAX_BYTES`] of kernel heap
+pub fn sys_mq_create(capacity: usize, msg_size: usize) -> isize {
+    mq_create(capacity, msg_size).map_or_else(MqError::errno, |id| id as isize)
+}
+
+/// Check that the current task may read, or write if `write`, the `len`
+/// bytes at `buf`, the syscall argument `arg`, giving lazy pages among them
+/// their frames.
+fn check_user_bytes(arg: &str, buf: usize, len: usize, write: bool) -> Result<(), UserPtrError> {
+    populate_current_lazy(buf, len);
+    let token = current_user_token();
+    let checked = if write {
+        UserSlice::writable(token, buf as *mut u8, len).map(drop)
+    } else {
+        UserSlice::readable(token, buf as *const u8, len).map(drop)
+    };
+    checked.map_err(|err| refuse_arg(arg, buf, err))
+}
+
+/// send the `len` bytes at `buf` to message queue `id`, returns 0; a full
+/// queue blocks the caller until a slot is free for it, and the senders
+/// that blocked before it have sent
+///
+/// -1 for an unknown queue, a message longer than its `msg_size` or a `buf`
+/// the caller may not read, and if the caller is killed while it waits.
+pub fn sys_mq_send(id: usize, buf: *const u8, len: usize) -> isize {
+    if check_user_bytes("sys_mq_send(buf)", buf as usize, len, false).is_err() {
+        return -1;
+    }
+    mq_send(id, buf as usize, len)
+}
+
+/// receive the oldest message of message queue `id` into `buf`, where `cap`
+/// bytes may be written, returns its length; an empty queue blocks the
+/// caller until a message comes for it, and the receivers that blocked
+/// before it have received
+///
+/// -1 for an unknown queue, a `cap` below its `msg_size` or a `buf` the
+/// caller may not write, and if the caller is killed while it waits.
+pub fn sys_mq_recv(id: usize, buf: *mut u8, cap: usize) -> isize {
+    if check_user_bytes("sys_mq_recv(buf)", buf as usize, cap, true).is_err() {
+        return -1;
+    }
+    mq_recv(id, buf as usize, cap)
+}
+
+/// drop message queue `id` and the messages left in it, returns 0; -1 for
+/// an unknown queue, and -2 while tasks are blocked on it
+pub fn sys_mq_destroy(id: usize) -> isize {
+    mq_destroy(id).map_or_else(MqError::errno, |_| 0)
+}
+
+/// permanently drop the capabilities in `mask`, returns the remaining set
+pub fn sys_cap_drop(mask: usize) -> isize {
+    drop_current_caps(CapSet::from_bits_truncate(mask as u32)).bits() as isize
+}
+
+/// from now on only allow the `count` syscall ids at `ids`, and only those
+/// earlier filters allowed as well; any other syscall kills the caller
+///
+/// Returns -1, leaving the filter as it was, if an id is out of range or
+/// `ids` cannot be read.
+pub fn sys_syscall_filter(ids: *const usize, count: usize) -> isize {
+    if count > MAX_SYSCALL_NUM {
+        return -1;
+    }
+    let mut allowed = vec![0; count];
+    if copy_slice_from_user("sys_syscall_filter(ids)", ids, &mut allowed).is_err() {
+        return -1;
+    }
+    match SyscallFilter::only(&allowed) {
+        Some(filter) => {
+            narrow_current_syscall_filter(&filter);
+            0
+        }
+        None => -1,
+    }
+}
+
+/// set the current task's stride scheduling priority, returns `prio` or -1
+/// if it is below 2
+pub fn sys_set_priority(prio: isize) -> isize {
+    if prio < 2 {
+        return -1;
+    }
+    set_current_priority(prio as usize);
+    prio
+}
+
+/// map anonymous memory at page-aligned `start`, `port` holds the R/W/X bits,
+/// at least one of them; -1 for any failure
+///
+/// This is the lab's mmap, which maps W without R as it is, the page
+/// faulting on any access, and refuses a `port` of 0; `sys_mmap2` has them
+/// the other way round and tells failures apart.
+pub fn sys_mmap(start: usize, len: usize, port: usize) -> isize {
+    let perm = MapPermission::from_lab_port(port).and_then(|perm| match perm.is_empty() {
+        true => Err(MapError::InvalidPermission),
+        false => Ok(perm),
+    });
+    mmap_checked("sys_mmap(start)", start, len, perm).map_or(-1, |ret| ret as isize)
+}
+
+/// `sys_mmap` under this kernel's own rules rather than the lab's: W without
+/// R, which SV39 reserves in a PTE, is refused with -1, and a `port` of 0
+/// reserves the range without any access until `sys_mprotect` commits it
+///
+/// Returns -2 if the range overlaps an area, the program's and the stack's
+/// as well as earlier mmaps, -3 if the task's page budget or the free frames
+/// cannot back it, and -1 for other invalid arguments; see
+/// [`MapError::errno`].
+pub fn sys_mmap2(start: usize, len: usize, port: usize) -> isize {
+    mmap_checked(
+        "sys_mmap2(start)",
+        start,
+        len,
+        MapPermission::from_port(port),
+    )
+    .map_or_else(|err| err.errno(), |ret| ret as isize)
+}
+
+/// the part of `sys_mmap` and `sys_mmap2` after converting `port` to `perm`
+fn mmap_checked(
+    arg: &str,
+    start: usize,
+    len: usize,
+    perm: Result<MapPermission, MapError>,
+) -> Result<usize, MapError> {
+    if let Err(err) = check_user_range(start, len, PAGE_SIZE) {
+        refuse_arg(arg, start, err);
+        return Err(MapError::Reserved);
+    }
+    task_mmap(start, len, perm?)
+}
+
+/// map memory at page-aligned `start` like `sys_mmap2`, starting out as a
+/// copy of read-only data blob `blob_id` and zeros after it; -1 for an
+/// unknown blob or a `len` too short to hold it
+pub fn sys_mmap_data(start: usize, len: usize, port: usize, blob_id: usize) -> isize {
+    if let Err(err) = check_user_range(start, len, PAGE_SIZE) {
+        refuse_arg("sys_mmap_data(start)", start, err);
+        return -1;
+    }
+    let perm = match MapPermission::from_port(port) {
+        Ok(perm) => perm,
+        Err(err) => return err.errno(),
+    };
+    match get_blob(blob_id) {
+        Some(blob) if blob.len() <= len => task_mmap_data(start, len, perm, blob)
+            .map_or_else(|err| err.errno(), |ret| ret as isize),
+        _ => -1,
+    }
+}
+
+/// move the program break by `size` bytes, returns the old break; -1 if the
+/// heap would end below where it started, -2 if it would run into another
+/// area and -3 if its pages cannot be backed
+pub fn sys_sbrk(size: i32) -> isize {
+    change_current_program_brk(size).map_or_else(|err| err.errno(), |old_brk| old_brk as isize)
+}
+
+/// unmap memory previously mapped by `sys_mmap` or `sys_mmap2`; -1 for any
+/// failure, as the lab has it
+pub fn sys_munmap(start: usize, len: usize) -> isize {
+    if let Err(err) = check_user_range(start, len, PAGE_SIZE) {
+        refuse_arg("sys_munmap(start)", start, err);
+        return -1;
+    }
+    task_munmap(start, len).map_or(-1, |_| 0)
+}
+
+/// change the permission of memory mapped by `sys_mmap` or `sys_mmap2` to
+/// the R/W/X bits of `port`, keeping its contents, and commit the pages
+/// `sys_mmap2` reserved in the range; -1 if `start` is not page aligned or part of the range is not
+/// such memory
+pub fn sys_mprotect(start: usize, len: usize, port: usize) -> isize {
+    if let Err(err) = check_user_range(start, len, PAGE_SIZE) {
+        refuse_arg("sys_mprotect(start)", start, err);
+        return -1;
+    }
+    task_mprotect(start, len, port).map_or_else(|err| err.errno(), |_| 0)
+}
+
+/// `sys_munmap` that also writes the frames it released to `stats`, which
+/// is left untouched on failure; -1 if `stats` is not writable or lies in
+/// the range, which is then left mapped
+pub fn sys_munmap2(start: usize, len: usize, stats: *mut MunmapStats) -> isize {
+    if let Err(err) = check_user_range(start, len, PAGE_SIZE) {
+        refuse_arg("sys_munmap2(start)", start, err);
+        return -1;
+    }
+    // checked before anything is unmapped, whose frame counts would be lost
+    let (stats_start, stats_len) = (stats as usize, size_of::<MunmapStats>());
+    if check_user_bytes("sys_munmap2(stats)", stats_start, stats_len, true).is_err() {
+        return -1;
+    }
+    // both ranges were checked to lie in the user half, nothing overflows
+    if stats_start < start + len && start < stats_start + stats_len {
+        return -1;
+    }
+    match task_munmap(start, len) {
+        Ok((data_frames, table_frames)) => {
+            let freed = MunmapStats {
+                data_frames: data_frames as u64,
+                table_frames: table_frames as u64,
+            };
+            copy_value_to_user("sys_munmap2(stats)", stats, &freed)
+                .expect("stats checked writable and kept mapped");
+            0
+        }
+        Err(err) => err.errno(),
+    }
+}
+
+/// create a shared memory segment of `pages` zeroed pages, returns its id
+pub fn sys_shm_create(pages: usize) -> isize {
+    shm_create(pages).map_or(-1, |id| id as isize)
+}
+
+/// map shared memory segment `id` at page-aligned `start`, or where the
+/// kernel finds room if `start` is 0, returns the address it is mapped at;
+/// `port` holds the R/W/X bits as for `sys_mmap`
+pub fn sys_shm_map(id: usize, start: usize, port: usize) -> isize {
+    task_shm_map(id, start, port)
+}
+
+/// write a summary of the caller's address space to `info`
+pub fn sys_mem_info(info: *mut MemInfo) -> isize {
+    copy_value_to_user("sys_mem_info(info)", info, &current_mem_info()).map_or(-1, |_| 0)
+}
+
+/// copy the first `cap` bytes of the kernel's [`KernelConfig`] to `buf`,
+/// returns its full size so that a short `cap` can be told apart
+pub fn sys_kernel_config(buf: *mut u8, cap: usize) -> isize {
+    let config = kernel_config();
+    let bytes = unsafe {
+        core::slice::from_raw_parts(
+            &config as *const KernelConfig as *const u8,
+            size_of::<KernelConfig>(),
+        )
+    };
+    match copy_slice_to_user(
+        "sys_kernel_config(buf)",
+        buf,
+        &bytes[..cap.min(bytes.len())],
+    ) {
+        Ok(()) => bytes.len() as isize,
+        Err(_) => -1,
+    }
+}
+
+/// system-wide memory pressure: 0 normal, 1 low, 2 critical on free frames
+pub fn sys_mem_pressure() -> isize {
+    mem_pressure() as isize
+}
+
+/// lowest address at which `sys_mmap` of `len` bytes aligned to `align`
+/// would succeed right now; nothing is mapped
+pub fn sys_vm_find_free(len: usize, align: usize) -> isize {
+    task_find_free(len, align).map_or_else(|err| err.errno(), |va| va.0 as isize)
+}
+
+/// V, R, W, X and U bits of the page table entry mapping `va` in the
+/// current task, -1 if there is no valid one
+#[cfg(feature = "debug-syscalls")]
+pub fn sys_translate(va: usize) -> isize {
+    use crate::mm::VirtAddr;
+    use crate::task::translate_current;
+    translate_current(VirtAddr::from(va).floor())
+        .filter(|pte| pte.is_valid())
+        .map_or(-1, |pte| (pte.flags().bits() & 0x1f) as isize)
+}
+
+/// print the current task's address space to the console
+#[cfg(feature = "debug-syscalls")]
+pub fn sys_dump_vm() -> isize {
+    crate::task::dump_current_address_space();
+    0
+}
+
+/// print the calls of each syscall so far with their latency, -1 if the
+/// kernel keeps no statistics
+#[cfg(feature = "debug-syscalls")]
+pub fn sys_syscall_stats() -> isize {
+    if !crate::kstat::ENABLED {
+        return -1;
+    }
+    super::print_syscall_stats();
+    0
+}
+
+/// write one bit per page of `[start, start + len)` to `bitmap`, set if a
+/// frame backs the page, returns how many are set
+///
+/// Bit `i % 8` of byte `i / 8` stands for page `i`; the unused bits of the
+/// last byte are 0. `start` must be page aligned.
+pub fn sys_vm_populated(start: usize, len: usize, bitmap: *mut u8) -> isize {
+    write_page_bitmap(start, len, bitmap, false)
+}
+
+/// like `sys_vm_populated`, but a bit is only set if the page was accessed
+/// since the previous call as well; the accessed bits are cleared, so
+/// successive calls sample which pages are in use
+pub fn sys_vm_accessed(start: usize, len: usize, bitmap: *mut u8) -> isize {
+    write_page_bitmap(start, len, bitmap, true)
+}
+
+fn write_page_bitmap(start: usize, len: usize, bitmap: *mut u8, take_accessed: bool) -> isize {
+    match current_page_bitmap(start, len, take_accessed) {
+        Ok((bits, set)) => {
+            let arg = if take_accessed {
+                "sys_vm_accessed(bitmap)"
+            } else {
+                "sys_vm_populated(bitmap)"
+            };
+            copy_slice_to_user(arg, bitmap, &bits).map_or(-1, |_| set as isize)
+        }
+        Err(err) => err.errno(),
+    }
+}
+
+/// only allow executing from the ELF text if `enable` is nonzero, so that
+/// JIT-style programs can opt out
+pub fn sys_exec_text_only(enable: usize) -> isize {
+    set_current_exec_text_only(enable != 0);
     0
 }
 
-// CLUE: 从 ch4 开始不再对调度算法进行测试~
-pub fn sys_set_priority(_prio: isize) -> isize {
-    -1
+/// fingerprint of the caller's address space layout, for comparing it
+/// before and after a sequence of mmap/munmap calls
+pub fn sys_mm_fingerprint() -> isize {
+    current_fingerprint() as isize
 }
 
-// YOUR JOB: 扩展内核以实现 sys_mmap 和 sys_munmap
-pub fn sys_mmap(_start: usize, _len: usize, _port: usize) -> isize {
-    -1
+/// start tracing the caller's syscalls into a fresh ring if `enable` is
+/// nonzero, stop and give the ring back otherwise; fails while every ring
+/// of the pool is lent out
+pub fn sys_trace_ctl(enable: usize) -> isize {
+    if !cfg!(feature = "trace") {
+        return -1;
+    }
+    if set_current_trace(enable != 0) {
+        0
+    } else {
+        -1
+    }
 }
 
-pub fn sys_munmap(_start: usize, _len: usize) -> isize {
-    -1
+/// log each syscall of the caller with its arguments and result if `enable`
+/// is nonzero, stop logging otherwise
+pub fn sys_strace(enable: usize) -> isize {
+    set_current_strace(enable != 0);
+    0
+}
+
+/// move up to `cap` of the oldest trace entries of task `pid` (the caller if
+/// negative) to `buf`, returns how many were moved
+///
+/// Reading another task's trace needs [`CapSet::DEBUG_INSPECT`].
+pub fn sys_trace_read(pid: isize, buf: *mut TraceEntry, cap: usize) -> isize {
+    if !cfg!(feature = "trace") {
+        return -1;
+    }
+    let current = current_task_id();
+    let pid = if pid < 0 { current } else { pid as usize };
+    if pid != current && !current_has_caps(CapSet::DEBUG_INSPECT) {
+        return -1;
+    }
+    let mut entries = vec![TraceEntry::default(); cap.min(TRACE_ENTRIES)];
+    match drain_task_trace(pid, &mut entries) {
+        Some(count) => copy_slice_to_user("sys_trace_read(buf)", buf, &entries[..count])
+            .map_or(-1, |_| count as isize),
+        None => -1,
+    }
+}
+
+/// current scheduler epoch, one more for every dispatch of any task
+pub fn sys_sched_epoch() -> isize {
+    sched_epoch() as isize
+}
+
+/// epoch task `pid` (the caller if negative) last ran in; for a task that
+/// is not running, the epoch it stopped in
+///
+/// Once every other task reports an epoch after `e`, each of them has been
+/// switched out since `e`. Returns -1 if there is no such task.
+pub fn sys_epoch_of(pid: isize) -> isize {
+    let pid = if pid < 0 {
+        current_task_id()
+    } else {
+        pid as usize
+    };
+    task_epoch(pid).map_or(-1, |epoch| epoch as isize)
+}
+
+/// copy up to `cap` of the non-empty profile buckets of task `pid` (the
+/// caller if negative) to `buf`, most samples first, returns how many
+///
+/// Reading another task's profile needs [`CapSet::DEBUG_INSPECT`].
+pub fn sys_profile_read(pid: isize, buf: *mut ProfileBucket, cap: usize) -> isize {
+    if !cfg!(feature = "profile") {
+        return -1;
+    }
+    let current = current_task_id();
+    let pid = if pid < 0 { current } else { pid as usize };
+    if pid != current && !current_has_caps(CapSet::DEBUG_INSPECT) {
+        return -1;
+    }
+    let mut buckets = vec![ProfileBucket::default(); cap.min(PROFILE_BUCKETS)];
+    match read_task_profile(pid, &mut buckets) {
+        Some(count) => copy_slice_to_user("sys_profile_read(buf)", buf, &buckets[..count])
+            .map_or(-1, |_| count as isize),
+        None => -1,
+    }
+}
+
+/// [`sys_selfcheck`] scope: the frame allocator's bookkeeping
+const SELFCHECK_FRAMES: usize = 1 << 0;
+/// [`sys_selfcheck`] scope: the caller's address space
+const SELFCHECK_MM: usize = 1 << 1;
+/// [`sys_selfcheck`] scope: task statuses
+const SELFCHECK_SCHED: usize = 1 << 2;
+
+/// run the kernel invariant checks selected by the `scope` bits, returns
+/// the bits of the checks that failed, 0 if all passed
+///
+/// Every violation found is logged. Needs [`CapSet::DEBUG_INSPECT`],
+/// returns -1 if refused.
+pub fn sys_selfcheck(scope: usize) -> isize {
+    if !current_has_caps(CapSet::DEBUG_INSPECT) {
+        return -1;
+    }
+    let checks: [(usize, fn() -> bool); 3] = [
+        (SELFCHECK_FRAMES, frame_allocator_check),
+        (SELFCHECK_MM, check_current_memory_set),
+        (SELFCHECK_SCHED, check_scheduler),
+    ];
+    checks
+        .iter()
+        .filter(|(bit, check)| scope & bit != 0 && !check())
+        .fold(0, |failed, (bit, _)| failed | bit) as isize
 }
 
-// YOUR JOB: 引入虚地址后重写 sys_task_info
+/// fill `ti` with a snapshot of the current task, -1 if `ti` is not a
+/// writable user buffer; it may straddle a page boundary
 pub fn sys_task_info(ti: *mut TaskInfo) -> isize {
-    -1
+    copy_value_to_user("sys_task_info(ti)", ti, &current_task_info()).map_or(-1, |_| 0)
 }
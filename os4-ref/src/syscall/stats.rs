@@ -0,0 +1,115 @@
+//! Per-syscall latency, printed in the `kstat` shutdown summary
+//!
+//! A call is timed with `get_time_ns` from entry until its handler returns,
+//! so one that blocks, like `sys_sleep` or `sys_wait_task`, also counts the
+//! time other tasks ran meanwhile. `sys_exit` never returns and is counted
+//! with no time at all. The statistics sit in a cell of their own, borrowed
+//! only once the handler is done: a handler is free to take the task
+//! manager, and one that switches away leaves nothing borrowed behind for
+//! the calls of the next task. A soft reboot keeps them, like the counters
+//! in [`crate::kstat`].
+
+use super::strace::name;
+use crate::config::MAX_SYSCALL_NUM;
+use crate::kstat;
+use crate::sync::UPSafeCell;
+use alloc::string::String;
+use core::fmt::Write;
+
+/// Histogram buckets: 0 ns, then `[2^(i-1), 2^i)` ns for bucket `i`, the
+/// last one, from about 4 ms on, also taking everything longer.
+const BUCKETS: usize = 24;
+
+/// calls of one syscall id
+#[derive(Copy, Clone)]
+struct SyscallStat {
+    count: u32,
+    total_ns: u64,
+    max_ns: u64,
+    buckets: [u32; BUCKETS],
+}
+
+impl SyscallStat {
+    const fn empty() -> Self {
+        Self {
+            count: 0,
+            total_ns: 0,
+            max_ns: 0,
+            buckets: [0; BUCKETS],
+        }
+    }
+}
+
+/// Calls of every syscall id below [`MAX_SYSCALL_NUM`]; a slot per id, so
+/// recording a call never allocates or searches.
+struct SyscallStats {
+    by_id: [SyscallStat; MAX_SYSCALL_NUM],
+}
+
+static SYSCALL_STATS: UPSafeCell<SyscallStats> = unsafe {
+    UPSafeCell::new(SyscallStats {
+        by_id: [SyscallStat::empty(); MAX_SYSCALL_NUM],
+    })
+};
+
+fn bucket(ns: usize) -> usize {
+    let bits = (usize::BITS - ns.leading_zeros()) as usize;
+    bits.min(BUCKETS - 1)
+}
+
+/// Count a call of `id` that took `ns` nanoseconds; ids past
+/// [`MAX_SYSCALL_NUM`] are not kept.
+pub fn record(id: usize, ns: usize) {
+    if !kstat::ENABLED || id >= MAX_SYSCALL_NUM {
+        return;
+    }
+    let mut stats = SYSCALL_STATS.exclusive_access();
+    let stat = &mut stats.by_id[id];
+    stat.count = stat.count.saturating_add(1);
+    stat.total_ns += ns as u64;
+    stat.max_ns = stat.max_ns.max(ns as u64);
+    stat.buckets[bucket(ns)] += 1;
+}
+
+/// Print a line for each syscall id called so far, with its non-empty
+/// histogram buckets named by their bound in nanoseconds, as in
+/// `[kernel] sys_yield: 40 calls, 913 us total, max 61 us, histogram <16384:31 <32768:8 <65536:1`.
+pub fn print_syscall_stats() {
+    if !kstat::ENABLED {
+        return;
+    }
+    // only a report, not worth a panic while something holds the statistics
+    let stats = match SYSCALL_STATS.try_exclusive_access() {
+        Some(stats) => stats,
+        None => {
+            println!("[kernel] syscall statistics in use, not printed");
+            return;
+        }
+    };
+    for (id, stat) in stats.by_id.iter().enumerate() {
+        if stat.count == 0 {
+            continue;
+        }
+        let mut histogram = String::new();
+        for (i, &n) in stat.buckets.iter().enumerate().filter(|(_, &n)| n > 0) {
+            if i == BUCKETS - 1 {
+                let _ = write!(histogram, " >={}:{}", 1usize << (i - 1), n);
+            } else {
+                let _ = write!(histogram, " <{}:{}", 1usize << i, n);
+            }
+        }
+        let mut label = String::new();
+        let _ = match name(id) {
+            Some(name) => write!(label, "sys_{}", name),
+            None => write!(label, "syscall_{}", id),
+        };
+        println!(
+            "[kernel] {}: {} calls, {} us total, max {} us, histogram{}",
+            label,
+            stat.count,
+            stat.total_ns / 1000,
+            stat.max_ns / 1000,
+            histogram
+        );
+    }
+}
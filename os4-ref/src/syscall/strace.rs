@@ -0,0 +1,132 @@
+//! Syscall log of the tasks that asked for one, see `sys_strace`
+//!
+//! Each call is logged once its handler returned, under the `strace` log
+//! target, as `[task 1] sys_mmap(0x10000000, 4096, 0x3) = 0`. The table
+//! below names every syscall and says how to print each of its arguments.
+
+use super::*;
+use crate::task::current_task_id;
+use core::fmt::{self, Display, Formatter};
+
+/// log target of the lines, see `LOG_MUTE` in [`crate::logging`]
+const TARGET: &str = "strace";
+
+/// how to print an argument
+#[derive(Copy, Clone)]
+pub(super) enum Arg {
+    /// addresses, pointers, flags and masks
+    Hex,
+    /// lengths, counts and ids
+    Dec,
+    /// signed values, a negative pid meaning the caller
+    Int,
+}
+
+use Arg::*;
+
+/// name and arguments of syscall `id`, `None` for an unknown one
+pub(super) fn signature(id: usize) -> Option<(&'static str, &'static [Arg])> {
+    Some(match id {
+        SYSCALL_WRITE => ("write", &[Dec, Hex, Dec]),
+        SYSCALL_EXIT => ("exit", &[Int]),
+        SYSCALL_SLEEP => ("sleep", &[Dec]),
+        SYSCALL_YIELD => ("yield", &[]),
+        SYSCALL_GET_TIME => ("get_time", &[Hex, Dec]),
+        SYSCALL_SBRK => ("sbrk", &[Int]),
+        SYSCALL_MUNMAP => ("munmap", &[Hex, Dec]),
+        SYSCALL_MMAP => ("mmap", &[Hex, Dec, Hex]),
+        SYSCALL_MPROTECT => ("mprotect", &[Hex, Dec, Hex]),
+        SYSCALL_TIMES => ("times", &[Hex]),
+        SYSCALL_SET_PRIORITY => ("set_priority", &[Int]),
+        SYSCALL_REBOOT => ("reboot", &[Hex]),
+        SYSCALL_SPAWN => ("spawn", &[Hex]),
+        SYSCALL_WAITPID => ("waitpid", &[Int, Hex, Hex]),
+        SYSCALL_TASK_INFO => ("task_info", &[Hex]),
+        SYSCALL_EXEC_TEXT_ONLY => ("exec_text_only", &[Dec]),
+        SYSCALL_CAP_DROP => ("cap_drop", &[Hex]),
+        SYSCALL_MM_FINGERPRINT => ("mm_fingerprint", &[]),
+        SYSCALL_TRACE_CTL => ("trace_ctl", &[Dec]),
+        SYSCALL_TRACE_READ => ("trace_read", &[Int, Hex, Dec]),
+        SYSCALL_SCHED_EPOCH => ("sched_epoch", &[]),
+        SYSCALL_EPOCH_OF => ("epoch_of", &[Int]),
+        SYSCALL_PROFILE_READ => ("profile_read", &[Int, Hex, Dec]),
+        SYSCALL_SELFCHECK => ("selfcheck", &[Hex]),
+        SYSCALL_MUNMAP2 => ("munmap2", &[Hex, Dec, Hex]),
+        SYSCALL_MEM_INFO => ("mem_info", &[Hex]),
+        SYSCALL_VM_FIND_FREE => ("vm_find_free", &[Dec, Hex]),
+        SYSCALL_SYSCALL_FILTER => ("syscall_filter", &[Hex, Dec]),
+        SYSCALL_MEM_PRESSURE => ("mem_pressure", &[]),
+        SYSCALL_KERNEL_CONFIG => ("kernel_config", &[Hex, Dec]),
+        SYSCALL_VM_POPULATED => ("vm_populated", &[Hex, Dec, Hex]),
+        SYSCALL_VM_ACCESSED => ("vm_accessed", &[Hex, Dec, Hex]),
+        #[cfg(feature = "debug-syscalls")]
+        SYSCALL_TRANSLATE => ("translate", &[Hex]),
+        #[cfg(feature = "debug-syscalls")]
+        SYSCALL_DUMP_VM => ("dump_vm", &[]),
+        #[cfg(feature = "debug-syscalls")]
+        SYSCALL_SYSCALL_STATS => ("syscall_stats", &[]),
+        SYSCALL_SHM_CREATE => ("shm_create", &[Dec]),
+        SYSCALL_SHM_MAP => ("shm_map", &[Dec, Hex, Hex]),
+        SYSCALL_STRACE => ("strace", &[Dec]),
+        SYSCALL_WAIT_TASK => ("wait_task", &[Dec]),
+        SYSCALL_MMAP_DATA => ("mmap_data", &[Hex, Dec, Hex, Dec]),
+        SYSCALL_SHUTDOWN => ("shutdown", &[Dec]),
+        SYSCALL_TASK_RESTART => ("task_restart", &[Dec]),
+        SYSCALL_MMAP2 => ("mmap2", &[Hex, Dec, Hex]),
+        SYSCALL_MQ_CREATE => ("mq_create", &[Dec, Dec]),
+        SYSCALL_MQ_SEND => ("mq_send", &[Dec, Hex, Dec]),
+        SYSCALL_MQ_RECV => ("mq_recv", &[Dec, Hex, Dec]),
+        SYSCALL_MQ_DESTROY => ("mq_destroy", &[Dec]),
+        SYSCALL_KILL => ("kill", &[Dec]),
+        _ => return None,
+    })
+}
+
+/// name of syscall `id` without the `sys_`, `None` for an unknown one
+pub(super) fn name(id: usize) -> Option<&'static str> {
+    signature(id).map(|(name, _)| name)
+}
+
+/// a call as it is logged, without its result
+struct Call {
+    id: usize,
+    args: [usize; 4],
+}
+
+impl Display for Call {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        // an unknown syscall gets its id as name and every argument in hex
+        let kinds: &[Arg] = match signature(self.id) {
+            Some((name, kinds)) => {
+                write!(f, "sys_{}(", name)?;
+                kinds
+            }
+            None => {
+                write!(f, "syscall_{}(", self.id)?;
+                &[Hex, Hex, Hex, Hex]
+            }
+        };
+        for (i, (&arg, kind)) in self.args.iter().zip(kinds).enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            match kind {
+                Hex => write!(f, "{:#x}", arg)?,
+                Dec => write!(f, "{}", arg)?,
+                Int => write!(f, "{}", arg as isize)?,
+            }
+        }
+        write!(f, ")")
+    }
+}
+
+/// Log a call of the current task with its result, or as `= ?` for one
+/// that does not return.
+pub fn log_call(id: usize, args: [usize; 4], ret: Option<isize>) {
+    let task = current_task_id();
+    let call = Call { id, args };
+    match ret {
+        Some(ret) => info!(target: TARGET, "[task {}] {} = {}", task, call, ret),
+        None => info!(target: TARGET, "[task {}] {} = ?", task, call),
+    }
+}
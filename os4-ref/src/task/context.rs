@@ -25,4 +25,17 @@ impl TaskContext {
             s: [0; 12],
         }
     }
+    /// where `__switch` returns to for this context
+    pub fn ra(&self) -> usize {
+        self.ra
+    }
+    /// kernel stack pointer restored by `__switch`
+    pub fn sp(&self) -> usize {
+        self.sp
+    }
+    /// This context returning to `ra` instead, for the dispatch check tests.
+    #[cfg(feature = "board_test")]
+    pub fn with_ra(self, ra: usize) -> Self {
+        Self { ra, ..self }
+    }
 }
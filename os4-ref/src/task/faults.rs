@@ -0,0 +1,52 @@
+//! Per-task history of the latest memory faults
+
+use riscv::register::scause::Exception;
+
+/// faults a task's history keeps
+const FAULT_HISTORY: usize = 16;
+
+/// One memory fault a task took.
+#[derive(Copy, Clone, Debug)]
+pub struct FaultRecord {
+    pub stval: usize,
+    pub sepc: usize,
+    pub cause: Exception,
+    /// microseconds since boot
+    pub time_us: usize,
+    /// whether the kernel resolved the fault, as for the first touch of a
+    /// lazy page, rather than killing the task for it
+    pub resolved: bool,
+}
+
+/// The latest [`FAULT_HISTORY`] faults of a task, the older ones
+/// overwritten.
+///
+/// A plain array and the index of the next slot, so that recording a fault
+/// never allocates, however low memory runs.
+#[derive(Copy, Clone)]
+pub struct FaultHistory {
+    records: [Option<FaultRecord>; FAULT_HISTORY],
+    /// slot the next fault goes to, which holds the oldest one once the
+    /// history is full
+    head: usize,
+}
+
+impl FaultHistory {
+    pub const fn empty() -> Self {
+        Self {
+            records: [None; FAULT_HISTORY],
+            head: 0,
+        }
+    }
+
+    pub fn record(&mut self, record: FaultRecord) {
+        self.records[self.head] = Some(record);
+        self.head = (self.head + 1) % FAULT_HISTORY;
+    }
+
+    /// The faults kept, the oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &FaultRecord> {
+        let (newer, older) = self.records.split_at(self.head);
+        older.iter().chain(newer).flatten()
+    }
+}
@@ -0,0 +1,465 @@
+//! Task manager tests of the `board_test` self-tests, see
+//! [`crate::kernel_tests`]
+//!
+//! Blocking calls are made through the task manager's own methods, which
+//! block the current task without switching away from it; another task is
+//! then made current to serve it, and the order in which the waiters were
+//! served is told by the lengths of the messages they got.
+
+use super::*;
+use crate::config::TRAP_CONTEXT;
+use crate::kernel_tests::TestResult;
+use crate::loader::AppCatalog;
+use crate::syscall::syscall;
+use alloc::format;
+
+/// `sys_get_time`, which the filtered task is left
+const SYSCALL_GET_TIME: usize = 169;
+/// an id past the syscall table, see [`SyscallFilter`]
+const PAST_TABLE: usize = 9999;
+
+/// where each task the tests make current gets a read-write page
+const MQ_BUF: usize = 0x1003_0000;
+
+/// Make task `id` current, mapping its [`MQ_BUF`] page the first time.
+fn enter(id: usize) -> TestResult {
+    ktest_assert!(id < task_count(), "the tests need {} apps", id + 1);
+    TASK_MANAGER.enter_for_test(id);
+    let mapped =
+        translate_current(VirtAddr::from(MQ_BUF).floor()).map_or(false, |pte| pte.is_valid());
+    if !mapped {
+        task_mmap(MQ_BUF, PAGE_SIZE, MapPermission::R | MapPermission::W)
+            .map_err(|err| format!("mapping the buffer of task {}: {:?}", id, err))?;
+    }
+    Ok(())
+}
+
+/// Write `bytes` to the current task's [`MQ_BUF`].
+fn fill(bytes: &[u8]) -> TestResult {
+    let mut pages = current_user_buffer(MQ_BUF as *const u8, bytes.len())
+        .map_err(|err| format!("filling the buffer: {:?}", err))?;
+    pages[0].copy_from_slice(bytes);
+    Ok(())
+}
+
+/// The first `len` bytes of the current task's [`MQ_BUF`].
+fn contents(len: usize) -> Vec<u8> {
+    let pte = translate_current(VirtAddr::from(MQ_BUF).floor()).expect("buffer unmapped");
+    pte.ppn().get_bytes_array()[..len].to_vec()
+}
+
+fn status(id: usize) -> TaskStatus {
+    TASK_MANAGER.inner.exclusive_access().tasks[id].task_status
+}
+
+fn unknown_syscalls(id: usize) -> usize {
+    TASK_MANAGER.inner.exclusive_access().tasks[id].unknown_syscalls
+}
+
+/// An id past the syscall table is refused as unknown while the task has
+/// no filter: the call returns -1, is counted, and the task runs on. Once a
+/// filter is installed the id is forbidden like any other it leaves out,
+/// which kills the caller before dispatch.
+pub fn syscall_ids_past_table() -> TestResult {
+    enter(1)?;
+    let unknown = unknown_syscalls(1);
+    ktest_assert!(current_syscall_allowed(PAST_TABLE));
+    ktest_assert_eq!(syscall(PAST_TABLE, [0; 4]), -1);
+    ktest_assert_eq!(status(1), TaskStatus::Running);
+    ktest_assert_eq!(unknown_syscalls(1), unknown + 1);
+    let filter = SyscallFilter::only(&[SYSCALL_GET_TIME]).expect("id in range");
+    narrow_current_syscall_filter(&filter);
+    ktest_assert!(current_syscall_allowed(SYSCALL_GET_TIME));
+    ktest_assert!(!current_syscall_allowed(PAST_TABLE));
+    // the tests after this one make syscalls on behalf of the task
+    TASK_MANAGER.inner.exclusive_access().tasks[1].syscall_filter = SyscallFilter::allow_all();
+    Ok(())
+}
+
+/// Receivers blocked on an empty queue get the messages sent later in the
+/// order they blocked in.
+pub fn mq_fifo_receivers() -> TestResult {
+    enter(4)?;
+    let id = mq_create(1, 8).map_err(|err| format!("mq_create: {:?}", err))?;
+    for receiver in 1..=3 {
+        enter(receiver)?;
+        ktest_assert_eq!(TASK_MANAGER.mq_recv(id, MQ_BUF, 8), None);
+        ktest_assert_eq!(status(receiver), TaskStatus::Blocked);
+    }
+    ktest_assert_eq!(mq_destroy(id), Err(MqError::Busy));
+    enter(4)?;
+    for len in 1..=3 {
+        fill(&b"abc"[..len])?;
+        ktest_assert_eq!(TASK_MANAGER.mq_send(id, MQ_BUF, len), Some(0));
+    }
+    for receiver in 1..=3 {
+        ktest_assert_eq!(status(receiver), TaskStatus::Ready);
+        enter(receiver)?;
+        ktest_assert_eq!(TASK_MANAGER.queue_result(), receiver as isize);
+        ktest_assert_eq!(contents(receiver), &b"abc"[..receiver]);
+    }
+    ktest_assert_eq!(mq_destroy(id), Ok(()));
+    Ok(())
+}
+
+/// Senders blocked on a full queue get their messages in, in the order they
+/// blocked in, each as a slot is freed for it.
+pub fn mq_fifo_senders() -> TestResult {
+    enter(1)?;
+    let id = mq_create(1, 8).map_err(|err| format!("mq_create: {:?}", err))?;
+    for sender in 1..=3 {
+        enter(sender)?;
+        fill(&b"xyz"[..sender])?;
+        let blocks = sender > 1;
+        let sent = TASK_MANAGER.mq_send(id, MQ_BUF, sender);
+        ktest_assert_eq!(sent, if blocks { None } else { Some(0) });
+    }
+    ktest_assert_eq!(mq_destroy(id), Err(MqError::Busy));
+    enter(4)?;
+    for len in 1..=3 {
+        ktest_assert_eq!(TASK_MANAGER.mq_recv(id, MQ_BUF, 8), Some(len as isize));
+        ktest_assert_eq!(contents(len), &b"xyz"[..len]);
+        // the next sender's message took the freed slot
+        if len < 3 {
+            ktest_assert_eq!(status(len + 1), TaskStatus::Ready);
+        }
+    }
+    for sender in 2..=3 {
+        enter(sender)?;
+        ktest_assert_eq!(TASK_MANAGER.queue_result(), 0);
+    }
+    ktest_assert_eq!(mq_destroy(id), Ok(()));
+    Ok(())
+}
+
+/// A receiver killed while blocked is taken off the queue: the next message
+/// goes to the receiver behind it, and the queue can be destroyed.
+pub fn mq_kill_blocked() -> TestResult {
+    enter(3)?;
+    let id = mq_create(1, 8).map_err(|err| format!("mq_create: {:?}", err))?;
+    for receiver in 1..=2 {
+        enter(receiver)?;
+        ktest_assert_eq!(TASK_MANAGER.mq_recv(id, MQ_BUF, 8), None);
+    }
+    enter(3)?;
+    ktest_assert!(!TASK_MANAGER.kill(3), "killed itself");
+    ktest_assert!(TASK_MANAGER.kill(1));
+    ktest_assert_eq!(status(1), TaskStatus::Ready);
+    fill(b"k")?;
+    ktest_assert_eq!(TASK_MANAGER.mq_send(id, MQ_BUF, 1), Some(0));
+    ktest_assert_eq!(mq_destroy(id), Ok(()));
+    enter(2)?;
+    ktest_assert_eq!(TASK_MANAGER.queue_result(), 1);
+    ktest_assert_eq!(TASK_MANAGER.pending_exit(), None);
+    enter(1)?;
+    ktest_assert_eq!(TASK_MANAGER.queue_result(), -1);
+    ktest_assert_eq!(TASK_MANAGER.pending_exit(), Some(ExitReason::Killed(3)));
+    Ok(())
+}
+
+/// Put tasks `ids` to sleep in that order, each until its own entry of
+/// `deadlines`, and give them equal passes, so that the ready queue orders
+/// them by when they became `Ready` alone.
+fn sleep_in_order(ids: &[usize], deadlines: &[usize]) -> TestResult {
+    for (&id, &deadline) in ids.iter().zip(deadlines) {
+        enter(id)?;
+        TASK_MANAGER.block_current_until(deadline);
+        ktest_assert_eq!(status(id), TaskStatus::Blocked);
+    }
+    let mut inner = TASK_MANAGER.inner.exclusive_access();
+    for &id in ids {
+        inner.tasks[id].pass = 0;
+    }
+    Ok(())
+}
+
+/// Wake the sleepers due at `now_us`, returning the order `ids` are queued
+/// in afterwards.
+fn wake_at(now_us: usize, ids: &[usize]) -> Vec<usize> {
+    let mut inner = TASK_MANAGER.inner.exclusive_access();
+    inner.wake_sleepers(now_us);
+    let queued = inner.ready.queued();
+    queued.into_iter().filter(|id| ids.contains(id)).collect()
+}
+
+/// Sleepers due at the same time wake in the order they went to sleep, not
+/// in id order, and an earlier deadline goes first whenever it was set.
+pub fn sleepers_equal_deadlines() -> TestResult {
+    // far past the tests, nothing else wakes them
+    let deadline = get_time_us() + 60_000_000;
+    let ids = [3, 1, 4, 2, 5];
+    sleep_in_order(
+        &ids,
+        &[deadline, deadline, deadline, deadline, deadline - 1],
+    )?;
+    ktest_assert_eq!(wake_at(deadline - 2, &ids), []);
+    ktest_assert!(ids.iter().all(|&id| status(id) == TaskStatus::Blocked));
+    ktest_assert_eq!(wake_at(deadline, &ids), [5, 3, 1, 4, 2]);
+    ktest_assert!(ids.iter().all(|&id| status(id) == TaskStatus::Ready));
+    Ok(())
+}
+
+/// Sleepers put to sleep in random orders with random, often equal,
+/// deadlines always queue up by deadline, then by the order they slept in.
+pub fn sleepers_drain_in_order() -> TestResult {
+    const ROUNDS: usize = 32;
+    let count = task_count().min(9);
+    ktest_assert!(count > 2, "the test needs 3 apps");
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut random = move |bound: usize| {
+        seed = seed
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (seed >> 33) as usize % bound
+    };
+    let base = get_time_us() + 60_000_000;
+    for round in 0..ROUNDS {
+        let mut ids: Vec<usize> = (1..count).collect();
+        for i in (1..ids.len()).rev() {
+            ids.swap(i, random(i + 1));
+        }
+        let deadlines: Vec<usize> = ids.iter().map(|_| base + random(3)).collect();
+        sleep_in_order(&ids, &deadlines)?;
+        let mut expected: Vec<(usize, usize)> = deadlines.iter().copied().zip(0..).collect();
+        expected.sort_unstable();
+        let expected: Vec<usize> = expected.into_iter().map(|(_, slept)| ids[slept]).collect();
+        let woken = wake_at(base + 2, &ids);
+        ktest_assert_eq!(woken, expected, "in round {}", round);
+    }
+    Ok(())
+}
+
+/// Kills and exits racing the wakes of queue and exit waiters: a receiver
+/// killed and gone before the message comes is skipped for the one behind
+/// it, and a waiter killed before the task it waits for exits is neither
+/// woken again nor queued twice, while the others are woken once.
+pub fn exit_races_wakes() -> TestResult {
+    enter(1)?;
+    let id = mq_create(1, 8).map_err(|err| format!("mq_create: {:?}", err))?;
+    for receiver in 3..=4 {
+        enter(receiver)?;
+        ktest_assert_eq!(TASK_MANAGER.mq_recv(id, MQ_BUF, 8), None);
+    }
+    for waiter in [5, 7] {
+        enter(waiter)?;
+        ktest_assert!(TASK_MANAGER.block_current_on(6));
+    }
+    enter(1)?;
+    ktest_assert!(TASK_MANAGER.kill(3));
+    ktest_assert!(TASK_MANAGER.kill(7));
+    enter(3)?;
+    TASK_MANAGER.mark_current_exited(ExitReason::Killed(1));
+    enter(6)?;
+    fill(b"m")?;
+    ktest_assert_eq!(TASK_MANAGER.mq_send(id, MQ_BUF, 1), Some(0));
+    ktest_assert_eq!(status(3), TaskStatus::Exited);
+    ktest_assert_eq!(status(4), TaskStatus::Ready);
+    TASK_MANAGER.mark_current_exited(ExitReason::Normal(0));
+    {
+        let inner = TASK_MANAGER.inner.exclusive_access();
+        ktest_assert_eq!(inner.tasks[3].queue_result, -1);
+        let queued = inner.ready.queued();
+        for task in [4, 5, 7] {
+            ktest_assert_eq!(inner.tasks[task].task_status, TaskStatus::Ready);
+            let times = queued.iter().filter(|&&id| id == task).count();
+            ktest_assert_eq!(times, 1, "task {} queued", task);
+        }
+    }
+    enter(7)?;
+    ktest_assert_eq!(TASK_MANAGER.pending_exit(), Some(ExitReason::Killed(1)));
+    TASK_MANAGER.mark_current_exited(ExitReason::Killed(1));
+    enter(5)?;
+    ktest_assert_eq!(TASK_MANAGER.pending_exit(), None);
+    enter(4)?;
+    ktest_assert_eq!(TASK_MANAGER.queue_result(), 1);
+    ktest_assert_eq!(contents(1), b"m");
+    ktest_assert_eq!(mq_destroy(id), Ok(()));
+    Ok(())
+}
+
+/// A task killed in its sleep and restarted leaves nothing of the old run
+/// behind: the new run is queued once and sleeps on no deadline, and the
+/// old id is refused as stale, though its exit code is still told.
+pub fn restart_stale_ids() -> TestResult {
+    let deadline = get_time_us() + 60_000_000;
+    enter(2)?;
+    let old = TASK_MANAGER.inner.exclusive_access().id_of(2);
+    TASK_MANAGER.block_current_until(deadline);
+    enter(1)?;
+    ktest_assert!(TASK_MANAGER.kill(old));
+    enter(2)?;
+    TASK_MANAGER.mark_current_exited(ExitReason::Killed(1));
+    enter(1)?;
+    let new = TASK_MANAGER.restart(old).ok_or("restart refused")?;
+    ktest_assert_eq!(new & ((1 << TASK_SLOT_BITS) - 1), 2);
+    ktest_assert_eq!(new >> TASK_SLOT_BITS, (old >> TASK_SLOT_BITS) + 1);
+    {
+        let mut inner = TASK_MANAGER.inner.exclusive_access();
+        inner.wake_sleepers(deadline);
+        ktest_assert_eq!(inner.tasks[2].task_status, TaskStatus::Ready);
+        ktest_assert_eq!(inner.tasks[2].wakeup_time_us, 0);
+        let queued = inner.ready.queued();
+        ktest_assert_eq!(queued.iter().filter(|&&id| id == 2).count(), 1);
+    }
+    let stale = KSTAT.stale_task_ids.load(Ordering::Relaxed);
+    ktest_assert!(!TASK_MANAGER.kill(old), "killed the new run by the old id");
+    ktest_assert_eq!(TASK_MANAGER.restart(old), None);
+    ktest_assert_eq!(status(2), TaskStatus::Ready);
+    if kstat::ENABLED {
+        ktest_assert_eq!(KSTAT.stale_task_ids.load(Ordering::Relaxed), stale + 2);
+    }
+    ktest_assert_eq!(TASK_MANAGER.exit_code(old), Some(-9));
+    ktest_assert_eq!(TASK_MANAGER.exit_code(new), None);
+    ktest_assert_eq!(TASK_MANAGER.slot_of(new), Some(2));
+    Ok(())
+}
+
+/// Each field of a dispatch target corrupted in turn gets its own
+/// complaint from the check before `__switch`, and nothing else: the saved
+/// sp, the saved ra of a first dispatch, and the user sepc.
+pub fn dispatch_check_faults() -> TestResult {
+    // task 0 is never made current by the tests, its contexts are as built
+    let mut inner = TASK_MANAGER.inner.exclusive_access();
+    let task = &mut inner.tasks[0];
+    ktest_assert_eq!(check_dispatch(task, 0, true), DispatchFaults::empty());
+    let saved = task.task_cx;
+    let (bottom, top) = kernel_stack_position(0);
+    for sp in [0, bottom, top + 1, top + PAGE_SIZE] {
+        task.task_cx = TaskContext::goto_trap_return(sp);
+        let faults = check_dispatch(task, 0, true);
+        ktest_assert_eq!(faults, DispatchFaults::STACK_POINTER, "for sp {:#x}", sp);
+    }
+    task.task_cx = saved.with_ra(0);
+    ktest_assert_eq!(
+        check_dispatch(task, 0, true),
+        DispatchFaults::RETURN_ADDRESS
+    );
+    // a later dispatch returns into the scheduler, not to trap_return
+    ktest_assert_eq!(check_dispatch(task, 0, false), DispatchFaults::empty());
+    task.task_cx = saved;
+    let sepc = task.get_trap_cx().sepc;
+    for bad in [0, TRAP_CONTEXT] {
+        task.get_trap_cx().sepc = bad;
+        let faults = check_dispatch(task, 0, true);
+        task.get_trap_cx().sepc = sepc;
+        ktest_assert_eq!(faults, DispatchFaults::USER_PC, "for sepc {:#x}", bad);
+    }
+    ktest_assert_eq!(check_dispatch(task, 0, true), DispatchFaults::empty());
+    Ok(())
+}
+
+/// The app catalog answers `None` past its last index and for unknown
+/// names, and so does spawning by name, and it leaves out a corrupted
+/// image: one cut short and one that does not start at its ELF header.
+pub fn app_catalog_bounds() -> TestResult {
+    let count = APP_CATALOG.len();
+    ktest_assert!(count > 0);
+    ktest_assert!(APP_CATALOG.get(0).is_some());
+    ktest_assert!(APP_CATALOG.get(count - 1).is_some());
+    ktest_assert!(APP_CATALOG.get(count).is_none());
+    ktest_assert!(APP_CATALOG.get(usize::MAX).is_none());
+    let last = APP_CATALOG.get(count - 1).unwrap();
+    let (index, found) = APP_CATALOG
+        .get_by_name(last.name)
+        .ok_or("last app not found")?;
+    ktest_assert_eq!(index, count - 1);
+    ktest_assert_eq!(found.checksum, last.checksum);
+    ktest_assert!(APP_CATALOG.get_by_name("").is_none());
+    ktest_assert!(APP_CATALOG.get_by_name("no_such_app").is_none());
+    ktest_assert_eq!(spawn("no_such_app"), None);
+    let app = APP_CATALOG.get(0).unwrap();
+    let images = [
+        ("cut_short", &app.data[..app.data.len() / 2]),
+        ("intact", app.data),
+        ("shifted", &app.data[1..]),
+    ];
+    let catalog = AppCatalog::from_images(images.iter().copied());
+    ktest_assert_eq!(catalog.len(), 1);
+    let (index, intact) = catalog.get_by_name("intact").ok_or("intact app left out")?;
+    ktest_assert_eq!(index, 0);
+    ktest_assert_eq!(intact.checksum, app.checksum);
+    ktest_assert!(catalog.get_by_name("cut_short").is_none());
+    ktest_assert!(catalog.get_by_name("shifted").is_none());
+    ktest_assert!(catalog.get(1).is_none());
+    Ok(())
+}
+
+/// A second task forced `Running` through the transition API, or none left
+/// `Running`, is caught by the check `check_running` makes after every
+/// batch, which passes again once the statuses are put right.
+pub fn running_unique() -> TestResult {
+    enter(1)?;
+    let mut inner = TASK_MANAGER.inner.exclusive_access();
+    ktest_assert!(inner.running_consistent());
+    // any task but the current one, as long as it is only `Ready`
+    let other = 2;
+    ktest_assert_eq!(inner.tasks[other].task_status, TaskStatus::Ready);
+    inner.set_status(other, TaskStatus::Running);
+    ktest_assert_eq!(inner.running, 2);
+    ktest_assert!(!inner.running_consistent(), "two tasks Running");
+    inner.set_status(other, TaskStatus::Ready);
+    ktest_assert!(inner.running_consistent());
+    inner.set_status(1, TaskStatus::Ready);
+    ktest_assert_eq!(inner.running, 0);
+    ktest_assert!(!inner.running_consistent(), "no task Running");
+    inner.set_status(1, TaskStatus::Running);
+    // passes silently, it would panic in a debug build otherwise
+    inner.check_running();
+    Ok(())
+}
+
+/// The scheduler check behind `sys_selfcheck` catches a second task forced
+/// `Running`, and passes again once it is `Ready`.
+pub fn selfcheck_sched_injected() -> TestResult {
+    enter(1)?;
+    ktest_assert!(check_scheduler());
+    let set_status = |id, status| TASK_MANAGER.inner.exclusive_access().set_status(id, status);
+    ktest_assert_eq!(status(3), TaskStatus::Ready);
+    set_status(3, TaskStatus::Running);
+    let caught = !check_scheduler();
+    set_status(3, TaskStatus::Ready);
+    ktest_assert!(caught, "two tasks Running");
+    ktest_assert!(check_scheduler());
+    Ok(())
+}
+
+/// A task spawned past the apps loaded at boot is counted and listed, is
+/// queued to be picked and passes the checks of its first dispatch, and a
+/// teardown buries it with the rest. Ends every task, so it runs last.
+pub fn spawned_past_boot() -> TestResult {
+    enter(1)?;
+    let name = APP_CATALOG.get(0).ok_or("no app")?.name;
+    let id = spawn(name).ok_or("spawn refused")?;
+    ktest_assert!(
+        id >= APP_CATALOG.len(),
+        "spawned task {} in a boot slot",
+        id
+    );
+    ktest_assert_eq!(task_count(), id + 1);
+    ktest_assert!(tasks_spawned());
+    ktest_assert_eq!(status(id), TaskStatus::Ready);
+    ktest_assert!(
+        TASK_MANAGER.epoch_of(id).is_some(),
+        "spawned task not listed"
+    );
+    {
+        let inner = TASK_MANAGER.inner.exclusive_access();
+        ktest_assert!(
+            inner.ready.queued().contains(&id),
+            "spawned task not queued"
+        );
+        let faults = check_dispatch(&inner.tasks[id], id, true);
+        ktest_assert_eq!(faults, DispatchFaults::empty());
+    }
+    enter(id)?;
+    ktest_assert!(TASK_MANAGER.inner.exclusive_access().running_consistent());
+    let memory_sets = TASK_MANAGER
+        .inner
+        .exclusive_access()
+        .bury_all(ExitReason::Shutdown, get_time());
+    drop(memory_sets);
+    ktest_assert_eq!(status(id), TaskStatus::Exited);
+    ktest_assert_eq!(status(0), TaskStatus::Exited);
+    ktest_assert_eq!(TASK_MANAGER.exit_code(id), Some(-14));
+    Ok(())
+}
@@ -0,0 +1,55 @@
+//! Fixed-size digest of per-task latencies
+
+/// samples kept for the median
+const RESERVOIR: usize = 32;
+
+/// Min, max and an estimated median of a stream of latencies in cycles.
+///
+/// The median comes from a reservoir sample, so recording never allocates
+/// and costs the same however many samples there are.
+#[derive(Copy, Clone)]
+pub struct LatencyDigest {
+    count: usize,
+    min: usize,
+    max: usize,
+    reservoir: [usize; RESERVOIR],
+}
+
+impl LatencyDigest {
+    pub const fn empty() -> Self {
+        Self {
+            count: 0,
+            min: usize::MAX,
+            max: 0,
+            reservoir: [0; RESERVOIR],
+        }
+    }
+
+    pub fn record(&mut self, cycles: usize) {
+        self.min = self.min.min(cycles);
+        self.max = self.max.max(cycles);
+        if self.count < RESERVOIR {
+            self.reservoir[self.count] = cycles;
+        } else {
+            // keep each sample with probability RESERVOIR / count, hashing
+            // the count instead of drawing a random number
+            let hash = self.count.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 16;
+            let slot = hash % (self.count + 1);
+            if slot < RESERVOIR {
+                self.reservoir[slot] = cycles;
+            }
+        }
+        self.count += 1;
+    }
+
+    /// `(samples, min, median, max)`, `None` before the first sample.
+    pub fn summary(&self) -> Option<(usize, usize, usize, usize)> {
+        if self.count == 0 {
+            return None;
+        }
+        let mut kept = self.reservoir;
+        let kept = &mut kept[..self.count.min(RESERVOIR)];
+        kept.sort_unstable();
+        Some((self.count, self.min, kept[kept.len() / 2], self.max))
+    }
+}
@@ -10,17 +10,63 @@
 //! might not be what you expect.
 
 mod context;
+mod faults;
+#[cfg(feature = "board_test")]
+pub mod kernel_tests;
+mod latency;
+mod mq;
+mod pool;
+mod processor;
+mod profile;
+mod sched;
 mod switch;
+mod syscall_counts;
 #[allow(clippy::module_inception)]
 mod task;
+mod trace;
 
-use crate::loader::{get_app_data, get_num_app};
-use crate::sync::UPSafeCell;
-use crate::trap::TrapContext;
+use crate::abi::{MemInfo, ProfileBucket, TaskInfo, TraceEntry};
+use crate::config::{
+    kernel_stack_position, NullMmapPolicy, KERNEL_STACK_SIZE, KERNEL_STACK_WARN_PERCENT,
+    MAX_SPAWNED_TASKS, MMAP_NULL_POLICY, PAGE_SIZE, PROFILE_POOL_SIZE, SCHED_STARVATION_EXPECTED,
+    STARVATION_SLICES, TASK_SLOT_BITS, TICKS_PER_SLICE, TIME_SLICE_PROFILES, TRACE_POOL_RINGS,
+};
+use crate::debug_mirror::{self, BootStage};
+use crate::kstat::{self, KSTAT};
+use crate::loader::APP_CATALOG;
+use crate::mm::{
+    aslr_seed, frame_stats, frames_available, frames_in_use, shm_clear, shm_get, AreaKind,
+    MapError, MapPermission, MemorySet, PageTableEntry, PhysPageNum, TlbSync, UserPtrError,
+    VirtAddr, VirtPageNum, KERNEL_SPACE,
+};
+use crate::sbi::shutdown;
+use crate::stack_probe;
+use crate::sync::{UPRefMut, UPSafeCell};
+use crate::syscall::print_syscall_stats;
+use crate::timer::{
+    cycles_to_ms, get_time, get_time_us, set_next_trigger, set_ticks_per_sec, slice_over,
+    tick_cycles,
+};
+use crate::trap::{enable_timer_interrupt, trap_return, TrapContext};
+use alloc::vec;
 use alloc::vec::Vec;
+use core::sync::atomic::Ordering;
+use faults::FaultRecord;
 use lazy_static::*;
+pub use mq::MqError;
+use mq::{MessageQueues, Waiter};
+use pool::BufferPool;
+use processor::Processor;
+use profile::Profile;
+use riscv::register::scause::Exception;
+use sched::ReadyQueue;
 pub use switch::__switch;
-pub use task::{TaskControlBlock, TaskStatus};
+pub use task::{
+    CapSet, ExitReason, FaultKind, SwitchReason, SyscallFilter, TaskControlBlock, TaskCounters,
+    TaskLabel, TaskStatus,
+};
+use trace::TraceRing;
+pub use trace::TRACE_ENTRIES;
 
 pub use context::TaskContext;
 
@@ -33,39 +79,552 @@ pub use context::TaskContext;
 /// Most of `TaskManager` are hidden behind the field `inner`, to defer
 /// borrowing checks to runtime. You can see examples on how to use `inner` in
 /// existing functions on `TaskManager`.
+///
+/// There is no cached task count: the task list is the only record of how
+/// many tasks there are.
 pub struct TaskManager {
-    /// total number of tasks
-    num_app: usize,
     /// use inner value to get mutable access
     inner: UPSafeCell<TaskManagerInner>,
+    /// what every trap asks of the current task, see [`CurrentCache`]
+    current: UPSafeCell<Option<CurrentCache>>,
+}
+
+/// What every trap and trap return asks of the current task, copied at
+/// each dispatch so that the trap path reads it without borrowing `inner`,
+/// which a handler may hold or take itself: its token, trap context frame,
+/// what syncing the TLB with its space takes, its text-only policy and
+/// whether it was killed.
+///
+/// Token and frame do not change while the task runs: mmap and munmap edit
+/// the page table under the same root and ASID, and the modified flag the
+/// TLB sync takes is shared with the page table. The policy bit is written
+/// through by `sys_exec_text_only`, and a kill is only ever pending for a
+/// task that is not running. Only a dispatch makes another task current,
+/// and only an exit, a shutdown or a reboot gives the address space away;
+/// those clear the cache, so a stale read panics instead of writing into a
+/// freed frame. A spawned task is cached at its first dispatch like any
+/// other. The id is not cached: it stays valid after an exit, which the
+/// exit diagnostics rely on, so [`Processor`] keeps it.
+struct CurrentCache {
+    token: usize,
+    trap_cx_ppn: PhysPageNum,
+    tlb_sync: TlbSync,
+    exec_text_only: bool,
+    pending_exit: Option<ExitReason>,
+}
+
+impl CurrentCache {
+    /// What is cached of `task` when it is dispatched.
+    fn of(task: &TaskControlBlock) -> Self {
+        Self {
+            token: task.get_user_token(),
+            trap_cx_ppn: task.trap_cx_ppn,
+            tlb_sync: task.memory_set.tlb_sync(),
+            exec_text_only: task.map_policy.exec_text_only,
+            pending_exit: task.pending_exit,
+        }
+    }
 }
 
 /// The task manager inner in 'UPSafeCell'
 struct TaskManagerInner {
     /// task list
     tasks: Vec<TaskControlBlock>,
-    /// id of current `Running` task
-    current_task: usize,
+    /// which task runs; only the dispatcher changes it
+    processor: Processor,
+    /// tasks waiting for a dispatch, kept in step by `set_status`
+    ready: ReadyQueue,
+    /// frames in use right after the first boot pass built its tasks
+    boot_frames: usize,
+    /// number of tasks in `Running` state, kept by `set_status`
+    running: usize,
+    /// exited tasks whose kernel stack is still mapped
+    unreaped: Vec<usize>,
+    /// number of dispatches so far, never reset
+    epoch: usize,
+    /// number of `__switch` calls so far, never reset
+    switches: usize,
+    /// number of times a task became `Blocked` so far, never reset
+    blocks: usize,
+    /// trace rings for tasks that enable tracing
+    trace_pool: BufferPool<TraceRing>,
+    /// profiles for tasks sampled by the profiler
+    profile_pool: BufferPool<Profile>,
+    /// message queues, see `sys_mq_create`
+    queues: MessageQueues,
+    /// where [`TaskManager::idle_loop`] waits for the next dispatch, on the
+    /// boot stack
+    idle_task_cx: TaskContext,
+}
+
+impl TaskManagerInner {
+    /// The current task.
+    fn current(&self) -> &TaskControlBlock {
+        &self.tasks[self.processor.current()]
+    }
+
+    /// The current task, mutably.
+    fn current_mut(&mut self) -> &mut TaskControlBlock {
+        let id = self.processor.current();
+        &mut self.tasks[id]
+    }
+
+    /// Free the kernel stacks of exited tasks, all but the current one's:
+    /// a task that just exited still runs on its stack until the switch.
+    fn reap_kernel_stacks(&mut self) {
+        let current = self.processor.current();
+        if self.unreaped.iter().all(|&id| id == current) {
+            return;
+        }
+        let mut kernel_space = KERNEL_SPACE.lock();
+        let tasks = &mut self.tasks;
+        self.unreaped.retain(|&id| {
+            if id == current {
+                return true;
+            }
+            tasks[id].kernel_stack_peak = Some(stack_probe::kernel_stack_usage(id));
+            let (bottom, _) = kernel_stack_position(id);
+            assert!(
+                kernel_space.remove_kernel_area(bottom.into()),
+                "kernel stack of task {} is not mapped",
+                id
+            );
+            false
+        });
+    }
+
+    /// Make every sleeper whose wakeup time is not after `now_us` `Ready`,
+    /// the earliest wakeup time first and sleepers sharing one in the order
+    /// they went to sleep, so that they queue up in that order.
+    fn wake_sleepers(&mut self, now_us: usize) {
+        while let Some(id) = (0..self.tasks.len())
+            .filter(|&id| self.tasks[id].is_sleeping() && self.tasks[id].wakeup_time_us <= now_us)
+            .min_by_key(|&id| (self.tasks[id].wakeup_time_us, self.tasks[id].blocked_seq))
+        {
+            self.set_status(id, TaskStatus::Ready);
+        }
+    }
+
+    /// Make every task waiting for task `id` to exit `Ready`, in the order
+    /// they blocked in.
+    fn wake_waiters(&mut self, id: usize) {
+        while let Some(waiter) = (0..self.tasks.len())
+            .filter(|&waiter| self.tasks[waiter].waiting_on == Some(id))
+            .min_by_key(|&waiter| self.tasks[waiter].blocked_seq)
+        {
+            self.tasks[waiter].waiting_on = None;
+            self.set_status(waiter, TaskStatus::Ready);
+        }
+    }
+
+    /// Wake the parent of task `id`, which just exited, if it waits for any
+    /// child, and leave the children of `id` without a parent.
+    ///
+    /// The wait ends with the first exit, so children exiting one after
+    /// another before the parent runs wake it once.
+    fn notify_parent(&mut self, id: usize) {
+        if let Some(parent) = self.tasks[id].parent {
+            if self.tasks[parent].waiting_child {
+                self.tasks[parent].waiting_child = false;
+                self.set_status(parent, TaskStatus::Ready);
+            }
+        }
+        for task in self.tasks.iter_mut() {
+            if task.parent == Some(id) {
+                task.parent = None;
+            }
+        }
+    }
+
+    /// The slot task id `id` names, `None` if there is no such slot or the
+    /// id names an earlier run of it, which is counted in
+    /// `KSTAT.stale_task_ids`.
+    fn slot_of(&self, id: usize) -> Option<usize> {
+        let slot = id & ((1 << TASK_SLOT_BITS) - 1);
+        if self.tasks.get(slot)?.restarts != id >> TASK_SLOT_BITS {
+            kstat::inc(&KSTAT.stale_task_ids);
+            return None;
+        }
+        Some(slot)
+    }
+
+    /// Id of the run in slot `slot`, the slot itself until it is restarted.
+    fn id_of(&self, slot: usize) -> usize {
+        slot | self.tasks[slot].restarts << TASK_SLOT_BITS
+    }
+
+    /// Ids of the children of task `id`, exited ones that were not reaped
+    /// included.
+    fn children(&self, id: usize) -> impl Iterator<Item = usize> + '_ {
+        (0..self.tasks.len()).filter(move |&child| self.tasks[child].parent == Some(id))
+    }
+
+    /// Make `Blocked` task `task` `Ready`; a queue call it blocked in
+    /// returns `result`.
+    fn wake_blocked(&mut self, task: usize, result: isize) {
+        self.tasks[task].waiting_queue = None;
+        self.tasks[task].queue_result = result;
+        self.set_status(task, TaskStatus::Ready);
+    }
+
+    /// Block the current task on queue `id`, whose waiters it was just
+    /// added to.
+    fn block_current_on_queue(&mut self, id: usize) {
+        let current = self.processor.current();
+        self.tasks[current].waiting_queue = Some(id);
+        self.set_status(current, TaskStatus::Blocked);
+    }
+
+    /// Hand the oldest message of queue `id` to the receiver that has waited
+    /// longest, if one does; a receiver whose buffer cannot take it is woken
+    /// with -1 and the next one tried.
+    fn serve_receiver(&mut self, id: usize) {
+        loop {
+            let queue = self.queues.get_mut(id).expect("served queue is gone");
+            let receiver = match queue.receivers.pop_front() {
+                Some(receiver) => receiver,
+                None => return,
+            };
+            let token = self.tasks[receiver.task].get_user_token();
+            let received = queue.pop_into(token, receiver.buf);
+            self.wake_blocked(receiver.task, received.map_or(-1, |len| len as isize));
+            if received.is_some() {
+                return;
+            }
+        }
+    }
+
+    /// Queue the message of the sender that has waited longest on queue
+    /// `id`, if one does, in the slot just freed; a sender whose message
+    /// cannot be read is woken with -1 and the next one tried.
+    fn serve_sender(&mut self, id: usize) {
+        loop {
+            let queue = self.queues.get_mut(id).expect("served queue is gone");
+            let sender = match queue.senders.pop_front() {
+                Some(sender) => sender,
+                None => return,
+            };
+            let token = self.tasks[sender.task].get_user_token();
+            let sent = queue.push_from(token, sender.buf, sender.len);
+            self.wake_blocked(sender.task, if sent { 0 } else { -1 });
+            if sent {
+                return;
+            }
+        }
+    }
+
+    /// Have task `id`, which has not exited and is not current, exit for
+    /// `reason` once it runs again; a second kill keeps the first reason.
+    ///
+    /// It is not torn down here: it may be in the middle of a syscall and
+    /// hold kernel heap on its stack, which only running on gives back. A
+    /// `Blocked` task is taken off what it waits for and woken, its call
+    /// returning -1; the exit happens on its way back to user mode, see
+    /// [`crate::trap::trap_return`].
+    fn kill(&mut self, id: usize, reason: ExitReason) {
+        debug_assert!(id != self.processor.current() || self.running == 0);
+        let task = &mut self.tasks[id];
+        task.pending_exit.get_or_insert(reason);
+        if task.task_status != TaskStatus::Blocked {
+            return;
+        }
+        task.waiting_on = None;
+        task.waiting_child = false;
+        if let Some(queue) = task.waiting_queue {
+            self.queues.forget(queue, id);
+        }
+        self.wake_blocked(id, -1);
+    }
+
+    /// Find next task to run, take it out of the ready queue and return its
+    /// id, `None` if no task is `Ready`.
+    ///
+    /// With [`crate::config::SCHED_STRIDE`] it is the `Ready` task with the
+    /// smallest pass, otherwise the one that has been `Ready` longest; ties
+    /// between passes go to the longest waiting as well, so the order
+    /// depends on task ids and the tasks' own actions alone.
+    ///
+    /// Passes are compared by their wrapping difference, which is right as
+    /// long as no two of them are more than `isize::MAX` apart; strides are
+    /// at most `BIG_STRIDE / 2`, so they never are.
+    ///
+    /// Sleepers whose time has come are woken first, by wakeup time and
+    /// then in the order they went to sleep; being `Ready` since then, the
+    /// ones with equal passes are picked in that order too.
+    fn find_next_task(&mut self) -> Option<usize> {
+        self.wake_sleepers(get_time_us());
+        self.ready.pick_next()
+    }
+
+    /// Which tasks, by id, could still exit if the current one never ran
+    /// again: one that does not wait for another task to exit could, one
+    /// waiting for a task could if that one could, and one waiting for any
+    /// child could if one of its children could.
+    ///
+    /// Each pass over the tasks but the last finds one more that could, so
+    /// the passes are at most one more than the tasks.
+    fn could_exit_without_current(&self) -> Vec<bool> {
+        let current = self.processor.current();
+        let mut could = vec![false; self.tasks.len()];
+        loop {
+            let mut found = false;
+            for id in 0..self.tasks.len() {
+                let task = &self.tasks[id];
+                if could[id] || id == current || task.task_status == TaskStatus::Exited {
+                    continue;
+                }
+                could[id] = match task.waiting_on {
+                    Some(target) => could[target],
+                    None if task.waiting_child => self.children(id).any(|child| could[child]),
+                    None => true,
+                };
+                found |= could[id];
+            }
+            if !found {
+                return could;
+            }
+        }
+    }
+
+    /// Change the status of task `id`; every status change goes through here.
+    ///
+    /// Becoming `Running` is a dispatch: it starts a new epoch, advances
+    /// the task's pass by its stride and ends its wait as `Ready`. A task
+    /// leaving `Running` keeps the epoch it stopped in. The ready queue
+    /// holds exactly the tasks last set `Ready`.
+    fn set_status(&mut self, id: usize, status: TaskStatus) {
+        if self.tasks[id].task_status == TaskStatus::Running {
+            self.running -= 1;
+        }
+        if status == TaskStatus::Running {
+            self.running += 1;
+            self.epoch += 1;
+            let task = &mut self.tasks[id];
+            task.pass = task.pass.wrapping_add(task.stride);
+            task.remaining_ticks = TICKS_PER_SLICE;
+            let now = get_time();
+            task.longest_ready = task.longest_ready.max(now - task.ready_since);
+            task.first_dispatched.get_or_insert(now);
+        }
+        if status == TaskStatus::Ready {
+            self.tasks[id].ready_since = get_time();
+            self.ready.on_ready(id, self.tasks[id].pass);
+        } else {
+            self.ready.remove(id);
+        }
+        if status == TaskStatus::Blocked {
+            self.tasks[id].blocked_seq = self.blocks;
+            self.blocks += 1;
+        }
+        debug_mirror::record_transition(
+            id,
+            self.tasks[id].task_status,
+            status,
+            self.tasks[id].get_trap_cx().sepc,
+            self.epoch,
+        );
+        self.tasks[id].last_epoch = self.epoch;
+        self.tasks[id].task_status = status;
+    }
+
+    /// Give the trace ring and profile of task `id` back to their pools.
+    fn return_buffers(&mut self, id: usize) {
+        if let Some(ring) = self.tasks[id].trace.take() {
+            self.trace_pool.give_back(ring);
+        }
+        if let Some(profile) = self.tasks[id].profile.take() {
+            self.profile_pool.give_back(profile);
+        }
+        self.note_buffers_lent();
+    }
+
+    /// Publish pool occupancy to `KSTAT.buffers_lent`.
+    fn note_buffers_lent(&self) {
+        kstat::set(
+            &KSTAT.buffers_lent,
+            self.trace_pool.lent() + self.profile_pool.lent(),
+        );
+    }
+
+    /// Whether the current task, and only it, is `Running`, and `running`
+    /// agrees with the statuses.
+    fn running_consistent(&self) -> bool {
+        let running = self
+            .tasks
+            .iter()
+            .filter(|task| task.task_status == TaskStatus::Running)
+            .count();
+        running == 1 && self.running == 1 && self.current().task_status == TaskStatus::Running
+    }
+
+    /// Check that the current task, and only it, is `Running`.
+    ///
+    /// Called at the end of every batch of transitions; between marking the
+    /// old task and the new one, no task is `Running` for a moment.
+    fn check_running(&self) {
+        if self.running_consistent() {
+            return;
+        }
+        let current = self.processor.current();
+        kstat::inc(&KSTAT.running_violations);
+        let running: Vec<usize> = (0..self.tasks.len())
+            .filter(|&id| self.tasks[id].task_status == TaskStatus::Running)
+            .collect();
+        if cfg!(debug_assertions) {
+            panic!(
+                "[kernel] tasks {:?} are Running, current task is {}",
+                running, current
+            );
+        }
+        error!(
+            "[kernel] tasks {:?} are Running, current task is {}",
+            running, current
+        );
+    }
+
+    /// Bury every task that has not exited yet, the current one included,
+    /// for `reason` at `now`, returning their address spaces for the caller
+    /// to free once it has let go of the task manager.
+    fn bury_all(&mut self, reason: ExitReason, now: usize) -> Vec<MemorySet> {
+        let current = self.processor.current();
+        let mut memory_sets = Vec::new();
+        for id in 0..self.tasks.len() {
+            if self.tasks[id].task_status == TaskStatus::Exited {
+                continue;
+            }
+            // only the current task has run since its last stamp
+            if id != current {
+                self.tasks[id].counters.get_mut().time_stamp = now;
+            }
+            memory_sets.push(self.tasks[id].bury(reason, now));
+            self.set_status(id, TaskStatus::Exited);
+            self.return_buffers(id);
+        }
+        memory_sets
+    }
+}
+
+bitflags! {
+    /// what `check_dispatch` found wrong with a task, one per complaint
+    struct DispatchFaults: u8 {
+        /// the saved sp is outside the task's mapped kernel stack
+        const STACK_POINTER = 1 << 0;
+        /// the saved ra is not `trap_return`
+        const RETURN_ADDRESS = 1 << 1;
+        /// the user sepc is not in an executable page
+        const USER_PC = 1 << 2;
+    }
+}
+
+/// Complain about everything in `task` that would send its dispatch astray,
+/// returning what was found; empty if all checks passed.
+///
+/// The saved `ra` must lead to `trap_return` only for a `first` dispatch;
+/// later contexts return into the scheduler instead.
+fn check_dispatch(task: &TaskControlBlock, app_id: usize, first: bool) -> DispatchFaults {
+    let mut faults = DispatchFaults::empty();
+    let (bottom, top) = kernel_stack_position(app_id);
+    let sp = task.task_cx.sp();
+    // a zeroed context has sp 0, which only the bounds keep from wrapping
+    let sp_mapped = sp > bottom
+        && sp <= top
+        && KERNEL_SPACE
+            .lock()
+            .translate(VirtAddr::from(sp - 1).floor())
+            .map_or(false, |pte| pte.is_valid());
+    if !sp_mapped {
+        error!(
+            "[kernel] app {}: sp {:#x} outside its mapped kernel stack [{:#x}, {:#x})",
+            task.label(app_id),
+            sp,
+            bottom,
+            top
+        );
+        faults |= DispatchFaults::STACK_POINTER;
+    }
+    if first && task.task_cx.ra() != trap_return as usize {
+        error!(
+            "[kernel] app {}: ra {:#x} is not trap_return {:#x}",
+            task.label(app_id),
+            task.task_cx.ra(),
+            trap_return as usize
+        );
+        faults |= DispatchFaults::RETURN_ADDRESS;
+    }
+    let sepc = task.get_trap_cx().sepc;
+    let sepc_executable = task
+        .memory_set
+        .translate(VirtAddr::from(sepc).floor())
+        .map_or(false, |pte| pte.is_valid() && pte.executable());
+    if !sepc_executable {
+        error!(
+            "[kernel] app {}: user sepc {:#x} is not in an executable area",
+            task.label(app_id),
+            sepc
+        );
+        faults |= DispatchFaults::USER_PC;
+    }
+    faults
+}
+
+/// Print the hottest buckets of the profile of task `id`.
+fn print_profile(task: TaskLabel, profile: &Profile) {
+    let mut top = [ProfileBucket::default(); 8];
+    let count = profile.read(&mut top);
+    println!(
+        "[kernel] app {} profile, {} samples lost:",
+        task,
+        profile.overflow()
+    );
+    for bucket in &top[..count] {
+        println!("[kernel]   {:#x}: {}", bucket.base, bucket.samples);
+    }
+}
+
+/// Deepest use of the kernel stack of `task`, which has id `id`: scanned
+/// while the stack is mapped, recorded once it is freed.
+fn kernel_stack_usage_of(task: &TaskControlBlock, id: usize) -> usize {
+    task.kernel_stack_peak
+        .unwrap_or_else(|| stack_probe::kernel_stack_usage(id))
+}
+
+/// Build a fresh control block for every app.
+fn load_tasks() -> Vec<TaskControlBlock> {
+    debug_mirror::set_num_tasks(APP_CATALOG.len());
+    APP_CATALOG
+        .iter()
+        .enumerate()
+        .map(|(i, app)| TaskControlBlock::new(app, i))
+        .collect()
 }
 
 lazy_static! {
     /// a `TaskManager` instance through lazy_static!
     pub static ref TASK_MANAGER: TaskManager = {
         info!("init TASK_MANAGER");
-        let num_app = get_num_app();
-        info!("num_app = {}", num_app);
-        let mut tasks: Vec<TaskControlBlock> = Vec::new();
-        for i in 0..num_app {
-            tasks.push(TaskControlBlock::new(get_app_data(i), i));
-        }
+        assert!(!APP_CATALOG.is_empty(), "no valid app to run");
+        info!("num_app = {}", APP_CATALOG.len());
+        aslr_seed(get_time_us());
+        let tasks = load_tasks();
         TaskManager {
-            num_app,
             inner: unsafe {
                 UPSafeCell::new(TaskManagerInner {
                     tasks,
-                    current_task: 0,
+                    processor: Processor::new(),
+                    ready: ReadyQueue::new(APP_CATALOG.len()),
+                    boot_frames: frames_in_use(),
+                    running: 0,
+                    unreaped: Vec::new(),
+                    epoch: 0,
+                    switches: 0,
+                    blocks: 0,
+                    trace_pool: BufferPool::new(TRACE_POOL_RINGS),
+                    profile_pool: BufferPool::new(PROFILE_POOL_SIZE),
+                    queues: MessageQueues::new(),
+                    idle_task_cx: TaskContext::zero_init(),
                 })
             },
+            current: unsafe { UPSafeCell::new(None) },
         }
     };
 }
@@ -74,120 +633,1855 @@ impl TaskManager {
     /// Run the first task in task list.
     ///
     /// Generally, the first task in task list is an idle task (we call it zero process later).
-    /// But in ch4, we load apps statically, so the first task is a real app.
+    /// But in ch4, we load apps statically, so the first task is a real app;
+    /// the idle loop of the boot stack stands in for the idle task.
     fn run_first_task(&self) -> ! {
+        self.idle_loop()
+    }
+
+    /// Make task `id` current as a dispatch would, but without switching to
+    /// it, so that the kernel tests can make syscalls on its behalf; the task
+    /// current so far is left `Ready` unless it blocked. The tests end the
+    /// boot before any task runs.
+    #[cfg(feature = "board_test")]
+    fn enter_for_test(&self, id: usize) {
         let mut inner = self.inner.exclusive_access();
-        let next_task = &mut inner.tasks[0];
-        next_task.task_status = TaskStatus::Running;
-        let next_task_cx_ptr = &next_task.task_cx as *const TaskContext;
+        if inner.tasks[id].task_status == TaskStatus::Running {
+            return;
+        }
+        if inner.processor.has_current() {
+            let previous = inner.processor.current();
+            if inner.tasks[previous].task_status == TaskStatus::Running {
+                inner.set_status(previous, TaskStatus::Ready);
+            }
+        }
+        inner.tasks[id].counters.exclusive_access().time_stamp = get_time();
+        inner.set_status(id, TaskStatus::Running);
+        inner.processor.set_current(id);
+        *self.current.exclusive_access() = Some(CurrentCache::of(&inner.tasks[id]));
+    }
+
+    /// The scheduler loop of the boot stack, run whenever no task is
+    /// `Ready`.
+    ///
+    /// Tasks switch between each other directly; only a task that finds no
+    /// other to run switches here, and so does a soft reboot. Without a
+    /// dispatched task the first one is started. Otherwise the processor
+    /// waits for the timer, waking sleepers each tick, until one is
+    /// `Ready`. Nothing wakes the tasks waiting for others or for a child
+    /// but an exit, and a wait no exit could end is refused, so with no
+    /// sleeper left a task must be `Ready` or blocked on a message queue.
+    /// Those tasks have nobody left to serve them once nothing else runs or
+    /// sleeps, and are killed for [`ExitReason::Stranded`].
+    ///
+    /// Idle time is charged to no task: the task that switched here was
+    /// charged up to the switch, and the next one starts counting at its
+    /// dispatch.
+    fn idle_loop(&self) -> ! {
+        // wfi only wakes up for an interrupt enabled in `sie`; with
+        // `sstatus.SIE` clear it does not trap, the tick is handled here
+        enable_timer_interrupt();
+        loop {
+            let mut inner = self.inner.exclusive_access();
+            let idle_task_cx_ptr = &mut inner.idle_task_cx as *mut TaskContext;
+            if !inner.processor.has_current() {
+                // a broken initial context hangs silently in __switch, so say why first
+                if cfg!(feature = "dispatch-check") {
+                    check_dispatch(&inner.tasks[0], 0, true);
+                }
+                debug_mirror::set_boot_stage(BootStage::TasksRunning);
+                self.switch_to(inner, idle_task_cx_ptr, 0);
+                continue;
+            }
+            if let Some(next) = inner.find_next_task() {
+                if cfg!(all(feature = "dispatch-check", debug_assertions)) {
+                    check_dispatch(&inner.tasks[next], next, false);
+                }
+                self.switch_to(inner, idle_task_cx_ptr, next);
+                continue;
+            }
+            if !inner.tasks.iter().any(|task| task.is_sleeping()) {
+                let mut stranded = false;
+                for id in 0..inner.tasks.len() {
+                    if inner.tasks[id].waiting_queue.is_some() {
+                        inner.kill(id, ExitReason::Stranded);
+                        stranded = true;
+                    }
+                }
+                assert!(stranded, "idle with no task Ready and no sleeper left");
+                continue;
+            }
+            drop(inner);
+            unsafe {
+                core::arch::asm!("wfi");
+            }
+            if slice_over() {
+                set_next_trigger();
+            }
+        }
+    }
+
+    /// Dispatch task `next`, which was just taken out of the ready queue,
+    /// saving the context of whatever is running, a task or the idle loop,
+    /// to `current_task_cx_ptr`; returns once something switches back.
+    fn switch_to(
+        &self,
+        mut inner: UPRefMut<'_, TaskManagerInner>,
+        current_task_cx_ptr: *mut TaskContext,
+        next: usize,
+    ) {
+        let now = get_time();
+        // a task that never ran has last_epoch 0, its cold start is not measured
+        let mut counters = inner.tasks[next].counters.exclusive_access();
+        if kstat::ENABLED && inner.tasks[next].last_epoch != 0 {
+            counters.dispatched_at = Some(now);
+        }
+        counters.time_stamp = now;
+        drop(counters);
+        inner.set_status(next, TaskStatus::Running);
+        inner.processor.set_current(next);
+        *self.current.exclusive_access() = Some(CurrentCache::of(&inner.tasks[next]));
+        inner.check_running();
+        let next_task_cx_ptr = &inner.tasks[next].task_cx as *const TaskContext;
+        inner.switches += 1;
         drop(inner);
-        let mut _unused = TaskContext::zero_init();
         // before this, we should drop local variables that must be dropped manually
         unsafe {
-            __switch(&mut _unused as *mut _, next_task_cx_ptr);
+            __switch(current_task_cx_ptr, next_task_cx_ptr);
         }
-        panic!("unreachable in run_first_task!");
     }
 
-    /// Change the status of current `Running` task into `Ready`.
-    fn mark_current_suspended(&self) {
+    /// Charge a timer tick to the current task, returning whether that
+    /// ended its time slice.
+    fn tick_current(&self) -> bool {
         let mut inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        inner.tasks[current].task_status = TaskStatus::Ready;
+        let task = inner.current_mut();
+        task.remaining_ticks = task.remaining_ticks.saturating_sub(1);
+        task.remaining_ticks == 0
     }
 
-    /// Change the status of current `Running` task into `Exited`.
-    fn mark_current_exited(&self) {
+    /// Change the status of current `Running` task into `Ready`, counting
+    /// the switch by `reason`.
+    fn mark_current_suspended(&self, reason: SwitchReason) {
         let mut inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        inner.tasks[current].task_status = TaskStatus::Exited;
+        let current = inner.processor.current();
+        let task = inner.current_mut();
+        match reason {
+            SwitchReason::Yield => task.voluntary_switches += 1,
+            SwitchReason::Preempted => task.preempted_switches += 1,
+        }
+        inner.set_status(current, TaskStatus::Ready);
     }
 
-    /// Find next task to run and return task id.
+    /// Change the status of current `Running` task into `Exited`.
+    ///
+    /// Every way a task can end comes through here, so `reason` is always
+    /// recorded, and the tasks waiting for it are woken.
     ///
-    /// In this case, we only return the first `Ready` task in task list.
-    fn find_next_task(&self) -> Option<usize> {
+    /// Only a running task can exit, so it holds no wait to take it off: a
+    /// wait ends before its task runs again, either served, or by
+    /// `TaskManagerInner::kill`, which clears `waiting_on` and
+    /// `waiting_child` and takes the task off the queue it waits on. A
+    /// wake that finds the task exiting, or a new run in its slot, finds
+    /// nothing of it to wake.
+    fn mark_current_exited(&self, reason: ExitReason) {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.processor.current();
+        let task = inner.current();
+        debug_assert!(
+            task.waiting_on.is_none() && !task.waiting_child && task.waiting_queue.is_none(),
+            "task {} exits while waiting",
+            current
+        );
+        let memory_set = inner.current_mut().bury(reason, get_time());
+        self.current.exclusive_access().take();
+        inner.set_status(current, TaskStatus::Exited);
+        inner.wake_waiters(current);
+        inner.notify_parent(current);
+        inner.unreaped.push(current);
+        // the profile is printed now, nothing keeps it past the lease
+        if let Some(profile) = inner.current().profile.as_ref() {
+            print_profile(inner.current().label(current), profile);
+        }
+        inner.return_buffers(current);
+        drop(inner);
+        // freeing the frames only needs the frame allocator
+        drop(memory_set);
+    }
+
+    /// Exit code of task `id`, `None` while it has not exited; the run
+    /// before the last restart of the slot is still told, any earlier one
+    /// not.
+    fn exit_code(&self, id: usize) -> Option<i32> {
         let inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        (current + 1..current + self.num_app + 1)
-            .map(|id| id % self.num_app)
-            .find(|id| inner.tasks[*id].task_status == TaskStatus::Ready)
+        let task = inner.tasks.get(id & ((1 << TASK_SLOT_BITS) - 1))?;
+        match task.restarts.checked_sub(id >> TASK_SLOT_BITS)? {
+            0 => Some(task.obituary?.exit_code),
+            1 => task.previous_exit_code,
+            _ => None,
+        }
     }
 
-    /// Get the current 'Running' task's token.
-    fn get_current_token(&self) -> usize {
+    /// The slot task id `id` names, see `TaskManagerInner::slot_of`.
+    fn slot_of(&self, id: usize) -> Option<usize> {
+        self.inner.exclusive_access().slot_of(id)
+    }
+
+    /// Print one line per app with its exit code, run time and syscalls,
+    /// in every build: this is what tells the run apart at a glance.
+    fn print_exit_summary(&self) {
         let inner = self.inner.exclusive_access();
-        inner.tasks[inner.current_task].get_user_token()
+        let mut ran = 0;
+        let (mut voluntary, mut preempted) = (0, 0);
+        for (i, task) in inner.tasks.iter().enumerate() {
+            voluntary += task.voluntary_switches;
+            preempted += task.preempted_switches;
+            let name = task.label(i);
+            if let Some(obituary) = task.obituary {
+                println!(
+                    "[kernel] app {}: exit code {}, ran {} ms, {} syscalls, peak {} pages",
+                    name,
+                    obituary.exit_code,
+                    cycles_to_ms(obituary.user_time + obituary.kernel_time),
+                    obituary.syscalls,
+                    task.peak_pages
+                );
+                ran += obituary.user_time + obituary.kernel_time;
+                if task.unknown_syscalls > 0 {
+                    println!(
+                        "[kernel] app {}: {} unknown syscalls refused",
+                        name, task.unknown_syscalls
+                    );
+                }
+                if task.restarts > 0 {
+                    println!("[kernel] app {}: restarted {} times", name, task.restarts);
+                }
+            } else {
+                println!("[kernel] app {}: not exited", name);
+            }
+        }
+        // one hart: the rest is boot, idle and the switches between tasks
+        println!(
+            "[kernel] apps ran {} ms of {} ms since boot",
+            cycles_to_ms(ran),
+            cycles_to_ms(get_time())
+        );
+        println!(
+            "[kernel] {} task switches: {} yields, {} preemptions",
+            inner.switches, voluntary, preempted
+        );
     }
 
-    #[allow(clippy::mut_from_ref)]
-    /// Get the current 'Running' task's trap contexts.
-    fn get_current_trap_cx(&self) -> &mut TrapContext {
+    /// Print the obituary of every task; nothing reaps them before shutdown.
+    fn print_obituaries(&self) {
         let inner = self.inner.exclusive_access();
-        inner.tasks[inner.current_task].get_trap_cx()
+        for (i, task) in inner.tasks.iter().enumerate() {
+            let name = task.label(i);
+            let latency = task.counters.exclusive_access().dispatch_latency;
+            if let Some((count, min, median, max)) = latency.summary() {
+                println!(
+                    "[kernel] app {}: {} dispatches to trap, min/median/max {}/{}/{} cycles",
+                    name, count, min, median, max
+                );
+            }
+            println!(
+                "[kernel] app {}: waited Ready for at most {} cycles",
+                name, task.longest_ready
+            );
+            println!(
+                "[kernel] app {}: kernel stack high-water mark {} of {} bytes",
+                name,
+                kernel_stack_usage_of(task, i),
+                KERNEL_STACK_SIZE
+            );
+            if let Some(obituary) = task.obituary {
+                println!("[kernel] app {}: {:?}", name, obituary);
+            } else {
+                println!("[kernel] app {}: not exited", name);
+            }
+        }
+        // every byte an app printed went through `count_output` and the console
+        let counted: usize = inner.tasks.iter().map(|task| task.output_bytes).sum();
+        let printed = KSTAT.app_output_bytes.load(Ordering::Relaxed);
+        println!("[kernel] apps wrote {} bytes", counted);
+        if counted != printed {
+            error!(
+                "[kernel] apps wrote {} bytes, but the console printed {}",
+                counted, printed
+            );
+        }
     }
 
-    /// Switch current `Running` task to the task we have found,
-    /// or there is no `Ready` task and we can exit with all applications completed
-    fn run_next_task(&self) {
-        if let Some(next) = self.find_next_task() {
-            let mut inner = self.inner.exclusive_access();
-            let current = inner.current_task;
-            inner.tasks[next].task_status = TaskStatus::Running;
-            inner.current_task = next;
-            let current_task_cx_ptr = &mut inner.tasks[current].task_cx as *mut TaskContext;
-            let next_task_cx_ptr = &inner.tasks[next].task_cx as *const TaskContext;
-            drop(inner);
-            // before this, we should drop local variables that must be dropped manually
-            unsafe {
-                __switch(current_task_cx_ptr, next_task_cx_ptr);
+    /// Deepest use of task `id`'s kernel stack in bytes, 0 if there is no
+    /// such task.
+    fn kernel_stack_usage(&self, id: usize) -> usize {
+        let inner = self.inner.shared_access();
+        inner
+            .tasks
+            .get(id)
+            .map_or(0, |task| kernel_stack_usage_of(task, id))
+    }
+
+    /// Warn once per task that the current task has used more than
+    /// `KERNEL_STACK_WARN_PERCENT` of its kernel stack; scans the stack.
+    fn check_kernel_stack(&self) {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.processor.current();
+        if inner.current().kernel_stack_warned {
+            return;
+        }
+        let used = stack_probe::kernel_stack_usage(current);
+        if used * 100 > KERNEL_STACK_SIZE * KERNEL_STACK_WARN_PERCENT {
+            let task = inner.current_mut();
+            task.kernel_stack_warned = true;
+            warn!(
+                "[kernel] app {} used {} of {} bytes of its kernel stack",
+                task.label(current),
+                used,
+                KERNEL_STACK_SIZE
+            );
+        }
+    }
+
+    /// Put the current task to sleep until `wakeup_time_us`.
+    fn block_current_until(&self, wakeup_time_us: usize) {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.processor.current();
+        inner.current_mut().wakeup_time_us = wakeup_time_us;
+        inner.set_status(current, TaskStatus::Blocked);
+    }
+
+    /// Block the current task until task `target`, which has not exited,
+    /// does. Returns false, leaving the task `Running`, if `target` could
+    /// only exit after the current task: nothing would ever wake either.
+    fn block_current_on(&self, target: usize) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.processor.current();
+        debug_assert!(target != current && inner.tasks[target].obituary.is_none());
+        if !inner.could_exit_without_current()[target] {
+            return false;
+        }
+        inner.current_mut().waiting_on = Some(target);
+        inner.set_status(current, TaskStatus::Blocked);
+        true
+    }
+
+    /// Block the current task until one of its children, none of which has
+    /// exited, does. Returns false, leaving the task `Running`, if each of
+    /// them could only exit after the current task.
+    fn block_current_on_child(&self) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.processor.current();
+        let could = inner.could_exit_without_current();
+        if !inner.children(current).any(|child| could[child]) {
+            return false;
+        }
+        inner.current_mut().waiting_child = true;
+        inner.set_status(current, TaskStatus::Blocked);
+        true
+    }
+
+    /// Whether the current task has child `pid`, or any child if `pid` is
+    /// `None`.
+    fn has_child(&self, pid: Option<usize>) -> bool {
+        let inner = self.inner.exclusive_access();
+        let current = inner.processor.current();
+        let mut children = inner.children(current);
+        match pid {
+            Some(pid) => children.any(|child| child == pid),
+            None => children.next().is_some(),
+        }
+    }
+
+    /// Reap child `pid` of the current task if it has exited, or if `pid`
+    /// is `None` the child that exited first, returning its id and exit
+    /// code; it is no child of the task any more, and its times are added
+    /// to the task's `children_times`.
+    fn reap_child(&self, pid: Option<usize>) -> Option<(usize, i32)> {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.processor.current();
+        let (child, obituary) = inner
+            .children(current)
+            .filter(|&child| pid.map_or(true, |pid| child == pid))
+            .filter_map(|child| Some((child, inner.tasks[child].obituary?)))
+            .min_by_key(|(_, obituary)| obituary.exited_at)?;
+        inner.tasks[child].parent = None;
+        let (user, kernel) = inner.tasks[child].children_times;
+        let times = &mut inner.current_mut().children_times;
+        times.0 += obituary.user_time + user;
+        times.1 += obituary.kernel_time + kernel;
+        Some((inner.id_of(child), obituary.exit_code))
+    }
+
+    /// Create a message queue charged to the current task, see
+    /// `sys_mq_create`.
+    fn mq_create(&self, capacity: usize, msg_size: usize) -> Result<usize, MqError> {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.processor.current();
+        inner.queues.create(current, capacity, msg_size)
+    }
+
+    /// Send the `len` bytes at `buf`, which the current task may read, to
+    /// queue `id`, returning what `sys_mq_send` does; `None` if the queue is
+    /// full and the task was blocked on it.
+    fn mq_send(&self, id: usize, buf: usize, len: usize) -> Option<isize> {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.processor.current();
+        let token = inner.current().get_user_token();
+        let queue = match inner.queues.get_mut(id) {
+            Some(queue) if len <= queue.msg_size => queue,
+            _ => return Some(MqError::Invalid.errno()),
+        };
+        if queue.is_full() {
+            queue.senders.push_back(Waiter {
+                task: current,
+                buf,
+                len,
+            });
+            inner.block_current_on_queue(id);
+            return None;
+        }
+        if !queue.push_from(token, buf, len) {
+            return Some(MqError::Invalid.errno());
+        }
+        // a receiver only waits while the queue is empty
+        inner.serve_receiver(id);
+        Some(0)
+    }
+
+    /// Receive the oldest message of queue `id` into `buf`, where the
+    /// current task may write `cap` bytes, returning what `sys_mq_recv`
+    /// does; `None` if the queue is empty and the task was blocked on it.
+    fn mq_recv(&self, id: usize, buf: usize, cap: usize) -> Option<isize> {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.processor.current();
+        let token = inner.current().get_user_token();
+        let queue = match inner.queues.get_mut(id) {
+            Some(queue) if cap >= queue.msg_size => queue,
+            _ => return Some(MqError::Invalid.errno()),
+        };
+        if queue.is_empty() {
+            queue.receivers.push_back(Waiter {
+                task: current,
+                buf,
+                len: cap,
+            });
+            inner.block_current_on_queue(id);
+            return None;
+        }
+        let len = match queue.pop_into(token, buf) {
+            Some(len) => len,
+            None => return Some(MqError::Invalid.errno()),
+        };
+        // a sender only waits while the queue is full
+        inner.serve_sender(id);
+        Some(len as isize)
+    }
+
+    /// What the queue call the current task was blocked in returns, now
+    /// that it was woken.
+    fn queue_result(&self) -> isize {
+        self.inner.exclusive_access().current().queue_result
+    }
+
+    /// Drop message queue `id`, see `sys_mq_destroy`.
+    fn mq_destroy(&self, id: usize) -> Result<(), MqError> {
+        self.inner.exclusive_access().queues.destroy(id)
+    }
+
+    /// Kill task `id` for the current task, see `sys_kill`; false if there
+    /// is no such task, it is the current one or it has exited.
+    fn kill(&self, id: usize) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.processor.current();
+        let slot = match inner.slot_of(id) {
+            Some(slot) if slot != current => slot,
+            _ => return false,
+        };
+        if inner.tasks[slot].task_status == TaskStatus::Exited {
+            return false;
+        }
+        inner.kill(slot, ExitReason::Killed(current));
+        true
+    }
+
+    /// Why the current task has to exit before it returns to user mode,
+    /// if it was killed.
+    fn pending_exit(&self) -> Option<ExitReason> {
+        self.with_cached_current(|current| current.pending_exit)
+    }
+
+    /// Number of tasks there are, exited or not.
+    fn task_count(&self) -> usize {
+        self.inner.exclusive_access().tasks.len()
+    }
+
+    /// Warn about every task that has waited `Ready` for more than
+    /// [`STARVATION_SLICES`] time slices, at most once per such period.
+    ///
+    /// Only `Ready` tasks are looked at, so a sleeping task never trips
+    /// this.
+    fn check_starvation(&self) {
+        if SCHED_STARVATION_EXPECTED {
+            return;
+        }
+        let mut inner = self.inner.exclusive_access();
+        let now = get_time();
+        let limit = STARVATION_SLICES * TICKS_PER_SLICE * tick_cycles();
+        let running = inner.current().label(inner.processor.current());
+        for (id, task) in inner.tasks.iter_mut().enumerate() {
+            if task.task_status != TaskStatus::Ready {
+                continue;
             }
-            // go back to user mode
-        } else {
-            panic!("All applications completed!");
+            let waited = now - task.ready_since;
+            task.longest_ready = task.longest_ready.max(waited);
+            if waited <= limit || now - task.starvation_warned_at <= limit {
+                continue;
+            }
+            task.starvation_warned_at = now;
+            kstat::inc(&KSTAT.starvation_warnings);
+            warn!(
+                "[kernel] app {} starving: Ready for {} cycles, priority {}, pass {:#x}; app {} ran meanwhile",
+                task.label(id),
+                waited,
+                task.priority,
+                task.pass,
+                running
+            );
         }
     }
-}
 
-/// Run the first task in task list.
-pub fn run_first_task() {
-    TASK_MANAGER.run_first_task();
-}
+    /// Count tasks in `Ready` state.
+    fn ready_task_count(&self) -> usize {
+        let inner = self.inner.exclusive_access();
+        inner
+            .tasks
+            .iter()
+            .filter(|task| task.task_status == TaskStatus::Ready)
+            .count()
+    }
 
-/// Switch current `Running` task to the task we have found,
-/// or there is no `Ready` task and we can exit with all applications completed
-fn run_next_task() {
-    TASK_MANAGER.run_next_task();
-}
+    /// Run `f` on the current task as of its dispatch, without borrowing
+    /// `inner`.
+    fn with_cached_current<R>(&self, f: impl FnOnce(&CurrentCache) -> R) -> R {
+        let current = self.current.shared_access();
+        f(current
+            .as_ref()
+            .expect("[kernel] no task running to trap from"))
+    }
 
-/// Change the status of current `Running` task into `Ready`.
-fn mark_current_suspended() {
-    TASK_MANAGER.mark_current_suspended();
-}
+    /// Get the current 'Running' task's token.
+    fn get_current_token(&self) -> usize {
+        self.with_cached_current(|current| current.token)
+    }
 
-/// Change the status of current `Running` task into `Exited`.
-fn mark_current_exited() {
-    TASK_MANAGER.mark_current_exited();
-}
+    /// Get the current task's token, with nothing stale of its space left
+    /// in the TLB.
+    fn sync_current_tlb(&self) -> usize {
+        self.with_cached_current(|current| {
+            current.tlb_sync.sync();
+            current.token
+        })
+    }
 
-/// Suspend the current 'Running' task and run the next task in task list.
-pub fn suspend_current_and_run_next() {
-    mark_current_suspended();
-    run_next_task();
-}
+    /// Where the current task executes `pc` from, if its policy forbids it.
+    fn exec_violation(&self, pc: usize) -> Option<Option<AreaKind>> {
+        // the policy is off for nearly every task, which then needs no look
+        // at its areas
+        if !self.with_cached_current(|current| current.exec_text_only) {
+            return None;
+        }
+        let inner = self.inner.exclusive_access();
+        match inner.current().memory_set.area_kind(VirtAddr::from(pc)) {
+            Some(AreaKind::ElfText) => None,
+            kind => Some(kind),
+        }
+    }
 
-/// Exit the current 'Running' task and run the next task in task list.
-pub fn exit_current_and_run_next() {
-    mark_current_exited();
-    run_next_task();
-}
+    /// Print the current task's areas, see `MemorySet::debug_print`.
+    fn dump_current_address_space(&self) {
+        let inner = self.inner.exclusive_access();
+        println!(
+            "[kernel] address space of task {}:",
+            inner.current().label(inner.processor.current())
+        );
+        let aslr = inner.current().aslr;
+        println!(
+            "[kernel] ASLR: stack +{} pages, mmap search +{} pages",
+            aslr.stack_pages, aslr.mmap_pages
+        );
+        inner.current().memory_set.debug_print();
+    }
 
-/// Get the current 'Running' task's token.
-pub fn current_user_token() -> usize {
-    TASK_MANAGER.get_current_token()
-}
+    /// Page table entry of `vpn` in the current task's page table.
+    #[cfg(any(feature = "debug-syscalls", feature = "board_test"))]
+    fn translate_current(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
+        let inner = self.inner.exclusive_access();
+        inner.current().memory_set.translate(vpn)
+    }
 
-/// Get the current 'Running' task's trap contexts.
-pub fn current_trap_cx() -> &'static mut TrapContext {
-    TASK_MANAGER.get_current_trap_cx()
+    /// Whether `va` is in the guard page below the current task's user stack.
+    fn stack_guard_hit(&self, va: usize) -> bool {
+        let inner = self.inner.exclusive_access();
+        inner.current().memory_set.area_kind(VirtAddr::from(va)) == Some(AreaKind::StackGuard)
+    }
+
+    /// Whether `va` lies in a reserved range of the current task that it
+    /// has not committed yet.
+    fn reservation_hit(&self, va: usize) -> bool {
+        let inner = self.inner.exclusive_access();
+        inner.current().memory_set.reserved_at(VirtAddr::from(va))
+    }
+
+    /// Whether the current task holds all of `caps`; a denial is logged.
+    fn current_has_caps(&self, caps: CapSet) -> bool {
+        let inner = self.inner.exclusive_access();
+        let current = inner.processor.current();
+        let held = inner.current().caps;
+        if !held.contains(caps) {
+            warn!(
+                "[kernel] app {} denied, lacks capabilities {:?}",
+                inner.current().label(current),
+                caps - held
+            );
+            return false;
+        }
+        true
+    }
+
+    /// Permanently remove `caps` from the current task, returning what is left.
+    fn drop_current_caps(&self, caps: CapSet) -> CapSet {
+        let mut inner = self.inner.exclusive_access();
+        let task = inner.current_mut();
+        task.caps.remove(caps);
+        task.caps
+    }
+
+    /// Narrow the current task's syscall filter to `filter`.
+    fn narrow_syscall_filter(&self, filter: &SyscallFilter) {
+        let mut inner = self.inner.exclusive_access();
+        inner.current_mut().syscall_filter.narrow(filter);
+    }
+
+    /// Whether the current task's syscall filter allows `syscall_id`.
+    fn syscall_allowed(&self, syscall_id: usize) -> bool {
+        self.current_task_with(|task| task.syscall_filter.allows(syscall_id))
+    }
+
+    /// Label, status and trap context of the current task, `None` while
+    /// the task manager is borrowed.
+    fn try_current(&self) -> Option<(TaskLabel, TaskStatus, &'static TrapContext)> {
+        let inner = self.inner.try_exclusive_access()?;
+        let task = inner.current();
+        let label = task.label(inner.processor.current());
+        Some((label, task.task_status, task.get_trap_cx()))
+    }
+
+    /// How diagnostics name the current 'Running' task.
+    fn current_task_label(&self) -> TaskLabel {
+        let inner = self.inner.exclusive_access();
+        inner.current().label(inner.processor.current())
+    }
+
+    /// Id of the current 'Running' task.
+    fn current_task_id(&self) -> usize {
+        self.inner.shared_access().processor.current()
+    }
+
+    /// Start (with an empty ring) or stop tracing the current task's syscalls;
+    /// `false` if no ring is left in the pool.
+    fn set_trace(&self, enable: bool) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        if !enable {
+            if let Some(ring) = inner.current_mut().trace.take() {
+                inner.trace_pool.give_back(ring);
+            }
+        } else if inner.current().trace.is_none() {
+            match inner.trace_pool.lend(TraceRing::starting_now) {
+                Some(ring) => inner.current_mut().trace = Some(ring),
+                None => {
+                    kstat::inc(&KSTAT.buffer_pool_misses);
+                    return false;
+                }
+            }
+        }
+        inner.note_buffers_lent();
+        true
+    }
+
+    /// Start or stop logging the current task's syscalls.
+    fn set_strace(&self, enable: bool) {
+        self.inner.exclusive_access().current_mut().strace = enable;
+    }
+
+    /// Whether the current task's syscalls are logged.
+    fn strace(&self) -> bool {
+        self.current_task_with(|task| task.strace)
+    }
+
+    /// Current scheduler epoch.
+    fn sched_epoch(&self) -> usize {
+        self.inner.exclusive_access().epoch
+    }
+
+    /// Epoch task `id` last ran in: the current one while it is `Running`.
+    fn epoch_of(&self, id: usize) -> Option<usize> {
+        let inner = self.inner.exclusive_access();
+        let task = inner.tasks.get(id)?;
+        if task.task_status == TaskStatus::Running {
+            Some(inner.epoch)
+        } else {
+            Some(task.last_epoch)
+        }
+    }
+
+    /// Run `f` on the current task, borrowing the task manager only shared,
+    /// which is enough to update its [`TaskCounters`].
+    fn current_task_with<R>(&self, f: impl FnOnce(&TaskControlBlock) -> R) -> R {
+        let inner = self.inner.shared_access();
+        f(inner.current())
+    }
+
+    /// Count a syscall of the current task.
+    fn count_syscall(&self, syscall_id: usize) {
+        self.current_task_with(|task| {
+            let mut counters = task.counters.exclusive_access();
+            counters.syscall_count += 1;
+            counters.syscall_counts.inc(syscall_id);
+        });
+    }
+
+    /// Refuse a syscall id the kernel does not implement, see
+    /// `TaskControlBlock::reject_unknown_syscall`.
+    fn reject_unknown_syscall(&self, syscall_id: usize) {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.processor.current();
+        inner
+            .current_mut()
+            .reject_unknown_syscall(syscall_id, current);
+    }
+
+    /// Status, syscall counts and run time of the current task, taken
+    /// together.
+    fn current_task_info(&self) -> TaskInfo {
+        let inner = self.inner.shared_access();
+        let task = inner.current();
+        let since = task
+            .first_dispatched
+            .expect("current task never dispatched");
+        let now = get_time();
+        let counters = task.counters.exclusive_access();
+        let (user_time, kernel_time) = counters.cpu_times(now);
+        TaskInfo {
+            status: task.task_status,
+            syscall_times: counters.syscall_counts.to_dense(),
+            time: cycles_to_ms(now.saturating_sub(since)),
+            user_time: cycles_to_ms(user_time),
+            kernel_time: cycles_to_ms(kernel_time),
+            voluntary_switches: task.voluntary_switches,
+            preempted_switches: task.preempted_switches,
+            name: task.label(inner.processor.current()).to_c_name(),
+            page_faults: task.page_faults,
+        }
+    }
+
+    /// Populate the lazy pages of a buffer of the current task.
+    fn populate_lazy(&self, ptr: usize, len: usize) {
+        let mut inner = self.inner.exclusive_access();
+        let task = inner.current_mut();
+        let populated = task.memory_set.populate_lazy(ptr, len);
+        task.charge_pages(populated as isize);
+    }
+
+    /// Add `bytes` written to stdout to the current task's count.
+    fn count_output(&self, bytes: usize) {
+        let mut inner = self.inner.exclusive_access();
+        inner.current_mut().output_bytes += bytes;
+    }
+
+    /// Append a finished syscall to the current task's trace, if it has one.
+    fn trace_syscall(&self, syscall_id: usize, arg0: usize, ret: isize) {
+        let mut inner = self.inner.exclusive_access();
+        if let Some(ring) = inner.current_mut().trace.as_mut() {
+            ring.record(syscall_id, arg0, ret);
+        }
+    }
+
+    /// Drain the trace of task `id` into `out`, `None` if it is not traced.
+    fn drain_trace(&self, id: usize, out: &mut [TraceEntry]) -> Option<usize> {
+        let mut inner = self.inner.exclusive_access();
+        let ring = inner.tasks.get_mut(id)?.trace.as_mut()?;
+        Some(ring.drain(out))
+    }
+
+    /// Count a profiler sample at user `pc` for the current task, which gets
+    /// a profile from the pool at its first sample; while none is left the
+    /// sample is dropped and the next one asks again.
+    fn profile_sample(&self, pc: usize) {
+        let mut inner = self.inner.exclusive_access();
+        if inner.current().profile.is_none() {
+            match inner.profile_pool.lend(Profile::empty) {
+                Some(profile) => inner.current_mut().profile = Some(profile),
+                None => {
+                    kstat::inc(&KSTAT.buffer_pool_misses);
+                    return;
+                }
+            }
+            inner.note_buffers_lent();
+        }
+        if let Some(profile) = inner.current_mut().profile.as_mut() {
+            profile.sample(pc);
+        }
+    }
+
+    /// Read the profile of task `id` into `out`, `None` if it has none.
+    fn read_profile(&self, id: usize, out: &mut [ProfileBucket]) -> Option<usize> {
+        let inner = self.inner.exclusive_access();
+        let profile = inner.tasks.get(id)?.profile.as_ref()?;
+        Some(profile.read(out))
+    }
+
+    /// Check the current task's address space, see `MemorySet::self_check`.
+    fn check_current_memory_set(&self) -> bool {
+        let inner = self.inner.exclusive_access();
+        inner.current().memory_set.self_check()
+    }
+
+    /// Check the scheduler's view of task statuses, logging a mismatch.
+    fn check_scheduler(&self) -> bool {
+        let inner = self.inner.exclusive_access();
+        let ok = inner.running_consistent();
+        if !ok {
+            error!(
+                "[kernel] selfcheck: {} tasks counted Running, current task {} is {:?}",
+                inner.running,
+                inner.current().label(inner.processor.current()),
+                inner.current().task_status
+            );
+        }
+        ok
+    }
+
+    /// Fingerprint of the current task's address space.
+    fn current_fingerprint(&self) -> u64 {
+        let inner = self.inner.exclusive_access();
+        inner.current().memory_set.fingerprint()
+    }
+
+    /// Summary of the current task's address space.
+    fn current_mem_info(&self) -> MemInfo {
+        let inner = self.inner.exclusive_access();
+        let memory_set = &inner.current().memory_set;
+        let (gap_start, gap_len) = memory_set
+            .largest_free_gap()
+            .map_or((0, 0), |(start, len)| (start.0, len));
+        let frames = frame_stats();
+        MemInfo {
+            user_pages: memory_set.user_pages() as u64,
+            peak_pages: memory_set.peak_user_pages() as u64,
+            gap_start: gap_start as u64,
+            gap_len: gap_len as u64,
+            frames_used: frames.in_use as u64,
+            frames_free: (frames.recycled + frames.untouched) as u64,
+            frames_total: frames.total as u64,
+        }
+    }
+
+    /// Page bitmap of the current task, see `MemorySet::page_bitmap`.
+    fn page_bitmap(
+        &self,
+        start: usize,
+        len: usize,
+        take_accessed: bool,
+    ) -> Result<(Vec<u8>, usize), MapError> {
+        let mut inner = self.inner.exclusive_access();
+        inner
+            .current_mut()
+            .memory_set
+            .page_bitmap(start, len, take_accessed)
+    }
+
+    /// Where the current task could mmap `len` bytes aligned to `align`.
+    fn find_free(&self, len: usize, align: usize) -> Result<VirtAddr, MapError> {
+        let inner = self.inner.exclusive_access();
+        let task = inner.current();
+        task.memory_set
+            .find_free_aligned(len, align, &task.map_policy)
+    }
+
+    /// Set the current task's stride scheduling priority.
+    fn set_priority(&self, priority: usize) {
+        let mut inner = self.inner.exclusive_access();
+        inner.current_mut().set_priority(priority);
+    }
+
+    /// Turn the current task's text-only execution policy on or off.
+    fn set_exec_text_only(&self, enable: bool) {
+        let mut inner = self.inner.exclusive_access();
+        inner.current_mut().map_policy.exec_text_only = enable;
+        if let Some(current) = self.current.exclusive_access().as_mut() {
+            current.exec_text_only = enable;
+        }
+    }
+
+    /// Translate a buffer of the current 'Running' task through its translate
+    /// cache, see [`MemorySet::user_byte_buffer`], once its untouched lazy
+    /// pages have their frames and are charged to it.
+    fn current_user_buffer(
+        &self,
+        ptr: *const u8,
+        len: usize,
+    ) -> Result<Vec<&'static mut [u8]>, UserPtrError> {
+        let mut inner = self.inner.exclusive_access();
+        let task = inner.current_mut();
+        let populated = task.memory_set.populate_lazy(ptr as usize, len);
+        task.charge_pages(populated as isize);
+        task.memory_set.user_byte_buffer(ptr, len)
+    }
+
+    #[allow(clippy::mut_from_ref)]
+    /// Get the current 'Running' task's trap contexts.
+    fn get_current_trap_cx(&self) -> &mut TrapContext {
+        self.with_cached_current(|current| current.trap_cx_ppn)
+            .get_mut()
+    }
+
+    /// Charge the time since the last stamp to the current task's user time,
+    /// called when it traps into the kernel.
+    fn user_time_end(&self) {
+        self.current_task_with(|task| task.counters.exclusive_access().charge_user(get_time()));
+    }
+
+    /// Charge the time since the last stamp to the current task's kernel time,
+    /// called right before it returns to user mode.
+    fn user_time_start(&self) {
+        self.current_task_with(|task| task.counters.exclusive_access().charge_kernel(get_time()));
+    }
+
+    /// Let the current task's memory set resolve a page fault at `va`,
+    /// raised by `cause` at `sepc`, and record it in the task's fault
+    /// history.
+    ///
+    /// This runs between `user_time_end` and `user_time_start`, so the time
+    /// spent is kernel time already; it is additionally summed up in
+    /// `fault_service_time`.
+    fn resolve_page_fault(&self, cause: Exception, va: usize, sepc: usize) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let task = inner.current_mut();
+        let start = get_time();
+        let resolved = task.memory_set.handle_page_fault(VirtAddr::from(va));
+        if resolved {
+            task.charge_pages(1);
+        }
+        task.counters.get_mut().fault_service_time += get_time() - start;
+        Self::record_fault(task, cause, va, sepc, resolved);
+        resolved
+    }
+
+    /// Record a page fault the current task is killed for without the
+    /// memory set being asked, see `resolve_page_fault`.
+    fn record_fatal_fault(&self, cause: Exception, va: usize, sepc: usize) {
+        let mut inner = self.inner.exclusive_access();
+        Self::record_fault(inner.current_mut(), cause, va, sepc, false);
+    }
+
+    fn record_fault(
+        task: &mut TaskControlBlock,
+        cause: Exception,
+        stval: usize,
+        sepc: usize,
+        resolved: bool,
+    ) {
+        task.page_faults += 1;
+        task.faults.record(FaultRecord {
+            stval,
+            sepc,
+            cause,
+            time_us: get_time_us(),
+            resolved,
+        });
+    }
+
+    /// Print the fault history of task `id`, the oldest fault first;
+    /// nothing for a task that took none.
+    fn dump_fault_history(&self, id: usize) {
+        let inner = self.inner.shared_access();
+        let task = match inner.tasks.get(id) {
+            Some(task) => task,
+            None => return,
+        };
+        let mut faults = task.faults.iter().peekable();
+        if faults.peek().is_none() {
+            return;
+        }
+        println!(
+            "[kernel] last page faults of task {}, {} in all:",
+            task.label(id),
+            task.page_faults
+        );
+        for fault in faults {
+            println!(
+                "[kernel]   {} us: {:?} at {:#x}, sepc = {:#x}, {}",
+                fault.time_us,
+                fault.cause,
+                fault.stval,
+                fault.sepc,
+                if fault.resolved { "resolved" } else { "fatal" }
+            );
+        }
+    }
+
+    /// Get the current task's (user, kernel) time in cycles, see
+    /// `TaskCounters::cpu_times`.
+    fn get_current_times(&self) -> (usize, usize) {
+        self.current_task_with(|task| task.counters.exclusive_access().cpu_times(get_time()))
+    }
+
+    /// Get the (user, kernel) time in cycles of the children the current
+    /// task reaped.
+    fn get_current_children_times(&self) -> (usize, usize) {
+        self.current_task_with(|task| task.children_times)
+    }
+
+    /// Map `[start, start + len)` into the current task with `perm`.
+    ///
+    /// A `start` of 0 is handled according to [`MMAP_NULL_POLICY`]; when the
+    /// kernel picks the address itself, that address is returned instead of 0.
+    /// With `data` the memory starts out holding it, see
+    /// [`MemorySet::checked_insert`].
+    fn mmap(
+        &self,
+        start: usize,
+        len: usize,
+        perm: MapPermission,
+        data: Option<&[u8]>,
+    ) -> Result<usize, MapError> {
+        let mut inner = self.inner.exclusive_access();
+        let task = inner.current_mut();
+        let policy = task.map_policy;
+        let (start, ret) = match (start, MMAP_NULL_POLICY) {
+            (0, NullMmapPolicy::NullInvalid) => return Err(MapError::Reserved),
+            (0, NullMmapPolicy::NullMeansAuto) => match task.memory_set.find_free_area(len) {
+                Some(va) => (va, va.0),
+                None => return Err(MapError::OutOfMemory),
+            },
+            (start, _) => (VirtAddr::from(start), 0),
+        };
+        // no access at all reserves the range, see `MemorySet::reserve`
+        if perm.is_empty() && data.is_none() {
+            return task.memory_set.reserve(start, len).map(|()| ret);
+        }
+        task.memory_set
+            .checked_insert(start, len, perm, AreaKind::Mmap, &policy, data)?;
+        // only areas below the lazy threshold get their frames now, and those
+        // with initial data
+        let pages = (len + PAGE_SIZE - 1) / PAGE_SIZE;
+        if pages < policy.lazy_pages || data.is_some() {
+            task.charge_pages(pages as isize);
+        }
+        Ok(ret)
+    }
+
+    /// Map shared memory segment `id` into the current task at `start`, or
+    /// where the kernel finds room for a `start` of 0; returns the address.
+    fn shm_map(&self, id: usize, start: usize, port: usize) -> isize {
+        let mapping = match shm_get(id) {
+            Some(mapping) => mapping,
+            None => return MapError::NotMapped.errno(),
+        };
+        let pages = mapping.pages();
+        let mut inner = self.inner.exclusive_access();
+        let task = inner.current_mut();
+        let policy = task.map_policy;
+        let start = match start {
+            0 => match task.memory_set.find_free_area(pages * PAGE_SIZE) {
+                Some(va) => va,
+                None => return MapError::OutOfMemory.errno(),
+            },
+            start => VirtAddr::from(start),
+        };
+        // a failed mapping must not take a segment nobody mapped yet along
+        let result = MapPermission::from_port(port).and_then(|perm| {
+            task.memory_set
+                .insert_shared(start, mapping.clone(), perm, &policy)
+        });
+        match result {
+            Ok(()) => {
+                mapping.set_mapped();
+                task.charge_pages(pages as isize);
+                start.0 as isize
+            }
+            Err(err) => err.errno(),
+        }
+    }
+
+    /// Change the permission of `[start, start + len)` of the current task's
+    /// mmap areas to the R/W/X bits of `port`.
+    fn mprotect(&self, start: usize, len: usize, port: usize) -> Result<(), MapError> {
+        let mut inner = self.inner.exclusive_access();
+        let task = inner.current_mut();
+        let policy = task.map_policy;
+        let perm = MapPermission::from_port(port)?;
+        let committed = task
+            .memory_set
+            .mprotect(VirtAddr::from(start), len, perm, &policy)?;
+        task.charge_pages(committed as isize);
+        Ok(())
+    }
+
+    /// Move the current task's program break by `size` bytes, returning the
+    /// old break.
+    fn change_program_brk(&self, size: i32) -> Result<usize, MapError> {
+        let mut inner = self.inner.exclusive_access();
+        let task = inner.current_mut();
+        let old_brk = task.program_brk;
+        let new_brk = (old_brk as isize)
+            .checked_add(size as isize)
+            .filter(|&brk| brk >= 0)
+            .ok_or(MapError::Reserved)? as usize;
+        let policy = task.map_policy;
+        task.memory_set.change_program_brk(
+            VirtAddr::from(task.heap_bottom),
+            VirtAddr::from(new_brk),
+            &policy,
+        )?;
+        task.program_brk = new_brk;
+        let pages = |brk: usize| VirtAddr::from(brk).ceil().0 as isize;
+        task.charge_pages(pages(new_brk) - pages(old_brk));
+        Ok(old_brk)
+    }
+
+    /// Unmap `[start, start + len)`, which must consist of whole mmap areas,
+    /// returning the number of data and page-table frames freed.
+    fn munmap(&self, start: usize, len: usize) -> Result<(usize, usize), MapError> {
+        let mut inner = self.inner.exclusive_access();
+        let task = inner.current_mut();
+        let (freed, tables) =
+            task.memory_set
+                .checked_remove(VirtAddr::from(start), len, AreaKind::Mmap)?;
+        task.charge_pages(-(freed as isize));
+        Ok((freed, tables))
+    }
+
+    /// Add a `Ready` task running app `name`, returning its id; `None` for
+    /// an unknown app, once [`MAX_SPAWNED_TASKS`] were added, or if the
+    /// frames may not be enough for it.
+    ///
+    /// The task gets the next id and the kernel stack there, which no task
+    /// ever had: ids are only reused by [`TaskManager::restart`], for the
+    /// app that had them, and all at once across a soft reboot, which is
+    /// refused once a task was spawned.
+    fn spawn(&self, name: &str) -> Option<usize> {
+        let (_, app) = APP_CATALOG.get_by_name(name)?;
+        let mut inner = self.inner.exclusive_access();
+        let id = inner.tasks.len();
+        if id >= APP_CATALOG.len() + MAX_SPAWNED_TASKS
+            || frames_available() < TaskControlBlock::frames_needed(app)
+        {
+            return None;
+        }
+        let mut task = TaskControlBlock::new(app, id);
+        task.parent = Some(inner.processor.current());
+        // starts level with its parent instead of ahead of every other task
+        task.pass = inner.current().pass;
+        inner.tasks.push(task);
+        inner.ready.add_task();
+        debug_mirror::add_task(id);
+        inner.set_status(id, TaskStatus::Ready);
+        Some(id)
+    }
+
+    /// Run exited task `id` again from the start of its app, see
+    /// `sys_task_restart`, returning the id of the new run; `None` if there
+    /// is no such task, it has not exited, or the frames may not be enough
+    /// for it.
+    ///
+    /// The control block is built anew, as at boot: address space, trap and
+    /// task contexts, counters and time accounting. Its exit already freed
+    /// the old address space; a kernel stack not reaped yet is taken over
+    /// as it is, poisoned again, instead of being unmapped under the new
+    /// run. What the slot keeps is the count of restarts, which is bumped
+    /// and makes the new run's ids, and the exit code of the old run.
+    ///
+    /// Nothing the kernel keeps for the old run outlives it: tasks waiting
+    /// for it were woken by its exit, which left its children without a
+    /// parent, and its sleep, kill and queue waits went with its block. Ids
+    /// of it held by tasks are told apart by their generation.
+    ///
+    /// The new block is built while the old one is still in its slot, which
+    /// costs a single frame: `TaskControlBlock::bury` handed the address
+    /// space to be freed at the exit and left a bare one, only a root page
+    /// table, and no area, so no user frame of the old run is held while the
+    /// new run's are allocated.
+    fn restart(&self, id: usize) -> Option<usize> {
+        let mut inner = self.inner.exclusive_access();
+        let id = inner.slot_of(id)?;
+        if inner.tasks[id].task_status != TaskStatus::Exited {
+            return None;
+        }
+        let app = inner.tasks[id].app;
+        if frames_available() < TaskControlBlock::frames_needed(app) {
+            return None;
+        }
+        debug_assert_eq!(
+            inner.tasks[id].memory_set.resident_pages(),
+            0,
+            "exited task still holds user frames"
+        );
+        inner.unreaped.retain(|&unreaped| unreaped != id);
+        let mut task = TaskControlBlock::new(app, id);
+        task.restarts = inner.tasks[id].restarts + 1;
+        task.previous_exit_code = inner.tasks[id].obituary.map(|obituary| obituary.exit_code);
+        // a child not reaped yet stays one, its parent reaps the new run
+        task.parent = inner.tasks[id].parent;
+        // starts level with the restarting task, as a spawned one does
+        task.pass = inner.current().pass;
+        // still exited until `set_status`, which records the transition
+        task.task_status = TaskStatus::Exited;
+        let old = core::mem::replace(&mut inner.tasks[id], task);
+        inner.set_status(id, TaskStatus::Ready);
+        let new_id = inner.id_of(id);
+        drop(inner);
+        // only the root page table its exit left, and the obituary
+        drop(old);
+        Some(new_id)
+    }
+
+    /// Whether tasks were added by `sys_spawn` since boot.
+    fn spawned(&self) -> bool {
+        self.inner.exclusive_access().tasks.len() > APP_CATALOG.len()
+    }
+
+    /// Drop every task, rebuild the app set under scheduler profile `profile`
+    /// and run it again from the first app.
+    ///
+    /// Physical frames are not reset: dropping the control blocks must give
+    /// back every user frame, which is checked against the first boot pass.
+    /// The kernel heap is kept as it is, and so are the kernel stacks not
+    /// reaped yet; the new tasks map the reaped ones again.
+    ///
+    /// Besides a restart, which reruns an exited task's own app in its slot
+    /// under a new generation, this is the only place task slots are reused,
+    /// and all of them at once; the kernel keeps no task id across it, and
+    /// the ids of the new tasks start at generation 0 again.
+    fn reboot(&self, profile: usize) -> ! {
+        let mut inner = self.inner.exclusive_access();
+        // we run on the kernel stack and in the kernel address space here,
+        // so the current task's user memory may go as well
+        for id in 0..inner.tasks.len() {
+            inner.return_buffers(id);
+        }
+        inner.tasks.clear();
+        // no task is left to map a segment or wait on a queue
+        shm_clear();
+        inner.queues.clear();
+        kstat::end_pass();
+        set_ticks_per_sec(TIME_SLICE_PROFILES[profile]);
+        inner.tasks = load_tasks();
+        inner.ready = ReadyQueue::new(inner.tasks.len());
+        inner.processor.take_current();
+        self.current.exclusive_access().take();
+        inner.running = 0;
+        inner.unreaped.clear();
+        assert_eq!(
+            frames_in_use(),
+            inner.boot_frames,
+            "frames leaked across soft reboot"
+        );
+        let idle_task_cx_ptr = &inner.idle_task_cx as *const TaskContext;
+        drop(inner);
+        info!("[kernel] soft reboot with scheduler profile {}", profile);
+        // the idle loop starts the first app again; this kernel stack may
+        // be the first one a new task runs on, so nothing is saved on it
+        let mut _unused = TaskContext::zero_init();
+        unsafe {
+            __switch(&mut _unused as *mut _, idle_task_cx_ptr);
+        }
+        panic!("unreachable in reboot!");
+    }
+
+    /// Switch current `Running` task to the task we have found, or to the
+    /// idle loop if there is no `Ready` task but some have not exited yet;
+    /// once all have, we can exit with all applications completed
+    fn run_next_task(&self) {
+        let mut inner = self.inner.exclusive_access();
+        inner.reap_kernel_stacks();
+        let current = inner.processor.current();
+        let prev = inner.tasks[current].counters.get_mut();
+        prev.kernel_time += get_time() - prev.time_stamp;
+        let current_task_cx_ptr = &mut inner.tasks[current].task_cx as *mut TaskContext;
+        if let Some(next) = inner.find_next_task() {
+            if cfg!(all(feature = "dispatch-check", debug_assertions)) {
+                check_dispatch(&inner.tasks[next], next, false);
+            }
+            self.switch_to(inner, current_task_cx_ptr, next);
+            // go back to user mode
+        } else if inner
+            .tasks
+            .iter()
+            .any(|task| task.task_status != TaskStatus::Exited)
+        {
+            // the rest wait for the clock or for each other
+            let idle_task_cx_ptr = &inner.idle_task_cx as *const TaskContext;
+            inner.switches += 1;
+            drop(inner);
+            unsafe {
+                __switch(current_task_cx_ptr, idle_task_cx_ptr);
+            }
+        } else {
+            drop(inner);
+            self.print_shutdown_summary();
+            println!("[kernel] All applications completed!");
+            shutdown(false);
+        }
+    }
+
+    /// Everything printed once no task runs any more: exit codes, CPU
+    /// times, memory peaks and, with `kstat`, the rest of the statistics.
+    fn print_shutdown_summary(&self) {
+        debug_mirror::set_boot_stage(BootStage::Shutdown);
+        self.print_exit_summary();
+        if kstat::ENABLED {
+            APP_CATALOG.print_skipped();
+            self.print_obituaries();
+            stack_probe::print();
+            println!("[kernel] {:?}", frame_stats());
+        }
+        kstat::print();
+        print_syscall_stats();
+        log::logger().flush();
+    }
+
+    /// Bury every task that has not exited yet, the current one included,
+    /// for [`ExitReason::Shutdown`], then print the shutdown summary and
+    /// power off, `failure` deciding qemu's exit status.
+    ///
+    /// This runs inside a syscall of the current task, on its kernel stack
+    /// and in the kernel address space, so its user memory may go as well.
+    /// The trap context the syscall works on is reached through the trap
+    /// context frame, not through `inner`, so the borrow taken here is the
+    /// only one; it ends before the summary borrows again.
+    fn shutdown_all(&self, failure: bool) -> ! {
+        let mut inner = self.inner.exclusive_access();
+        let memory_sets = inner.bury_all(ExitReason::Shutdown, get_time());
+        self.current.exclusive_access().take();
+        drop(inner);
+        // freeing the frames only needs the frame allocator
+        drop(memory_sets);
+        self.print_shutdown_summary();
+        println!(
+            "[kernel] shut down by app {}{}",
+            self.current_task_label(),
+            if failure { ", reporting failure" } else { "" }
+        );
+        shutdown(failure)
+    }
+}
+
+/// End every task and power off, see `sys_shutdown`; `failure` makes qemu
+/// exit with a nonzero status.
+pub fn shutdown_all(failure: bool) -> ! {
+    TASK_MANAGER.shutdown_all(failure)
+}
+
+/// Run the first task in task list.
+pub fn run_first_task() {
+    TASK_MANAGER.run_first_task();
+}
+
+/// Make task `id` current without running it, see `TaskManager::enter_for_test`.
+#[cfg(feature = "board_test")]
+pub fn enter_task_for_test(id: usize) {
+    TASK_MANAGER.enter_for_test(id);
+}
+
+/// Switch current `Running` task to the task we have found, or to the idle
+/// loop, or there is no task left and we can exit with all applications completed
+fn run_next_task() {
+    TASK_MANAGER.run_next_task();
+}
+
+/// Change the status of current `Running` task into `Ready`.
+fn mark_current_suspended(reason: SwitchReason) {
+    TASK_MANAGER.mark_current_suspended(reason);
+}
+
+/// Change the status of current `Running` task into `Exited`.
+fn mark_current_exited(reason: ExitReason) {
+    TASK_MANAGER.mark_current_exited(reason);
+}
+
+/// Suspend the current 'Running' task and run the next task in task list,
+/// as the task itself asked for.
+pub fn suspend_current_and_run_next() {
+    suspend_current_and_run_next_with_reason(SwitchReason::Yield);
+}
+
+/// Suspend the current 'Running' task for `reason` and run the next task in
+/// task list.
+pub fn suspend_current_and_run_next_with_reason(reason: SwitchReason) {
+    mark_current_suspended(reason);
+    run_next_task();
+}
+
+/// Charge the timer tick that just came in to the current task, returning
+/// whether its time slice is over.
+pub fn tick_current() -> bool {
+    TASK_MANAGER.tick_current()
+}
+
+/// Suspend the current task in the middle of a syscall if its time slice is
+/// over; the syscall goes on where it was once the task is run again.
+pub fn yield_if_slice_over() {
+    if slice_over() {
+        set_next_trigger();
+        if tick_current() {
+            suspend_current_and_run_next_with_reason(SwitchReason::Preempted);
+        }
+    }
+}
+
+/// Put the current 'Running' task to sleep until `wakeup_time_us` and run
+/// the next task in task list.
+pub fn block_current_and_run_next(wakeup_time_us: usize) {
+    TASK_MANAGER.block_current_until(wakeup_time_us);
+    run_next_task();
+}
+
+/// Put the current 'Running' task to sleep until task `target` exits and run
+/// the next task in task list. Returns false, doing neither, if `target`
+/// waits for the current task, directly or through others.
+pub fn block_current_on_and_run_next(target: usize) -> bool {
+    if !TASK_MANAGER.block_current_on(target) {
+        return false;
+    }
+    run_next_task();
+    true
+}
+
+/// Put the current 'Running' task to sleep until one of its children exits
+/// and run the next task in task list. Returns false, doing neither, if
+/// every child waits for the current task, directly or through others.
+pub fn block_current_on_child_and_run_next() -> bool {
+    if !TASK_MANAGER.block_current_on_child() {
+        return false;
+    }
+    run_next_task();
+    true
+}
+
+/// Whether the current task has child `pid`, or any child if `pid` is
+/// `None`, see `sys_waitpid`.
+pub fn current_has_child(pid: Option<usize>) -> bool {
+    TASK_MANAGER.has_child(pid)
+}
+
+/// Reap an exited child of the current task, see `sys_waitpid`.
+pub fn reap_current_child(pid: Option<usize>) -> Option<(usize, i32)> {
+    TASK_MANAGER.reap_child(pid)
+}
+
+/// Create a message queue of `capacity` messages of up to `msg_size` bytes,
+/// charged to the current task, returning its id.
+pub fn mq_create(capacity: usize, msg_size: usize) -> Result<usize, MqError> {
+    TASK_MANAGER.mq_create(capacity, msg_size)
+}
+
+/// Send the `len` bytes at `buf` to queue `id`, running the next task while
+/// the queue is full; returns what `sys_mq_send` does.
+pub fn mq_send(id: usize, buf: usize, len: usize) -> isize {
+    TASK_MANAGER.mq_send(id, buf, len).unwrap_or_else(|| {
+        run_next_task();
+        TASK_MANAGER.queue_result()
+    })
+}
+
+/// Receive a message of queue `id` into the `cap` bytes at `buf`, running
+/// the next task while the queue is empty; returns what `sys_mq_recv` does.
+pub fn mq_recv(id: usize, buf: usize, cap: usize) -> isize {
+    TASK_MANAGER.mq_recv(id, buf, cap).unwrap_or_else(|| {
+        run_next_task();
+        TASK_MANAGER.queue_result()
+    })
+}
+
+/// Drop message queue `id` unless a task is blocked on it.
+pub fn mq_destroy(id: usize) -> Result<(), MqError> {
+    TASK_MANAGER.mq_destroy(id)
+}
+
+/// Have task `id` exit as killed by the current task; false if there is no
+/// such task, it is the current one or it has exited.
+pub fn kill_task(id: usize) -> bool {
+    TASK_MANAGER.kill(id)
+}
+
+/// Why the current task has to exit before it returns to user mode, if it
+/// was killed.
+pub fn current_pending_exit() -> Option<ExitReason> {
+    TASK_MANAGER.pending_exit()
+}
+
+/// Number of tasks there are, exited or not; ids are below it.
+pub fn task_count() -> usize {
+    TASK_MANAGER.task_count()
+}
+
+/// Exit the current 'Running' task and run the next task in task list; a
+/// task killed for a fault gets its fault history printed first.
+pub fn exit_current_and_run_next(reason: ExitReason) {
+    if let ExitReason::Fault(..) = reason {
+        dump_fault_history(current_task_id());
+    }
+    mark_current_exited(reason);
+    run_next_task();
+}
+
+/// Warn about tasks that have waited `Ready` for too long, see
+/// [`crate::config::STARVATION_SLICES`].
+pub fn check_starvation() {
+    TASK_MANAGER.check_starvation();
+}
+
+/// Count tasks in `Ready` state.
+pub fn ready_task_count() -> usize {
+    TASK_MANAGER.ready_task_count()
+}
+
+/// Get the current 'Running' task's token.
+pub fn current_user_token() -> usize {
+    TASK_MANAGER.get_current_token()
+}
+
+/// Get the current 'Running' task's token to return to it.
+pub fn sync_current_user_tlb() -> usize {
+    TASK_MANAGER.sync_current_tlb()
+}
+
+/// Kind of area the current task runs `pc` from, if its policy forbids it;
+/// `Some(None)` means `pc` is not inside any area.
+pub fn current_exec_violation(pc: usize) -> Option<Option<AreaKind>> {
+    TASK_MANAGER.exec_violation(pc)
+}
+
+/// Whether the current 'Running' task holds all of `caps`.
+pub fn current_has_caps(caps: CapSet) -> bool {
+    TASK_MANAGER.current_has_caps(caps)
+}
+
+/// Permanently remove `caps` from the current 'Running' task.
+pub fn drop_current_caps(caps: CapSet) -> CapSet {
+    TASK_MANAGER.drop_current_caps(caps)
+}
+
+/// Permanently restrict the current 'Running' task to the syscalls `filter`
+/// allows, on top of any earlier filter.
+pub fn narrow_current_syscall_filter(filter: &SyscallFilter) {
+    TASK_MANAGER.narrow_syscall_filter(filter);
+}
+
+/// Whether the current 'Running' task may make syscall `syscall_id`.
+pub fn current_syscall_allowed(syscall_id: usize) -> bool {
+    TASK_MANAGER.syscall_allowed(syscall_id)
+}
+
+/// Exit code of task `task_id`, `None` while it has not exited, see
+/// [`ExitReason::exit_code`].
+pub fn get_task_exit_code(task_id: usize) -> Option<i32> {
+    TASK_MANAGER.exit_code(task_id)
+}
+
+/// The slot task id `task_id` names, `None` for an earlier run of it or no
+/// task at all, see [`crate::config::TASK_SLOT_BITS`].
+pub fn task_slot(task_id: usize) -> Option<usize> {
+    TASK_MANAGER.slot_of(task_id)
+}
+
+/// Label, status and trap context of the current task for the panic
+/// handler, which may have interrupted a holder of the task manager; `None`
+/// then.
+pub fn try_current_task() -> Option<(TaskLabel, TaskStatus, &'static TrapContext)> {
+    TASK_MANAGER.try_current()
+}
+
+/// Run `f` on the current 'Running' task, see `TaskManager::current_task_with`.
+pub fn current_task_with<R>(f: impl FnOnce(&TaskControlBlock) -> R) -> R {
+    TASK_MANAGER.current_task_with(f)
+}
+
+/// Id of the current 'Running' task.
+pub fn current_task_id() -> usize {
+    TASK_MANAGER.current_task_id()
+}
+
+/// How diagnostics name the current 'Running' task, see [`TaskLabel`].
+pub fn current_task_label() -> TaskLabel {
+    TASK_MANAGER.current_task_label()
+}
+
+/// Start or stop tracing the syscalls of the current 'Running' task;
+/// `false` if tracing could not start because every ring is lent out.
+pub fn set_current_trace(enable: bool) -> bool {
+    TASK_MANAGER.set_trace(enable)
+}
+
+/// Start or stop logging the syscalls of the current 'Running' task, see
+/// `sys_strace`.
+pub fn set_current_strace(enable: bool) {
+    TASK_MANAGER.set_strace(enable);
+}
+
+/// Whether the syscalls of the current 'Running' task are logged.
+pub fn current_strace() -> bool {
+    TASK_MANAGER.strace()
+}
+
+/// Current scheduler epoch, see `sys_sched_epoch`.
+pub fn sched_epoch() -> usize {
+    TASK_MANAGER.sched_epoch()
+}
+
+/// Epoch task `id` last ran in, `None` if there is no such task.
+pub fn task_epoch(id: usize) -> Option<usize> {
+    TASK_MANAGER.epoch_of(id)
+}
+
+/// Count a syscall of the current 'Running' task.
+pub fn count_current_syscall(syscall_id: usize) {
+    TASK_MANAGER.count_syscall(syscall_id);
+}
+
+/// Refuse a syscall id the current 'Running' task made that the kernel does
+/// not implement.
+pub fn reject_current_unknown_syscall(syscall_id: usize) {
+    TASK_MANAGER.reject_unknown_syscall(syscall_id);
+}
+
+/// Snapshot of the current 'Running' task, see `sys_task_info`.
+pub fn current_task_info() -> TaskInfo {
+    TASK_MANAGER.current_task_info()
+}
+
+/// Give the untouched lazy pages of `[ptr, ptr + len)` in the current
+/// 'Running' task their frames, before the kernel copies from or to it.
+pub fn populate_current_lazy(ptr: usize, len: usize) {
+    TASK_MANAGER.populate_lazy(ptr, len);
+}
+
+/// Count `bytes` the current 'Running' task wrote to stdout.
+pub fn count_current_output(bytes: usize) {
+    TASK_MANAGER.count_output(bytes);
+}
+
+/// Record a syscall of the current 'Running' task if it is being traced.
+pub fn trace_current_syscall(syscall_id: usize, arg0: usize, ret: isize) {
+    TASK_MANAGER.trace_syscall(syscall_id, arg0, ret);
+}
+
+/// Drain the trace ring of task `id`, see `sys_trace_read`.
+pub fn drain_task_trace(id: usize, out: &mut [TraceEntry]) -> Option<usize> {
+    TASK_MANAGER.drain_trace(id, out)
+}
+
+/// Sample the user pc of the current 'Running' task, on a timer tick.
+pub fn profile_current_tick(pc: usize) {
+    TASK_MANAGER.profile_sample(pc);
+}
+
+/// Read the profile of task `id`, see `sys_profile_read`.
+pub fn read_task_profile(id: usize, out: &mut [ProfileBucket]) -> Option<usize> {
+    TASK_MANAGER.read_profile(id, out)
+}
+
+/// Check the current 'Running' task's address space, see `sys_selfcheck`.
+pub fn check_current_memory_set() -> bool {
+    TASK_MANAGER.check_current_memory_set()
+}
+
+/// Check the scheduler's task statuses, see `sys_selfcheck`.
+pub fn check_scheduler() -> bool {
+    TASK_MANAGER.check_scheduler()
+}
+
+/// Fingerprint of the current 'Running' task's address space, see
+/// [`crate::mm::MemorySet::fingerprint`].
+pub fn current_fingerprint() -> u64 {
+    TASK_MANAGER.current_fingerprint()
+}
+
+/// Set the stride scheduling priority of the current 'Running' task.
+pub fn set_current_priority(priority: usize) {
+    TASK_MANAGER.set_priority(priority);
+}
+
+/// Turn the current task's text-only execution policy on or off.
+pub fn set_current_exec_text_only(enable: bool) {
+    TASK_MANAGER.set_exec_text_only(enable);
+}
+
+/// Split a buffer of the current 'Running' task into per-page byte slices,
+/// if it may read all of it.
+pub fn current_user_buffer(
+    ptr: *const u8,
+    len: usize,
+) -> Result<Vec<&'static mut [u8]>, UserPtrError> {
+    TASK_MANAGER.current_user_buffer(ptr, len)
+}
+
+/// Get the current 'Running' task's trap contexts.
+pub fn current_trap_cx() -> &'static mut TrapContext {
+    TASK_MANAGER.get_current_trap_cx()
+}
+
+/// Account the current task's user time up to now, see `TaskManager::user_time_end`.
+pub fn user_time_end() {
+    TASK_MANAGER.user_time_end();
+}
+
+/// Account the current task's kernel time up to now, see `TaskManager::user_time_start`.
+pub fn user_time_start() {
+    TASK_MANAGER.user_time_start();
+}
+
+/// Try to resolve a page fault of the current 'Running' task at `va`,
+/// raised by `cause` at `sepc`, returning whether it may retry the access.
+pub fn resolve_page_fault(cause: Exception, va: usize, sepc: usize) -> bool {
+    TASK_MANAGER.resolve_page_fault(cause, va, sepc)
+}
+
+/// Record a page fault the current 'Running' task is killed for right away.
+pub fn record_fatal_fault(cause: Exception, va: usize, sepc: usize) {
+    TASK_MANAGER.record_fatal_fault(cause, va, sepc);
+}
+
+/// Print the latest page faults of task `task_id`, the oldest first.
+pub fn dump_fault_history(task_id: usize) {
+    TASK_MANAGER.dump_fault_history(task_id);
+}
+
+/// Print the areas and mapped pages of the current 'Running' task.
+pub fn dump_current_address_space() {
+    TASK_MANAGER.dump_current_address_space();
+}
+
+/// Page table entry of `vpn` in the current 'Running' task, if there is
+/// one, for `sys_translate` and the self-tests.
+#[cfg(any(feature = "debug-syscalls", feature = "board_test"))]
+pub fn translate_current(vpn: VirtPageNum) -> Option<PageTableEntry> {
+    TASK_MANAGER.translate_current(vpn)
+}
+
+/// Whether `va` is in the guard page below the current 'Running' task's
+/// user stack.
+pub fn current_stack_guard_hit(va: usize) -> bool {
+    TASK_MANAGER.stack_guard_hit(va)
+}
+
+/// Deepest use of task `task_id`'s kernel stack in bytes since it was
+/// built, 0 if there is no such task.
+pub fn kernel_stack_usage(task_id: usize) -> usize {
+    TASK_MANAGER.kernel_stack_usage(task_id)
+}
+
+/// Warn if the current task is running low on kernel stack, see
+/// `TaskManager::check_kernel_stack`.
+pub fn check_current_kernel_stack() {
+    TASK_MANAGER.check_kernel_stack();
+}
+
+/// Whether the current task faulted at `va` on a page it only reserved,
+/// see `TaskManager::reservation_hit`.
+pub fn current_reservation_hit(va: usize) -> bool {
+    TASK_MANAGER.reservation_hit(va)
+}
+
+/// Get the current 'Running' task's (user, kernel) time in cycles.
+pub fn current_task_cpu_time() -> (usize, usize) {
+    TASK_MANAGER.get_current_times()
+}
+
+/// Get the (user, kernel) time in cycles of the children the current
+/// 'Running' task reaped, see `sys_waitpid`.
+pub fn current_children_cpu_time() -> (usize, usize) {
+    TASK_MANAGER.get_current_children_times()
+}
+
+/// Start a new task running app `name`, see `sys_spawn`.
+pub fn spawn(name: &str) -> Option<usize> {
+    TASK_MANAGER.spawn(name)
+}
+
+/// Run exited task `id` again, returning the id of the new run, see
+/// `sys_task_restart`.
+pub fn restart_task(id: usize) -> Option<usize> {
+    TASK_MANAGER.restart(id)
+}
+
+/// Whether `sys_spawn` added tasks since boot.
+pub fn tasks_spawned() -> bool {
+    TASK_MANAGER.spawned()
+}
+
+/// Restart all apps under scheduler profile `profile`, see `sys_reboot`.
+pub fn reboot(profile: usize) -> ! {
+    TASK_MANAGER.reboot(profile)
+}
+
+/// Map memory for the current 'Running' task, see `sys_mmap` and `sys_mmap2`;
+/// returns the address if the kernel picked it, 0 otherwise.
+pub fn task_mmap(start: usize, len: usize, perm: MapPermission) -> Result<usize, MapError> {
+    TASK_MANAGER.mmap(start, len, perm, None)
+}
+
+/// Map memory starting out as `data` and zeros after it for the current
+/// 'Running' task, see `sys_mmap_data`; `data` must fit in `len`.
+pub fn task_mmap_data(
+    start: usize,
+    len: usize,
+    perm: MapPermission,
+    data: &[u8],
+) -> Result<usize, MapError> {
+    TASK_MANAGER.mmap(start, len, perm, Some(data))
+}
+
+/// Map shared memory into the current 'Running' task, see `sys_shm_map`.
+pub fn task_shm_map(id: usize, start: usize, port: usize) -> isize {
+    TASK_MANAGER.shm_map(id, start, port)
+}
+
+/// Change the permission of memory of the current 'Running' task, see
+/// `sys_mprotect`.
+pub fn task_mprotect(start: usize, len: usize, port: usize) -> Result<(), MapError> {
+    TASK_MANAGER.mprotect(start, len, port)
+}
+
+/// Address-space summary of the current 'Running' task, see `sys_mem_info`.
+pub fn current_mem_info() -> MemInfo {
+    TASK_MANAGER.current_mem_info()
+}
+
+/// Page bitmap of the current 'Running' task, see `sys_vm_populated`.
+pub fn current_page_bitmap(
+    start: usize,
+    len: usize,
+    take_accessed: bool,
+) -> Result<(Vec<u8>, usize), MapError> {
+    TASK_MANAGER.page_bitmap(start, len, take_accessed)
+}
+
+/// Advisory mmap address for the current 'Running' task, see `sys_vm_find_free`.
+pub fn task_find_free(len: usize, align: usize) -> Result<VirtAddr, MapError> {
+    TASK_MANAGER.find_free(len, align)
+}
+
+/// Move the program break of the current 'Running' task, see `sys_sbrk`;
+/// returns the old break.
+pub fn change_current_program_brk(size: i32) -> Result<usize, MapError> {
+    TASK_MANAGER.change_program_brk(size)
+}
+
+/// Unmap memory of the current 'Running' task, see `sys_munmap`; returns
+/// the number of data and page-table frames freed.
+pub fn task_munmap(start: usize, len: usize) -> Result<(usize, usize), MapError> {
+    TASK_MANAGER.munmap(start, len)
 }
@@ -0,0 +1,216 @@
+//! Bounded message queues, see `sys_mq_create`
+//!
+//! A queue holds up to its capacity of messages, each of at most
+//! `msg_size` bytes, in storage taken from the kernel heap when it is
+//! created; the heap it takes is charged to the task that created it, see
+//! [`MQ_MAX_BYTES`]. Messages are copied between the slots and user memory
+//! through [`UserSlice`], so a buffer may span pages.
+//!
+//! A task sending to a full queue, or receiving from an empty one, is
+//! queued as a [`Waiter`] and `Blocked`; the task manager keeps the queues,
+//! since every call may wake another task. A call that frees a slot or
+//! fills one hands it to the first waiter of the other side right away, so
+//! senders wait only while the queue is full and receivers only while it is
+//! empty, and a newcomer never overtakes a waiter: both sides are served in
+//! FIFO order.
+
+use crate::config::{MQ_MAX_BYTES, MQ_MAX_QUEUES};
+use crate::mm::UserSlice;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+/// Why a queue call was refused.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum MqError {
+    /// no queue has the id, or an argument is out of range
+    Invalid,
+    /// tasks are blocked on the queue
+    Busy,
+    /// every id is taken, the creator's share of the heap is used up or the
+    /// heap is
+    NoSpace,
+}
+
+impl MqError {
+    /// What a syscall returns for the error.
+    pub fn errno(self) -> isize {
+        match self {
+            MqError::Invalid => -1,
+            MqError::Busy => -2,
+            MqError::NoSpace => -3,
+        }
+    }
+}
+
+/// A task blocked on a queue, and the user buffer of its call: the message
+/// to send, or where a received one goes.
+#[derive(Copy, Clone, Debug)]
+pub struct Waiter {
+    pub task: usize,
+    pub buf: usize,
+    pub len: usize,
+}
+
+/// One queue, a ring of slots.
+pub struct MessageQueue {
+    /// task charged for the storage
+    creator: usize,
+    /// most bytes a message may have
+    pub msg_size: usize,
+    /// one `msg_size` run of bytes per slot
+    storage: Vec<u8>,
+    /// length of the message in each slot
+    lens: Vec<usize>,
+    /// slot of the oldest message
+    head: usize,
+    /// messages queued
+    count: usize,
+    /// tasks waiting for a free slot, the longest waiting first
+    pub senders: VecDeque<Waiter>,
+    /// tasks waiting for a message, the longest waiting first
+    pub receivers: VecDeque<Waiter>,
+}
+
+impl MessageQueue {
+    /// An empty queue of `capacity` slots of `msg_size` bytes; `None` if
+    /// the heap cannot hold it.
+    fn new(creator: usize, capacity: usize, msg_size: usize) -> Option<Self> {
+        let mut storage = Vec::new();
+        storage.try_reserve_exact(capacity * msg_size).ok()?;
+        storage.resize(capacity * msg_size, 0);
+        let mut lens = Vec::new();
+        lens.try_reserve_exact(capacity).ok()?;
+        lens.resize(capacity, 0);
+        Some(Self {
+            creator,
+            msg_size,
+            storage,
+            lens,
+            head: 0,
+            count: 0,
+            senders: VecDeque::new(),
+            receivers: VecDeque::new(),
+        })
+    }
+    pub fn is_full(&self) -> bool {
+        self.count == self.lens.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+    /// Kernel heap the slots take.
+    fn bytes(&self) -> usize {
+        self.storage.len()
+    }
+    /// Bytes of slot `slot`.
+    fn slot(&mut self, slot: usize) -> &mut [u8] {
+        &mut self.storage[slot * self.msg_size..(slot + 1) * self.msg_size]
+    }
+    /// Queue the `len` bytes at `buf` in the address space of `token` as the
+    /// newest message; false, queueing nothing, if they cannot be read.
+    /// The queue must not be full, and `len` at most `msg_size`.
+    pub fn push_from(&mut self, token: usize, buf: usize, len: usize) -> bool {
+        debug_assert!(!self.is_full() && len <= self.msg_size);
+        let src = match UserSlice::readable(token, buf as *const u8, len) {
+            Ok(src) => src,
+            Err(_) => return false,
+        };
+        let slot = (self.head + self.count) % self.lens.len();
+        src.read(&mut self.slot(slot)[..len]);
+        self.lens[slot] = len;
+        self.count += 1;
+        true
+    }
+    /// Move the oldest message to `buf` in the address space of `token`,
+    /// which must have room for `msg_size` bytes, returning its length;
+    /// `None`, keeping it queued, if it cannot be written there. The queue
+    /// must not be empty.
+    pub fn pop_into(&mut self, token: usize, buf: usize) -> Option<usize> {
+        debug_assert!(!self.is_empty());
+        let len = self.lens[self.head];
+        let mut dst = UserSlice::writable(token, buf as *mut u8, len).ok()?;
+        let head = self.head;
+        dst.write(&self.slot(head)[..len]);
+        self.head = (self.head + 1) % self.lens.len();
+        self.count -= 1;
+        Some(len)
+    }
+    /// Take task `task` off the waiters.
+    fn forget(&mut self, task: usize) {
+        self.senders.retain(|waiter| waiter.task != task);
+        self.receivers.retain(|waiter| waiter.task != task);
+    }
+}
+
+/// Every queue, by id.
+pub struct MessageQueues {
+    /// `None` where an id is free
+    queues: Vec<Option<MessageQueue>>,
+}
+
+impl MessageQueues {
+    pub const fn new() -> Self {
+        Self { queues: Vec::new() }
+    }
+    /// Create a queue of `capacity` messages of up to `msg_size` bytes for
+    /// task `creator`, returning its id, the lowest free one.
+    pub fn create(
+        &mut self,
+        creator: usize,
+        capacity: usize,
+        msg_size: usize,
+    ) -> Result<usize, MqError> {
+        let bytes = capacity
+            .checked_mul(msg_size)
+            .filter(|_| capacity > 0 && msg_size > 0)
+            .ok_or(MqError::Invalid)?;
+        if bytes > MQ_MAX_BYTES - self.charged(creator) {
+            return Err(MqError::NoSpace);
+        }
+        let id = match self.queues.iter().position(|slot| slot.is_none()) {
+            Some(id) => id,
+            None if self.queues.len() < MQ_MAX_QUEUES => {
+                self.queues.push(None);
+                self.queues.len() - 1
+            }
+            None => return Err(MqError::NoSpace),
+        };
+        self.queues[id] =
+            Some(MessageQueue::new(creator, capacity, msg_size).ok_or(MqError::NoSpace)?);
+        Ok(id)
+    }
+    /// Queue `id`, if there is one.
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut MessageQueue> {
+        self.queues.get_mut(id)?.as_mut()
+    }
+    /// Drop queue `id` with the messages still in it; refused while a task
+    /// is blocked on it.
+    pub fn destroy(&mut self, id: usize) -> Result<(), MqError> {
+        let queue = self.get_mut(id).ok_or(MqError::Invalid)?;
+        if !queue.senders.is_empty() || !queue.receivers.is_empty() {
+            return Err(MqError::Busy);
+        }
+        self.queues[id] = None;
+        Ok(())
+    }
+    /// Take task `task`, blocked on queue `id`, off its waiters.
+    pub fn forget(&mut self, id: usize, task: usize) {
+        if let Some(queue) = self.get_mut(id) {
+            queue.forget(task);
+        }
+    }
+    /// Kernel heap held by the queues task `creator` created; they outlive
+    /// it, so a task restarted in its slot inherits the charge.
+    pub fn charged(&self, creator: usize) -> usize {
+        self.queues
+            .iter()
+            .flatten()
+            .filter(|queue| queue.creator == creator)
+            .map(MessageQueue::bytes)
+            .sum()
+    }
+    /// Drop every queue; no task may be blocked on one any more.
+    pub fn clear(&mut self) {
+        self.queues.clear();
+    }
+}
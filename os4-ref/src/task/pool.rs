@@ -0,0 +1,51 @@
+//! Bounded pools of per-task trace rings and profiles
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+/// At most `capacity` boxed buffers, allocated the first time they are lent
+/// and kept for the next task once given back, so the kernel heap spent on
+/// them is bounded however many tasks ask.
+pub struct BufferPool<T> {
+    /// buffers given back, ready to be lent again
+    free: Vec<Box<T>>,
+    capacity: usize,
+    /// buffers currently held by tasks
+    lent: usize,
+}
+
+impl<T> BufferPool<T> {
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            free: Vec::new(),
+            capacity,
+            lent: 0,
+        }
+    }
+
+    /// Lend a buffer holding `fresh()`, or `None` if all of them are out.
+    pub fn lend(&mut self, fresh: impl FnOnce() -> T) -> Option<Box<T>> {
+        if self.lent == self.capacity {
+            return None;
+        }
+        self.lent += 1;
+        Some(match self.free.pop() {
+            Some(mut buffer) => {
+                *buffer = fresh();
+                buffer
+            }
+            None => Box::new(fresh()),
+        })
+    }
+
+    /// Take back a buffer lent by [`BufferPool::lend`].
+    pub fn give_back(&mut self, buffer: Box<T>) {
+        self.lent -= 1;
+        self.free.push(buffer);
+    }
+
+    /// Buffers currently held by tasks.
+    pub fn lent(&self) -> usize {
+        self.lent
+    }
+}
@@ -0,0 +1,40 @@
+//! Which task the processor is running
+//!
+//! The id lives in a private field so that only the dispatch path in
+//! [`super::TaskManager`] can change it; everything else asks
+//! `TaskManagerInner::current` for the task itself instead of indexing the
+//! task list with a copied id.
+
+/// The single processor of the kernel.
+pub struct Processor {
+    /// id of the task dispatched last, `None` before the first dispatch
+    current: Option<usize>,
+}
+
+impl Processor {
+    /// A processor that has not dispatched anything yet.
+    pub fn new() -> Self {
+        Self { current: None }
+    }
+
+    /// Id of the current task.
+    pub fn current(&self) -> usize {
+        self.current
+            .expect("[kernel] no task has been dispatched yet")
+    }
+
+    /// Whether a task has been dispatched since boot or the last reboot.
+    pub fn has_current(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// Make task `id` the current one; only the dispatcher calls this.
+    pub fn set_current(&mut self, id: usize) {
+        self.current = Some(id);
+    }
+
+    /// Forget the current task, for a reboot that drops every task.
+    pub fn take_current(&mut self) -> Option<usize> {
+        self.current.take()
+    }
+}
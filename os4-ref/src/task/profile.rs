@@ -0,0 +1,62 @@
+//! Per-task sampling profile of user pcs
+
+use crate::abi::ProfileBucket;
+use crate::config::{PROFILE_BUCKETS, PROFILE_BUCKET_SHIFT};
+
+/// Open-addressed table of sample counts by address bucket, allocated with
+/// the task so that sampling never allocates.
+pub struct Profile {
+    /// (bucket number, samples); a slot with no samples is free
+    buckets: [(usize, usize); PROFILE_BUCKETS],
+    /// samples that found the table full
+    overflow: usize,
+}
+
+impl Profile {
+    pub fn empty() -> Self {
+        Self {
+            buckets: [(0, 0); PROFILE_BUCKETS],
+            overflow: 0,
+        }
+    }
+
+    /// Count one sample at `pc`.
+    ///
+    /// Probing is linear from a multiplicative hash, so a hot bucket is
+    /// found within a few slots while the table is far from full.
+    pub fn sample(&mut self, pc: usize) {
+        let key = pc >> PROFILE_BUCKET_SHIFT;
+        let start = key.wrapping_mul(0x9e37_79b9_7f4a_7c15) % PROFILE_BUCKETS;
+        for i in 0..PROFILE_BUCKETS {
+            let slot = &mut self.buckets[(start + i) % PROFILE_BUCKETS];
+            if slot.1 == 0 || slot.0 == key {
+                *slot = (key, slot.1 + 1);
+                return;
+            }
+        }
+        self.overflow += 1;
+    }
+
+    /// Samples that could not be counted in a bucket.
+    pub fn overflow(&self) -> usize {
+        self.overflow
+    }
+
+    /// Non-empty buckets into `out`, most samples first; returns how many.
+    pub fn read(&self, out: &mut [ProfileBucket]) -> usize {
+        let mut buckets = self.buckets;
+        buckets.sort_unstable_by(|a, b| b.1.cmp(&a.1));
+        let mut count = 0;
+        for (&(key, samples), dst) in buckets.iter().zip(out.iter_mut()) {
+            if samples == 0 {
+                break;
+            }
+            *dst = ProfileBucket {
+                base: (key << PROFILE_BUCKET_SHIFT) as u64,
+                samples: samples as u64,
+            };
+            count += 1;
+        }
+        count
+    }
+}
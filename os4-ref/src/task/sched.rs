@@ -0,0 +1,130 @@
+//! Ready queue of the scheduling policy
+//!
+//! The queue is the only record of which tasks wait for a dispatch and in
+//! what order; [`super::TaskManager`] tells it when a task becomes `Ready`
+//! and asks it for the next one.
+
+use crate::config::SCHED_STRIDE;
+use alloc::collections::{BinaryHeap, VecDeque};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+/// A queued task, valid while `generation` matches the task's own.
+#[derive(Copy, Clone, PartialEq, Eq)]
+struct Entry {
+    /// stride pass when the task became `Ready`, 0 for round robin
+    pass: usize,
+    /// order in which tasks became `Ready`, breaks ties
+    seq: usize,
+    id: usize,
+    generation: u32,
+}
+
+impl Ord for Entry {
+    /// `BinaryHeap` pops the greatest entry, so the smallest pass and then
+    /// the earliest `seq` compare greatest. Passes are compared by their
+    /// wrapping difference, see `TaskManager::find_next_task`.
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.pass.wrapping_sub(self.pass) as isize)
+            .cmp(&0)
+            .then(other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+enum Queue {
+    /// first in, first out
+    RoundRobin(VecDeque<Entry>),
+    /// smallest pass first
+    Stride(BinaryHeap<Entry>),
+}
+
+/// Tasks waiting for a dispatch, in the order [`SCHED_STRIDE`] picks.
+///
+/// A task leaves the queue without a search: bumping its generation turns
+/// its entry stale, and stale entries are dropped when they come up.
+pub struct ReadyQueue {
+    queue: Queue,
+    /// current generation of every task
+    generations: Vec<u32>,
+    next_seq: usize,
+}
+
+impl ReadyQueue {
+    /// A queue holding tasks `0..count` in id order, all at pass 0.
+    pub fn new(count: usize) -> Self {
+        let queue = if SCHED_STRIDE {
+            Queue::Stride(BinaryHeap::with_capacity(count))
+        } else {
+            Queue::RoundRobin(VecDeque::with_capacity(count))
+        };
+        let mut ready = Self {
+            queue,
+            generations: vec![0; count],
+            next_seq: 0,
+        };
+        for id in 0..count {
+            ready.on_ready(id, 0);
+        }
+        ready
+    }
+
+    /// Queue task `id`, which just became `Ready` at stride pass `pass`;
+    /// an entry queued for it before goes stale.
+    pub fn on_ready(&mut self, id: usize, pass: usize) {
+        self.remove(id);
+        let entry = Entry {
+            pass: if SCHED_STRIDE { pass } else { 0 },
+            seq: self.next_seq,
+            id,
+            generation: self.generations[id],
+        };
+        self.next_seq += 1;
+        match &mut self.queue {
+            Queue::RoundRobin(queue) => queue.push_back(entry),
+            Queue::Stride(heap) => heap.push(entry),
+        }
+    }
+
+    /// Make room for one more task, with the next id, not queued yet.
+    pub fn add_task(&mut self) {
+        self.generations.push(0);
+    }
+
+    /// Take task `id` out of the queue, if it is in there.
+    pub fn remove(&mut self, id: usize) {
+        self.generations[id] = self.generations[id].wrapping_add(1);
+    }
+
+    /// The queued tasks, in the order they would be picked.
+    #[cfg(feature = "board_test")]
+    pub fn queued(&self) -> Vec<usize> {
+        let mut entries: Vec<Entry> = match &self.queue {
+            Queue::RoundRobin(queue) => queue.iter().copied().collect(),
+            // the greatest entry is picked first
+            Queue::Stride(heap) => heap.clone().into_sorted_vec().into_iter().rev().collect(),
+        };
+        entries.retain(|entry| entry.generation == self.generations[entry.id]);
+        entries.into_iter().map(|entry| entry.id).collect()
+    }
+
+    /// Take the task to dispatch next out of the queue.
+    pub fn pick_next(&mut self) -> Option<usize> {
+        loop {
+            let entry = match &mut self.queue {
+                Queue::RoundRobin(queue) => queue.pop_front(),
+                Queue::Stride(heap) => heap.pop(),
+            }?;
+            if entry.generation == self.generations[entry.id] {
+                self.remove(entry.id);
+                return Some(entry.id);
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Per-task syscall counts, by id
+//!
+//! A task only ever makes the few dozen syscalls the kernel implements, so
+//! the counts live in a small map instead of one slot for every possible
+//! id; the dense array of `TaskInfo` is built when a task asks for it.
+
+use crate::config::MAX_SYSCALL_NUM;
+
+/// Distinct ids a task can have counts for, more than the kernel implements.
+const SLOTS: usize = 48;
+
+/// Syscall counts of one task, in the order the ids were first made.
+pub struct SyscallCounts {
+    ids: [u16; SLOTS],
+    counts: [u32; SLOTS],
+    used: usize,
+}
+
+impl SyscallCounts {
+    pub const fn new() -> Self {
+        Self {
+            ids: [0; SLOTS],
+            counts: [0; SLOTS],
+            used: 0,
+        }
+    }
+
+    /// Count one call of `id`. Ids past [`MAX_SYSCALL_NUM`] are not kept,
+    /// and neither are new ids once every slot is taken; neither happens
+    /// for a syscall the kernel implements.
+    pub fn inc(&mut self, id: usize) {
+        if id >= MAX_SYSCALL_NUM {
+            return;
+        }
+        if let Some(slot) = self.slot(id) {
+            self.counts[slot] += 1;
+        } else if self.used < SLOTS {
+            self.ids[self.used] = id as u16;
+            self.counts[self.used] = 1;
+            self.used += 1;
+        }
+    }
+
+    /// Drop the counts of `id`, freeing its slot.
+    pub fn forget(&mut self, id: usize) {
+        if let Some(slot) = self.slot(id) {
+            self.used -= 1;
+            self.ids[slot] = self.ids[self.used];
+            self.counts[slot] = self.counts[self.used];
+        }
+    }
+
+    /// The counts as one entry per id, as `sys_task_info` reports them.
+    pub fn to_dense(&self) -> [u32; MAX_SYSCALL_NUM] {
+        let mut dense = [0; MAX_SYSCALL_NUM];
+        for slot in 0..self.used {
+            dense[self.ids[slot] as usize] = self.counts[slot];
+        }
+        dense
+    }
+
+    fn slot(&self, id: usize) -> Option<usize> {
+        self.ids[..self.used]
+            .iter()
+            .position(|&slot_id| slot_id as usize == id)
+    }
+}
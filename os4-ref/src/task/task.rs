@@ -1,46 +1,371 @@
 //! Types related to task management
+use super::faults::FaultHistory;
+use super::latency::LatencyDigest;
+use super::profile::Profile;
+use super::syscall_counts::SyscallCounts;
+use super::trace::TraceRing;
 use super::TaskContext;
-use crate::config::{kernel_stack_position, TRAP_CONTEXT};
-use crate::mm::{MapPermission, MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE};
+use crate::abi::TASK_NAME_LEN;
+use crate::config::{
+    kernel_stack_position, BIG_STRIDE, CAP_DEFAULT, CAP_GRANTS, DEFAULT_PRIORITY,
+    KERNEL_STACK_SIZE, MAX_SYSCALL_NUM, PAGE_SIZE, TICKS_PER_SLICE, TRACE_SYSCALLS, TRAP_CONTEXT,
+    USER_STACK_SIZE,
+};
+use crate::loader::AppEntry;
+use crate::mm::{
+    AslrOffsets, MapPermission, MapPolicy, MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE,
+};
+use crate::stack_probe;
+use crate::sync::UPSafeCell;
+use crate::timer::get_time;
 use crate::trap::{trap_handler, TrapContext};
+use alloc::boxed::Box;
+use core::fmt::{self, Display, Formatter};
+
+/// Distinct unknown syscall ids a task is warned about, later ones are
+/// only counted.
+const UNKNOWN_WARNINGS: usize = 8;
 
 /// task control block structure
 pub struct TaskControlBlock {
+    /// name of the app, empty if it has none fit to print
+    pub name: &'static str,
+    /// the app this task runs, loaded again by a restart
+    pub app: &'static AppEntry,
+    /// how far the user stack and the mmap search start were moved
+    pub aslr: AslrOffsets,
     pub task_status: TaskStatus,
     pub task_cx: TaskContext,
     pub memory_set: MemorySet,
     pub trap_cx_ppn: PhysPageNum,
     pub base_size: usize,
+    /// lowest program break, right above the user stack
+    pub heap_bottom: usize,
+    /// current program break, see `sys_sbrk`
+    pub program_brk: usize,
+    /// limits applied to this task's `sys_mmap` requests
+    pub map_policy: MapPolicy,
+    /// time accounting and syscall counts, see [`TaskCounters`]
+    pub counters: UPSafeCell<TaskCounters>,
+    /// calls of syscall ids the kernel does not implement, refused with -1
+    pub unknown_syscalls: usize,
+    /// unknown ids warned about already, the first `UNKNOWN_WARNINGS` only
+    pub warned_unknown: [usize; UNKNOWN_WARNINGS],
+    /// when the task was first dispatched
+    pub first_dispatched: Option<usize>,
+    /// page faults taken so far, resolved or not
+    pub page_faults: usize,
+    /// the latest of them, see `TaskManager::resolve_page_fault`
+    pub faults: FaultHistory,
+    /// bytes written to stdout so far
+    pub output_bytes: usize,
+    /// deepest use of the kernel stack, taken when the stack is freed
+    pub kernel_stack_peak: Option<usize>,
+    /// set once the task was warned about its kernel stack usage
+    pub kernel_stack_warned: bool,
+    /// frames backing the task's user pages, see `charge_pages`
+    pub current_pages: usize,
+    /// most `current_pages` so far
+    pub peak_pages: usize,
+    /// times the task yielded the processor
+    pub voluntary_switches: usize,
+    /// times the task was suspended at the end of its time slice
+    pub preempted_switches: usize,
+    /// when a sleeping `Blocked` task becomes `Ready` again, in microseconds
+    pub wakeup_time_us: usize,
+    /// task whose exit this one waits for while `Blocked`, see
+    /// `sys_wait_task`; `None` for a sleeper
+    pub waiting_on: Option<usize>,
+    /// set while `Blocked` until any child of this one exits, see
+    /// `sys_waitpid`
+    pub waiting_child: bool,
+    /// task that spawned this one, until it exits or reaps this one
+    pub parent: Option<usize>,
+    /// (user, kernel) cycles of the children this one reaped, their own
+    /// reaped children's included, see `sys_times`
+    pub children_times: (usize, usize),
+    /// message queue this one is blocked on, see `sys_mq_send`
+    pub waiting_queue: Option<usize>,
+    /// what the queue call the task blocked in returns once it is woken
+    pub queue_result: isize,
+    /// why the task was killed while it was not running; it exits for it
+    /// before returning to user mode, see `sys_kill`
+    pub pending_exit: Option<ExitReason>,
+    /// timer ticks left in the current slice, refilled on dispatch
+    pub remaining_ticks: usize,
+    /// stride scheduling priority, at least 2
+    pub priority: usize,
+    /// what `pass` advances by per dispatch, `BIG_STRIDE / priority`
+    pub stride: usize,
+    /// virtual time of the task, wrapping; the smallest one runs next
+    pub pass: usize,
+    /// when the task last became `Ready`
+    pub ready_since: usize,
+    /// longest the task waited `Ready` for a dispatch, in cycles
+    pub longest_ready: usize,
+    /// when the last starvation warning about the task was given
+    pub starvation_warned_at: usize,
+    /// scheduler epoch of the task's last dispatch or, once it stopped
+    /// running, of the moment it stopped
+    pub last_epoch: usize,
+    /// `TaskManagerInner::blocks` when the task last became `Blocked`;
+    /// tasks woken together become `Ready` in this order
+    pub blocked_seq: usize,
+    /// set once the task is `Exited`
+    pub obituary: Option<TaskObituary>,
+    /// times `sys_task_restart` ran the app again in this slot; the
+    /// obituary is of the last run only. Also the generation in the ids of
+    /// this run, see [`crate::config::TASK_SLOT_BITS`]
+    pub restarts: usize,
+    /// exit code of the run before this one in the slot, see
+    /// `sys_wait_task`
+    pub previous_exit_code: Option<i32>,
+    /// privileged operations this task may perform, can only shrink
+    pub caps: CapSet,
+    /// syscalls the task may still make, see `sys_syscall_filter`
+    pub syscall_filter: SyscallFilter,
+    /// syscall trace, lent from the pool while tracing is enabled
+    pub trace: Option<Box<TraceRing>>,
+    /// whether the task's syscalls are logged, see `sys_strace`
+    pub strace: bool,
+    /// timer samples of the user pc, lent from the pool at the first
+    /// sample if the `profile` feature is on
+    pub profile: Option<Box<Profile>>,
+}
+
+/// Counters of a task updated on every trap and syscall.
+///
+/// They sit in a cell of their own, so that the trap path and the syscall
+/// dispatcher update them under a shared borrow of the task manager, see
+/// `TaskManager::current_task_with`, and the task manager's own cell only
+/// guards scheduling state.
+pub struct TaskCounters {
+    /// cycles spent running in user mode
+    pub user_time: usize,
+    /// cycles spent in the kernel on behalf of this task
+    pub kernel_time: usize,
+    /// start of the interval not yet charged to `user_time` or `kernel_time`
+    pub time_stamp: usize,
+    /// cycles of `kernel_time` spent resolving page faults
+    pub fault_service_time: usize,
+    /// syscalls made so far
+    pub syscall_count: usize,
+    /// syscalls made so far, by id; ids past the end are only in `syscall_count`
+    pub syscall_counts: SyscallCounts,
+    /// when the task was last switched to, until its next trap
+    pub dispatched_at: Option<usize>,
+    /// from a switch to the task to its next trap, first dispatch excluded
+    pub dispatch_latency: LatencyDigest,
+}
+
+impl TaskCounters {
+    const fn new() -> Self {
+        Self {
+            user_time: 0,
+            kernel_time: 0,
+            time_stamp: 0,
+            fault_service_time: 0,
+            syscall_count: 0,
+            syscall_counts: SyscallCounts::new(),
+            dispatched_at: None,
+            dispatch_latency: LatencyDigest::empty(),
+        }
+    }
+    /// Charge the time since the last stamp to the user time, when the task
+    /// traps into the kernel at `now`.
+    pub fn charge_user(&mut self, now: usize) {
+        self.user_time += now - self.time_stamp;
+        self.time_stamp = now;
+        if let Some(dispatched_at) = self.dispatched_at.take() {
+            self.dispatch_latency.record(now - dispatched_at);
+        }
+    }
+    /// Charge the time since the last stamp to the kernel time, when the
+    /// task returns to user mode or stops running at `now`.
+    pub fn charge_kernel(&mut self, now: usize) {
+        self.kernel_time += now - self.time_stamp;
+        self.time_stamp = now;
+    }
+    /// (user, kernel) time in cycles of the running task up to `now`; the
+    /// task is in the kernel, so the time since the last stamp is kernel time.
+    pub fn cpu_times(&self, now: usize) -> (usize, usize) {
+        (self.user_time, self.kernel_time + (now - self.time_stamp))
+    }
 }
 
 impl TaskControlBlock {
     pub fn get_trap_cx(&self) -> &'static mut TrapContext {
         self.trap_cx_ppn.get_mut()
     }
+    /// Frames a new task of `app` takes at most: its segments, user stack,
+    /// trap context and kernel stack, and the page tables mapping them.
+    pub fn frames_needed(app: &AppEntry) -> usize {
+        // a root, and a level-1 and two last-level tables per end of the
+        // user space, one more for the kernel stack
+        let tables = 1 + 2 * 3 + 1;
+        app.elf.load_pages + (USER_STACK_SIZE + KERNEL_STACK_SIZE) / PAGE_SIZE + 1 + tables
+    }
+    /// How diagnostics name this task, which has id `id`.
+    pub fn label(&self, id: usize) -> TaskLabel {
+        TaskLabel {
+            id,
+            name: self.name,
+        }
+    }
+    /// Whether this task is `Blocked` until its wakeup time, rather than
+    /// until another task exits or serves its queue call.
+    pub fn is_sleeping(&self) -> bool {
+        self.task_status == TaskStatus::Blocked
+            && self.waiting_on.is_none()
+            && !self.waiting_child
+            && self.waiting_queue.is_none()
+    }
+    /// Count `delta` frames gained, or given back if negative, by the task's
+    /// user pages. Debug builds check the count against the memory set.
+    pub fn charge_pages(&mut self, delta: isize) {
+        self.current_pages = (self.current_pages as isize + delta) as usize;
+        self.peak_pages = self.peak_pages.max(self.current_pages);
+        if cfg!(debug_assertions) {
+            assert_eq!(
+                self.current_pages,
+                self.memory_set.resident_pages(),
+                "page count out of sync with the memory set"
+            );
+        }
+    }
+    /// Refuse a call of `id`, which the kernel does not implement: it is
+    /// counted apart from the known ones, and warned about the first time.
+    pub fn reject_unknown_syscall(&mut self, id: usize, task_id: usize) {
+        self.counters.get_mut().syscall_counts.forget(id);
+        self.unknown_syscalls += 1;
+        if self.warned_unknown.contains(&id) {
+            return;
+        }
+        if let Some(slot) = self
+            .warned_unknown
+            .iter_mut()
+            .find(|slot| **slot == usize::MAX)
+        {
+            *slot = id;
+            warn!(
+                "[kernel] app {} made unknown syscall {}, returned -1",
+                self.label(task_id),
+                id
+            );
+        }
+    }
+    /// First phase of teardown: charge the kernel time up to `now`, freeze
+    /// the obituary and hand back the user address space to be freed.
+    ///
+    /// The obituary is written once and never touched again. The kernel
+    /// stack stays mapped, the task is still running on it; the next
+    /// dispatch frees it, see `reap_kernel_stacks`.
+    pub fn bury(&mut self, reason: ExitReason, now: usize) -> MemorySet {
+        debug_assert!(self.obituary.is_none(), "task buried twice");
+        let counters = self.counters.get_mut();
+        counters.charge_kernel(now);
+        self.obituary = Some(TaskObituary {
+            reason,
+            exit_code: reason.exit_code(),
+            user_time: counters.user_time,
+            kernel_time: counters.kernel_time,
+            syscalls: counters.syscall_count,
+            peak_pages: self.memory_set.peak_user_pages(),
+            page_faults: self.page_faults,
+            output_bytes: self.output_bytes,
+            exited_at: now,
+        });
+        core::mem::replace(&mut self.memory_set, MemorySet::new_bare())
+    }
+    /// Set the stride scheduling priority; a huge one still lets `pass`
+    /// advance.
+    pub fn set_priority(&mut self, priority: usize) {
+        self.priority = priority;
+        self.stride = (BIG_STRIDE / priority).max(1);
+    }
     pub fn get_user_token(&self) -> usize {
         self.memory_set.token()
     }
-    pub fn new(elf_data: &[u8], app_id: usize) -> Self {
+    pub fn new(app: &'static AppEntry, app_id: usize) -> Self {
         // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        let aslr = AslrOffsets::pick();
+        let (memory_set, user_sp, entry_point) =
+            MemorySet::from_elf(app.data, app.elf.exec_stack, aslr);
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(TRAP_CONTEXT).into())
             .unwrap()
             .ppn();
         let task_status = TaskStatus::Ready;
-        // map a kernel-stack in kernel space
+        // the ELF segments, the user stack and the trap context
+        let resident_pages = memory_set.resident_pages();
+        // map a kernel-stack in kernel space, unless an earlier boot pass
+        // already did: the rebooting task is still running on it
         let (kernel_stack_bottom, kernel_stack_top) = kernel_stack_position(app_id);
-        KERNEL_SPACE.lock().insert_framed_area(
-            kernel_stack_bottom.into(),
-            kernel_stack_top.into(),
-            MapPermission::R | MapPermission::W,
-        );
+        let mut kernel_space = KERNEL_SPACE.lock();
+        let stack_mapped = kernel_space
+            .translate(VirtAddr::from(kernel_stack_bottom).floor())
+            .map_or(false, |pte| pte.is_valid());
+        if !stack_mapped {
+            kernel_space
+                .insert_framed_area(
+                    kernel_stack_bottom.into(),
+                    kernel_stack_top.into(),
+                    MapPermission::R | MapPermission::W,
+                )
+                .expect("no frames left for a kernel stack");
+        }
+        drop(kernel_space);
+        stack_probe::poison_kernel_stack(app_id);
         let task_control_block = Self {
+            name: app.name,
+            app,
+            aslr,
             task_status,
             task_cx: TaskContext::goto_trap_return(kernel_stack_top),
             memory_set,
             trap_cx_ppn,
             base_size: user_sp,
+            heap_bottom: user_sp,
+            program_brk: user_sp,
+            map_policy: MapPolicy::default(),
+            counters: unsafe { UPSafeCell::new(TaskCounters::new()) },
+            unknown_syscalls: 0,
+            warned_unknown: [usize::MAX; UNKNOWN_WARNINGS],
+            first_dispatched: None,
+            page_faults: 0,
+            faults: FaultHistory::empty(),
+            output_bytes: 0,
+            kernel_stack_peak: None,
+            kernel_stack_warned: false,
+            current_pages: resident_pages,
+            peak_pages: resident_pages,
+            voluntary_switches: 0,
+            preempted_switches: 0,
+            wakeup_time_us: 0,
+            waiting_on: None,
+            waiting_child: false,
+            parent: None,
+            children_times: (0, 0),
+            waiting_queue: None,
+            queue_result: 0,
+            pending_exit: None,
+            remaining_ticks: TICKS_PER_SLICE,
+            priority: DEFAULT_PRIORITY,
+            stride: BIG_STRIDE / DEFAULT_PRIORITY,
+            pass: 0,
+            ready_since: get_time(),
+            longest_ready: 0,
+            starvation_warned_at: 0,
+            last_epoch: 0,
+            blocked_seq: 0,
+            obituary: None,
+            restarts: 0,
+            previous_exit_code: None,
+            caps: CapSet::granted_to(app.name),
+            syscall_filter: SyscallFilter::allow_all(),
+            trace: None,
+            strace: TRACE_SYSCALLS,
+            profile: None,
         };
         // prepare TrapContext in user space
         let trap_cx = task_control_block.get_trap_cx();
@@ -55,11 +380,225 @@ impl TaskControlBlock {
     }
 }
 
-#[derive(Copy, Clone, PartialEq)]
-/// task status: UnInit, Ready, Running, Exited
+/// A task as diagnostics print it, `3 (ch4b_sbrk)`, or just its id if it
+/// has no name.
+#[derive(Copy, Clone)]
+pub struct TaskLabel {
+    pub id: usize,
+    pub name: &'static str,
+}
+
+impl TaskLabel {
+    /// The name, or the id if there is none, as
+    /// [`crate::abi::TaskInfo::name`] holds it.
+    pub fn to_c_name(self) -> [u8; TASK_NAME_LEN] {
+        let mut buf = [0; TASK_NAME_LEN];
+        let mut digits = [0; 20];
+        let name = if self.name.is_empty() {
+            let (mut id, mut start) = (self.id, digits.len());
+            loop {
+                start -= 1;
+                digits[start] = b'0' + (id % 10) as u8;
+                id /= 10;
+                if id == 0 {
+                    break &digits[start..];
+                }
+            }
+        } else {
+            self.name.as_bytes()
+        };
+        let len = name.len().min(TASK_NAME_LEN - 1);
+        buf[..len].copy_from_slice(&name[..len]);
+        buf
+    }
+}
+
+impl Display for TaskLabel {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if self.name.is_empty() {
+            write!(f, "{}", self.id)
+        } else {
+            write!(f, "{} ({})", self.id, self.name)
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+/// task status: UnInit, Ready, Running, Exited, Blocked
 pub enum TaskStatus {
     UnInit,
     Ready,
     Running,
     Exited,
+    /// asleep until `wakeup_time_us`, until `waiting_on` or a child exits,
+    /// or until `waiting_queue` serves it
+    Blocked,
+}
+
+/// What is left of a task once it has exited.
+#[derive(Copy, Clone, Debug)]
+pub struct TaskObituary {
+    pub reason: ExitReason,
+    /// see [`ExitReason::exit_code`]
+    pub exit_code: i32,
+    /// cycles, as in the control block
+    pub user_time: usize,
+    pub kernel_time: usize,
+    pub syscalls: usize,
+    pub peak_pages: usize,
+    pub page_faults: usize,
+    pub output_bytes: usize,
+    /// when the task exited, in cycles
+    pub exited_at: usize,
+}
+
+/// why a task stopped running, recorded by [`super::exit_current_and_run_next`]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ExitReason {
+    /// the task called `sys_exit` with this code
+    Normal(i32),
+    /// the task was killed for a fault at this address
+    Fault(FaultKind, usize),
+    /// another task with this id killed it
+    Killed(usize),
+    /// the task made this syscall, which its syscall filter forbids
+    FilteredSyscall(usize),
+    /// waiting for the task with this id, or for any child if it is the
+    /// task's own, would have closed a cycle of tasks waiting for each other
+    Deadlock(usize),
+    /// the kernel was shut down by `sys_shutdown` before the task exited
+    Shutdown,
+    /// the task was blocked on a message queue with no task left that could
+    /// serve it
+    Stranded,
+}
+
+impl ExitReason {
+    /// The code a task exited with: its own after `sys_exit`, otherwise a
+    /// negative one per reason. -1 is left out, it is what a panicking app
+    /// passes to `sys_exit`; so are -7 and -10, which no reason has.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ExitReason::Normal(code) => code,
+            ExitReason::Fault(FaultKind::PageFault, _) => -2,
+            ExitReason::Fault(FaultKind::IllegalInstruction, _) => -3,
+            ExitReason::Fault(FaultKind::InstructionFault, _) => -4,
+            ExitReason::Fault(FaultKind::NonTextExecution, _) => -5,
+            ExitReason::Fault(FaultKind::OtherException, _) => -6,
+            ExitReason::FilteredSyscall(_) => -8,
+            ExitReason::Killed(_) => -9,
+            ExitReason::Fault(FaultKind::StackOverflow, _) => -11,
+            ExitReason::Deadlock(_) => -12,
+            ExitReason::Fault(FaultKind::ReservedAccess, _) => -13,
+            ExitReason::Shutdown => -14,
+            ExitReason::Stranded => -15,
+        }
+    }
+}
+
+/// why a task gave up the processor while it could still run
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SwitchReason {
+    /// the task asked for it with `sys_yield`
+    Yield,
+    /// its time slice ran out
+    Preempted,
+}
+
+/// faults that kill a task
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FaultKind {
+    PageFault,
+    InstructionFault,
+    IllegalInstruction,
+    /// executed outside its ELF text while that is forbidden
+    NonTextExecution,
+    /// any other exception raised in user mode, such as a breakpoint
+    OtherException,
+    /// touched the guard page below the user stack
+    StackOverflow,
+    /// touched a page reserved by `sys_mmap` without committing it
+    ReservedAccess,
+}
+
+bitflags! {
+    /// capabilities gating privileged syscalls
+    pub struct CapSet: u32 {
+        /// inspect other tasks and kernel internals
+        const DEBUG_INSPECT = 1 << 0;
+        /// map device memory
+        const DEVICE_MAP = 1 << 1;
+        /// soft reboot the kernel
+        const REBOOT = 1 << 2;
+        /// change limits of, kill or restart other tasks
+        const CROSS_TASK_CONTROL = 1 << 3;
+        /// trace other tasks
+        const TRACE = 1 << 4;
+    }
+}
+
+impl CapSet {
+    /// Capabilities an app starts with, see [`CAP_DEFAULT`] and [`CAP_GRANTS`].
+    pub fn granted_to(name: &str) -> Self {
+        CAP_GRANTS
+            .iter()
+            .filter(|(app, _)| *app == name)
+            .fold(CAP_DEFAULT, |caps, (_, grant)| caps | *grant)
+    }
+}
+
+/// words of a [`SyscallFilter`] bitmap
+const FILTER_WORDS: usize = (MAX_SYSCALL_NUM + 63) / 64;
+
+/// The syscall ids a task may make; it only ever shrinks.
+#[derive(Copy, Clone)]
+pub struct SyscallFilter {
+    /// bitmap of the allowed ids, `None` until a filter is installed: every
+    /// id is then allowed, those past the bitmap included
+    allowed: Option<[u64; FILTER_WORDS]>,
+}
+
+impl SyscallFilter {
+    pub const fn allow_all() -> Self {
+        Self { allowed: None }
+    }
+
+    /// Allow only the syscalls in `ids`, `None` if one is out of range.
+    pub fn only(ids: &[usize]) -> Option<Self> {
+        let mut allowed = [0; FILTER_WORDS];
+        for &id in ids {
+            if id >= MAX_SYSCALL_NUM {
+                return None;
+            }
+            allowed[id / 64] |= 1 << (id % 64);
+        }
+        Some(Self {
+            allowed: Some(allowed),
+        })
+    }
+
+    /// Keep only the syscalls `other` allows as well.
+    pub fn narrow(&mut self, other: &Self) {
+        let other = match other.allowed {
+            Some(other) => other,
+            None => return,
+        };
+        match self.allowed.as_mut() {
+            Some(allowed) => {
+                for (word, other) in allowed.iter_mut().zip(other.iter()) {
+                    *word &= other;
+                }
+            }
+            None => self.allowed = Some(other),
+        }
+    }
+
+    /// Whether id `id` may be made; one past the bitmap only while no filter
+    /// is installed, when it is refused as unknown instead.
+    pub fn allows(&self, id: usize) -> bool {
+        match self.allowed {
+            Some(allowed) => id < MAX_SYSCALL_NUM && allowed[id / 64] & (1 << (id % 64)) != 0,
+            None => true,
+        }
+    }
 }
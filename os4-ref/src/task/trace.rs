@@ -0,0 +1,69 @@
+//! Per-task syscall trace ring
+
+use crate::abi::TraceEntry;
+use crate::timer::get_time_us;
+use core::mem::size_of;
+
+/// size of the ring in bytes
+const TRACE_RING_BYTES: usize = 4096;
+/// entries the ring holds before overwriting the oldest
+pub const TRACE_ENTRIES: usize = TRACE_RING_BYTES / size_of::<TraceEntry>();
+
+/// Fixed-size ring of [`TraceEntry`], allocated once when tracing is enabled
+/// so that recording never allocates.
+pub struct TraceRing {
+    entries: [TraceEntry; TRACE_ENTRIES],
+    /// entries ever written, the next one goes to `written % TRACE_ENTRIES`
+    written: usize,
+    /// entries ever drained or skipped
+    read: usize,
+    /// time of the previous entry in microseconds
+    last_us: usize,
+}
+
+impl TraceRing {
+    /// An empty ring whose first entry is timed from now.
+    pub fn starting_now() -> Self {
+        Self {
+            entries: [TraceEntry::default(); TRACE_ENTRIES],
+            written: 0,
+            read: 0,
+            last_us: get_time_us(),
+        }
+    }
+
+    /// Record one finished syscall, overwriting the oldest entry when full.
+    pub fn record(&mut self, syscall_id: usize, arg0: usize, ret: isize) {
+        let now = get_time_us();
+        let delta = (now - self.last_us).min(u32::MAX as usize);
+        self.last_us = now;
+        self.written += 1;
+        self.entries[(self.written - 1) % TRACE_ENTRIES] = TraceEntry {
+            seq: self.written as u32,
+            delta_us: delta as u32,
+            syscall_id: syscall_id as u32,
+            arg0: arg0 as u32,
+            ret: ret as i64,
+        };
+    }
+
+    /// Move the oldest unread entries into `out`, returning how many.
+    ///
+    /// Entries overwritten before they were read are skipped, as are entries
+    /// whose sequence number shows they were rewritten under the reader.
+    pub fn drain(&mut self, out: &mut [TraceEntry]) -> usize {
+        if self.written - self.read > TRACE_ENTRIES {
+            self.read = self.written - TRACE_ENTRIES;
+        }
+        let mut count = 0;
+        while count < out.len() && self.read < self.written {
+            let entry = self.entries[self.read % TRACE_ENTRIES];
+            self.read += 1;
+            if entry.seq == self.read as u32 {
+                out[count] = entry;
+                count += 1;
+            }
+        }
+        count
+    }
+}
@@ -1,23 +1,66 @@
 //! RISC-V timer-related functionality
 
-use crate::config::CLOCK_FREQ;
+use crate::config::{CLOCK_FREQ, CLOCK_TICKS_PER_SEC, TIME_SLICE_PROFILES};
 use crate::sbi::set_timer;
-use riscv::register::time;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use riscv::register::{sip, time};
 
-const TICKS_PER_SEC: usize = 100;
+/// timer interrupts per second, switched by a soft reboot
+static TICKS_PER_SEC: AtomicUsize = AtomicUsize::new(TIME_SLICE_PROFILES[0]);
 const MICRO_PER_SEC: usize = 1_000_000;
+const NANO_PER_SEC: usize = 1_000_000_000;
 
-/// read the `mtime` register
+/// read the `mtime` register, the raw cycles all time accounting is kept in
 pub fn get_time() -> usize {
     time::read()
 }
 
 /// get current time in microseconds
 pub fn get_time_us() -> usize {
-    time::read() / (CLOCK_FREQ / MICRO_PER_SEC)
+    cycles_to(time::read(), MICRO_PER_SEC)
+}
+
+/// get current time in nanoseconds
+pub fn get_time_ns() -> usize {
+    cycles_to(time::read(), NANO_PER_SEC)
+}
+
+/// convert `mtime` cycles into milliseconds
+pub fn cycles_to_ms(cycles: usize) -> usize {
+    cycles_to(cycles, 1000)
+}
+
+/// convert `mtime` cycles into `sys_times` clock ticks
+pub fn cycles_to_clock_ticks(cycles: usize) -> usize {
+    cycles_to(cycles, CLOCK_TICKS_PER_SEC)
+}
+
+/// `cycles` in units of `1 / per_sec` seconds, rounded down. The product
+/// is taken in 128 bits, so that a `CLOCK_FREQ` that is not a multiple of
+/// `per_sec` does not skew the result.
+fn cycles_to(cycles: usize, per_sec: usize) -> usize {
+    (cycles as u128 * per_sec as u128 / CLOCK_FREQ as u128) as usize
+}
+
+/// change the time slice length used from the next timer interrupt on
+pub fn set_ticks_per_sec(ticks: usize) {
+    TICKS_PER_SEC.store(ticks, Ordering::Relaxed);
+}
+
+/// length of a timer tick in cycles
+pub fn tick_cycles() -> usize {
+    CLOCK_FREQ / TICKS_PER_SEC.load(Ordering::Relaxed)
+}
+
+/// whether a timer tick is due
+///
+/// The kernel runs with interrupts off, but the timer interrupt still shows
+/// up as pending once it is due.
+pub fn slice_over() -> bool {
+    sip::read().stimer()
 }
 
 /// set the next timer interrupt
 pub fn set_next_trigger() {
-    set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
+    set_timer(get_time() + tick_cycles());
 }
@@ -11,14 +11,27 @@
 //! It then calls different functionality based on what exactly the exception
 //! was. For example, timer interrupts trigger task preemption, and syscalls go
 //! to [`syscall()`].
+//!
+//! Timer interrupts are split in two halves. The top half in
+//! [`trap_handler()`] only reprograms the timer, counts the tick and makes the
+//! preemption decision. Statistical bookkeeping is deferred to the bottom half,
+//! which runs in [`trap_return()`] once the next task has been chosen.
 mod context;
 
 use crate::config::{TRAMPOLINE, TRAP_CONTEXT};
+use crate::kstat::{self, KSTAT};
+use crate::stack_probe::StackPath;
 use crate::syscall::syscall;
 use crate::task::{
-    current_trap_cx, current_user_token, exit_current_and_run_next, suspend_current_and_run_next,
+    check_current_kernel_stack, check_starvation, current_exec_violation, current_pending_exit,
+    current_reservation_hit, current_stack_guard_hit, current_task_label, current_trap_cx,
+    dump_current_address_space, exit_current_and_run_next, profile_current_tick, ready_task_count,
+    record_fatal_fault, resolve_page_fault, suspend_current_and_run_next_with_reason,
+    sync_current_user_tlb, tick_current, user_time_end, user_time_start, ExitReason, FaultKind,
+    SwitchReason,
 };
-use crate::timer::set_next_trigger;
+use crate::timer::{get_time, set_next_trigger};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use riscv::register::{
     mtvec::TrapMode,
     scause::{self, Exception, Interrupt, Trap},
@@ -49,30 +62,158 @@ pub fn enable_timer_interrupt() {
     }
 }
 
+/// cycle count at the latest trap entry, 0 once consumed by `trap_return`
+static TRAP_ENTRY_CYCLES: AtomicUsize = AtomicUsize::new(0);
+/// set by the timer top half, consumed by the bottom half
+static TICK_WORK_PENDING: AtomicBool = AtomicBool::new(false);
+/// guards against the bottom half being entered while it runs
+static IN_BOTTOM_HALF: AtomicBool = AtomicBool::new(false);
+
+/// Timer top half: exact per-tick work only.
+fn timer_top_half() {
+    set_next_trigger();
+    kstat::inc(&KSTAT.ticks);
+    TICK_WORK_PENDING.store(true, Ordering::Relaxed);
+}
+
+/// Timer bottom half: statistics that may lag a tick behind.
+fn run_bottom_half() {
+    if !TICK_WORK_PENDING.swap(false, Ordering::Relaxed) {
+        return;
+    }
+    assert!(
+        !IN_BOTTOM_HALF.swap(true, Ordering::Acquire),
+        "bottom half entered re-entrantly"
+    );
+    if !kstat::ENABLED {
+        IN_BOTTOM_HALF.store(false, Ordering::Release);
+        return;
+    }
+    let start = get_time();
+    // ewma = ewma * 7/8 + sample/8, kept scaled by 256
+    let sample = ready_task_count() << 8;
+    let ewma = KSTAT.ready_ewma.load(Ordering::Relaxed);
+    KSTAT
+        .ready_ewma
+        .store(ewma - (ewma >> 3) + (sample >> 3), Ordering::Relaxed);
+    check_starvation();
+    kstat::record_max(&KSTAT.max_bottom_half_cycles, get_time() - start);
+    IN_BOTTOM_HALF.store(false, Ordering::Release);
+}
+
+/// Kill the current task for exception `cause`, raised at `sepc` with
+/// `stval`, and run the next one; the kernel only panics for its own faults.
+fn kill_current(cause: Exception, kind: FaultKind, stval: usize, sepc: usize, addr: usize) {
+    error!(
+        "[kernel] {:?} in application, task = {}, stval = {:#x}, sepc = {:#x}, core dumped.",
+        cause,
+        current_task_label(),
+        stval,
+        sepc
+    );
+    if kind == FaultKind::PageFault {
+        dump_current_address_space();
+    }
+    exit_current_and_run_next(ExitReason::Fault(kind, addr));
+}
+
+/// Kill the current task for touching `va`, which it only reserved.
+fn kill_reserved_access(va: usize, sepc: usize) {
+    error!(
+        "[kernel] access to reserved mapping in task {} at {:#x}, sepc = {:#x}, core dumped.",
+        current_task_label(),
+        va,
+        sepc
+    );
+    exit_current_and_run_next(ExitReason::Fault(FaultKind::ReservedAccess, va));
+}
+
 #[no_mangle]
 pub fn trap_handler() -> ! {
     set_kernel_trap_entry();
+    stack_probe!(StackPath::TrapHandler);
+    TRAP_ENTRY_CYCLES.store(get_time(), Ordering::Relaxed);
+    user_time_end();
     let cx = current_trap_cx();
     let scause = scause::read();
     let stval = stval::read();
+    if let Some(kind) = current_exec_violation(cx.sepc) {
+        error!(
+            "[kernel] NonTextExecution in application, task = {}, pc = {:#x} in {:?} area, core dumped.",
+            current_task_label(),
+            cx.sepc,
+            kind
+        );
+        exit_current_and_run_next(ExitReason::Fault(FaultKind::NonTextExecution, cx.sepc));
+    }
     match scause.cause() {
         Trap::Exception(Exception::UserEnvCall) => {
+            // the only place sepc moves past an ecall
+            let ecall_pc = cx.sepc;
             cx.sepc += 4;
-            cx.x[10] = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]) as usize;
+            cx.x[10] = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12], cx.x[13]]) as usize;
+            // no syscall runs an ecall again or sends the task elsewhere
+            debug_assert!(
+                cx.sepc.wrapping_sub(ecall_pc) == 4,
+                "sepc moved from {:#x} to {:#x} across a syscall",
+                ecall_pc,
+                cx.sepc
+            );
         }
-        Trap::Exception(Exception::StoreFault)
-        | Trap::Exception(Exception::StorePageFault)
-        | Trap::Exception(Exception::LoadPageFault) => {
-            error!("[kernel] PageFault in application, bad addr = {:#x}, bad instruction = {:#x}, core dumped.", stval, cx.sepc);
-            exit_current_and_run_next();
+        Trap::Exception(
+            cause @ (Exception::StoreFault
+            | Exception::StorePageFault
+            | Exception::LoadFault
+            | Exception::LoadPageFault),
+        ) => {
+            if !resolve_page_fault(cause, stval, cx.sepc) {
+                if current_stack_guard_hit(stval) {
+                    error!(
+                        "[kernel] user stack overflow in task {} at {:#x}, sepc = {:#x}, core dumped.",
+                        current_task_label(),
+                        stval,
+                        cx.sepc
+                    );
+                    exit_current_and_run_next(ExitReason::Fault(FaultKind::StackOverflow, stval));
+                } else if current_reservation_hit(stval) {
+                    kill_reserved_access(stval, cx.sepc);
+                } else {
+                    kill_current(cause, FaultKind::PageFault, stval, cx.sepc, stval);
+                }
+            }
         }
-        Trap::Exception(Exception::IllegalInstruction) => {
-            error!("[kernel] IllegalInstruction in application, core dumped.");
-            exit_current_and_run_next();
+        Trap::Exception(
+            cause @ (Exception::InstructionFault | Exception::InstructionPageFault),
+        ) => {
+            record_fatal_fault(cause, stval, cx.sepc);
+            if current_reservation_hit(stval) {
+                kill_reserved_access(stval, cx.sepc);
+            }
+            kill_current(cause, FaultKind::InstructionFault, stval, cx.sepc, stval);
+        }
+        Trap::Exception(cause @ Exception::IllegalInstruction) => {
+            kill_current(
+                cause,
+                FaultKind::IllegalInstruction,
+                stval,
+                cx.sepc,
+                cx.sepc,
+            );
         }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
-            set_next_trigger();
-            suspend_current_and_run_next();
+            timer_top_half();
+            // the kernel runs with interrupts off, so every tick lands in user mode
+            if cfg!(feature = "profile") {
+                profile_current_tick(cx.sepc);
+            }
+            if tick_current() {
+                suspend_current_and_run_next_with_reason(SwitchReason::Preempted);
+            }
+        }
+        // every exception here was raised in user mode, kernel ones go to
+        // `trap_from_kernel`
+        Trap::Exception(cause) => {
+            kill_current(cause, FaultKind::OtherException, stval, cx.sepc, cx.sepc);
         }
         _ => {
             panic!(
@@ -82,14 +223,27 @@ pub fn trap_handler() -> ! {
             );
         }
     }
+    if cfg!(feature = "stack-watermark") {
+        check_current_kernel_stack();
+    }
     trap_return();
 }
 
 #[no_mangle]
 pub fn trap_return() -> ! {
+    // a task killed while it was not running ends before it gets back
+    if let Some(reason) = current_pending_exit() {
+        exit_current_and_run_next(reason);
+    }
+    run_bottom_half();
+    let entry = TRAP_ENTRY_CYCLES.swap(0, Ordering::Relaxed);
+    if entry != 0 {
+        kstat::record_max(&KSTAT.max_trap_cycles, get_time() - entry);
+    }
+    user_time_start();
     set_user_trap_entry();
     let trap_cx_ptr = TRAP_CONTEXT;
-    let user_satp = current_user_token();
+    let user_satp = sync_current_user_tlb();
     extern "C" {
         fn __alltraps();
         fn __restore();
@@ -112,4 +266,31 @@ pub fn trap_from_kernel() -> ! {
     panic!("a trap from kernel!");
 }
 
+/// Print the registers saved in `cx`, four to a line.
+pub fn dump_trap_cx(cx: &TrapContext) {
+    const NAMES: [&str; 32] = [
+        "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+        "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+        "t5", "t6",
+    ];
+    println!(
+        "[kernel] sepc {:#018x} sstatus {:#018x}",
+        cx.sepc,
+        cx.sstatus.bits()
+    );
+    for i in (0..32).step_by(4) {
+        println!(
+            "  {:>4} {:#018x}  {:>4} {:#018x}  {:>4} {:#018x}  {:>4} {:#018x}",
+            NAMES[i],
+            cx.x[i],
+            NAMES[i + 1],
+            cx.x[i + 1],
+            NAMES[i + 2],
+            cx.x[i + 2],
+            NAMES[i + 3],
+            cx.x[i + 3]
+        );
+    }
+}
+
 pub use context::TrapContext;
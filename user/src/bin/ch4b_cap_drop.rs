@@ -0,0 +1,24 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{cap_drop, reboot};
+
+/// 正确输出：
+/// Test cap drop OK!
+
+const CAP_REBOOT: usize = 1 << 2;
+
+#[no_mangle]
+fn main() -> i32 {
+    // dropping nothing reports the current set, this app is granted REBOOT
+    assert_eq!(cap_drop(0) as usize & CAP_REBOOT, CAP_REBOOT);
+    assert_eq!(cap_drop(CAP_REBOOT) as usize & CAP_REBOOT, 0);
+    assert_eq!(reboot(0), -1);
+    // dropped capabilities stay dropped
+    assert_eq!(cap_drop(0) as usize & CAP_REBOOT, 0);
+    println!("Test cap drop OK!");
+    0
+}
@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    get_time, mmap, munmap, syscall, TimeVal, Tms, SYSCALL_GETTIMEOFDAY, SYSCALL_TIMES,
+};
+
+/// 正确输出：
+/// Test copy user OK!
+
+const PAGE: usize = 4096;
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    assert_eq!(mmap(start, 2 * PAGE, 3), 0);
+    assert_eq!(mmap(start + 2 * PAGE, PAGE, 1), 0);
+    // each struct starts 8 bytes before the end of the first page
+    let boundary = start + PAGE - 8;
+    let before = get_time();
+    assert_eq!(syscall(SYSCALL_GETTIMEOFDAY, [boundary, 0, 0]), 0);
+    let time = unsafe { &*(boundary as *const TimeVal) };
+    assert!(time.usec < 1_000_000);
+    assert!((time.sec * 1000 + time.usec / 1000) as isize >= before);
+    // back-to-back calls never go backwards
+    let first = (time.sec, time.usec);
+    assert_eq!(syscall(SYSCALL_GETTIMEOFDAY, [boundary, 0, 0]), 0);
+    let time = unsafe { &*(boundary as *const TimeVal) };
+    assert!((time.sec, time.usec) >= first);
+    // every field is written, those on the second page as well
+    unsafe { core::ptr::write_bytes(boundary as *mut Tms, 0xff, 1) };
+    let ticks = syscall(SYSCALL_TIMES, [boundary, 0, 0]);
+    assert!(ticks > 0);
+    let tms = unsafe { &*(boundary as *const Tms) };
+    assert!(tms.utime + tms.stime <= ticks as usize);
+    assert!(tms.cutime <= ticks as usize && tms.cstime <= ticks as usize);
+    // unmapped and read-only buffers are refused
+    for bad in [0, start + 2 * PAGE, start + 3 * PAGE] {
+        assert_eq!(syscall(SYSCALL_GETTIMEOFDAY, [bad, 0, 0]), -1);
+        assert_eq!(syscall(SYSCALL_TIMES, [bad, 0, 0]), -1);
+    }
+    assert_eq!(munmap(start, 3 * PAGE), 0);
+    println!("Test copy user OK!");
+    0
+}
@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{dump_vm, kernel_feature, mmap, munmap};
+
+require_kernel_features!(kernel_feature::DEBUG_SYSCALLS);
+
+/// 正确输出：（仅在带 debug-syscalls 特性构建的内核上加载；内核打印的地址空间中 mmap 区域各占一行）
+/// [kernel]   [0x10000000, 0x10010000) perms=RW-U frames=16 type=Framed kind=Mmap
+/// [kernel]   [0x10010000, 0x10012000) perms=R--U frames=2 type=Framed kind=Mmap
+/// Test dump vm OK!
+
+const PAGE: usize = 4096;
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    // two areas next to each other, told apart by their permissions
+    assert_eq!(mmap(start, 16 * PAGE, 3), 0);
+    assert_eq!(mmap(start + 16 * PAGE, 2 * PAGE, 1), 0);
+    for i in 0..16 {
+        unsafe {
+            ((start + i * PAGE) as *mut u8).write_volatile(i as u8);
+        }
+    }
+    assert_eq!(dump_vm(), 0);
+    assert_eq!(munmap(start, 18 * PAGE), 0);
+    println!("Test dump vm OK!");
+    0
+}
@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{config_flag, exec_text_only, kernel_config, mmap};
+
+/// 正确输出：
+/// Test exec mmap OK!
+
+/// `li a7, 124; ecall; ret`: a sys_yield, so the kernel sees us here
+const YIELD_AND_RET: [u32; 3] = [0x07c0_0893, 0x0000_0073, 0x0000_8067];
+
+/// copy [`YIELD_AND_RET`] into a fresh RWX page at `start` and return it as a function
+fn jit(start: usize) -> extern "C" fn() -> isize {
+    assert_eq!(mmap(start, 4096, 7), 0);
+    let code = unsafe { core::slice::from_raw_parts_mut(start as *mut u32, YIELD_AND_RET.len()) };
+    code.copy_from_slice(&YIELD_AND_RET);
+    unsafe {
+        core::arch::asm!("fence.i");
+        core::mem::transmute(start)
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(exec_text_only(false), 0);
+    if kernel_config().has_flag(config_flag::DENY_WX) {
+        // no page can be both written and run on this kernel
+        assert_eq!(mmap(0x10000000, 4096, 7), -1);
+        println!("Test exec mmap OK!");
+        return 0;
+    }
+    let f = jit(0x10000000);
+    assert_eq!(f(), 0);
+    println!("Test exec mmap OK!");
+    0
+}
@@ -0,0 +1,36 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{config_flag, exec_text_only, kernel_config, mmap};
+
+/// 正确输出：程序在执行 mmap 代码时被杀死，不输出 FAIL
+/// exec text only
+
+/// `li a7, 124; ecall; ret`: a sys_yield, so the kernel sees us here
+const YIELD_AND_RET: [u32; 3] = [0x07c0_0893, 0x0000_0073, 0x0000_8067];
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    // without W^X the page is executable and the policy has to stop us,
+    // with it the page is not executable to begin with
+    let port = if kernel_config().has_flag(config_flag::DENY_WX) {
+        3
+    } else {
+        7
+    };
+    assert_eq!(mmap(start, 4096, port), 0);
+    let code = unsafe { core::slice::from_raw_parts_mut(start as *mut u32, YIELD_AND_RET.len()) };
+    code.copy_from_slice(&YIELD_AND_RET);
+    let f: extern "C" fn() -> isize = unsafe {
+        core::arch::asm!("fence.i");
+        core::mem::transmute(start)
+    };
+    assert_eq!(exec_text_only(true), 0);
+    println!("exec text only");
+    f();
+    panic!("FAIL: executed from an mmap area!");
+}
@@ -0,0 +1,19 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// 正确输出：程序在 ebreak 处被杀死，不输出 FAIL，其余程序照常完成（关机前的汇总中本程序 exit code 为 -6）
+/// before ebreak
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("before ebreak");
+    // a breakpoint nobody handles used to panic the whole kernel
+    unsafe {
+        core::arch::asm!("ebreak");
+    }
+    println!("FAIL: T.T");
+    0
+}
@@ -0,0 +1,38 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{cap_drop, kill, sleep_blocking, spawn, wait_task};
+
+/// 正确输出：（最后拉起的 ch4b_kill_child 另外打出一次 Test kill child OK!）
+/// Test kill OK!
+
+const CAP_CROSS_TASK_CONTROL: usize = 1 << 3;
+const KILLED: isize = -9;
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(kill(usize::MAX), -1);
+    // killed before it ever ran
+    let first = spawn("ch4b_kill_child\0");
+    assert!(first > 0);
+    assert_eq!(kill(first as usize), 0);
+    assert_eq!(wait_task(first as usize), KILLED);
+    assert_eq!(kill(first as usize), -1);
+    // killed while blocked in its sleep
+    let second = spawn("ch4b_kill_child\0");
+    assert!(second > 0);
+    sleep_blocking(50);
+    assert_eq!(kill(second as usize), 0);
+    assert_eq!(wait_task(second as usize), KILLED);
+    // without the capability the child is left to finish its sleep
+    assert_eq!(cap_drop(CAP_CROSS_TASK_CONTROL) as usize & CAP_CROSS_TASK_CONTROL, 0);
+    let third = spawn("ch4b_kill_child\0");
+    assert!(third > 0);
+    assert_eq!(kill(third as usize), -1);
+    assert_eq!(wait_task(third as usize), 0);
+    println!("Test kill OK!");
+    0
+}
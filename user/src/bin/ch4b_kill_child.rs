@@ -0,0 +1,18 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::sleep_blocking;
+
+/// 正确输出：（ch4b_kill 拉起的各份在打出之前就被杀掉）
+/// Test kill child OK!
+
+#[no_mangle]
+fn main() -> i32 {
+    // long enough for ch4b_kill to catch it asleep
+    sleep_blocking(200);
+    println!("Test kill child OK!");
+    0
+}
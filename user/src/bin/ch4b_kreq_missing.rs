@@ -0,0 +1,18 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::kernel_feature;
+
+require_kernel_features!(kernel_feature::SIGNALS);
+
+/// 正确输出：（本程序不应被加载，内核启动时打印）
+/// [kernel] app "ch4b_kreq_missing" skipped: MissingFeatures(32)
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("ch4b_kreq_missing should not have been loaded!");
+    -1
+}
@@ -0,0 +1,35 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mem_pressure, mmap, munmap};
+
+/// 正确输出：
+/// Test mem_pressure OK!
+
+const PAGE: usize = 4096;
+const CHUNK_PAGES: usize = 16;
+const MAX_CHUNKS: usize = 1024;
+const BASE: usize = 0x10000000;
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(mem_pressure(), 0);
+    // take frames until the kernel reports pressure, well before it runs out
+    let mut chunks = 0;
+    while mem_pressure() == 0 {
+        assert!(chunks < MAX_CHUNKS);
+        let start = BASE + chunks * CHUNK_PAGES * PAGE;
+        assert_eq!(mmap(start, CHUNK_PAGES * PAGE, 3), 0);
+        chunks += 1;
+    }
+    // releasing the cache brings it back to normal
+    for i in 0..chunks {
+        assert_eq!(munmap(BASE + i * CHUNK_PAGES * PAGE, CHUNK_PAGES * PAGE), 0);
+    }
+    assert_eq!(mem_pressure(), 0);
+    println!("Test mem_pressure OK!");
+    0
+}
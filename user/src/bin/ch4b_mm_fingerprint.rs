@@ -0,0 +1,38 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mm_fingerprint, mmap, munmap};
+
+/// 正确输出：
+/// Test mm fingerprint OK!
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    let len: usize = 4096 * 3;
+    let before = mm_fingerprint();
+    assert_eq!(mm_fingerprint(), before);
+    for i in 0..100usize {
+        let addr = start + i % 4 * len;
+        assert_eq!(mmap(addr, len, 3), 0);
+        assert_ne!(mm_fingerprint(), before);
+        assert_eq!(munmap(addr, len), 0);
+        assert_eq!(mm_fingerprint(), before);
+    }
+    // the same layout built in a different order matches as well
+    assert_eq!(mmap(start, len, 3), 0);
+    assert_eq!(mmap(start + len, len, 1), 0);
+    let both = mm_fingerprint();
+    assert_eq!(munmap(start, len), 0);
+    assert_eq!(munmap(start + len, len), 0);
+    assert_eq!(mmap(start + len, len, 1), 0);
+    assert_eq!(mmap(start, len, 3), 0);
+    assert_eq!(mm_fingerprint(), both);
+    assert_eq!(munmap(start, len * 2), 0);
+    assert_eq!(mm_fingerprint(), before);
+    println!("Test mm fingerprint OK!");
+    0
+}
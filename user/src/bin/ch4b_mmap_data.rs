@@ -0,0 +1,37 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mmap_data, munmap};
+
+/// 正确输出：
+/// Test mmap data OK!
+
+const PAGE: usize = 4096;
+/// user/data/ch4b_mmap_data.bin, the only blob: byte i is (i * 7 + 3) % 251
+const BLOB_ID: usize = 0;
+const BLOB_LEN: usize = 5000;
+const LEN: usize = 3 * PAGE;
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    // the area must hold the whole blob
+    assert_eq!(mmap_data(start, PAGE, 1, BLOB_ID), -1);
+    assert_eq!(mmap_data(start, LEN, 1, usize::MAX), -1);
+    // nor may it wrap around or reach into the kernel
+    assert_eq!(mmap_data(usize::MAX & !(PAGE - 1), LEN, 1, BLOB_ID), -1);
+    assert_eq!(mmap_data(start + 1, LEN, 1, BLOB_ID), -1);
+    // read-only, the kernel fills it in itself
+    assert_eq!(mmap_data(start, LEN, 1, BLOB_ID), 0);
+    let data = unsafe { core::slice::from_raw_parts(start as *const u8, LEN) };
+    for (i, &byte) in data.iter().enumerate() {
+        let expected = if i < BLOB_LEN { (i * 7 + 3) % 251 } else { 0 };
+        assert_eq!(byte as usize, expected, "byte {} of the area", i);
+    }
+    assert_eq!(munmap(start, LEN), 0);
+    println!("Test mmap data OK!");
+    0
+}
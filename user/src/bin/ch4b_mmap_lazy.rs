@@ -0,0 +1,45 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mem_info, mmap, munmap, write, MemInfo};
+
+/// 正确输出：
+/// written to a lazy page
+/// Test mmap lazy OK!
+
+const STDOUT: usize = 1;
+const PAGE: usize = 4096;
+/// far more than the kernel has frames for
+const LEN: usize = 64 << 20;
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    assert_eq!(mmap(start, LEN, 3), 0);
+    // only the pages touched get a frame, and they start out zeroed
+    for addr in [start, start + LEN / 2, start + LEN - PAGE] {
+        let p = addr as *mut u8;
+        unsafe {
+            assert_eq!(p.read_volatile(), 0);
+            p.write_volatile(0x5a);
+            assert_eq!(p.read_volatile(), 0x5a);
+        }
+    }
+    // the kernel writes into a page the app has not touched yet as well
+    let info = unsafe { &mut *((start + PAGE) as *mut MemInfo) };
+    assert_eq!(mem_info(info), 0);
+    assert!(info.user_pages as usize >= LEN / PAGE);
+    let msg = b"written to a lazy page\n";
+    let buf = unsafe { core::slice::from_raw_parts_mut((start + 2 * PAGE) as *mut u8, msg.len()) };
+    buf.copy_from_slice(msg);
+    assert_eq!(write(STDOUT, buf), msg.len() as isize);
+    // the pages the kernel populated are charged to the task like any other,
+    // which a debug kernel checks against the memory set at the unmap below
+    // pages never touched unmap as well
+    assert_eq!(munmap(start, LEN), 0);
+    println!("Test mmap lazy OK!");
+    0
+}
@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{kernel_config, mmap, munmap, null_policy};
+
+/// 正确输出：程序在最后的空指针写入处被杀死，不输出 FAIL
+/// mmap(0) OK!
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    let len: usize = 4096 * 4;
+    for _ in 0..100 {
+        assert_eq!(mmap(start, len, 3), 0);
+        assert_eq!(munmap(start, len), 0);
+    }
+    // whether 0 is rejected or replaced by an address picked by the kernel
+    // is a build option
+    let addr = mmap(0, len, 3);
+    if kernel_config().null_policy == null_policy::NULL_MEANS_AUTO {
+        assert!(addr as usize >= 4096);
+        // the next one goes elsewhere, and a freed gap is handed out again
+        let next = mmap(0, len, 3);
+        assert!(next >= addr + len as isize);
+        assert_eq!(munmap(addr as usize, len), 0);
+        assert_eq!(mmap(0, len, 3), addr);
+        assert_eq!(munmap(addr as usize, len), 0);
+        assert_eq!(munmap(next as usize, len), 0);
+    } else {
+        assert_eq!(addr, -1);
+    }
+    println!("mmap(0) OK!");
+    unsafe {
+        #[allow(clippy::zero_ptr)]
+        (0x0 as *mut u8).write_volatile(0);
+    }
+    panic!("FAIL: null write survived!");
+}
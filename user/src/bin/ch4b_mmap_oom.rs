@@ -0,0 +1,71 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    kernel_config, map_error, mem_info, mmap2, munmap2, sched_epoch, yield_, MemInfo, MunmapStats,
+};
+
+/// 正确输出：
+/// Test mmap oom OK!
+
+const PAGE: usize = 4096;
+const BASE: usize = 0x10000000;
+/// far more than physical memory can back
+const MAX_CHUNKS: usize = 1024;
+
+fn frames() -> MemInfo {
+    let mut info = MemInfo::default();
+    assert_eq!(mem_info(&mut info), 0);
+    assert_eq!(info.frames_used + info.frames_free, info.frames_total);
+    info
+}
+
+/// Yield until no other app runs any more: a yield that dispatches no one
+/// but us advances the scheduler epoch by exactly one. Taking all memory
+/// while others still run would make their mmaps fail.
+fn wait_until_alone() {
+    loop {
+        let epoch = sched_epoch();
+        yield_();
+        if sched_epoch() == epoch + 1 {
+            return;
+        }
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    wait_until_alone();
+    // the largest area still populated right away
+    let chunk = (kernel_config().mmap_lazy_pages as usize - 1) * PAGE;
+    let before = frames();
+    let mut chunks = 0;
+    loop {
+        assert!(chunks < MAX_CHUNKS);
+        match mmap2(BASE + chunks * chunk, chunk, 3) {
+            0 => chunks += 1,
+            map_error::OUT_OF_MEMORY => break,
+            ret => panic!("mmap2 returned {}", ret),
+        }
+    }
+    assert!(chunks > 0);
+    assert!(frames().frames_used > before.frames_used);
+    // every data frame goes back, and the page tables of the window too
+    let (mut data_frames, mut table_frames) = (0, 0);
+    for i in 0..chunks {
+        let mut stats = MunmapStats::default();
+        assert_eq!(munmap2(BASE + i * chunk, chunk, &mut stats), 0);
+        data_frames += stats.data_frames;
+        table_frames += stats.table_frames;
+    }
+    assert_eq!(data_frames as usize, chunks * chunk / PAGE);
+    assert!(table_frames > 0);
+    let after = frames();
+    assert_eq!(after.user_pages, before.user_pages);
+    assert_eq!(after.frames_used, before.frames_used);
+    println!("Test mmap oom OK!");
+    0
+}
@@ -0,0 +1,36 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{map_error, mmap, mmap2, munmap};
+
+/// 正确输出：（内核对每次重叠的 mmap 打印 overlaps the ElfText area 等警告）
+/// Test mmap overlap OK!
+
+const PAGE: usize = 4096;
+
+#[no_mangle]
+fn main() -> i32 {
+    let text = main as usize & !(PAGE - 1);
+    let local = 0usize;
+    let stack = &local as *const usize as usize & !(PAGE - 1);
+    // the program's own areas are taken like any earlier mmap
+    assert_eq!(mmap2(text, PAGE, 3), map_error::OVERLAP);
+    assert_eq!(mmap2(stack, PAGE, 3), map_error::OVERLAP);
+    // so is an area only partly covered
+    assert_eq!(mmap2(stack - PAGE, 2 * PAGE, 3), map_error::OVERLAP);
+    let start: usize = 0x10000000;
+    assert_eq!(mmap(start, 2 * PAGE, 3), 0);
+    assert_eq!(mmap2(start + PAGE, 2 * PAGE, 3), map_error::OVERLAP);
+    // the lab's mmap does not tell the reasons apart
+    assert_eq!(mmap(start + PAGE, 2 * PAGE, 3), -1);
+    // not an overlap at all, just a bad start
+    assert_eq!(mmap2(start + 1, PAGE, 3), map_error::INVALID);
+    assert_eq!(munmap(start, 2 * PAGE), 0);
+    assert_eq!(mmap(start + PAGE, 2 * PAGE, 3), 0);
+    assert_eq!(munmap(start + PAGE, 2 * PAGE), 0);
+    println!("Test mmap overlap OK!");
+    0
+}
@@ -0,0 +1,44 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{map_error, mmap2, mprotect};
+
+/// 正确输出：（提交的页可读写；随后访问未提交的页时程序被杀死，内核打印 access to reserved mapping in task N at 0x10000000 ...，不输出 FAIL，关机前的汇总中本程序 exit code 为 -13）
+/// reserved pages committed
+
+const PAGE: usize = 4096;
+const RESERVED: usize = 64;
+/// first of the committed pages, in the middle of the reservation
+const COMMIT_START: usize = 30;
+const COMMITTED: usize = 4;
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    assert_eq!(mmap2(start, RESERVED * PAGE, 0), 0);
+    // the whole range is taken, committed or not
+    assert_eq!(mmap2(start, PAGE, 3), map_error::OVERLAP);
+    assert_eq!(mmap2(start + (RESERVED - 1) * PAGE, PAGE, 3), map_error::OVERLAP);
+    let commit = start + COMMIT_START * PAGE;
+    assert_eq!(mprotect(commit, COMMITTED * PAGE, 3), 0);
+    for i in 0..COMMITTED {
+        let page = (commit + i * PAGE) as *mut usize;
+        unsafe {
+            assert_eq!(page.read_volatile(), 0);
+            page.write_volatile(i + 1);
+        }
+    }
+    for i in 0..COMMITTED {
+        let page = (commit + i * PAGE) as *const usize;
+        assert_eq!(unsafe { page.read_volatile() }, i + 1);
+    }
+    println!("reserved pages committed");
+    unsafe {
+        (start as *mut u8).write_volatile(1);
+    }
+    println!("FAIL: T.T");
+    0
+}
@@ -0,0 +1,37 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mmap, munmap};
+
+/// 正确输出：
+/// Test mmap rezero OK!
+
+const PAGE: usize = 4096;
+const PAGES: usize = 4;
+const PATTERN: u64 = 0xdead_beef_5a5a_a5a5;
+
+fn words(start: usize) -> &'static mut [u64] {
+    unsafe { core::slice::from_raw_parts_mut(start as *mut u64, PAGES * PAGE / 8) }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    assert_eq!(mmap(start, PAGES * PAGE, 3), 0);
+    for word in words(start).iter_mut() {
+        unsafe { (word as *mut u64).write_volatile(PATTERN) };
+    }
+    assert_eq!(munmap(start, PAGES * PAGE), 0);
+    // the frames just freed are the first ones handed out again
+    assert_eq!(mmap(start, PAGES * PAGE, 3), 0);
+    for (i, word) in words(start).iter().enumerate() {
+        let value = unsafe { (word as *const u64).read_volatile() };
+        assert_eq!(value, 0, "word {} of the new mapping is {:#x}", i, value);
+    }
+    assert_eq!(munmap(start, PAGES * PAGE), 0);
+    println!("Test mmap rezero OK!");
+    0
+}
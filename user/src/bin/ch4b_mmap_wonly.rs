@@ -0,0 +1,30 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mmap, mmap2, munmap};
+
+/// 正确输出：
+/// Test mmap write-only OK!
+
+const PAGE: usize = 4096;
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    // W without R is reserved in SV39, with or without X
+    assert_eq!(mmap2(start, PAGE, 2), -1);
+    assert_eq!(mmap2(start, PAGE, 6), -1);
+    // nothing was mapped by the refused calls
+    assert_eq!(munmap(start, PAGE), -1);
+    assert_eq!(mmap2(start, PAGE, 3), 0);
+    assert_eq!(munmap(start, PAGE), 0);
+    // the lab's mmap maps it all the same, see ch4_mmap2; the page is left
+    // untouched, any access to it faults
+    assert_eq!(mmap(start, PAGE, 2), 0);
+    assert_eq!(munmap(start, PAGE), 0);
+    println!("Test mmap write-only OK!");
+    0
+}
@@ -0,0 +1,46 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mmap, mprotect};
+
+/// 正确输出：最后一次写入只读页时触发 StorePageFault 被杀死，不输出 FAIL，其余程序照常完成（关机前的汇总中本程序 exit code 为 -2）
+/// data kept across mprotect
+
+const PAGE: usize = 4096;
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    assert_eq!(mmap(start, 3 * PAGE, 3), 0);
+    let words = start as *mut usize;
+    for page in 0..3 {
+        unsafe { words.add(page * PAGE / 8).write_volatile(page + 1) };
+    }
+    // refused: unaligned, past the user half, partly unmapped, W without R,
+    // and the ELF text
+    assert_eq!(mprotect(start + 8, PAGE, 1), -1);
+    assert_eq!(mprotect(usize::MAX & !(PAGE - 1), 2 * PAGE, 1), -1);
+    assert_eq!(mprotect(start, 4 * PAGE, 1), -1);
+    assert_eq!(mprotect(start, PAGE, 2), -1);
+    assert_eq!(mprotect(main as usize & !(PAGE - 1), PAGE, 7), -1);
+    // the middle page only, splitting the area in three
+    assert_eq!(mprotect(start + PAGE, PAGE, 1), 0);
+    for page in 0..3 {
+        assert_eq!(
+            unsafe { words.add(page * PAGE / 8).read_volatile() },
+            page + 1
+        );
+    }
+    // its neighbours are still writable
+    unsafe {
+        words.write_volatile(10);
+        words.add(2 * PAGE / 8).write_volatile(30);
+    }
+    println!("data kept across mprotect");
+    unsafe { words.add(PAGE / 8).write_volatile(20) };
+    println!("FAIL: wrote to a read-only page");
+    0
+}
@@ -0,0 +1,82 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{get_time, kernel_feature, mmap, mq_recv, mq_send, munmap, shm_map, yield_};
+
+require_kernel_features!(kernel_feature::MQ | kernel_feature::SHM);
+
+/// 正确输出：（需与 ch4b_mq_producer 一同运行）
+/// Test mq consumer OK!
+
+const PAGE: usize = 4096;
+/// written by ch4b_mq_producer into its segment
+const MAGIC: usize = 0x4d51_2121;
+const MESSAGES: u32 = 10_000;
+const MSG_SIZE: usize = 32;
+const TIMEOUT_MS: isize = 10_000;
+/// segment ids the kernel hands out, its `SHM_MAX_SEGMENTS`
+const SEGMENTS: usize = 16;
+/// two pages, messages are received across the boundary between them
+const BUF: usize = 0x1000_0000;
+
+/// laid out at the start of the segment, shared with ch4b_mq_producer
+#[repr(C)]
+struct Mailbox {
+    magic: usize,
+    data: usize,
+    ack: usize,
+}
+
+/// Map every segment in turn until one holds the producer's mailbox.
+fn find_mailbox() -> Option<*mut Mailbox> {
+    for id in 0..SEGMENTS {
+        let addr = shm_map(id, 0, 3);
+        if addr <= 0 {
+            continue;
+        }
+        let mailbox = addr as *mut Mailbox;
+        if unsafe { core::ptr::addr_of!((*mailbox).magic).read_volatile() } == MAGIC {
+            return Some(mailbox);
+        }
+        assert_eq!(munmap(addr as usize, PAGE), 0);
+    }
+    None
+}
+
+/// Check that `msg` is message `seq` as ch4b_mq_producer builds it.
+fn check(seq: u32, msg: &[u8]) {
+    assert_eq!(msg.len(), 4 + seq as usize % (MSG_SIZE - 3), "message {}", seq);
+    assert_eq!(&msg[..4], &seq.to_le_bytes(), "message {} out of order", seq);
+    for (i, &byte) in msg[4..].iter().enumerate() {
+        assert_eq!(byte, (seq as u8).wrapping_mul(31).wrapping_add(i as u8));
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    // before any segment is mapped, which may go anywhere in the mmap area
+    assert_eq!(mmap(BUF, 2 * PAGE, 3), 0);
+    let deadline = get_time() + TIMEOUT_MS;
+    let mailbox = loop {
+        if let Some(mailbox) = find_mailbox() {
+            break mailbox;
+        }
+        assert!(get_time() < deadline, "no producer showed up");
+        yield_();
+    };
+    let (data, ack) = unsafe { ((*mailbox).data, (*mailbox).ack) };
+    let buf = unsafe { core::slice::from_raw_parts_mut((BUF + PAGE - 16) as *mut u8, MSG_SIZE) };
+    for seq in 0..MESSAGES {
+        let len = mq_recv(data, buf);
+        assert!(len >= 0, "message {} not received", seq);
+        check(seq, &buf[..len as usize]);
+    }
+    assert_eq!(mq_send(ack, &MESSAGES.to_le_bytes()), 0);
+    assert_eq!(munmap(BUF, 2 * PAGE), 0);
+    assert_eq!(munmap(mailbox as usize, PAGE), 0);
+    println!("Test mq consumer OK!");
+    0
+}
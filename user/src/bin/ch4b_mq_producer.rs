@@ -0,0 +1,103 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    kernel_feature, mmap, mq_create, mq_destroy, mq_recv, mq_send, munmap, shm_create, shm_map,
+};
+
+require_kernel_features!(kernel_feature::MQ | kernel_feature::SHM);
+
+/// 正确输出：（需与 ch4b_mq_consumer 一同运行）
+/// Test mq producer OK!
+
+const PAGE: usize = 4096;
+/// tells the consumer which segment is ours
+const MAGIC: usize = 0x4d51_2121;
+const MESSAGES: u32 = 10_000;
+/// few slots, so that both sides block often
+const CAPACITY: usize = 4;
+const MSG_SIZE: usize = 32;
+/// two pages, the message is built across the boundary between them
+const BUF: usize = 0x1000_0000;
+
+/// laid out at the start of the segment, shared with ch4b_mq_consumer
+#[repr(C)]
+struct Mailbox {
+    magic: usize,
+    /// queue the messages go through
+    data: usize,
+    /// queue the consumer reports the count it received on
+    ack: usize,
+}
+
+/// Write message `seq` to `buf`, returning its length: the sequence number,
+/// then bytes that depend on it and on their position; ch4b_mq_consumer
+/// checks them the same way.
+fn message(seq: u32, buf: &mut [u8]) -> usize {
+    let len = 4 + seq as usize % (MSG_SIZE - 3);
+    buf[..4].copy_from_slice(&seq.to_le_bytes());
+    for (i, byte) in buf[4..len].iter_mut().enumerate() {
+        *byte = (seq as u8).wrapping_mul(31).wrapping_add(i as u8);
+    }
+    len
+}
+
+/// Create and map a segment holding the mailbox. An app looking through the
+/// segments may map and unmap it before we do, which drops it, so a new one
+/// is made until the mapping holds.
+fn publish(data: usize, ack: usize) -> *mut Mailbox {
+    loop {
+        let id = shm_create(1);
+        assert!(id >= 0);
+        let addr = shm_map(id as usize, 0, 3);
+        if addr <= 0 {
+            continue;
+        }
+        let mailbox = addr as *mut Mailbox;
+        unsafe {
+            core::ptr::addr_of_mut!((*mailbox).data).write_volatile(data);
+            core::ptr::addr_of_mut!((*mailbox).ack).write_volatile(ack);
+            // the ids are there once the consumer can tell the segment
+            core::ptr::addr_of_mut!((*mailbox).magic).write_volatile(MAGIC);
+        }
+        return mailbox;
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(mq_create(0, MSG_SIZE), -1);
+    assert_eq!(mq_create(CAPACITY, 0), -1);
+    // more kernel heap than one task's queues may hold
+    assert_eq!(mq_create(1, 64 * 1024 + 1), -3);
+    let data = mq_create(CAPACITY, MSG_SIZE);
+    let ack = mq_create(1, 4);
+    assert!(data >= 0 && ack >= 0);
+    let (data, ack) = (data as usize, ack as usize);
+    // refused before anything could block
+    assert_eq!(mq_send(data, &[0; MSG_SIZE + 1]), -1);
+    assert_eq!(mq_recv(ack, &mut [0; 3]), -1);
+    assert_eq!(mq_send(usize::MAX, &[0]), -1);
+    // before the segment is mapped, which may go anywhere in the mmap area
+    assert_eq!(mmap(BUF, 2 * PAGE, 3), 0);
+    let mailbox = publish(data, ack);
+    let buf = unsafe { core::slice::from_raw_parts_mut((BUF + PAGE - 16) as *mut u8, MSG_SIZE) };
+    for seq in 0..MESSAGES {
+        let len = message(seq, buf);
+        assert_eq!(mq_send(data, &buf[..len]), 0, "message {}", seq);
+    }
+    let mut count = [0; 4];
+    assert_eq!(mq_recv(ack, &mut count), 4);
+    assert_eq!(u32::from_le_bytes(count), MESSAGES);
+    // the consumer is done with both, nobody waits on them
+    assert_eq!(mq_destroy(data), 0);
+    assert_eq!(mq_destroy(ack), 0);
+    assert_eq!(mq_destroy(data), -1);
+    assert_eq!(munmap(BUF, 2 * PAGE), 0);
+    assert_eq!(munmap(mailbox as usize, PAGE), 0);
+    println!("Test mq producer OK!");
+    0
+}
@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mmap, munmap2, syscall, MunmapStats, SYSCALL_MUNMAP2};
+
+/// 正确输出：
+/// Test munmap2 OK!
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    let len: usize = 4096 * 4;
+    assert_eq!(mmap(start, len, 3), 0);
+    let mut stats = MunmapStats::default();
+    assert_eq!(munmap2(start, len, &mut stats), 0);
+    // mmap populates every page right away
+    assert_eq!(stats.data_frames, 4);
+    // the leaf table of the window held nothing else and goes too
+    assert_eq!(stats.table_frames, 1);
+    // a failed unmap leaves the stats alone
+    let mut untouched = MunmapStats {
+        data_frames: 7,
+        table_frames: 7,
+    };
+    assert_eq!(munmap2(start, len, &mut untouched), -1);
+    assert_eq!(untouched.data_frames, 7);
+    assert_eq!(untouched.table_frames, 7);
+    // and so does a range refused before anything is looked up
+    let top = usize::MAX & !(4096 - 1);
+    assert_eq!(munmap2(top, len, &mut untouched), -1);
+    assert_eq!(munmap2(start + 1, len, &mut untouched), -1);
+    assert_eq!(untouched.data_frames, 7);
+    // stats that cannot be written, or would be unmapped with the range,
+    // are refused before the range is unmapped
+    assert_eq!(mmap(start, len, 3), 0);
+    assert_eq!(syscall(SYSCALL_MUNMAP2, [start, len, 0]), -1);
+    let inside = start + len - 8;
+    assert_eq!(syscall(SYSCALL_MUNMAP2, [start, len, inside]), -1);
+    // still mapped, so mapping it again fails
+    assert_eq!(mmap(start, len, 3), -1);
+    assert_eq!(munmap2(start, len, &mut stats), 0);
+    assert_eq!(stats.data_frames, 4);
+    println!("Test munmap2 OK!");
+    0
+}
@@ -0,0 +1,56 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mem_info, mmap, munmap, munmap2, MemInfo, MunmapStats};
+
+/// 正确输出：
+/// Test munmap split OK!
+
+const PAGE: usize = 4096;
+const PAGES: usize = 10;
+
+fn user_pages() -> u64 {
+    let mut info = MemInfo::default();
+    assert_eq!(mem_info(&mut info), 0);
+    info.user_pages
+}
+
+fn page(start: usize, i: usize) -> *mut usize {
+    (start + i * PAGE) as *mut usize
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    let base = user_pages();
+    assert_eq!(mmap(start, PAGES * PAGE, 3), 0);
+    for i in 0..PAGES {
+        unsafe { page(start, i).write_volatile(i + 1) };
+    }
+    // the middle four pages go, both ends stay
+    let mut stats = MunmapStats::default();
+    assert_eq!(munmap2(start + 3 * PAGE, 4 * PAGE, &mut stats), 0);
+    assert_eq!(stats.data_frames, 4);
+    assert_eq!(user_pages(), base + 6);
+    for i in (0..3).chain(7..PAGES) {
+        assert_eq!(unsafe { page(start, i).read_volatile() }, i + 1);
+    }
+    // the hole is not mapped any more, so the whole range is refused
+    assert_eq!(munmap(start, PAGES * PAGE), -1);
+    assert_eq!(user_pages(), base + 6);
+    // and can be mapped again, with fresh pages
+    assert_eq!(mmap(start + 3 * PAGE, 4 * PAGE, 3), 0);
+    for i in 3..7 {
+        assert_eq!(unsafe { page(start, i).read_volatile() }, 0);
+    }
+    assert_eq!(user_pages(), base + PAGES as u64);
+    // one call removes the three areas the range is made of now
+    assert_eq!(munmap2(start, PAGES * PAGE, &mut stats), 0);
+    assert_eq!(stats.data_frames, PAGES as u64);
+    assert_eq!(user_pages(), base);
+    println!("Test munmap split OK!");
+    0
+}
@@ -0,0 +1,29 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mmap, munmap, write};
+
+/// 正确输出：（内核打印的遗言中还应有 output_bytes: 39）
+/// across two pages
+/// Test output bytes OK!
+
+const STDOUT: usize = 1;
+const PAGE: usize = 4096;
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    assert_eq!(mmap(start, 2 * PAGE, 3), 0);
+    let msg = b"across two pages\n";
+    // the message starts 8 bytes before the page boundary
+    let buf = unsafe { core::slice::from_raw_parts_mut((start + PAGE - 8) as *mut u8, msg.len()) };
+    buf.copy_from_slice(msg);
+    assert_eq!(write(STDOUT, buf), msg.len() as isize);
+    assert_eq!(munmap(start, 2 * PAGE), 0);
+    // 17 bytes above and 22 here
+    println!("Test output bytes OK!");
+    0
+}
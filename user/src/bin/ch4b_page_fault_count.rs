@@ -0,0 +1,37 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mmap, munmap, task_info, TaskInfo};
+
+/// 正确输出：
+/// Test page fault count OK!
+
+const PAGE: usize = 4096;
+/// enough pages for the area to be populated lazily
+const LEN: usize = 1 << 20;
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    assert_eq!(mmap(start, LEN, 3), 0);
+    let info = TaskInfo::new();
+    assert_eq!(task_info(&info), 0);
+    let before = info.page_faults;
+    // the first touch of each lazy page faults once, later ones do not
+    for page in [0, 7, LEN / PAGE - 1] {
+        let p = (start + page * PAGE) as *mut u8;
+        unsafe {
+            assert_eq!(p.read_volatile(), 0);
+            p.write_volatile(1);
+            assert_eq!(p.read_volatile(), 1);
+        }
+    }
+    assert_eq!(task_info(&info), 0);
+    assert_eq!(info.page_faults, before + 3);
+    assert_eq!(munmap(start, LEN), 0);
+    println!("Test page fault count OK!");
+    0
+}
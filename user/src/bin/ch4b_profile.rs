@@ -0,0 +1,55 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::ptr::{read_volatile, write_volatile};
+use user_lib::{get_time, profile_read, ProfileBucket};
+
+/// 正确输出：
+/// Test profile OK!
+
+const BUCKET_SIZE: u64 = 4096;
+
+#[inline(never)]
+fn hot_add(sink: &mut usize, ms: isize) {
+    let end = get_time() + ms;
+    while get_time() < end {
+        for i in 0..100_000 {
+            unsafe { write_volatile(sink, read_volatile(sink).wrapping_add(i)) };
+        }
+    }
+}
+
+#[inline(never)]
+fn hot_mul(sink: &mut usize, ms: isize) {
+    let end = get_time() + ms;
+    while get_time() < end {
+        for i in 0..100_000 {
+            unsafe { write_volatile(sink, read_volatile(sink).wrapping_mul(i | 1)) };
+        }
+    }
+}
+
+fn in_bucket(bucket: &ProfileBucket, addr: usize) -> bool {
+    (bucket.base..bucket.base + BUCKET_SIZE).contains(&(addr as u64))
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut sink = 1;
+    hot_add(&mut sink, 300);
+    hot_mul(&mut sink, 300);
+    let mut buf = [ProfileBucket::default(); 8];
+    let count = profile_read(-1, &mut buf);
+    assert!(count > 0);
+    // the loops are where the time went, so they hold the hottest bucket
+    let top = &buf[0];
+    assert!(in_bucket(top, hot_add as usize) || in_bucket(top, hot_mul as usize));
+    for pair in buf[..count as usize].windows(2) {
+        assert!(pair[0].samples >= pair[1].samples);
+    }
+    println!("Test profile OK!");
+    0
+}
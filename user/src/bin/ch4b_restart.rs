@@ -0,0 +1,103 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    epoch_of, getpid, kill, mem_info, sched_epoch, spawn, task_restart, wait_task, yield_,
+    MemInfo, TASK_SLOT_BITS,
+};
+
+/// 正确输出：
+/// Test task restart OK!
+
+/// what ch4b_restart_worker exits with
+const WORKER_EXIT_CODE: isize = 7;
+/// runs whose frames are compared with those before them
+const RESTARTS: usize = 50;
+/// restarts tried at most to get them, however busy the other tasks keep
+/// the frame allocator
+const MAX_RUNS: usize = 20 * RESTARTS;
+
+fn frames_used() -> u64 {
+    let mut info = MemInfo::default();
+    assert_eq!(mem_info(&mut info), 0);
+    info.frames_used
+}
+
+fn slot(id: usize) -> usize {
+    id & ((1 << TASK_SLOT_BITS) - 1)
+}
+
+/// Whether no task but us and the worker was dispatched, or even set
+/// `Ready`, since epoch `since`, so that only the worker changed the frames
+/// in use. `epoch_of` takes slots, up to the first one no task has.
+fn only_worker_ran(worker: usize, since: isize) -> bool {
+    let me = getpid() as usize;
+    (0..)
+        .map(|id| (id, epoch_of(id as isize)))
+        .take_while(|&(_, epoch)| epoch != -1)
+        .all(|(id, epoch)| id == me || id == slot(worker) || epoch <= since)
+}
+
+/// Run the worker again and wait for it to exit, returns the id of the new
+/// run, in the same slot.
+fn rerun(worker: usize) -> usize {
+    let new = task_restart(worker);
+    assert!(new > 0);
+    let new = new as usize;
+    assert_eq!(slot(new), slot(worker));
+    assert_ne!(new, worker);
+    // not exited any more until the new run is done
+    assert_eq!(task_restart(new), -1);
+    // the old id names no task any more, whether the new run is alive
+    assert_eq!(kill(worker), -1);
+    assert_eq!(task_restart(worker), -1);
+    assert_eq!(wait_task(new), WORKER_EXIT_CODE);
+    new
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(task_restart(usize::MAX), -1);
+    let worker = spawn("ch4b_restart_worker\0");
+    assert!(worker > 0);
+    let mut worker = worker as usize;
+    assert_eq!(wait_task(worker), WORKER_EXIT_CODE);
+    // the run before the last restart is still told its exit code, the one
+    // before that not
+    let first = worker;
+    worker = rerun(worker);
+    assert_eq!(wait_task(first), WORKER_EXIT_CODE);
+    worker = rerun(worker);
+    assert_eq!(wait_task(first), -1);
+    let mut checked = 0;
+    let mut runs = 0;
+    while checked < RESTARTS && runs < MAX_RUNS {
+        // the kernel frees the kernel stack of every exited task but the
+        // current one at each dispatch, which a yield makes sure of, so no
+        // stack is left to free behind the baseline
+        yield_();
+        let since = sched_epoch();
+        let baseline = frames_used();
+        worker = rerun(worker);
+        runs += 1;
+        // every other time run it again at once, so that the new run takes
+        // over the kernel stack of the last one before it was freed
+        if runs % 2 == 0 {
+            worker = rerun(worker);
+            runs += 1;
+        }
+        yield_();
+        let frames = frames_used();
+        if only_worker_ran(worker, since) {
+            // the address space of every run and its kernel stack came back
+            assert_eq!(frames, baseline, "after run {}", runs);
+            checked += 1;
+        }
+    }
+    assert!(checked > 0, "other tasks ran alongside all {} runs", runs);
+    println!("Test task restart OK!");
+    0
+}
@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+extern crate user_lib;
+
+use user_lib::{mmap, munmap};
+
+/// 正确输出：（无输出，ch4b_restart 一再重启它）
+
+const PAGE: usize = 4096;
+const BASE: usize = 0x10000000;
+const PAGES: usize = 4;
+/// what ch4b_restart expects every run to exit with
+const EXIT_CODE: i32 = 7;
+
+#[no_mangle]
+fn main() -> i32 {
+    // a restart starts from a fresh address space, so the area is free
+    // again each run
+    assert_eq!(mmap(BASE, PAGES * PAGE, 3), 0);
+    for i in 0..PAGES {
+        let word = (BASE + i * PAGE) as *mut usize;
+        unsafe {
+            assert_eq!(word.read_volatile(), 0);
+            word.write_volatile(i + 1);
+        }
+    }
+    // only half of it given back, the exit frees the rest
+    assert_eq!(munmap(BASE, PAGES / 2 * PAGE), 0);
+    EXIT_CODE
+}
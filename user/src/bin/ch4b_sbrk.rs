@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::ptr::{read_volatile, write_volatile};
+use user_lib::sbrk;
+
+/// 正确输出：
+/// Test sbrk OK!
+
+const PAGE: i32 = 4096;
+
+#[no_mangle]
+fn main() -> i32 {
+    let bottom = sbrk(0);
+    assert!(bottom > 0);
+    // nothing below where the heap started
+    assert_eq!(sbrk(-1), -1);
+    assert_eq!(sbrk(0), bottom);
+    assert_eq!(sbrk(3 * PAGE), bottom);
+    let brk = sbrk(0) as usize;
+    assert_eq!(brk, bottom as usize + 3 * PAGE as usize);
+    for addr in (bottom as usize..brk).step_by(PAGE as usize) {
+        unsafe {
+            write_volatile(addr as *mut usize, addr);
+            assert_eq!(read_volatile(addr as *const usize), addr);
+        }
+    }
+    // an unaligned break keeps its page mapped
+    assert_eq!(sbrk(-(PAGE + PAGE / 2)) as usize, brk);
+    let last = bottom as usize + PAGE as usize;
+    unsafe { assert_eq!(read_volatile(last as *const usize), last) };
+    assert_eq!(
+        sbrk(-(PAGE + PAGE / 2)) as usize,
+        brk - (PAGE + PAGE / 2) as usize
+    );
+    assert_eq!(sbrk(0), bottom);
+    assert_eq!(sbrk(-PAGE), -1);
+    println!("Test sbrk OK!");
+    0
+}
@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{epoch_of, sched_epoch, yield_};
+
+/// 正确输出：
+/// Test sched_epoch OK!
+
+#[no_mangle]
+fn main() -> i32 {
+    let start = sched_epoch();
+    assert!(start > 0);
+    // a running task is always in the current epoch
+    assert_eq!(epoch_of(-1), start);
+    yield_();
+    let after = sched_epoch();
+    assert!(after > start);
+    assert_eq!(epoch_of(-1), after);
+    // every task that is not running stopped before now
+    let mut pid = 0;
+    while epoch_of(pid) >= 0 {
+        assert!(epoch_of(pid) <= sched_epoch());
+        pid += 1;
+    }
+    assert_eq!(epoch_of(pid), -1);
+    println!("Test sched_epoch OK!");
+    0
+}
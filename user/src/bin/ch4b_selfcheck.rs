@@ -0,0 +1,32 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{cap_drop, mmap, munmap, selfcheck};
+
+/// 正确输出：
+/// Test selfcheck OK!
+
+const SELFCHECK_ALL: usize = 0b111;
+const CAP_DEBUG_INSPECT: usize = 1 << 0;
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(selfcheck(SELFCHECK_ALL), 0);
+    let start: usize = 0x10000000;
+    let len: usize = 4096 * 4;
+    assert_eq!(mmap(start, len, 3), 0);
+    for addr in (start..start + len).step_by(4096) {
+        unsafe { (addr as *mut u8).write_volatile(1) };
+    }
+    assert_eq!(selfcheck(SELFCHECK_ALL), 0);
+    assert_eq!(munmap(start, len), 0);
+    assert_eq!(selfcheck(SELFCHECK_ALL), 0);
+    // the checks are only for apps granted DEBUG_INSPECT
+    cap_drop(CAP_DEBUG_INSPECT);
+    assert_eq!(selfcheck(SELFCHECK_ALL), -1);
+    println!("Test selfcheck OK!");
+    0
+}
@@ -0,0 +1,68 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{get_time, kernel_feature, munmap, shm_map, yield_};
+
+require_kernel_features!(kernel_feature::SHM);
+
+/// 正确输出：（需与 ch4b_shm_producer 一同运行）
+/// Test shm consumer OK!
+
+const PAGE: usize = 4096;
+/// written by ch4b_shm_producer into its segment
+const MAGIC: usize = 0x5348_4d21;
+const ROUNDS: usize = 100;
+const TIMEOUT_MS: isize = 10_000;
+/// segment ids the kernel hands out, its `SHM_MAX_SEGMENTS`
+const SEGMENTS: usize = 16;
+
+/// laid out at the start of the segment, shared with ch4b_shm_producer
+#[repr(C)]
+struct Mailbox {
+    magic: usize,
+    counter: usize,
+    ack: usize,
+}
+
+/// Map every segment in turn until one holds the producer's mailbox.
+fn find_mailbox() -> Option<*mut Mailbox> {
+    for id in 0..SEGMENTS {
+        let addr = shm_map(id, 0, 3);
+        if addr <= 0 {
+            continue;
+        }
+        let mailbox = addr as *mut Mailbox;
+        if unsafe { core::ptr::addr_of!((*mailbox).magic).read_volatile() } == MAGIC {
+            return Some(mailbox);
+        }
+        assert_eq!(munmap(addr as usize, PAGE), 0);
+    }
+    None
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let deadline = get_time() + TIMEOUT_MS;
+    let mailbox = loop {
+        if let Some(mailbox) = find_mailbox() {
+            break mailbox;
+        }
+        assert!(get_time() < deadline, "no producer showed up");
+        yield_();
+    };
+    for round in 1..=ROUNDS {
+        let deadline = get_time() + TIMEOUT_MS;
+        // the producer waits for the ack, so no round is skipped
+        while unsafe { core::ptr::addr_of!((*mailbox).counter).read_volatile() } != round {
+            assert!(get_time() < deadline, "round {} never came", round);
+            yield_();
+        }
+        unsafe { core::ptr::addr_of_mut!((*mailbox).ack).write_volatile(round) };
+    }
+    assert_eq!(munmap(mailbox as usize, PAGE), 0);
+    println!("Test shm consumer OK!");
+    0
+}
@@ -0,0 +1,52 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{get_time, kernel_feature, munmap, shm_create, shm_map, yield_};
+
+require_kernel_features!(kernel_feature::SHM);
+
+/// 正确输出：（需与 ch4b_shm_consumer 一同运行）
+/// Test shm producer OK!
+
+const PAGE: usize = 4096;
+/// tells the consumer which segment is ours
+const MAGIC: usize = 0x5348_4d21;
+const ROUNDS: usize = 100;
+const TIMEOUT_MS: isize = 10_000;
+
+/// laid out at the start of the segment, shared with ch4b_shm_consumer
+#[repr(C)]
+struct Mailbox {
+    magic: usize,
+    counter: usize,
+    ack: usize,
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let id = shm_create(1);
+    assert!(id >= 0);
+    let addr = shm_map(id as usize, 0, 3);
+    assert!(addr > 0);
+    let mailbox = addr as *mut Mailbox;
+    unsafe {
+        // a new segment reads 0
+        assert_eq!((*mailbox).counter, 0);
+        core::ptr::addr_of_mut!((*mailbox).magic).write_volatile(MAGIC);
+    }
+    for round in 1..=ROUNDS {
+        unsafe { core::ptr::addr_of_mut!((*mailbox).counter).write_volatile(round) };
+        let deadline = get_time() + TIMEOUT_MS;
+        while unsafe { core::ptr::addr_of!((*mailbox).ack).read_volatile() } != round {
+            assert!(get_time() < deadline, "no ack for round {}", round);
+            yield_();
+        }
+    }
+    // the consumer may still have it mapped, the frames stay until it unmaps
+    assert_eq!(munmap(addr as usize, PAGE), 0);
+    println!("Test shm producer OK!");
+    0
+}
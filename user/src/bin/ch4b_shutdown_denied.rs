@@ -0,0 +1,22 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::shutdown;
+
+/// 正确输出：
+/// shutdown denied
+/// Test shutdown_denied OK!
+
+#[no_mangle]
+fn main() -> i32 {
+    // neither task 0 nor the shutdown app, so the kernel has to refuse and
+    // keep running everyone else
+    assert_eq!(shutdown(false), -1);
+    assert_eq!(shutdown(true), -1);
+    println!("shutdown denied");
+    println!("Test shutdown_denied OK!");
+    0
+}
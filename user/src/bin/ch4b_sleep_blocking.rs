@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{get_time, kernel_config, kernel_feature, sleep_blocking, task_info, TaskInfo};
+
+/// 正确输出：
+/// Test sleep blocking OK!
+
+const SLEEP_MS: usize = 100;
+
+fn run_ms(info: &TaskInfo) -> usize {
+    assert_eq!(task_info(info), 0);
+    info.user_time + info.kernel_time
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let config = kernel_config();
+    assert_ne!(config.features & kernel_feature::SLEEP, 0);
+    let tick_ms = 1000 / config.ticks_per_sec as usize;
+    let slice_ms = config.ticks_per_slice as usize * tick_ms;
+    let info = TaskInfo::new();
+    let ran = run_ms(&info);
+    let start = get_time() as usize;
+    sleep_blocking(SLEEP_MS);
+    let slept = get_time() as usize - start;
+    // the wakeup is seen on the next tick, and the task then waits for
+    // whoever holds the processor to finish its slice
+    assert!(slept >= SLEEP_MS, "woke up after {} ms", slept);
+    assert!(
+        slept <= SLEEP_MS + tick_ms + slice_ms,
+        "woke up after {} ms",
+        slept
+    );
+    // a sleeper takes no CPU time
+    assert!(run_ms(&info) - ran < SLEEP_MS / 2);
+    println!("Test sleep blocking OK!");
+    0
+}
@@ -0,0 +1,21 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::spawn;
+
+/// 正确输出：（子任务另外各打出一次 Test spawn child OK!）
+/// Test spawn OK!
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(spawn("ch4b_no_such_app\0"), -1);
+    // the same app twice, each in an address space of its own
+    let first = spawn("ch4b_spawn_child\0");
+    let second = spawn("ch4b_spawn_child\0");
+    assert!(first > 0 && second == first + 1);
+    println!("Test spawn OK!");
+    0
+}
@@ -0,0 +1,21 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+/// 正确输出：（启动时和被 ch4b_spawn 拉起的两次各一行）
+/// Test spawn child OK!
+
+static mut RUNS: usize = 0;
+
+#[no_mangle]
+fn main() -> i32 {
+    // a spawned copy starts from the app's own data, not from another copy's
+    unsafe {
+        RUNS += 1;
+        assert_eq!(RUNS, 1);
+    }
+    println!("Test spawn child OK!");
+    0
+}
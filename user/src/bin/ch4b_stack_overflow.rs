@@ -0,0 +1,35 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{map_error, mmap2, munmap, sbrk};
+
+/// 正确输出：程序递归耗尽用户栈后被杀死，内核打印 user stack overflow in task N at ...，不输出 FAIL，其余程序照常完成（关机前的汇总中本程序 exit code 为 -11）
+/// guard page cannot be mapped
+
+const PAGE: usize = 4096;
+const USER_STACK_SIZE: usize = 4096 * 2;
+
+/// Recurse forever, each frame holding a kilobyte of stack.
+#[allow(unconditional_recursion)]
+fn recurse(depth: usize) -> usize {
+    let mut frame = [0u8; 1024];
+    unsafe {
+        core::ptr::write_volatile(&mut frame[depth % 1024], depth as u8);
+    }
+    recurse(depth + 1) + unsafe { core::ptr::read_volatile(&frame[depth % 1024]) } as usize
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    // the heap starts right above the stack, the guard page right below it
+    let guard = sbrk(0) as usize - USER_STACK_SIZE - PAGE;
+    assert_eq!(mmap2(guard, PAGE, 3), map_error::OVERLAP);
+    assert_eq!(munmap(guard, PAGE), -1);
+    println!("guard page cannot be mapped");
+    recurse(0);
+    println!("FAIL: T.T");
+    0
+}
@@ -0,0 +1,28 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mmap, munmap, strace};
+
+/// 正确输出：（LOG=INFO 时内核另外打出下面几行，N 为任务号）
+/// [task N] sys_mmap(0x10000000, 4096, 0x3) = 0
+/// [task N] sys_munmap(0x10000000, 4096) = 0
+/// [task N] sys_strace(0) = 0
+/// Test strace OK!
+
+const START: usize = 0x1000_0000;
+const LEN: usize = 4096;
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(strace(true), 0);
+    assert_eq!(mmap(START, LEN, 0x3), 0);
+    assert_eq!(munmap(START, LEN), 0);
+    assert_eq!(strace(false), 0);
+    // not logged any more
+    assert_eq!(strace(false), 0);
+    println!("Test strace OK!");
+    0
+}
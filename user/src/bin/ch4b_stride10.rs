@@ -0,0 +1,37 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{get_time, set_priority};
+
+/// 正确输出：（与 ch4b_stride5 同时运行时，两者的 count 之比约为 1:2）
+/// priority = 10, count = ...
+
+const PRIORITY: isize = 10;
+/// long enough for many time slices of both tasks
+const MAX_TIME: isize = 2000;
+
+fn spin_delay() {
+    let mut j = true;
+    for _ in 0..10 {
+        j = !j;
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(set_priority(PRIORITY), PRIORITY);
+    let start = get_time();
+    let mut count: usize = 0;
+    loop {
+        spin_delay();
+        count += 1;
+        if count % 400 == 0 && get_time() - start > MAX_TIME {
+            break;
+        }
+    }
+    println!("priority = {}, count = {}", PRIORITY, count);
+    0
+}
@@ -0,0 +1,38 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{kernel_config, sys_get_time, yield_, TimeVal};
+
+/// 正确输出：（耗时因机器而异，其他应用就绪时会偏大，单独运行时才有可比性；有 ASID 的内核应更快）
+/// ASID bits: 16
+/// yield: ... ns per round trip
+/// Test switch bench OK!
+
+const BATCHES: usize = 20;
+const ROUNDS: usize = 100;
+
+fn now_us() -> usize {
+    let time = TimeVal::new();
+    assert_eq!(sys_get_time(&time, 0), 0);
+    time.sec * 1_000_000 + time.usec
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("ASID bits: {}", kernel_config().asid_bits);
+    // the fastest batch is the one the fewest other slices ran in
+    let mut best = usize::MAX;
+    for _ in 0..BATCHES {
+        let start = now_us();
+        for _ in 0..ROUNDS {
+            yield_();
+        }
+        best = best.min(now_us() - start);
+    }
+    println!("yield: {} ns per round trip", best * 1000 / ROUNDS);
+    println!("Test switch bench OK!");
+    0
+}
@@ -0,0 +1,38 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{task_info, yield_, TaskInfo};
+
+/// 正确输出：
+/// Test switch counts OK!
+
+#[no_mangle]
+fn main() -> i32 {
+    let info = TaskInfo::new();
+    assert_eq!(task_info(&info), 0);
+    let (voluntary, preempted) = (info.voluntary_switches, info.preempted_switches);
+    for _ in 0..3 {
+        yield_();
+    }
+    assert_eq!(task_info(&info), 0);
+    assert_eq!(info.voluntary_switches, voluntary + 3);
+    // spinning only ever ends a slice by preemption
+    let start = info.user_time + info.kernel_time;
+    let voluntary = info.voluntary_switches;
+    loop {
+        assert_eq!(task_info(&info), 0);
+        if info.preempted_switches > preempted {
+            break;
+        }
+        assert!(
+            info.user_time + info.kernel_time < start + 1000,
+            "never preempted"
+        );
+    }
+    assert_eq!(info.voluntary_switches, voluntary);
+    println!("Test switch counts OK!");
+    0
+}
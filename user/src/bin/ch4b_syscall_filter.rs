@@ -0,0 +1,44 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    get_time, syscall_filter, yield_, SYSCALL_EXIT, SYSCALL_GETTIMEOFDAY, SYSCALL_SYSCALL_FILTER,
+    SYSCALL_WRITE, SYSCALL_YIELD,
+};
+
+/// 正确输出：
+/// Test syscall filter allowed calls OK!
+/// 之后本程序应被内核杀死，退出原因为 FilteredSyscall(169)
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(
+        syscall_filter(&[
+            SYSCALL_WRITE,
+            SYSCALL_EXIT,
+            SYSCALL_YIELD,
+            SYSCALL_SYSCALL_FILTER
+        ]),
+        0
+    );
+    assert_eq!(syscall_filter(&[100_000]), -1);
+    yield_();
+    // asking for get_time back only narrows the filter further
+    assert_eq!(
+        syscall_filter(&[
+            SYSCALL_WRITE,
+            SYSCALL_EXIT,
+            SYSCALL_YIELD,
+            SYSCALL_GETTIMEOFDAY
+        ]),
+        0
+    );
+    yield_();
+    println!("Test syscall filter allowed calls OK!");
+    get_time();
+    println!("get_time was not filtered!");
+    -1
+}
@@ -0,0 +1,27 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{get_time, kernel_feature, syscall_stats, yield_};
+
+require_kernel_features!(kernel_feature::DEBUG_SYSCALLS | kernel_feature::KSTAT);
+
+/// 正确输出：（仅在带 debug-syscalls 与 kstat 特性构建的内核上加载；内核为每个调用过的 syscall 打印一行，其中包含以下两行，次数至少如此）
+/// [kernel] sys_yield: 20 calls, ...
+/// [kernel] sys_get_time: 20 calls, ...
+/// Test syscall stats OK!
+
+const CALLS: usize = 20;
+
+#[no_mangle]
+fn main() -> i32 {
+    for _ in 0..CALLS {
+        yield_();
+        assert!(get_time() >= 0);
+    }
+    assert_eq!(syscall_stats(), 0);
+    println!("Test syscall stats OK!");
+    0
+}
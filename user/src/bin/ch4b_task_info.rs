@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    mmap, munmap, syscall, task_info, TaskInfo, TaskStatus, SYSCALL_MMAP, SYSCALL_TASK_INFO,
+};
+
+/// 正确输出：
+/// Test task_info OK!
+
+const PAGE: usize = 4096;
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    assert_eq!(mmap(start, 2 * PAGE, 3), 0);
+    assert_eq!(mmap(start + 2 * PAGE, PAGE, 1), 0);
+    // the struct starts 8 bytes before the end of the first page
+    let info = unsafe { &mut *((start + PAGE - 8) as *mut TaskInfo) };
+    assert_eq!(task_info(info), 0);
+    assert!(info.status == TaskStatus::Running);
+    assert_eq!(info.syscall_times[SYSCALL_MMAP], 2);
+    assert_eq!(info.syscall_times[SYSCALL_TASK_INFO], 1);
+    // null, unmapped and read-only buffers are refused, but still counted
+    assert_eq!(syscall(SYSCALL_TASK_INFO, [0, 0, 0]), -1);
+    assert_eq!(syscall(SYSCALL_TASK_INFO, [start + 4 * PAGE, 0, 0]), -1);
+    assert_eq!(syscall(SYSCALL_TASK_INFO, [start + 2 * PAGE, 0, 0]), -1);
+    assert_eq!(task_info(info), 0);
+    assert_eq!(info.syscall_times[SYSCALL_TASK_INFO], 5);
+    // spinning in user mode shows up as user time, and CPU time never
+    // exceeds the time since the first dispatch
+    let (user_time, kernel_time) = (info.user_time, info.kernel_time);
+    loop {
+        assert_eq!(task_info(info), 0);
+        assert!(info.kernel_time >= kernel_time);
+        assert!(info.user_time + info.kernel_time <= info.time + 1);
+        if info.user_time >= user_time + 10 {
+            break;
+        }
+    }
+    assert_eq!(munmap(start, 3 * PAGE), 0);
+    println!("Test task_info OK!");
+    0
+}
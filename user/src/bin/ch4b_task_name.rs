@@ -0,0 +1,22 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{task_info, TaskInfo, TASK_NAME_LEN};
+
+/// 正确输出：
+/// Test task name OK!
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut info = TaskInfo::new();
+    info.name = [0xff; TASK_NAME_LEN];
+    assert_eq!(task_info(&info), 0);
+    assert_eq!(info.name(), "ch4b_task_name");
+    // zero padded after the name
+    assert!(info.name["ch4b_task_name".len()..].iter().all(|&b| b == 0));
+    println!("Test task name OK!");
+    0
+}
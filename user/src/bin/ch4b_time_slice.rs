@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{epoch_of, kernel_config, task_info, TaskInfo};
+
+/// 正确输出：
+/// Test time slice OK!
+
+/// CPU time to spin for, in milliseconds
+const RUN_MS: usize = 200;
+
+fn run_ms(info: &TaskInfo) -> usize {
+    assert_eq!(task_info(info), 0);
+    info.user_time + info.kernel_time
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let config = kernel_config();
+    let tick_ms = 1000 / config.ticks_per_sec as usize;
+    let slice_ticks = config.ticks_per_slice as usize;
+    assert!(slice_ticks >= 1);
+    let info = TaskInfo::new();
+    let start = run_ms(&info);
+    let mut epoch = epoch_of(-1);
+    let mut dispatches: usize = 1;
+    while run_ms(&info) < start + RUN_MS {
+        let now = epoch_of(-1);
+        if now != epoch {
+            epoch = now;
+            dispatches += 1;
+        }
+    }
+    let ran = run_ms(&info) - start;
+    // every slice but the first and the last one runs its ticks in full,
+    // less the part of a tick that had passed when it began
+    let shortest = (slice_ticks - 1) * tick_ms;
+    assert!(
+        dispatches.saturating_sub(2) * shortest <= ran + tick_ms,
+        "dispatched {} times in {} ms with {}-tick slices",
+        dispatches,
+        ran,
+        slice_ticks
+    );
+    println!("Test time slice OK!");
+    0
+}
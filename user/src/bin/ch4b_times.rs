@@ -0,0 +1,45 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{kernel_feature, spawn, times, waitpid, Tms};
+
+require_kernel_features!(kernel_feature::WAITPID);
+
+/// 正确输出：（拉起的 ch4b_times_child 另外打出一次 Test times child OK!）
+/// Test times OK!
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut before = Tms::new();
+    let up_before = times(&mut before);
+    assert!(up_before > 0);
+    let mut acc: usize = 0;
+    for i in 0..10_000_000usize {
+        acc = acc.wrapping_mul(31).wrapping_add(i);
+    }
+    let mut sink = 0;
+    unsafe { core::ptr::write_volatile(&mut sink, acc) };
+    let mut after = Tms::new();
+    let up_after = times(&mut after);
+    assert!(up_after >= up_before);
+    assert!(after.utime >= before.utime);
+    assert!(after.stime >= before.stime);
+    assert!(after.utime + after.stime <= up_after as usize);
+    // nothing was reaped yet
+    assert_eq!(after.cutime + after.cstime, 0);
+    // a reaped child's times are added to ours, not before it is reaped
+    let child = spawn("ch4b_times_child\0");
+    assert!(child > 0);
+    let mut exit_code = -1;
+    assert_eq!(waitpid(child as usize, &mut exit_code), child);
+    assert_eq!(exit_code, 0);
+    let mut reaped = Tms::new();
+    times(&mut reaped);
+    assert!(reaped.cutime > after.cutime);
+    assert!(reaped.cstime >= after.cstime);
+    println!("Test times OK!");
+    0
+}
@@ -0,0 +1,26 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{times, Tms};
+
+/// 正确输出：（启动时一行，被 ch4b_times 拉起时再一行）
+/// Test times child OK!
+
+#[no_mangle]
+fn main() -> i32 {
+    // long enough to span several clock ticks of user time
+    let mut acc: usize = 0;
+    for i in 0..20_000_000usize {
+        acc = acc.wrapping_mul(31).wrapping_add(i);
+    }
+    let mut sink = 0;
+    unsafe { core::ptr::write_volatile(&mut sink, acc) };
+    let mut tms = Tms::new();
+    times(&mut tms);
+    assert!(tms.utime > 0);
+    println!("Test times child OK!");
+    0
+}
@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    mm_fingerprint, times, trace_ctl, trace_read, yield_, Tms, TraceEntry, SYSCALL_MM_FINGERPRINT,
+    SYSCALL_TIMES, SYSCALL_TRACE_CTL, SYSCALL_YIELD,
+};
+
+/// 正确输出：
+/// Test trace OK!
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(trace_ctl(true), 0);
+    yield_();
+    mm_fingerprint();
+    let mut tms = Tms::new();
+    times(&mut tms);
+    let mut buf = [TraceEntry::default(); 8];
+    assert_eq!(trace_read(-1, &mut buf), 4);
+    let expected = [
+        (SYSCALL_TRACE_CTL, 1),
+        (SYSCALL_YIELD, 0),
+        (SYSCALL_MM_FINGERPRINT, 0),
+        (SYSCALL_TIMES, &tms as *const _ as usize as u32),
+    ];
+    for (i, (entry, (id, arg0))) in buf.iter().zip(expected.iter()).enumerate() {
+        assert_eq!(entry.seq as usize, i + 1);
+        assert_eq!(entry.syscall_id as usize, *id);
+        assert_eq!(entry.arg0, *arg0 as u32);
+    }
+    assert_eq!(buf[0].ret, 0);
+    // the first read is recorded too, then nothing else is pending
+    assert_eq!(trace_read(-1, &mut buf), 1);
+    assert_eq!(buf[0].seq, 5);
+    assert_eq!(trace_ctl(false), 0);
+    assert_eq!(trace_read(-1, &mut buf), -1);
+    println!("Test trace OK!");
+    0
+}
@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{trace_ctl, trace_read, yield_, TraceEntry, SYSCALL_TRACE_CTL, SYSCALL_YIELD};
+
+/// 正确输出：
+/// Test trace pool OK!
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut buf = [TraceEntry::default(); 8];
+    assert_eq!(trace_ctl(true), 0);
+    yield_();
+    yield_();
+    assert_eq!(trace_ctl(false), 0);
+    // the ring goes back to the pool and comes out empty the next time
+    assert_eq!(trace_ctl(true), 0);
+    assert_eq!(trace_read(-1, &mut buf), 1);
+    assert_eq!(buf[0].seq, 1);
+    assert_eq!(buf[0].syscall_id as usize, SYSCALL_TRACE_CTL);
+    assert_eq!(buf[0].arg0, 1);
+    yield_();
+    assert_eq!(trace_read(-1, &mut buf), 2);
+    assert_eq!(buf[1].syscall_id as usize, SYSCALL_YIELD);
+    assert_eq!(trace_ctl(false), 0);
+    println!("Test trace pool OK!");
+    0
+}
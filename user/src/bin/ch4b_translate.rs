@@ -0,0 +1,34 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{kernel_feature, mmap, munmap, pte_flag, translate};
+
+require_kernel_features!(kernel_feature::DEBUG_SYSCALLS);
+
+/// 正确输出：（仅在带 debug-syscalls 特性构建的内核上加载）
+/// Test translate OK!
+
+const PAGE: usize = 4096;
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    assert_eq!(translate(start), -1);
+    // port bits 0..3 are R, W and X; every user page is valid and has U
+    for (i, &port) in [1, 3, 4, 5, 7].iter().enumerate() {
+        let va = start + i * PAGE;
+        assert_eq!(mmap(va, PAGE, port), 0);
+        let expected = pte_flag::V | pte_flag::U | ((port as isize) << 1);
+        assert_eq!(translate(va), expected);
+        // any address inside the page reports the same entry
+        assert_eq!(translate(va + PAGE - 1), expected);
+    }
+    assert_eq!(translate(start) & pte_flag::W, 0);
+    assert_eq!(munmap(start, 5 * PAGE), 0);
+    assert_eq!(translate(start), -1);
+    println!("Test translate OK!");
+    0
+}
@@ -0,0 +1,85 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exec_text_only, mmap, munmap, sched_epoch, sys_get_time, yield_, TimeVal};
+
+/// 正确输出：（耗时因机器而异，单独运行时才有可比性；对比 trap 路径读缓存与借用任务管理器的开销）
+/// trap, cached: ... ns per round trip
+/// trap, task manager borrowed: ... ns per round trip
+/// yield: ... ns per round trip
+/// Test trap stress OK!
+
+const BATCHES: usize = 20;
+const ROUNDS: usize = 500;
+const PAGE: usize = 4096;
+
+fn now_us() -> usize {
+    let time = TimeVal::new();
+    assert_eq!(sys_get_time(&time, 0), 0);
+    time.sec * 1_000_000 + time.usec
+}
+
+/// Nanoseconds per call of `f` in the fastest of [`BATCHES`] batches, the
+/// one the fewest other slices ran in.
+fn best_round_trip(mut f: impl FnMut()) -> usize {
+    let mut best = usize::MAX;
+    for _ in 0..BATCHES {
+        let start = now_us();
+        for _ in 0..ROUNDS {
+            f();
+        }
+        best = best.min(now_us() - start);
+    }
+    best * 1000 / ROUNDS
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    // about the cheapest syscall there is, so nearly all of it is the trap
+    let cached = best_round_trip(|| {
+        sched_epoch();
+    });
+    println!("trap, cached: {} ns per round trip", cached);
+    // under the text-only policy every trap entry borrows the task manager
+    // to look up the area of the pc, as trap entry and return both did
+    // before the kernel cached what they need at dispatch
+    assert_eq!(exec_text_only(true), 0);
+    let borrowed = best_round_trip(|| {
+        sched_epoch();
+    });
+    assert_eq!(exec_text_only(false), 0);
+    println!(
+        "trap, task manager borrowed: {} ns per round trip",
+        borrowed
+    );
+    // the best batches leave a little noise, not a slower cached path
+    assert!(
+        cached <= borrowed + borrowed / 10,
+        "cached trap slower than one borrowing the task manager"
+    );
+    println!(
+        "yield: {} ns per round trip",
+        best_round_trip(|| {
+            yield_();
+        })
+    );
+    // mmap and munmap keep the token, so whatever the kernel cached of us
+    // at dispatch has to stay right while our address space changes
+    let start: usize = 0x10000000;
+    for round in 0..ROUNDS {
+        assert_eq!(mmap(start, PAGE, 3), 0);
+        let word = start as *mut usize;
+        unsafe { word.write_volatile(round) };
+        let epoch = sched_epoch();
+        yield_();
+        assert_eq!(unsafe { word.read_volatile() }, round);
+        // a yield always dispatches, us again if no one else is ready
+        assert!(sched_epoch() > epoch);
+        assert_eq!(munmap(start, PAGE), 0);
+    }
+    println!("Test trap stress OK!");
+    0
+}
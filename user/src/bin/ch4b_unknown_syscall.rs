@@ -0,0 +1,25 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{syscall, task_info, TaskInfo, SYSCALL_TASK_INFO};
+
+/// 正确输出：（内核对 9999 和 499 各警告一次 unknown syscall，关机前的汇总中本程序有 3 unknown syscalls refused）
+/// Test unknown syscall OK!
+
+#[no_mangle]
+fn main() -> i32 {
+    // past the counters and just inside them, neither is implemented
+    assert_eq!(syscall(9999, [0, 0, 0]), -1);
+    assert_eq!(syscall(499, [0, 0, 0]), -1);
+    assert_eq!(syscall(499, [0, 0, 0]), -1);
+    let info = TaskInfo::new();
+    assert_eq!(task_info(&info), 0);
+    // refused ids are not counted as calls of that syscall
+    assert_eq!(info.syscall_times[499], 0);
+    assert_eq!(info.syscall_times[SYSCALL_TASK_INFO], 1);
+    println!("Test unknown syscall OK!");
+    0
+}
@@ -0,0 +1,65 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    mmap, munmap, syscall, write, SYSCALL_GETTIMEOFDAY, SYSCALL_TASK_INFO, SYSCALL_WRITE,
+};
+
+/// 正确输出：
+/// split é
+/// Test user ptr OK!
+
+const PAGE: usize = 4096;
+/// end of the lower half of SV39, the kernel half starts above it
+const USER_SPACE_END: usize = 1 << 38;
+/// where the kernel itself runs
+const KERNEL: usize = 0xffff_ffff_8020_0000;
+/// never mapped by this app
+const UNMAPPED: usize = 0x7000_0000;
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    assert_eq!(mmap(start, PAGE, 3), 0);
+    // writable, but not aligned for the structs
+    let misaligned = start + 1;
+    for bad in [KERNEL, USER_SPACE_END - 8, UNMAPPED, misaligned] {
+        assert_eq!(syscall(SYSCALL_GETTIMEOFDAY, [bad, 0, 0]), -1);
+        assert_eq!(syscall(SYSCALL_TASK_INFO, [bad, 0, 0]), -1);
+    }
+    // the same buffer, aligned, is fine
+    assert_eq!(syscall(SYSCALL_GETTIMEOFDAY, [start, 0, 0]), 0);
+    assert_eq!(syscall(SYSCALL_TASK_INFO, [start, 0, 0]), 0);
+    // a write must be able to read every byte, the first page here is fine
+    for (bad, len) in [
+        (KERNEL, 8),
+        (USER_SPACE_END - 8, 16),
+        (UNMAPPED, 8),
+        (start, 2 * PAGE),
+    ] {
+        assert_eq!(syscall(SYSCALL_WRITE, [1, bad, len]), -1);
+    }
+    assert_eq!(syscall(SYSCALL_WRITE, [1, usize::MAX - 1, 4]), -1);
+    // a character that a page boundary splits is written whole
+    let text = "split é\n".as_bytes();
+    let split = text.len() - 2;
+    assert_eq!(mmap(start + PAGE, PAGE, 3), 0);
+    let buf =
+        unsafe { core::slice::from_raw_parts_mut((start + PAGE - split) as *mut u8, text.len()) };
+    buf.copy_from_slice(text);
+    assert_eq!(write(1, buf), text.len() as isize);
+    assert_eq!(munmap(start + PAGE, PAGE), 0);
+    // ranges reaching into the kernel half, or not page aligned
+    assert_eq!(mmap(KERNEL, PAGE, 3), -1);
+    assert_eq!(mmap(USER_SPACE_END - PAGE, 2 * PAGE, 3), -1);
+    assert_eq!(mmap(misaligned, PAGE, 3), -1);
+    assert_eq!(munmap(KERNEL, PAGE), -1);
+    assert_eq!(munmap(UNMAPPED, PAGE), -1);
+    assert_eq!(munmap(misaligned, PAGE), -1);
+    assert_eq!(munmap(start, PAGE), 0);
+    println!("Test user ptr OK!");
+    0
+}
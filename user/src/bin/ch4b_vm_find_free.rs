@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mem_info, mmap, munmap, vm_find_free, MemInfo};
+
+/// 正确输出：
+/// Test vm_find_free OK!
+
+const PAGE: usize = 4096;
+const WINDOW_BASE: usize = 0x10000000;
+/// end of the lower half of SV39, where the mmap window ends
+const USER_SPACE_END: usize = 1 << 38;
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut before = MemInfo::default();
+    assert_eq!(mem_info(&mut before), 0);
+    assert_eq!(before.gap_start as usize, WINDOW_BASE);
+    // window: [used][free][free][used][free ...]
+    assert_eq!(mmap(WINDOW_BASE, PAGE, 3), 0);
+    assert_eq!(mmap(WINDOW_BASE + 3 * PAGE, PAGE, 3), 0);
+    let mut info = MemInfo::default();
+    assert_eq!(mem_info(&mut info), 0);
+    assert_eq!(info.user_pages, before.user_pages + 2);
+    assert_eq!(info.gap_start as usize, WINDOW_BASE + 4 * PAGE);
+    assert_eq!(
+        info.gap_len as usize,
+        USER_SPACE_END - (WINDOW_BASE + 4 * PAGE)
+    );
+    // the hole fits two pages, not three
+    assert_eq!(vm_find_free(2 * PAGE, PAGE), (WINDOW_BASE + PAGE) as isize);
+    assert_eq!(
+        vm_find_free(3 * PAGE, PAGE),
+        (WINDOW_BASE + 4 * PAGE) as isize
+    );
+    assert_eq!(
+        vm_find_free(PAGE, 8 * PAGE),
+        (WINDOW_BASE + 8 * PAGE) as isize
+    );
+    assert_eq!(vm_find_free(PAGE, 3 * PAGE), -1);
+    // nothing was mapped, the advice can be taken
+    let hint = vm_find_free(2 * PAGE, PAGE) as usize;
+    assert_eq!(mmap(hint, 2 * PAGE, 3), 0);
+    assert_eq!(vm_find_free(PAGE, PAGE), (WINDOW_BASE + 4 * PAGE) as isize);
+    assert_eq!(munmap(hint, 2 * PAGE), 0);
+    assert_eq!(munmap(WINDOW_BASE, PAGE), 0);
+    assert_eq!(munmap(WINDOW_BASE + 3 * PAGE, PAGE), 0);
+    println!("Test vm_find_free OK!");
+    0
+}
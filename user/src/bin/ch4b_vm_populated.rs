@@ -0,0 +1,69 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{kernel_config, mmap, munmap, vm_accessed, vm_populated};
+
+/// 正确输出：
+/// Test vm populated OK!
+
+const PAGE: usize = 4096;
+/// large enough for the kernel to map it lazily
+const PAGES: usize = 256;
+const LEN: usize = PAGES * PAGE;
+const TOUCHED: [usize; 5] = [0, 3, 8, 9, 255];
+
+fn touch(start: usize, page: usize) {
+    let p = (start + page * PAGE) as *mut u8;
+    unsafe { p.write_volatile(p.read_volatile() + 1) };
+}
+
+fn expected(pages: &[usize]) -> [u8; PAGES / 8] {
+    let mut bitmap = [0u8; PAGES / 8];
+    for &page in pages {
+        bitmap[page / 8] |= 1 << (page % 8);
+    }
+    bitmap
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    assert!(kernel_config().mmap_lazy_pages as usize <= PAGES);
+    let start: usize = 0x10000000;
+    assert_eq!(mmap(start, LEN, 3), 0);
+    let mut bitmap = [0u8; PAGES / 8];
+    assert_eq!(vm_populated(start, LEN, &mut bitmap), 0);
+    assert_eq!(bitmap, [0; PAGES / 8]);
+    for page in TOUCHED {
+        touch(start, page);
+    }
+    assert_eq!(
+        vm_populated(start, LEN, &mut bitmap),
+        TOUCHED.len() as isize
+    );
+    assert_eq!(bitmap, expected(&TOUCHED));
+    // ten pages fill one byte and two bits of the next, the rest is padding
+    let mut short = [0xffu8; 3];
+    assert_eq!(vm_populated(start, 10 * PAGE, &mut short), 4);
+    assert_eq!(short, [0b0000_1001, 0b0000_0011, 0xff]);
+    // the first round sees every page touched since the mapping
+    assert_eq!(vm_accessed(start, LEN, &mut bitmap), TOUCHED.len() as isize);
+    assert_eq!(bitmap, expected(&TOUCHED));
+    touch(start, 3);
+    touch(start, 255);
+    assert_eq!(vm_accessed(start, LEN, &mut bitmap), 2);
+    assert_eq!(bitmap, expected(&[3, 255]));
+    assert_eq!(vm_accessed(start, LEN, &mut bitmap), 0);
+    // the pages stay populated though
+    assert_eq!(
+        vm_populated(start, LEN, &mut bitmap),
+        TOUCHED.len() as isize
+    );
+    assert_eq!(vm_populated(start + 1, PAGE, &mut bitmap), -1);
+    assert_eq!(munmap(start, LEN), 0);
+    assert_eq!(vm_populated(start, LEN, &mut bitmap), 0);
+    println!("Test vm populated OK!");
+    0
+}
@@ -0,0 +1,27 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{get_time, spawn, wait_task};
+
+/// 正确输出：（子任务另外打出 Test wait_task child OK!）
+/// Test wait_task OK!
+
+const CHILD_EXIT_CODE: isize = 42;
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(wait_task(usize::MAX), -1);
+    let child = spawn("ch4b_wait_task_child\0");
+    assert!(child > 0);
+    let start = get_time();
+    assert_eq!(wait_task(child as usize), CHILD_EXIT_CODE);
+    // the child sleeps before it exits, so the wait has blocked
+    assert!(get_time() - start >= 50);
+    // an exited task is not waited for
+    assert_eq!(wait_task(child as usize), CHILD_EXIT_CODE);
+    println!("Test wait_task OK!");
+    0
+}
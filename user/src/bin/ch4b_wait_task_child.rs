@@ -0,0 +1,18 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::sleep_blocking;
+
+/// 正确输出：（启动时一行，每次被 ch4b_wait_task、ch4b_waitpid 或
+/// ch4b_waitpid_parent 拉起时各一行）
+/// Test wait_task child OK!
+
+#[no_mangle]
+fn main() -> i32 {
+    sleep_blocking(100);
+    println!("Test wait_task child OK!");
+    42
+}
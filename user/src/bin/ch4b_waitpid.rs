@@ -0,0 +1,84 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::wait_flag::WNOHANG;
+use user_lib::{
+    get_time, kernel_feature, kill, sleep_blocking, spawn, wait, waitpid, waitpid_flags,
+};
+
+require_kernel_features!(kernel_feature::WAITPID);
+
+/// 正确输出：（拉起的子任务另外打出 Test wait_task child OK! 三次，
+/// Test kill child OK! 被杀的三个不打出）
+/// Test waitpid OK!
+
+/// what ch4b_wait_task_child exits with
+const CHILD_EXIT_CODE: i32 = 42;
+const KILLED: i32 = -9;
+
+fn spawn_child(name: &str) -> usize {
+    let child = spawn(name);
+    assert!(child > 0, "spawning {}", name);
+    child as usize
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut exit_code = 0;
+    // no children yet, blocking or not; task 0 is no child either
+    assert_eq!(wait(&mut exit_code), -1);
+    assert_eq!(waitpid_flags(-1, &mut exit_code, WNOHANG), -1);
+    assert_eq!(waitpid(0, &mut exit_code), -1);
+    assert_eq!(waitpid_flags(-2, &mut exit_code, 0), -1);
+
+    // blocking on one child, which is reaped by it
+    let child = spawn_child("ch4b_wait_task_child\0");
+    assert_eq!(waitpid_flags(-1, &mut exit_code, 1 << 1), -1);
+    assert_eq!(waitpid(child, &mut exit_code), child as isize);
+    assert_eq!(exit_code, CHILD_EXIT_CODE);
+    assert_eq!(waitpid(child, &mut exit_code), -1);
+
+    // polling, until the child's sleep is over
+    let child = spawn_child("ch4b_wait_task_child\0");
+    let start = get_time();
+    let mut polls = 0;
+    let reaped = loop {
+        match waitpid_flags(child as isize, &mut exit_code, WNOHANG) {
+            -2 => polls += 1,
+            reaped => break reaped,
+        }
+        sleep_blocking(10);
+    };
+    assert_eq!(reaped, child as isize);
+    assert_eq!(exit_code, CHILD_EXIT_CODE);
+    assert!(polls > 0 && get_time() - start >= 100);
+
+    // any child, three of them exiting in the order they are killed in; a
+    // killed child exits when it runs next, in the order it was woken
+    let children = [(); 3].map(|_| spawn_child("ch4b_kill_child\0"));
+    // all asleep by now
+    sleep_blocking(50);
+    assert_eq!(waitpid_flags(-1, &mut exit_code, WNOHANG), -2);
+    let order = [children[1], children[2], children[0]];
+    for &child in order.iter() {
+        assert_eq!(kill(child), 0);
+    }
+    for &child in order.iter() {
+        exit_code = 0;
+        assert_eq!(wait(&mut exit_code), child as isize);
+        assert_eq!(exit_code, KILLED);
+    }
+    assert_eq!(wait(&mut exit_code), -1);
+
+    // a parent killed while it waits for its own child, which lives on
+    let parent = spawn_child("ch4b_waitpid_parent\0");
+    sleep_blocking(50);
+    assert_eq!(kill(parent), 0);
+    assert_eq!(waitpid(parent, &mut exit_code), parent as isize);
+    assert_eq!(exit_code, KILLED);
+    println!("Test waitpid OK!");
+    0
+}
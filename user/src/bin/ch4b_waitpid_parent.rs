@@ -0,0 +1,23 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{kernel_feature, spawn, wait};
+
+require_kernel_features!(kernel_feature::WAITPID);
+
+/// 正确输出：（ch4b_waitpid 拉起的一份在等待中被杀，不打出）
+/// Test waitpid parent OK!
+
+#[no_mangle]
+fn main() -> i32 {
+    let child = spawn("ch4b_wait_task_child\0");
+    assert!(child > 0);
+    let mut exit_code = 0;
+    assert_eq!(wait(&mut exit_code), child);
+    assert_eq!(exit_code, 42);
+    println!("Test waitpid parent OK!");
+    0
+}
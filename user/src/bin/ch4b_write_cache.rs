@@ -0,0 +1,46 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mmap, munmap, write};
+
+/// 正确输出：
+/// first mapping
+/// second mapping
+/// Test write cache OK!
+
+const STDOUT: usize = 1;
+const PAGE: usize = 4096;
+
+/// map a page at `start` and fill its beginning with `msg`
+fn map_with(start: usize, msg: &[u8]) -> &'static [u8] {
+    assert_eq!(mmap(start, PAGE, 3), 0);
+    let buf = unsafe { core::slice::from_raw_parts_mut(start as *mut u8, msg.len()) };
+    buf.copy_from_slice(msg);
+    buf
+}
+
+/// write `buf` out in two parts, the second one hits the cached translation
+fn write_twice(buf: &[u8]) {
+    let (head, tail) = buf.split_at(buf.len() / 2);
+    assert_eq!(write(STDOUT, head), head.len() as isize);
+    assert_eq!(write(STDOUT, tail), tail.len() as isize);
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let a: usize = 0x10000000;
+    let b: usize = a + PAGE;
+    write_twice(map_with(a, b"first mapping\n"));
+    assert_eq!(munmap(a, PAGE), 0);
+    // b takes the frame just freed by a, a gets a different one
+    map_with(b, b"stale frame!!\n");
+    // a stale translation for a would print the bytes of b
+    write_twice(map_with(a, b"second mapping\n"));
+    assert_eq!(munmap(a, PAGE), 0);
+    assert_eq!(munmap(b, PAGE), 0);
+    println!("Test write cache OK!");
+    0
+}
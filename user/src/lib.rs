@@ -98,12 +98,190 @@ impl TimeVal {
     }
 }
 
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct Tms {
+    pub utime: usize,
+    pub stime: usize,
+    pub cutime: usize,
+    pub cstime: usize,
+}
+
+impl Tms {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// one syscall recorded by the kernel's trace ring
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TraceEntry {
+    pub seq: u32,
+    pub delta_us: u32,
+    pub syscall_id: u32,
+    pub arg0: u32,
+    pub ret: i64,
+}
+
+/// frames released by `munmap2`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MunmapStats {
+    pub data_frames: u64,
+    pub table_frames: u64,
+}
+
+/// address-space summary written by `mem_info`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MemInfo {
+    pub user_pages: u64,
+    pub peak_pages: u64,
+    pub gap_start: u64,
+    pub gap_len: u64,
+    /// frames of the whole system
+    pub frames_used: u64,
+    pub frames_free: u64,
+    pub frames_total: u64,
+}
+
+/// samples the kernel's profiler took in one address bucket
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ProfileBucket {
+    pub base: u64,
+    pub samples: u64,
+}
+
+/// kernel features an app can require with [`require_kernel_features!`]
+pub mod kernel_feature {
+    pub const TRACE: u64 = 1 << 0;
+    pub const PROFILE: u64 = 1 << 1;
+    pub const KSTAT: u64 = 1 << 2;
+    pub const SLEEP: u64 = 1 << 3;
+    pub const SHM: u64 = 1 << 4;
+    pub const SIGNALS: u64 = 1 << 5;
+    pub const DEBUG_SYSCALLS: u64 = 1 << 6;
+    pub const MQ: u64 = 1 << 7;
+    pub const WAITPID: u64 = 1 << 8;
+}
+
+/// bits of the `flags` of [`waitpid_flags`]
+pub mod wait_flag {
+    /// return -2 instead of blocking while no child waited for has exited
+    pub const WNOHANG: usize = 1 << 0;
+}
+
+/// bits returned by [`translate`], as in the page table entry
+pub mod pte_flag {
+    pub const V: isize = 1 << 0;
+    pub const R: isize = 1 << 1;
+    pub const W: isize = 1 << 2;
+    pub const X: isize = 1 << 3;
+    pub const U: isize = 1 << 4;
+}
+
+/// errors returned by [`mmap2`], [`mmap_data`], [`munmap2`], [`mprotect`] and
+/// [`sbrk`]; the lab's [`mmap`] and [`munmap`] return -1 for any of them
+pub mod map_error {
+    /// invalid arguments, such as an unaligned start or a bad `prot`
+    pub const INVALID: isize = -1;
+    /// the range overlaps an area: the program, the stack or an earlier mmap
+    pub const OVERLAP: isize = -2;
+    /// the task's page budget or the free frames cannot back the range
+    pub const OUT_OF_MEMORY: isize = -3;
+}
+
+pub const KREQ_MAGIC: u32 = u32::from_le_bytes(*b"KREQ");
+
+/// read by the kernel loader from the `.note.kreq` section
+#[repr(C)]
+pub struct KernelRequirements {
+    pub magic: u32,
+    pub reserved: u32,
+    pub features: u64,
+}
+
+/// Declare the kernel features this app needs; a kernel without them
+/// refuses to load it instead of failing at the first syscall.
+#[macro_export]
+macro_rules! require_kernel_features {
+    ($features:expr) => {
+        #[used]
+        #[link_section = ".note.kreq"]
+        static KERNEL_REQUIREMENTS: $crate::KernelRequirements = $crate::KernelRequirements {
+            magic: $crate::KREQ_MAGIC,
+            reserved: 0,
+            features: $features,
+        };
+    };
+}
+
+pub const ABI_VERSION: u32 = 4;
+
+/// bits of [`KernelConfig::flags`]
+pub mod config_flag {
+    pub const DENY_WX: u64 = 1 << 0;
+    pub const EXEC_TEXT_ONLY: u64 = 1 << 1;
+}
+
+/// values of [`KernelConfig::sched_policy`]
+pub mod sched_policy {
+    pub const ROUND_ROBIN: u32 = 0;
+}
+
+/// values of [`KernelConfig::null_policy`]
+pub mod null_policy {
+    pub const NULL_INVALID: u32 = 0;
+    pub const NULL_MEANS_AUTO: u32 = 1;
+}
+
+/// how the kernel was built, see [`kernel_config`]
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct KernelConfig {
+    pub abi_version: u32,
+    pub sched_policy: u32,
+    pub ticks_per_sec: u32,
+    pub ticks_per_slice: u32,
+    pub null_policy: u32,
+    pub asid_bits: u32,
+    pub profile: [u8; 16],
+    pub features: u64,
+    pub flags: u64,
+    pub mmap_max_pages: u64,
+    pub mmap_lazy_pages: u64,
+}
+
+impl KernelConfig {
+    pub fn has_flag(&self, flag: u64) -> bool {
+        self.flags & flag != 0
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref KERNEL_CONFIG: KernelConfig = {
+        let mut config = KernelConfig::default();
+        let size = sys_kernel_config(&mut config);
+        assert_eq!(size, core::mem::size_of::<KernelConfig>() as isize);
+        assert_eq!(config.abi_version, ABI_VERSION);
+        config
+    };
+}
+
+/// the kernel's build configuration, asked for at the first call
+pub fn kernel_config() -> &'static KernelConfig {
+    &KERNEL_CONFIG
+}
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum TaskStatus {
     UnInit,
     Ready,
     Running,
     Exited,
+    Blocked,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -114,21 +292,49 @@ pub struct SyscallInfo {
 
 const MAX_SYSCALL_NUM: usize = 500;
 
+#[repr(C)]
 #[derive(Debug)]
 pub struct TaskInfo {
     pub status: TaskStatus,
     pub syscall_times: [u32; MAX_SYSCALL_NUM],
     pub time: usize,
+    /// milliseconds of CPU time in user mode and in the kernel
+    pub user_time: usize,
+    pub kernel_time: usize,
+    /// times the task yielded, and was suspended at the end of its slice
+    pub voluntary_switches: usize,
+    pub preempted_switches: usize,
+    /// app name, or the task id if it has none, NUL terminated
+    pub name: [u8; TASK_NAME_LEN],
+    /// page faults taken, resolved or not
+    pub page_faults: usize,
 }
 
+pub const TASK_NAME_LEN: usize = 32;
+
 impl TaskInfo {
     pub fn new() -> Self {
         TaskInfo {
             status: TaskStatus::UnInit,
             syscall_times: [0; MAX_SYSCALL_NUM],
             time: 0,
+            user_time: 0,
+            kernel_time: 0,
+            voluntary_switches: 0,
+            preempted_switches: 0,
+            name: [0; TASK_NAME_LEN],
+            page_faults: 0,
         }
     }
+    /// `name` up to its NUL
+    pub fn name(&self) -> &str {
+        let len = self
+            .name
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(TASK_NAME_LEN);
+        core::str::from_utf8(&self.name[..len]).unwrap_or("")
+    }
 }
 
 #[repr(C)]
@@ -232,6 +438,10 @@ pub fn get_time() -> isize {
     }
 }
 
+pub fn times(tms: &mut Tms) -> isize {
+    sys_times(tms)
+}
+
 pub fn getpid() -> isize {
     sys_getpid()
 }
@@ -248,30 +458,23 @@ pub fn set_priority(prio: isize) -> isize {
     sys_set_priority(prio)
 }
 
+/// block until any child exits, reap it and return its id; -1 without
+/// children
 pub fn wait(exit_code: &mut i32) -> isize {
-    loop {
-        match sys_waitpid(-1, exit_code as *mut _) {
-            -2 => {
-                sys_yield();
-            }
-            n => {
-                return n;
-            }
-        }
-    }
+    sys_waitpid(-1, exit_code as *mut _, 0)
 }
 
+/// block until child `pid` exits, reap it and return `pid`; -1 if there is
+/// no such child
 pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
-    loop {
-        match sys_waitpid(pid as isize, exit_code as *mut _) {
-            -2 => {
-                sys_yield();
-            }
-            n => {
-                return n;
-            }
-        }
-    }
+    sys_waitpid(pid as isize, exit_code as *mut _, 0)
+}
+
+/// [`waitpid`] for child `pid`, or any child if `pid` is -1, with `flags`
+/// of [`wait_flag`]; returns -2 under [`wait_flag::WNOHANG`] while none of
+/// them exited
+pub fn waitpid_flags(pid: isize, exit_code: &mut i32, flags: usize) -> isize {
+    sys_waitpid(pid, exit_code as *mut _, flags)
 }
 
 pub fn sleep_blocking(sleep_ms: usize) {
@@ -284,6 +487,10 @@ pub fn sleep(period_ms: usize) {
         sys_yield();
     }
 }
+pub fn reboot(flags: usize) -> isize {
+    sys_reboot(flags)
+}
+
 pub fn mmap(start: usize, len: usize, prot: usize) -> isize {
     sys_mmap(start, len, prot)
 }
@@ -292,6 +499,18 @@ pub fn munmap(start: usize, len: usize) -> isize {
     sys_munmap(start, len)
 }
 
+/// `mmap` under the kernel's own rules instead of the lab's: a W-only `prot`
+/// is refused, and a `prot` of 0 reserves the range for `mprotect` to commit
+pub fn mmap2(start: usize, len: usize, prot: usize) -> isize {
+    sys_mmap2(start, len, prot)
+}
+
+/// change the permission of mmap memory to `prot`, keeping its contents;
+/// pages reserved by an `mmap2` with `prot` 0 get their memory now
+pub fn mprotect(start: usize, len: usize, prot: usize) -> isize {
+    sys_mprotect(start, len, prot)
+}
+
 pub fn spawn(path: &str) -> isize {
     sys_spawn(path)
 }
@@ -307,6 +526,183 @@ pub fn task_info(info: &TaskInfo) -> isize {
     sys_task_info(info)
 }
 
+/// `munmap` that reports the frames it released in `stats`
+pub fn munmap2(start: usize, len: usize, stats: &mut MunmapStats) -> isize {
+    sys_munmap2(start, len, stats)
+}
+
+pub fn mem_info(info: &mut MemInfo) -> isize {
+    sys_mem_info(info)
+}
+
+pub fn vm_find_free(len: usize, align: usize) -> isize {
+    sys_vm_find_free(len, align)
+}
+
+pub fn syscall_filter(ids: &[usize]) -> isize {
+    sys_syscall_filter(ids)
+}
+
+/// bit `i` of `bitmap` tells whether page `i` from `start` has a frame,
+/// returns how many do; `bitmap` needs a bit for every page of `len`
+pub fn vm_populated(start: usize, len: usize, bitmap: &mut [u8]) -> isize {
+    sys_vm_populated(start, len, bitmap)
+}
+
+/// like [`vm_populated`], but only pages accessed since the last call count
+pub fn vm_accessed(start: usize, len: usize, bitmap: &mut [u8]) -> isize {
+    sys_vm_accessed(start, len, bitmap)
+}
+
+/// [`pte_flag`]s of the page holding `va`, -1 if it is not mapped; needs
+/// [`kernel_feature::DEBUG_SYSCALLS`]
+pub fn translate(va: usize) -> isize {
+    sys_translate(va)
+}
+
+/// have the kernel print this app's address space; needs
+/// [`kernel_feature::DEBUG_SYSCALLS`]
+pub fn dump_vm() -> isize {
+    sys_dump_vm()
+}
+
+/// create a shared memory segment of `pages` zeroed pages, returns its id
+/// or -1
+pub fn shm_create(pages: usize) -> isize {
+    sys_shm_create(pages)
+}
+
+/// map shared memory segment `id` at `start`, or where the kernel finds
+/// room if it is 0; returns the address or -1. `munmap` unmaps it.
+pub fn shm_map(id: usize, start: usize, port: usize) -> isize {
+    sys_shm_map(id, start, port)
+}
+
+/// 0 normal, 1 low, 2 critical
+pub fn mem_pressure() -> isize {
+    sys_mem_pressure()
+}
+
+/// move the program break by `size` bytes, returns the old break
+pub fn sbrk(size: i32) -> isize {
+    sys_sbrk(size)
+}
+
+pub fn exec_text_only(enable: bool) -> isize {
+    sys_exec_text_only(enable as usize)
+}
+
+pub fn cap_drop(mask: usize) -> isize {
+    sys_cap_drop(mask)
+}
+
+pub fn mm_fingerprint() -> u64 {
+    sys_mm_fingerprint() as u64
+}
+
+pub fn trace_ctl(enable: bool) -> isize {
+    sys_trace_ctl(enable as usize)
+}
+
+/// log each syscall of the caller to the kernel log, target `strace`
+pub fn strace(enable: bool) -> isize {
+    sys_strace(enable as usize)
+}
+
+/// block until task `task_id` exits and return its exit code, -1 for an
+/// unknown id or the caller's own
+pub fn wait_task(task_id: usize) -> isize {
+    sys_wait_task(task_id)
+}
+
+/// map memory like `mmap`, starting out as a copy of the kernel's read-only
+/// data blob `blob_id` (the files of `user/data` in name order) and zeros
+/// after it
+pub fn mmap_data(start: usize, len: usize, prot: usize, blob_id: usize) -> isize {
+    sys_mmap_data(start, len, prot, blob_id)
+}
+
+/// have the kernel print how often each syscall was called so far and how
+/// long the calls took; needs [`kernel_feature::DEBUG_SYSCALLS`] and
+/// [`kernel_feature::KSTAT`]
+pub fn syscall_stats() -> isize {
+    sys_syscall_stats()
+}
+
+/// end every task and power off, qemu exiting with a failure status if
+/// `failure`; only task 0 and the kernel's shutdown app may, anyone else
+/// gets -1
+pub fn shutdown(failure: bool) -> isize {
+    sys_shutdown(failure as usize)
+}
+
+/// low bits of a task id, which name its slot; the bits above count the
+/// slot's restarts
+pub const TASK_SLOT_BITS: usize = 16;
+
+/// run the exited task `task_id` again from the start of its app, in the
+/// same slot, returns the id of the new run; -1 if it has not exited, for
+/// the id of an earlier run, or without the capability to
+pub fn task_restart(task_id: usize) -> isize {
+    sys_task_restart(task_id)
+}
+
+/// create a message queue of `capacity` messages of up to `msg_size` bytes,
+/// returns its id; -1 if either is 0, -3 if the kernel has no room for it
+pub fn mq_create(capacity: usize, msg_size: usize) -> isize {
+    sys_mq_create(capacity, msg_size)
+}
+
+/// send `msg` to queue `id`, blocking while it is full; 0, or -1 for an
+/// unknown queue or a message longer than its `msg_size`
+pub fn mq_send(id: usize, msg: &[u8]) -> isize {
+    sys_mq_send(id, msg)
+}
+
+/// receive the oldest message of queue `id` into `buf`, blocking while the
+/// queue is empty; returns its length, or -1 for an unknown queue or a
+/// `buf` shorter than its `msg_size`
+pub fn mq_recv(id: usize, buf: &mut [u8]) -> isize {
+    sys_mq_recv(id, buf)
+}
+
+/// drop queue `id` and the messages left in it; -1 for an unknown queue,
+/// -2 while a task is blocked on it
+pub fn mq_destroy(id: usize) -> isize {
+    sys_mq_destroy(id)
+}
+
+/// kill task `task_id`, which exits with code -9; -1 for an unknown or
+/// exited task, the caller itself, or without the capability to
+pub fn kill(task_id: usize) -> isize {
+    sys_kill(task_id)
+}
+
+/// drain the trace ring of `pid`, or of the caller if `pid` is negative
+pub fn trace_read(pid: isize, buf: &mut [TraceEntry]) -> isize {
+    sys_trace_read(pid, buf)
+}
+
+pub fn sched_epoch() -> isize {
+    sys_sched_epoch()
+}
+
+/// scheduler epoch `pid`, or the caller if `pid` is negative, last ran in
+pub fn epoch_of(pid: isize) -> isize {
+    sys_epoch_of(pid)
+}
+
+/// read the profile of `pid`, or of the caller if `pid` is negative,
+/// hottest bucket first
+pub fn profile_read(pid: isize, buf: &mut [ProfileBucket]) -> isize {
+    sys_profile_read(pid, buf)
+}
+
+/// run the kernel self-checks in `scope`, returns the ones that failed
+pub fn selfcheck(scope: usize) -> isize {
+    sys_selfcheck(scope)
+}
+
 pub fn thread_create(entry: usize, arg: usize) -> isize {
     sys_thread_create(entry, arg)
 }
@@ -1,6 +1,6 @@
 use crate::TaskInfo;
 
-use super::{Stat, TimeVal};
+use super::{KernelConfig, MemInfo, MunmapStats, ProfileBucket, Stat, TimeVal, Tms, TraceEntry};
 
 pub const SYSCALL_OPENAT: usize = 56;
 pub const SYSCALL_CLOSE: usize = 57;
@@ -12,6 +12,7 @@ pub const SYSCALL_FSTAT: usize = 80;
 pub const SYSCALL_EXIT: usize = 93;
 pub const SYSCALL_SLEEP: usize = 101;
 pub const SYSCALL_YIELD: usize = 124;
+pub const SYSCALL_TIMES: usize = 153;
 pub const SYSCALL_GETTIMEOFDAY: usize = 169;
 pub const SYSCALL_GETPID: usize = 172;
 pub const SYSCALL_GETTID: usize = 178;
@@ -19,14 +20,50 @@ pub const SYSCALL_FORK: usize = 220;
 pub const SYSCALL_EXEC: usize = 221;
 pub const SYSCALL_WAITPID: usize = 260;
 pub const SYSCALL_SET_PRIORITY: usize = 140;
+pub const SYSCALL_REBOOT: usize = 142;
+pub const SYSCALL_SBRK: usize = 214;
 pub const SYSCALL_MUNMAP: usize = 215;
 pub const SYSCALL_MMAP: usize = 222;
+pub const SYSCALL_MPROTECT: usize = 226;
 pub const SYSCALL_SPAWN: usize = 400;
 pub const SYSCALL_MAIL_READ: usize = 401;
 pub const SYSCALL_MAIL_WRITE: usize = 402;
 pub const SYSCALL_DUP: usize = 24;
 pub const SYSCALL_PIPE: usize = 59;
 pub const SYSCALL_TASK_INFO: usize = 410;
+pub const SYSCALL_EXEC_TEXT_ONLY: usize = 411;
+pub const SYSCALL_CAP_DROP: usize = 412;
+pub const SYSCALL_MM_FINGERPRINT: usize = 413;
+pub const SYSCALL_TRACE_CTL: usize = 414;
+pub const SYSCALL_TRACE_READ: usize = 415;
+pub const SYSCALL_SCHED_EPOCH: usize = 416;
+pub const SYSCALL_EPOCH_OF: usize = 417;
+pub const SYSCALL_PROFILE_READ: usize = 418;
+pub const SYSCALL_SELFCHECK: usize = 419;
+pub const SYSCALL_MUNMAP2: usize = 420;
+pub const SYSCALL_MEM_INFO: usize = 421;
+pub const SYSCALL_VM_FIND_FREE: usize = 422;
+pub const SYSCALL_SYSCALL_FILTER: usize = 423;
+pub const SYSCALL_MEM_PRESSURE: usize = 424;
+pub const SYSCALL_KERNEL_CONFIG: usize = 425;
+pub const SYSCALL_VM_POPULATED: usize = 426;
+pub const SYSCALL_VM_ACCESSED: usize = 427;
+pub const SYSCALL_TRANSLATE: usize = 428;
+pub const SYSCALL_DUMP_VM: usize = 429;
+pub const SYSCALL_SHM_CREATE: usize = 430;
+pub const SYSCALL_SHM_MAP: usize = 431;
+pub const SYSCALL_STRACE: usize = 432;
+pub const SYSCALL_WAIT_TASK: usize = 433;
+pub const SYSCALL_MMAP_DATA: usize = 434;
+pub const SYSCALL_SYSCALL_STATS: usize = 435;
+pub const SYSCALL_SHUTDOWN: usize = 436;
+pub const SYSCALL_TASK_RESTART: usize = 437;
+pub const SYSCALL_MMAP2: usize = 438;
+pub const SYSCALL_MQ_CREATE: usize = 439;
+pub const SYSCALL_MQ_SEND: usize = 440;
+pub const SYSCALL_MQ_RECV: usize = 441;
+pub const SYSCALL_MQ_DESTROY: usize = 442;
+pub const SYSCALL_KILL: usize = 443;
 pub const SYSCALL_THREAD_CREATE: usize = 460;
 pub const SYSCALL_WAITTID: usize = 462;
 pub const SYSCALL_MUTEX_CREATE: usize = 463;
@@ -158,6 +195,10 @@ pub fn sys_get_time(time: &TimeVal, tz: usize) -> isize {
     syscall(SYSCALL_GETTIMEOFDAY, [time as *const _ as usize, tz, 0])
 }
 
+pub fn sys_times(tms: &mut Tms) -> isize {
+    syscall(SYSCALL_TIMES, [tms as *mut _ as usize, 0, 0])
+}
+
 pub fn sys_getpid() -> isize {
     syscall(SYSCALL_GETPID, [0, 0, 0])
 }
@@ -173,14 +214,18 @@ pub fn sys_exec(path: &str, args: &[*const u8]) -> isize {
     )
 }
 
-pub fn sys_waitpid(pid: isize, xstatus: *mut i32) -> isize {
-    syscall(SYSCALL_WAITPID, [pid as usize, xstatus as usize, 0])
+pub fn sys_waitpid(pid: isize, xstatus: *mut i32, flags: usize) -> isize {
+    syscall(SYSCALL_WAITPID, [pid as usize, xstatus as usize, flags])
 }
 
 pub fn sys_set_priority(prio: isize) -> isize {
     syscall(SYSCALL_SET_PRIORITY, [prio as usize, 0, 0])
 }
 
+pub fn sys_reboot(flags: usize) -> isize {
+    syscall(SYSCALL_REBOOT, [flags, 0, 0])
+}
+
 pub fn sys_mmap(start: usize, len: usize, prot: usize) -> isize {
     syscall(SYSCALL_MMAP, [start, len, prot])
 }
@@ -189,6 +234,14 @@ pub fn sys_munmap(start: usize, len: usize) -> isize {
     syscall(SYSCALL_MUNMAP, [start, len, 0])
 }
 
+pub fn sys_mmap2(start: usize, len: usize, prot: usize) -> isize {
+    syscall(SYSCALL_MMAP2, [start, len, prot])
+}
+
+pub fn sys_mprotect(start: usize, len: usize, prot: usize) -> isize {
+    syscall(SYSCALL_MPROTECT, [start, len, prot])
+}
+
 pub fn sys_spawn(path: &str) -> isize {
     syscall(SYSCALL_SPAWN, [path.as_ptr() as usize, 0, 0])
 }
@@ -205,6 +258,163 @@ pub fn sys_task_info(info: &TaskInfo) -> isize {
     syscall(SYSCALL_TASK_INFO, [info as *const _ as usize, 0, 0])
 }
 
+pub fn sys_exec_text_only(enable: usize) -> isize {
+    syscall(SYSCALL_EXEC_TEXT_ONLY, [enable, 0, 0])
+}
+
+pub fn sys_cap_drop(mask: usize) -> isize {
+    syscall(SYSCALL_CAP_DROP, [mask, 0, 0])
+}
+
+pub fn sys_mm_fingerprint() -> isize {
+    syscall(SYSCALL_MM_FINGERPRINT, [0, 0, 0])
+}
+
+pub fn sys_trace_ctl(enable: usize) -> isize {
+    syscall(SYSCALL_TRACE_CTL, [enable, 0, 0])
+}
+
+pub fn sys_trace_read(pid: isize, buf: &mut [TraceEntry]) -> isize {
+    syscall(
+        SYSCALL_TRACE_READ,
+        [pid as usize, buf.as_mut_ptr() as usize, buf.len()],
+    )
+}
+
+pub fn sys_sched_epoch() -> isize {
+    syscall(SYSCALL_SCHED_EPOCH, [0, 0, 0])
+}
+
+pub fn sys_epoch_of(pid: isize) -> isize {
+    syscall(SYSCALL_EPOCH_OF, [pid as usize, 0, 0])
+}
+
+pub fn sys_profile_read(pid: isize, buf: &mut [ProfileBucket]) -> isize {
+    syscall(
+        SYSCALL_PROFILE_READ,
+        [pid as usize, buf.as_mut_ptr() as usize, buf.len()],
+    )
+}
+
+pub fn sys_selfcheck(scope: usize) -> isize {
+    syscall(SYSCALL_SELFCHECK, [scope, 0, 0])
+}
+
+pub fn sys_munmap2(start: usize, len: usize, stats: &mut MunmapStats) -> isize {
+    syscall(
+        SYSCALL_MUNMAP2,
+        [start, len, stats as *mut MunmapStats as usize],
+    )
+}
+
+pub fn sys_mem_info(info: &mut MemInfo) -> isize {
+    syscall(SYSCALL_MEM_INFO, [info as *mut MemInfo as usize, 0, 0])
+}
+
+pub fn sys_vm_find_free(len: usize, align: usize) -> isize {
+    syscall(SYSCALL_VM_FIND_FREE, [len, align, 0])
+}
+
+pub fn sys_syscall_filter(ids: &[usize]) -> isize {
+    syscall(
+        SYSCALL_SYSCALL_FILTER,
+        [ids.as_ptr() as usize, ids.len(), 0],
+    )
+}
+
+pub fn sys_mem_pressure() -> isize {
+    syscall(SYSCALL_MEM_PRESSURE, [0, 0, 0])
+}
+
+pub fn sys_kernel_config(config: &mut KernelConfig) -> isize {
+    syscall(
+        SYSCALL_KERNEL_CONFIG,
+        [
+            config as *mut KernelConfig as usize,
+            core::mem::size_of::<KernelConfig>(),
+            0,
+        ],
+    )
+}
+
+pub fn sys_vm_populated(start: usize, len: usize, bitmap: &mut [u8]) -> isize {
+    syscall(
+        SYSCALL_VM_POPULATED,
+        [start, len, bitmap.as_mut_ptr() as usize],
+    )
+}
+
+pub fn sys_vm_accessed(start: usize, len: usize, bitmap: &mut [u8]) -> isize {
+    syscall(
+        SYSCALL_VM_ACCESSED,
+        [start, len, bitmap.as_mut_ptr() as usize],
+    )
+}
+
+pub fn sys_translate(va: usize) -> isize {
+    syscall(SYSCALL_TRANSLATE, [va, 0, 0])
+}
+
+pub fn sys_dump_vm() -> isize {
+    syscall(SYSCALL_DUMP_VM, [0, 0, 0])
+}
+
+pub fn sys_shm_create(pages: usize) -> isize {
+    syscall(SYSCALL_SHM_CREATE, [pages, 0, 0])
+}
+
+pub fn sys_shm_map(id: usize, start: usize, port: usize) -> isize {
+    syscall(SYSCALL_SHM_MAP, [id, start, port])
+}
+
+pub fn sys_strace(enable: usize) -> isize {
+    syscall(SYSCALL_STRACE, [enable, 0, 0])
+}
+
+pub fn sys_wait_task(task_id: usize) -> isize {
+    syscall(SYSCALL_WAIT_TASK, [task_id, 0, 0])
+}
+
+pub fn sys_mmap_data(start: usize, len: usize, prot: usize, blob_id: usize) -> isize {
+    syscall6(SYSCALL_MMAP_DATA, [start, len, prot, blob_id, 0, 0])
+}
+
+pub fn sys_syscall_stats() -> isize {
+    syscall(SYSCALL_SYSCALL_STATS, [0, 0, 0])
+}
+
+pub fn sys_shutdown(failure: usize) -> isize {
+    syscall(SYSCALL_SHUTDOWN, [failure, 0, 0])
+}
+
+pub fn sys_task_restart(task_id: usize) -> isize {
+    syscall(SYSCALL_TASK_RESTART, [task_id, 0, 0])
+}
+
+pub fn sys_mq_create(capacity: usize, msg_size: usize) -> isize {
+    syscall(SYSCALL_MQ_CREATE, [capacity, msg_size, 0])
+}
+
+pub fn sys_mq_send(id: usize, buf: &[u8]) -> isize {
+    syscall(SYSCALL_MQ_SEND, [id, buf.as_ptr() as usize, buf.len()])
+}
+
+pub fn sys_mq_recv(id: usize, buf: &mut [u8]) -> isize {
+    syscall(SYSCALL_MQ_RECV, [id, buf.as_mut_ptr() as usize, buf.len()])
+}
+
+pub fn sys_mq_destroy(id: usize) -> isize {
+    syscall(SYSCALL_MQ_DESTROY, [id, 0, 0])
+}
+
+pub fn sys_kill(task_id: usize) -> isize {
+    syscall(SYSCALL_KILL, [task_id, 0, 0])
+}
+
+pub fn sys_sbrk(size: i32) -> isize {
+    syscall(SYSCALL_SBRK, [size as usize, 0, 0])
+}
+
 pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
     syscall(SYSCALL_THREAD_CREATE, [entry, arg, 0])
 }